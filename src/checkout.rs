@@ -0,0 +1,333 @@
+// src/checkout.rs
+
+use crate::diff::tree::Snapshot;
+use crate::index::{self, Index, IndexEntry};
+use crate::objects::{GitObject, ObjectKind, Oid};
+use crate::odb::{self, ObjectStore};
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// How the working tree copy of a tracked file compares with its index entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorktreeState {
+    Unchanged,
+    Modified,
+    Missing,
+}
+
+/// Compares a file in the working tree with its index entry. Matching stat
+/// data is trusted unless the entry is racily clean; otherwise the file is
+/// read and hashed.
+pub fn worktree_state(work_tree: &Path, entry: &IndexEntry, index_mtime: Option<(u32, u32)>) -> Result<WorktreeState> {
+    let path = work_tree.join(&entry.path);
+    let Ok(metadata) = fs::symlink_metadata(&path) else {
+        return Ok(WorktreeState::Missing);
+    };
+    // A submodule is checked out as a directory; we don't look inside it.
+    if entry.mode == 0o160000 {
+        return Ok(if metadata.is_dir() { WorktreeState::Unchanged } else { WorktreeState::Modified });
+    }
+    if metadata.is_dir() {
+        return Ok(WorktreeState::Missing);
+    }
+    if index::mode_from_metadata(&metadata) != entry.mode {
+        return Ok(WorktreeState::Modified);
+    }
+    if entry.stat_matches(&metadata) && !entry.is_racy(index_mtime) {
+        return Ok(WorktreeState::Unchanged);
+    }
+    let content = if metadata.file_type().is_symlink() {
+        index::path_bytes(&fs::read_link(&path)?).to_vec()
+    } else {
+        fs::read(&path)?
+    };
+    if odb::hash_object(ObjectKind::Blob, &content) == entry.hash {
+        Ok(WorktreeState::Unchanged)
+    } else {
+        Ok(WorktreeState::Modified)
+    }
+}
+
+/// Refuses a path that could reach outside the working tree or into the
+/// repository, as git's `verify_path` does: one with an empty component, `.`,
+/// `..` or `.git` in any case. Trees and indexes can hold such names, so
+/// every path is checked before anything is written or removed for it.
+pub fn verify_path(path: &[u8]) -> Result<()> {
+    let invalid = |component: &[u8]| {
+        component.is_empty() || component == b"." || component == b".." || component.eq_ignore_ascii_case(b".git")
+    };
+    if path.split(|&byte| byte == b'/').any(invalid) {
+        bail!("invalid path '{}'", String::from_utf8_lossy(path));
+    }
+    Ok(())
+}
+
+/// Writes the blob `oid` to `path` in the working tree: an executable or
+/// regular file, a symlink, or for a submodule an empty directory. Whatever
+/// is in the way is replaced. Returns the index entry for the new file, with
+/// its stat data.
+pub fn checkout_entry(store: &ObjectStore, work_tree: &Path, path: &[u8], mode: u32, oid: &Oid) -> Result<IndexEntry> {
+    verify_path(path)?;
+    let full_path = work_tree.join(index::path_from_bytes(path));
+    make_room(work_tree, path, mode != 0o160000)?;
+
+    match mode {
+        0o160000 => fs::create_dir_all(&full_path)?,
+        0o120000 => {
+            let target = read_blob(store, oid)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::ffi::OsStrExt;
+                std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(&target), &full_path)?;
+            }
+            // Without symlinks, the link is a file holding its target.
+            #[cfg(not(unix))]
+            fs::write(&full_path, &target)?;
+        }
+        _ => {
+            let content = read_blob(store, oid)?;
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                // The umask takes away whatever the user doesn't want.
+                options.mode(if mode == 0o100755 { 0o777 } else { 0o666 });
+            }
+            let mut file = options
+                .open(&full_path)
+                .with_context(|| format!("Failed to create {:?}", full_path))?;
+            file.write_all(&content)?;
+        }
+    }
+
+    let metadata = fs::symlink_metadata(&full_path)?;
+    let mut entry = IndexEntry::from_metadata(index::path_from_bytes(path), *oid, &metadata);
+    entry.mode = mode;
+    Ok(entry)
+}
+
+/// Removes a tracked file from the working tree, along with any directories
+/// that are left empty. A file that is already gone is fine.
+pub fn remove_entry(work_tree: &Path, path: &[u8]) -> Result<()> {
+    verify_path(path)?;
+    let full_path = work_tree.join(index::path_from_bytes(path));
+    let result = match fs::symlink_metadata(&full_path) {
+        // A submodule's directory only goes if nothing is checked out in it.
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir(&full_path).or(Ok(())),
+        Ok(_) => fs::remove_file(&full_path),
+        Err(_) => Ok(()),
+    };
+    result.with_context(|| format!("Failed to remove {:?}", full_path))?;
+
+    let mut dir = full_path.parent();
+    while let Some(current) = dir {
+        if current == work_tree || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+    Ok(())
+}
+
+/// Moves the index and working tree from `current` (the tree HEAD points at)
+/// to `target`, the way `git checkout` does.
+///
+/// Paths the target doesn't change keep whatever is staged and in the working
+/// tree, so local changes carry over. Paths it does change are only updated
+/// if they have no local changes and no untracked file is in the way;
+/// otherwise nothing is touched and an error lists them. With `force`, the
+/// index and working tree are made to match the target regardless.
+pub fn switch_trees(
+    store: &ObjectStore,
+    work_tree: &Path,
+    index: &mut Index,
+    current: &Snapshot,
+    target: &Snapshot,
+    force: bool,
+) -> Result<()> {
+    let unmerged: BTreeSet<PathBuf> =
+        index.entries.iter().filter(|entry| entry.stage != 0).map(|entry| entry.path.clone()).collect();
+    if !unmerged.is_empty() {
+        if !force {
+            let paths: Vec<String> =
+                unmerged.iter().map(|path| format!("{}: needs merge", path.display())).collect();
+            bail!("you need to resolve your current index first\n{}", paths.join("\n"));
+        }
+        for path in &unmerged {
+            index.remove(path);
+        }
+    }
+
+    let staged: BTreeMap<Vec<u8>, IndexEntry> = index
+        .entries
+        .iter()
+        .map(|entry| (index::path_bytes(&entry.path).to_vec(), entry.clone()))
+        .collect();
+    let paths: BTreeSet<&Vec<u8>> = current.keys().chain(staged.keys()).chain(target.keys()).collect();
+
+    // 1. Decide what changes, without touching anything yet.
+    let mut updates = Vec::new();
+    let (mut local_changes, mut untracked) = (Vec::new(), Vec::new());
+    for path in paths {
+        let head = current.get(path).copied();
+        let entry = staged.get(path);
+        let ours = entry.map(|entry| (entry.mode, entry.hash));
+        let theirs = target.get(path).copied();
+
+        if force {
+            let clean = match entry {
+                Some(entry) => worktree_state(work_tree, entry, index.mtime)? == WorktreeState::Unchanged,
+                None => false,
+            };
+            if ours != theirs || (theirs.is_some() && !clean) {
+                updates.push((path, theirs));
+            }
+            continue;
+        }
+
+        if ours == theirs || head == theirs {
+            continue;
+        }
+        if ours != head {
+            local_changes.push(String::from_utf8_lossy(path));
+            continue;
+        }
+        match entry {
+            Some(entry) if worktree_state(work_tree, entry, index.mtime)? == WorktreeState::Modified => {
+                local_changes.push(String::from_utf8_lossy(path))
+            }
+            None if untracked_in_the_way(work_tree, path, &staged)? => untracked.push(String::from_utf8_lossy(path)),
+            _ => updates.push((path, theirs)),
+        }
+    }
+
+    if !local_changes.is_empty() || !untracked.is_empty() {
+        let mut message = String::new();
+        if !local_changes.is_empty() {
+            message.push_str(&format!(
+                "Your local changes to the following files would be overwritten by checkout:\n\t{}\n\
+                 Please commit your changes or stash them before you switch branches.\n",
+                local_changes.join("\n\t")
+            ));
+        }
+        if !untracked.is_empty() {
+            message.push_str(&format!(
+                "The following untracked working tree files would be overwritten by checkout:\n\t{}\n\
+                 Please move or remove them before you switch branches.\n",
+                untracked.join("\n\t")
+            ));
+        }
+        bail!("{}Aborting", message);
+    }
+
+    // 2. Delete first, so directories can replace files and files replace
+    // directories. A bad path anywhere stops the switch before any change.
+    for (path, _) in &updates {
+        verify_path(path)?;
+    }
+    let mut written = BTreeSet::new();
+    for (path, _) in updates.iter().rev().filter(|(_, new)| new.is_none()) {
+        remove_entry(work_tree, path)?;
+        index.remove(&index::path_from_bytes(path));
+    }
+    for (path, new) in &updates {
+        if let Some((mode, oid)) = new {
+            index.add(checkout_entry(store, work_tree, path, *mode, oid)?);
+            written.insert(index::path_from_bytes(path));
+        }
+    }
+
+    // 3. Everything else keeps its entry, with its stat data refreshed if
+    // the file is unchanged.
+    let index_mtime = index.mtime;
+    for entry in index.entries.iter_mut().filter(|entry| !written.contains(&entry.path)) {
+        if worktree_state(work_tree, entry, index_mtime)? == WorktreeState::Unchanged
+            && let Ok(metadata) = fs::symlink_metadata(work_tree.join(&entry.path))
+        {
+            entry.update_stat(&metadata);
+        }
+    }
+    Ok(())
+}
+
+/// Whether checking out a new file at `path` would overwrite a file the
+/// index doesn't track: at the path itself, inside a directory there, or in
+/// place of one of its parent directories.
+fn untracked_in_the_way(work_tree: &Path, path: &[u8], staged: &BTreeMap<Vec<u8>, IndexEntry>) -> Result<bool> {
+    for i in slash_positions(path) {
+        let parent = &path[..i];
+        if let Ok(metadata) = fs::symlink_metadata(work_tree.join(index::path_from_bytes(parent)))
+            && !metadata.is_dir()
+        {
+            return Ok(!staged.contains_key(parent));
+        }
+    }
+    let full_path = work_tree.join(index::path_from_bytes(path));
+    match fs::symlink_metadata(&full_path) {
+        Ok(metadata) if metadata.is_dir() => {
+            let mut files = Vec::new();
+            walk_files(&full_path, path, &mut files)?;
+            Ok(files.iter().any(|file| !staged.contains_key(file)))
+        }
+        Ok(_) => Ok(true),
+        Err(_) => Ok(false),
+    }
+}
+
+fn walk_files(dir: &Path, prefix: &[u8], files: &mut Vec<Vec<u8>>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = [prefix, b"/", entry.file_name().as_encoded_bytes()].concat();
+        if entry.file_type()?.is_dir() {
+            walk_files(&entry.path(), &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Clears the way for a new file at `path`: parent directories are created,
+/// replacing files that stand in their place, and whatever is at the path
+/// itself goes unless `replace` is false and it is a directory.
+fn make_room(work_tree: &Path, path: &[u8], replace: bool) -> Result<()> {
+    for i in slash_positions(path) {
+        let parent = work_tree.join(index::path_from_bytes(&path[..i]));
+        if let Ok(metadata) = fs::symlink_metadata(&parent)
+            && !metadata.is_dir()
+        {
+            fs::remove_file(&parent)?;
+        }
+    }
+    let full_path = work_tree.join(index::path_from_bytes(path));
+    if let Some(parent) = full_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::symlink_metadata(&full_path) {
+        Ok(metadata) if metadata.is_dir() => {
+            if replace {
+                fs::remove_dir_all(&full_path)?;
+            }
+        }
+        Ok(_) => fs::remove_file(&full_path)?,
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+/// Where the slashes in a path are, so each parent directory is the path
+/// up to one of them.
+fn slash_positions(path: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    path.iter().enumerate().filter(|(_, byte)| **byte == b'/').map(|(i, _)| i)
+}
+
+fn read_blob(store: &ObjectStore, oid: &Oid) -> Result<Vec<u8>> {
+    match store.read(oid)? {
+        GitObject::Blob(blob) => Ok(blob.content),
+        other => bail!("{} is a {}, not a blob", hex::encode(oid), other.kind()),
+    }
+}
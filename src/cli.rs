@@ -1,6 +1,7 @@
 // src/cli.rs
 
 use clap::{Parser, Subcommand};
+use std::ffi::OsString;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -10,6 +11,34 @@ pub struct Cli {
     pub command: Commands,
 }
 
+impl Cli {
+    /// Parses the command line. `diff -M<n>`, `-C<n>` and `-B<n>` take their
+    /// threshold stuck to the flag, which clap can't tell apart from a bare
+    /// flag followed by a revision, so they are rewritten to the
+    /// `--find-renames=<n>`, `--find-copies=<n>` and `--break-rewrites=<n>`
+    /// forms first.
+    pub fn parse_args() -> Self {
+        let args: Vec<OsString> = std::env::args_os().collect();
+        if args.get(1).is_none_or(|command| command != "diff") {
+            return Cli::parse_from(args);
+        }
+        let end = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
+        let args = args.into_iter().enumerate().map(|(i, arg)| match arg.to_str() {
+            Some(flag) if i < end && flag.len() > 2 && flag.starts_with("-M") => {
+                format!("--find-renames={}", &flag[2..]).into()
+            }
+            Some(flag) if i < end && flag.len() > 2 && flag.starts_with("-C") => {
+                format!("--find-copies={}", &flag[2..]).into()
+            }
+            Some(flag) if i < end && flag.len() > 2 && flag.starts_with("-B") => {
+                format!("--break-rewrites={}", &flag[2..]).into()
+            }
+            _ => arg,
+        });
+        Cli::parse_from(args)
+    }
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Initialize a new, empty Rusgit repository
@@ -17,6 +46,9 @@ pub enum Commands {
         /// Where to create the repository. Defaults to the current directory.
         #[arg(default_value = ".")]
         path: PathBuf,
+        /// Create a bare repository, without a working tree
+        #[arg(long)]
+        bare: bool,
     },
     
     HashObject {
@@ -38,9 +70,9 @@ pub enum Commands {
     CommitTree {
         /// The hash of the tree object
         tree_hash: String,
-        /// The hash of the parent commit
+        /// The hash of a parent commit; repeat it for a merge
         #[arg(short)]
-        parent_hash: Option<String>,
+        parent_hash: Vec<String>,
         /// The commit message
         #[arg(short)]
         message: String,
@@ -59,4 +91,400 @@ pub enum Commands {
 
     WriteTree,
 
+    /// Show staged, unstaged and untracked changes
+    Status {
+        /// Give the output in the short format
+        #[arg(short, long)]
+        short: bool,
+        /// Give the output in a stable, machine-readable format (v1 or v2)
+        #[arg(long, value_name = "VERSION", num_args = 0..=1, default_missing_value = "v1")]
+        porcelain: Option<String>,
+    },
+
+    /// Write the objects listed on stdin to a new packfile
+    PackObjects {
+        /// Path prefix of the pack; `-<checksum>.pack` and `.idx` are appended
+        base_name: PathBuf,
+    },
+
+    /// Pack reachable objects into a single packfile
+    Repack {
+        /// Pack everything reachable, not just loose objects
+        #[arg(short)]
+        all: bool,
+        /// Remove loose objects and packs made redundant by the new pack
+        #[arg(short)]
+        delete: bool,
+    },
+
+    /// List, create, rename or delete branches
+    Branch {
+        /// The branch to create (and where to start it), rename or delete,
+        /// or patterns to list with -l
+        names: Vec<String>,
+        /// Delete branches that are fully merged
+        #[arg(short, long, conflicts_with_all = ["force_delete", "rename", "set_upstream_to"])]
+        delete: bool,
+        /// Delete branches even if they aren't merged
+        #[arg(short = 'D', conflicts_with_all = ["rename", "set_upstream_to"])]
+        force_delete: bool,
+        /// Rename a branch: `-m <old> <new>`, or `-m <new>` for the current one
+        #[arg(short = 'm', long = "move", conflicts_with = "set_upstream_to")]
+        rename: bool,
+        /// List branches, optionally only those matching the patterns given
+        #[arg(short, long)]
+        list: bool,
+        /// Make the branch (the current one by default) track this upstream
+        #[arg(short = 'u', long, value_name = "UPSTREAM")]
+        set_upstream_to: Option<String>,
+        /// Only list branches that contain this commit (HEAD by default)
+        #[arg(long, value_name = "COMMIT", num_args = 0..=1, default_missing_value = "HEAD")]
+        contains: Option<String>,
+        /// Only list branches merged into this commit (HEAD by default)
+        #[arg(long, value_name = "COMMIT", num_args = 0..=1, default_missing_value = "HEAD")]
+        merged: Option<String>,
+    },
+
+    /// Switch branches, or check out a commit on a detached HEAD
+    Checkout {
+        /// The branch or commit to check out (the start point with -b)
+        target: Option<String>,
+        /// Create this branch and switch to it
+        #[arg(short = 'b', value_name = "BRANCH")]
+        new_branch: Option<String>,
+        /// Detach HEAD at the commit, even if it is a branch
+        #[arg(long)]
+        detach: bool,
+        /// Throw away local changes that are in the way
+        #[arg(short, long)]
+        force: bool,
+        /// Check out only these files (given after --), from the index or the commit
+        #[arg(last = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Switch branches
+    Switch {
+        /// The branch to switch to (the start point with -c), or - for the previous one
+        target: Option<String>,
+        /// Create this branch and switch to it
+        #[arg(short = 'c', long = "create", value_name = "BRANCH")]
+        new_branch: Option<String>,
+        /// Switch to a commit on a detached HEAD
+        #[arg(short, long)]
+        detach: bool,
+        /// Throw away local changes that are in the way
+        #[arg(short, long, visible_alias = "discard-changes")]
+        force: bool,
+    },
+
+    /// Restore working tree files, or unstage them, from the index or a commit
+    Restore {
+        /// The files (or directories) to restore
+        paths: Vec<PathBuf>,
+        /// Restore the index, from HEAD unless --source is given
+        #[arg(short = 'S', long)]
+        staged: bool,
+        /// Restore the working tree (the default unless --staged is given)
+        #[arg(short = 'W', long)]
+        worktree: bool,
+        /// The commit to restore from
+        #[arg(short, long, value_name = "TREE")]
+        source: Option<String>,
+    },
+
+    /// Create, list or delete tags
+    Tag {
+        /// The tag to create or delete, or the pattern to list with -l
+        name: Option<String>,
+        /// The object to tag. Defaults to HEAD.
+        rev: Option<String>,
+        /// Make an annotated tag object
+        #[arg(short)]
+        annotate: bool,
+        /// The tag message (implies -a)
+        #[arg(short)]
+        message: Option<String>,
+        /// List tags, optionally matching a pattern
+        #[arg(short)]
+        list: bool,
+        /// Delete the tag
+        #[arg(short)]
+        delete: bool,
+        /// Replace an existing tag
+        #[arg(short)]
+        force: bool,
+    },
+
+    /// List refs and the objects they point to
+    ShowRef {
+        /// Only show refs whose trailing components match one of these
+        patterns: Vec<String>,
+        /// Show HEAD as well
+        #[arg(long)]
+        head: bool,
+        /// Only show branches
+        #[arg(long)]
+        heads: bool,
+        /// Only show tags
+        #[arg(long)]
+        tags: bool,
+        /// Also show what annotated tags point to, as `<ref>^{}`
+        #[arg(short, long)]
+        dereference: bool,
+        /// Only print the object ids
+        #[arg(short = 's', long = "hash")]
+        hash_only: bool,
+        /// Require each pattern to be an exact ref name
+        #[arg(long)]
+        verify: bool,
+    },
+
+    /// Read, change or delete a symbolic ref
+    SymbolicRef {
+        /// The symbolic ref, e.g. HEAD
+        name: String,
+        /// The ref it should point to
+        target: Option<String>,
+        /// Delete the symbolic ref
+        #[arg(short, long)]
+        delete: bool,
+        /// Print the target in its short form, e.g. `main`
+        #[arg(long)]
+        short: bool,
+        /// Exit silently with status 1 if the ref isn't symbolic
+        #[arg(short, long)]
+        quiet: bool,
+    },
+
+    /// Print refs using a format string
+    ForEachRef {
+        /// Ref prefixes or globs to include
+        patterns: Vec<String>,
+        /// Format with %(atom) placeholders, e.g. %(refname:short)
+        #[arg(long, default_value = "%(objectname) %(objecttype)\t%(refname)")]
+        format: String,
+        /// Sort by this atom; prefix with - for descending order
+        #[arg(long)]
+        sort: Option<String>,
+        /// Stop after this many refs
+        #[arg(long)]
+        count: Option<usize>,
+    },
+
+    /// Pack loose refs into .git/packed-refs
+    PackRefs {
+        /// Pack branches too, not only tags
+        #[arg(long)]
+        all: bool,
+        /// Keep the loose ref files
+        #[arg(long)]
+        no_prune: bool,
+    },
+
+    /// Update the object a ref points to, safely
+    UpdateRef {
+        /// The ref to update
+        #[arg(required_unless_present = "stdin")]
+        ref_name: Option<String>,
+        /// The new value, then optionally the value the ref must currently hold
+        #[arg(num_args = 0..=2)]
+        values: Vec<String>,
+        /// Delete the ref instead (optionally checking its old value)
+        #[arg(short)]
+        delete: bool,
+        /// Update a symbolic ref itself rather than the ref it points to
+        #[arg(long)]
+        no_deref: bool,
+        /// The reason to record in the reflog
+        #[arg(short)]
+        message: Option<String>,
+        /// Read update, create, delete and verify commands from stdin and apply them all or none
+        #[arg(long, conflicts_with = "ref_name")]
+        stdin: bool,
+    },
+
+    /// Show the commit history
+    Log {
+        /// Commits to start from, or ranges like main..feature. Defaults to HEAD.
+        revisions: Vec<String>,
+        /// Only show commits that change these paths (given after --)
+        #[arg(last = true)]
+        paths: Vec<PathBuf>,
+        /// One line per commit: the abbreviated hash and the subject
+        #[arg(long)]
+        oneline: bool,
+        /// Show at most this many commits
+        #[arg(short = 'n', long)]
+        max_count: Option<usize>,
+        /// oneline, short, medium, full, fuller, format:<template> or tformat:<template>
+        #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "medium")]
+        pretty: Option<String>,
+        /// A template of placeholders such as %h, %an, %ad and %s
+        #[arg(long, conflicts_with = "pretty")]
+        format: Option<String>,
+        /// Draw the history as a graph beside the commits
+        #[arg(long)]
+        graph: bool,
+        /// Only show commits whose author contains this text
+        #[arg(long)]
+        author: Vec<String>,
+        /// Only show commits whose message contains this text
+        #[arg(long)]
+        grep: Vec<String>,
+        /// Only show commits made after this date (e.g. 2.weeks.ago or 2024-01-31)
+        #[arg(long, visible_alias = "after")]
+        since: Option<String>,
+        /// Only show commits made before this date
+        #[arg(long, visible_alias = "before")]
+        until: Option<String>,
+        /// Follow only the first parent of merges
+        #[arg(long)]
+        first_parent: bool,
+    },
+
+    /// Show changes between the working tree, the index and commits
+    Diff {
+        /// Compare the index instead of the working tree
+        #[arg(long, visible_alias = "staged")]
+        cached: bool,
+        /// A commit to compare against, or two (or A..B) to compare with each other
+        revisions: Vec<String>,
+        /// Only show changes to these paths (given after --)
+        #[arg(last = true)]
+        paths: Vec<PathBuf>,
+        /// Lines of context around each change
+        #[arg(short = 'U', long = "unified", default_value_t = 3)]
+        unified: usize,
+        /// Use the patience diff algorithm
+        #[arg(long, conflicts_with = "histogram")]
+        patience: bool,
+        /// Use the histogram diff algorithm
+        #[arg(long)]
+        histogram: bool,
+        /// Detect renames, optionally with a similarity threshold (e.g. -M90%)
+        #[arg(short = 'M', long = "find-renames", num_args = 0..=1, require_equals = true, default_missing_value = "")]
+        find_renames: Option<String>,
+        /// Detect copies as well as renames, optionally with a threshold
+        #[arg(short = 'C', long = "find-copies", num_args = 0..=1, require_equals = true, default_missing_value = "")]
+        find_copies: Option<String>,
+        /// Show renamed files as deleted and added
+        #[arg(long, conflicts_with_all = ["find_renames", "find_copies"])]
+        no_renames: bool,
+        /// Break mostly rewritten files up for rename detection and show them
+        /// as rewrites, optionally with thresholds (e.g. -B50%/70%)
+        #[arg(short = 'B', long = "break-rewrites", num_args = 0..=1, require_equals = true, default_missing_value = "")]
+        break_rewrites: Option<String>,
+        /// Show only the names of changed files
+        #[arg(long, conflicts_with_all = ["name_status", "raw"])]
+        name_only: bool,
+        /// Show the names and status (A, D, M, R...) of changed files
+        #[arg(long, conflicts_with = "raw")]
+        name_status: bool,
+        /// Show modes, ids, status and names of changed files
+        #[arg(long)]
+        raw: bool,
+        /// Show how many lines each file gained and lost, with a graph
+        #[arg(long)]
+        stat: bool,
+        /// Show lines added and deleted per file, in a machine-friendly form
+        #[arg(long)]
+        numstat: bool,
+        /// Show created, deleted, renamed and rewritten files and mode changes
+        #[arg(long)]
+        summary: bool,
+    },
+
+    /// Get and set repository or global options
+    Config {
+        /// The key, e.g. user.name
+        key: Option<String>,
+        /// The value to set
+        value: Option<String>,
+        /// Use the global config file (~/.gitconfig)
+        #[arg(long)]
+        global: bool,
+        /// Use the system-wide config file
+        #[arg(long)]
+        system: bool,
+        /// Use the repository's config file
+        #[arg(long)]
+        local: bool,
+        /// Use the given config file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Get the value of a key (the default with just a key)
+        #[arg(long)]
+        get: bool,
+        /// Get every value of a multi-valued key
+        #[arg(long)]
+        get_all: bool,
+        /// Add a value without replacing existing ones
+        #[arg(long)]
+        add: bool,
+        /// Remove a key
+        #[arg(long)]
+        unset: bool,
+        /// Remove every value of a multi-valued key
+        #[arg(long)]
+        unset_all: bool,
+        /// List every setting
+        #[arg(short, long)]
+        list: bool,
+        /// With --list, show which file each setting comes from
+        #[arg(long)]
+        show_origin: bool,
+    },
+
+    /// Turn revision expressions into object ids
+    RevParse {
+        /// Revisions: names, hashes, HEAD~2, main^{tree}, HEAD:path, A..B, ...
+        revisions: Vec<String>,
+        /// Require exactly one revision naming an existing object
+        #[arg(long)]
+        verify: bool,
+        /// Print abbreviated hashes, at least this many digits long
+        #[arg(long, value_name = "LENGTH", num_args = 0..=1, require_equals = true, default_missing_value = "7")]
+        short: Option<usize>,
+        /// Print the short name of the ref a revision names, e.g. main for HEAD
+        #[arg(long)]
+        abbrev_ref: bool,
+        /// Print the full name of the ref a revision names, e.g. refs/heads/main
+        #[arg(long)]
+        symbolic_full_name: bool,
+    },
+
+    /// Show or prune the history of where refs have pointed
+    #[command(args_conflicts_with_subcommands = true)]
+    Reflog {
+        #[command(subcommand)]
+        action: Option<ReflogAction>,
+        /// The ref whose log to show. Defaults to HEAD.
+        ref_name: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ReflogAction {
+    /// Show a ref's log, newest entry first
+    Show {
+        /// The ref whose log to show. Defaults to HEAD.
+        ref_name: Option<String>,
+    },
+    /// Remove entries older than a given time
+    Expire {
+        /// Entries older than this are removed ("90.days.ago" by default,
+        /// "now" or "all" for everything, "never" for nothing)
+        #[arg(long)]
+        expire: Option<String>,
+        /// Expire the logs of every ref
+        #[arg(long)]
+        all: bool,
+        /// The refs whose logs to expire
+        refs: Vec<String>,
+    },
+    /// Remove single entries, given as <ref>@{<n>}
+    Delete {
+        entries: Vec<String>,
+    },
 }
@@ -1,39 +1,41 @@
 // src/commands/add.rs
 
-use crate::index::Index;
+use crate::index::{self, Index, IndexEntry};
+use crate::objects::{Blob, GitObject};
+use crate::repository::Repository;
 use anyhow::{Context, Result};
 use std::fs;
 use std::path::PathBuf;
-use sha1::{Digest, Sha1};
 
-pub fn add(files: Vec<PathBuf>) -> Result<()> {
-    let index_path = PathBuf::from(".git/index");
+/// Stages files given relative to the current directory.
+pub fn add(repo: &Repository, files: Vec<PathBuf>) -> Result<()> {
+    let index_path = repo.index_path();
     let mut index = Index::from_path(&index_path)?;
+    let store = repo.odb();
 
     for file_path in files {
-        // 1. Run the `hash-object` logic to get the blob hash.
-        let content = fs::read(&file_path)
+        // 1. Write the blob object to the database. The store skips objects
+        // that already exist and hands back the blob hash either way. A
+        // symlink is not followed: its blob holds the path it points to.
+        let metadata = fs::symlink_metadata(&file_path)
             .with_context(|| format!("Failed to read file: {:?}", file_path))?;
-        
-        let header = format!("blob {}\0", content.len());
-        let mut full_content = header.as_bytes().to_vec();
-        full_content.extend_from_slice(&content);
-        
-        let hash: [u8; 20] = sha1::Sha1::digest(&full_content).into();
+        let content = if metadata.file_type().is_symlink() {
+            index::path_bytes(&fs::read_link(&file_path)?).to_vec()
+        } else {
+            fs::read(&file_path).with_context(|| format!("Failed to read file: {:?}", file_path))?
+        };
+        let hash = store.write(&GitObject::Blob(Blob { content }))?;
 
-        // 2. Write the blob object to the database (if it doesn't already exist).
-        // Note: For simplicity, we are not checking for existence first.
-        // A real implementation would.
-        let _ = crate::commands::hash_object::hash_object(&file_path, true)?;
-
-        // 3. Add the file to the index.
-        // We'll use a standard file mode. `0o` prefix indicates octal.
-        index.add(file_path, hash, 0o100644);
+        // 2. Add the file to the index, recording its mode (120000 for a
+        // symlink) and stat data. Index paths are relative to the root of the
+        // working tree.
+        let index_path = repo.worktree_path(&file_path)?;
+        index.add(IndexEntry::from_metadata(index_path, hash, &metadata));
         println!("Added file to index.");
     }
 
-    // 4. Write the updated index back to disk.
+    // 3. Write the updated index back to disk.
     index.write(&index_path)?;
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,297 @@
+// src/commands/branch.rs
+
+use crate::config::{self, ConfigLevel};
+use crate::objects::Oid;
+use crate::reflog;
+use crate::refs::{self, RefTransaction, RefValue};
+use crate::repository::{self, Repository};
+use crate::revision;
+use anyhow::{bail, Context, Result};
+use std::fs;
+
+/// Which branches `list_branches` shows.
+#[derive(Debug, Default)]
+pub struct BranchFilter {
+    /// Only branches matching one of these patterns, if any are given.
+    pub patterns: Vec<String>,
+    /// Only branches whose history includes this commit.
+    pub contains: Option<String>,
+    /// Only branches whose tip is reachable from this commit.
+    pub merged: Option<String>,
+}
+
+/// Prints the local branches, marking the current one with `*`. A detached
+/// HEAD is listed first, the way `status` describes it.
+pub fn list_branches(repo: &Repository, filter: &BranchFilter) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let store = repo.odb();
+    let contains = filter
+        .contains
+        .as_deref()
+        .map(|rev| revision::resolve_commit(repo, rev))
+        .transpose()?;
+    let merged_into = match filter.merged.as_deref() {
+        Some(rev) => Some(revision::ancestors(store, &revision::resolve_commit(repo, rev)?)?),
+        None => None,
+    };
+
+    let current = repository::current_branch(git_dir)?;
+    if current.is_none() && filter.patterns.is_empty() {
+        let head = refs::resolve_ref(git_dir, "HEAD")?.context("Invalid HEAD")?;
+        let shown = contains.is_none_or(|commit| {
+            revision::ancestors(store, &head).is_ok_and(|ancestors| ancestors.contains(&commit))
+        }) && merged_into.as_ref().is_none_or(|merged| merged.contains(&head));
+        if shown {
+            println!("* ({})", repository::describe_head(git_dir)?);
+        }
+    }
+
+    for (ref_name, oid) in refs::list_refs(git_dir, "refs/heads/")? {
+        let name = refs::shorten_ref_name(&ref_name);
+        let matches = filter.patterns.is_empty()
+            || filter
+                .patterns
+                .iter()
+                .any(|pattern| refs::glob_match(pattern.as_bytes(), name.as_bytes()));
+        if !matches {
+            continue;
+        }
+        if let Some(commit) = contains
+            && !revision::ancestors(store, &oid)?.contains(&commit)
+        {
+            continue;
+        }
+        if let Some(merged) = &merged_into
+            && !merged.contains(&oid)
+        {
+            continue;
+        }
+        let marker = if current.as_deref() == Some(name) { '*' } else { ' ' };
+        println!("{} {}", marker, name);
+    }
+    Ok(())
+}
+
+/// Creates `refs/heads/<name>` at `start` (HEAD by default). Starting from a
+/// remote-tracking branch also makes it the new branch's upstream, as
+/// `branch.autoSetupMerge` asks by default.
+pub fn create_branch(repo: &Repository, name: &str, start: Option<&str>) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let ref_name = branch_ref_name(name)?;
+    if refs::resolve_ref(git_dir, &ref_name)?.is_some() {
+        bail!("a branch named '{}' already exists", name);
+    }
+
+    let start = start.unwrap_or("HEAD");
+    let oid = revision::resolve_commit(repo, start)
+        .with_context(|| format!("not a valid object name: '{}'", start))?;
+
+    let mut transaction = RefTransaction::new();
+    transaction.set_message(&format!("branch: Created from {}", start));
+    transaction.update(&ref_name, oid, Some(None));
+    transaction.commit(git_dir)?;
+
+    let track = match repo.config().get("branch.autoSetupMerge") {
+        Some("always") => true,
+        Some(value) => config::parse_bool(value).unwrap_or(true) && is_remote_tracking(repo, start)?,
+        None => is_remote_tracking(repo, start)?,
+    };
+    if track && let Some(upstream) = revision::symbolic_full_name(repo, start)? {
+        set_tracking(repo, name, &upstream)?;
+    }
+    Ok(())
+}
+
+/// Renames a branch (the current one if `old` is None), moving its reflog
+/// and its `branch.<name>` settings along with it. HEAD follows the branch
+/// if it was checked out, even before its first commit.
+pub fn rename_branch(repo: &Repository, old: Option<&str>, new: &str) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let current = repository::current_branch(git_dir)?;
+    let old = match old {
+        Some(old) => old.to_string(),
+        None => current.clone().context("cannot rename the current branch while not on any.")?,
+    };
+    let old_ref = format!("refs/heads/{}", old);
+    let new_ref = branch_ref_name(new)?;
+    let is_current = current.as_deref() == Some(old.as_str());
+
+    let oid = refs::resolve_ref(git_dir, &old_ref)?;
+    if oid.is_none() && !is_current {
+        bail!("No branch named '{}'.", old);
+    }
+    if old != new && refs::resolve_ref(git_dir, &new_ref)?.is_some() {
+        bail!("a branch named '{}' already exists", new);
+    }
+
+    if let Some(oid) = oid
+        && old != new
+    {
+        // The history moves first, so the rename is its newest entry.
+        let old_log = reflog::log_path(git_dir, &old_ref);
+        if old_log.is_file() {
+            let new_log = reflog::log_path(git_dir, &new_ref);
+            if let Some(parent) = new_log.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&old_log, &new_log).context("Failed to move the branch's reflog")?;
+        }
+
+        let mut transaction = RefTransaction::new();
+        transaction.set_deref(false);
+        transaction.set_message(&format!("Branch: renamed {} to {}", old_ref, new_ref));
+        transaction.delete(&old_ref, Some(oid));
+        transaction.update(&new_ref, oid, Some(None));
+        transaction.commit(git_dir)?;
+    }
+
+    if is_current {
+        refs::write_symbolic_ref(git_dir, "HEAD", &new_ref)?;
+    }
+    let config_path = config::write_path(ConfigLevel::Local, Some(git_dir))?;
+    config::rename_section(&config_path, &format!("branch.{}", old), Some(&format!("branch.{}", new)))?;
+    Ok(())
+}
+
+/// Deletes branches. Without `force`, a branch must be merged into its
+/// upstream, or into HEAD if it has none, so no commits are lost.
+pub fn delete_branches(repo: &Repository, names: &[String], force: bool) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let store = repo.odb();
+    if names.is_empty() {
+        bail!("branch name required");
+    }
+    let current = repository::current_branch(git_dir)?;
+    let head = refs::resolve_ref(git_dir, "HEAD")?;
+
+    for name in names {
+        let ref_name = format!("refs/heads/{}", name);
+        if current.as_deref() == Some(name.as_str()) {
+            let location = repo.work_tree().unwrap_or(git_dir);
+            bail!("Cannot delete branch '{}' checked out at '{}'", name, location.display());
+        }
+        let oid = match refs::read_ref(git_dir, &ref_name)? {
+            Some(RefValue::Direct(oid)) => oid,
+            _ => bail!("branch '{}' not found.", name),
+        };
+        if !force && !is_merged(repo, name, &oid, head.as_ref())? {
+            bail!(
+                "The branch '{}' is not fully merged.\nIf you are sure you want to delete it, run 'rusgit branch -D {}'.",
+                name,
+                name
+            );
+        }
+
+        let mut transaction = RefTransaction::new();
+        transaction.set_deref(false);
+        transaction.delete(&ref_name, Some(oid));
+        transaction.commit(git_dir)?;
+        let config_path = config::write_path(ConfigLevel::Local, Some(git_dir))?;
+        config::rename_section(&config_path, &format!("branch.{}", name), None)?;
+        println!("Deleted branch {} (was {}).", name, revision::abbreviate(store, &oid, 7)?);
+    }
+    Ok(())
+}
+
+/// Makes `upstream` the branch `name` (the current one by default) tracks,
+/// recording it as `branch.<name>.remote` and `branch.<name>.merge`.
+pub fn set_upstream(repo: &Repository, upstream: &str, name: Option<&str>) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => repository::current_branch(git_dir)?.with_context(|| {
+            format!("could not set upstream of HEAD to {} when it does not point to any branch.", upstream)
+        })?,
+    };
+    if refs::resolve_ref(git_dir, &format!("refs/heads/{}", name))?.is_none() {
+        bail!("branch '{}' does not exist", name);
+    }
+    let full_name = revision::symbolic_full_name(repo, upstream)
+        .ok()
+        .flatten()
+        .filter(|full| full.starts_with("refs/heads/") || full.starts_with("refs/remotes/"))
+        .with_context(|| format!("the requested upstream branch '{}' does not exist", upstream))?;
+    set_tracking(repo, &name, &full_name)
+}
+
+/// Writes a branch's upstream settings: a remote-tracking branch
+/// `refs/remotes/<remote>/<branch>` is `<branch>` on `<remote>`, which must be
+/// configured, and a local branch is tracked on the `.` remote.
+fn set_tracking(repo: &Repository, name: &str, upstream: &str) -> Result<()> {
+    let (remote, merge) = match upstream.strip_prefix("refs/remotes/") {
+        Some(rest) => {
+            let (remote, branch) = rest
+                .split_once('/')
+                .with_context(|| format!("cannot tell which remote '{}' belongs to", upstream))?;
+            let config = repo.config();
+            let configured = ["url", "fetch"]
+                .iter()
+                .any(|key| config.get(&format!("remote.{}.{}", remote, key)).is_some());
+            if !configured {
+                bail!(
+                    "cannot set up tracking information; starting point '{}' is not a branch",
+                    refs::shorten_ref_name(upstream)
+                );
+            }
+            (remote.to_string(), format!("refs/heads/{}", branch))
+        }
+        None => (".".to_string(), upstream.to_string()),
+    };
+    let config_path = config::write_path(ConfigLevel::Local, Some(repo.git_dir()))?;
+    config::set_value(&config_path, &format!("branch.{}.remote", name), &remote, false)?;
+    config::set_value(&config_path, &format!("branch.{}.merge", name), &merge, false)?;
+    println!("branch '{}' set up to track '{}'.", name, refs::shorten_ref_name(upstream));
+    Ok(())
+}
+
+/// Whether a branch at `oid` is reachable from its upstream, or from HEAD if
+/// it has none. Warns when the two would give different answers.
+fn is_merged(repo: &Repository, name: &str, oid: &Oid, head: Option<&Oid>) -> Result<bool> {
+    let store = repo.odb();
+    let upstream = revision::symbolic_full_name(repo, &format!("{}@{{upstream}}", name))
+        .ok()
+        .flatten()
+        .and_then(|upstream| Some((refs::resolve_ref(repo.git_dir(), &upstream).ok()??, upstream)));
+    let reaches = |tip: Option<&Oid>| -> Result<bool> {
+        match tip {
+            Some(tip) => Ok(revision::ancestors(store, tip)?.contains(oid)),
+            None => Ok(false),
+        }
+    };
+
+    let Some((upstream_oid, upstream)) = upstream else {
+        return reaches(head);
+    };
+    let merged = reaches(Some(&upstream_oid))?;
+    if head != Some(&upstream_oid) && reaches(head)? != merged {
+        if merged {
+            eprintln!(
+                "warning: deleting branch '{}' that has been merged to\n         '{}', but not yet merged to HEAD.",
+                name, upstream
+            );
+        } else {
+            eprintln!(
+                "warning: not deleting branch '{}' that is not yet merged to\n         '{}', even though it is merged to HEAD.",
+                name, upstream
+            );
+        }
+    }
+    Ok(merged)
+}
+
+/// Whether `rev` names a remote-tracking branch.
+fn is_remote_tracking(repo: &Repository, rev: &str) -> Result<bool> {
+    Ok(revision::symbolic_full_name(repo, rev)
+        .ok()
+        .flatten()
+        .is_some_and(|full| full.starts_with("refs/remotes/")))
+}
+
+/// `refs/heads/<name>`, if `name` is allowed as a branch name.
+pub fn branch_ref_name(name: &str) -> Result<String> {
+    let ref_name = format!("refs/heads/{}", name);
+    if name == "HEAD" || refs::check_ref_name(&ref_name).is_err() {
+        bail!("'{}' is not a valid branch name", name);
+    }
+    Ok(ref_name)
+}
@@ -0,0 +1,237 @@
+// src/commands/checkout.rs
+
+use crate::checkout::{self, WorktreeState};
+use crate::commands::branch;
+use crate::commands::restore::{self, RestoreOptions};
+use crate::diff::tree::Snapshot;
+use crate::index::{self, Index};
+use crate::objects::{GitObject, Oid};
+use crate::odb::ObjectStore;
+use crate::reflog;
+use crate::refs;
+use crate::repository::{self, Repository};
+use crate::revision;
+use anyhow::{bail, Result};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// How `switch` and `checkout` move HEAD.
+#[derive(Debug, Default)]
+pub struct SwitchOptions {
+    /// Throw away local changes that are in the way.
+    pub force: bool,
+    /// Detach HEAD at the commit even if it names a branch.
+    pub detach: bool,
+    /// Create this branch at the commit and switch to it.
+    pub new_branch: Option<String>,
+    /// Refuse anything but a branch unless detaching, as `switch` does.
+    pub branch_only: bool,
+}
+
+/// Checks out a branch or commit: the index and working tree are moved to its
+/// tree, keeping local changes the switch doesn't touch, and HEAD is pointed
+/// at the branch or detached at the commit. `-` is the previous branch.
+pub fn switch(repo: &Repository, target: Option<&str>, options: &SwitchOptions) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let work_tree = repo.require_work_tree()?;
+    let store = repo.odb();
+    let spec = match target {
+        Some("-") => "@{-1}",
+        Some(target) => target,
+        None if options.new_branch.is_some() || options.detach => "HEAD",
+        None => bail!("missing branch or commit argument"),
+    };
+
+    // 1. Work out which branch HEAD will point to, if any.
+    let old_branch = repository::current_branch(git_dir)?;
+    let old_oid = refs::resolve_ref(git_dir, "HEAD")?;
+    let branch = if options.detach {
+        None
+    } else if let Some(name) = &options.new_branch {
+        let ref_name = branch::branch_ref_name(name)?;
+        if refs::resolve_ref(git_dir, &ref_name)?.is_some() {
+            bail!("a branch named '{}' already exists", name);
+        }
+        Some(ref_name)
+    } else {
+        local_branch(repo, spec)?
+    };
+    if branch.is_none() && options.branch_only && !options.detach {
+        if revision::resolve_commit(repo, spec).is_err() {
+            bail!("invalid reference: {}", spec);
+        }
+        let kind = match revision::symbolic_full_name(repo, spec).ok().flatten() {
+            Some(full) if full.starts_with("refs/tags/") => "tag",
+            Some(full) if full.starts_with("refs/remotes/") => "remote branch",
+            _ => "commit",
+        };
+        bail!(
+            "a branch is expected, got {} '{}'\nhint: If you want to detach HEAD at the commit, try again with the --detach option.",
+            kind,
+            spec
+        );
+    }
+
+    // A new branch on an unborn HEAD has nothing to check out.
+    if let Some(name) = &options.new_branch
+        && target.is_none()
+        && old_oid.is_none()
+    {
+        refs::write_symbolic_ref(git_dir, "HEAD", &format!("refs/heads/{}", name))?;
+        eprintln!("Switched to a new branch '{}'", name);
+        return Ok(());
+    }
+
+    // 2. Move the index and working tree.
+    let new_oid = match (&branch, &options.new_branch) {
+        (Some(ref_name), None) => revision::resolve_commit(repo, ref_name)?,
+        _ => revision::resolve_commit(repo, spec)?,
+    };
+    let current = match &old_oid {
+        Some(oid) => commit_snapshot(store, oid)?,
+        None => Snapshot::new(),
+    };
+    let target_files = commit_snapshot(store, &new_oid)?;
+    let index_path = repo.index_path();
+    let mut index = Index::from_path(&index_path)?;
+    checkout::switch_trees(store, work_tree, &mut index, &current, &target_files, options.force)?;
+    index.write(&index_path)?;
+
+    // 3. Point HEAD at the branch or the commit.
+    if let Some(name) = &options.new_branch {
+        branch::create_branch(repo, name, target)?;
+    }
+    let from = match (&old_branch, &old_oid) {
+        (Some(branch), _) => branch.clone(),
+        (None, Some(oid)) => hex::encode(oid),
+        (None, None) => "HEAD".to_string(),
+    };
+    let to = match (&branch, &options.new_branch) {
+        (_, Some(name)) => name.clone(),
+        (Some(ref_name), None) => refs::shorten_ref_name(ref_name).to_string(),
+        (None, None) => spec.to_string(),
+    };
+    let message = format!("checkout: moving from {} to {}", from, to);
+    match &branch {
+        Some(ref_name) => {
+            refs::write_symbolic_ref(git_dir, "HEAD", ref_name)?;
+            reflog::append(git_dir, "HEAD", old_oid, Some(new_oid), &message)?;
+        }
+        // Like git, a detached HEAD that stays put isn't logged again.
+        None if old_branch.is_none() && old_oid == Some(new_oid) => {}
+        None => repository::detach_head(git_dir, &hex::encode(new_oid), &message)?,
+    }
+
+    // 4. Report, the way git does.
+    for (status, path) in local_changes(work_tree, &index, &target_files)? {
+        println!("{}\t{}", status, index::quote_path(&path));
+    }
+    if let (None, Some(old)) = (&old_branch, &old_oid)
+        && (branch.is_some() || *old != new_oid)
+    {
+        eprintln!("Previous HEAD position was {}", describe_commit(store, old)?);
+    }
+    match &branch {
+        Some(_) if options.new_branch.is_some() => eprintln!("Switched to a new branch '{}'", to),
+        Some(_) if old_branch.as_deref() == Some(to.as_str()) => {
+            eprintln!("Already on '{}'", to)
+        }
+        Some(_) => eprintln!("Switched to branch '{}'", to),
+        None => {
+            let advice = repo.config().get_bool("advice.detachedHead")?.unwrap_or(true);
+            if old_branch.is_some() && !options.detach && advice {
+                eprintln!("{}", detached_advice(spec));
+            }
+            eprintln!("HEAD is now at {}", describe_commit(store, &new_oid)?);
+        }
+    }
+    Ok(())
+}
+
+/// Checks out paths rather than a branch: from the index into the working
+/// tree or, with `source`, from a commit into both. Unlike `restore`, files
+/// the commit doesn't have are left alone.
+pub fn checkout_paths(repo: &Repository, source: Option<&str>, paths: &[PathBuf]) -> Result<()> {
+    let options = RestoreOptions {
+        staged: source.is_some(),
+        worktree: true,
+        source: source.map(str::to_string),
+        overlay: true,
+    };
+    restore::restore(repo, paths, &options)?;
+    Ok(())
+}
+
+/// The local branch `spec` names, as a full ref name: `<spec>` itself under
+/// refs/heads, or the branch `@{-n}` refers to.
+fn local_branch(repo: &Repository, spec: &str) -> Result<Option<String>> {
+    let ref_name = format!("refs/heads/{}", spec);
+    if refs::check_ref_name(&ref_name).is_ok() && refs::resolve_ref(repo.git_dir(), &ref_name)?.is_some() {
+        return Ok(Some(ref_name));
+    }
+    if spec.starts_with("@{-") {
+        let full = revision::symbolic_full_name(repo, spec)?;
+        return Ok(full.filter(|full| full.starts_with("refs/heads/")));
+    }
+    Ok(None)
+}
+
+/// What differs between the new HEAD's tree and the index and working tree,
+/// as `A`, `M` and `D` lines.
+fn local_changes(work_tree: &Path, index: &Index, target: &Snapshot) -> Result<Vec<(char, Vec<u8>)>> {
+    let staged: BTreeSet<&[u8]> = index.entries.iter().map(|entry| index::path_bytes(&entry.path)).collect();
+    let mut changes = Vec::new();
+    for entry in &index.entries {
+        let path = index::path_bytes(&entry.path).to_vec();
+        let status = match target.get(&path) {
+            None => 'A',
+            Some(&(mode, oid)) => match checkout::worktree_state(work_tree, entry, index.mtime)? {
+                WorktreeState::Missing => 'D',
+                WorktreeState::Modified => 'M',
+                WorktreeState::Unchanged if (entry.mode, entry.hash) != (mode, oid) => 'M',
+                WorktreeState::Unchanged => continue,
+            },
+        };
+        changes.push((status, path));
+    }
+    changes.extend(target.keys().filter(|path| !staged.contains(path.as_slice())).map(|path| ('D', path.clone())));
+    changes.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(changes)
+}
+
+fn commit_snapshot(store: &ObjectStore, commit: &Oid) -> Result<Snapshot> {
+    let tree = repository::commit_tree_oid(store, commit)?;
+    repository::read_tree_recursive(store, &tree)
+}
+
+/// `1a2b3c4 Subject line`
+fn describe_commit(store: &ObjectStore, oid: &Oid) -> Result<String> {
+    let subject = match store.read(oid)? {
+        GitObject::Commit(commit) => String::from_utf8_lossy(&commit.message).lines().next().unwrap_or("").to_string(),
+        _ => String::new(),
+    };
+    Ok(format!("{} {}", revision::abbreviate(store, oid, 7)?, subject))
+}
+
+fn detached_advice(spec: &str) -> String {
+    format!(
+        "Note: switching to '{}'.
+
+You are in 'detached HEAD' state. You can look around, make experimental
+changes and commit them, and you can discard any commits you make in this
+state without impacting any branches by switching back to a branch.
+
+If you want to create a new branch to retain commits you create, you may
+do so (now or later) by using -c with the switch command. Example:
+
+  rusgit switch -c <new-branch-name>
+
+Or undo this operation with:
+
+  rusgit switch -
+
+Turn off this advice by setting config variable advice.detachedHead to false
+",
+        spec
+    )
+}
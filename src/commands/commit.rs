@@ -1,44 +1,59 @@
 // src/commands/commit.rs
 
 use crate::index::Index;
-use crate::repository;
+use crate::objects;
+use crate::repository::{self, Repository};
 use anyhow::Result;
-use std::path::Path;
 
-pub fn commit(message: String) -> Result<()> {
-    let git_dir = Path::new(".git");
-    let index_path = git_dir.join("index");
+pub fn commit(repo: &Repository, message: String) -> Result<()> {
+    repo.require_work_tree()?;
+    let git_dir = repo.git_dir();
+    let index_path = repo.index_path();
 
     // 1. Read the index.
-    let index = Index::from_path(&index_path)?;
+    let mut index = Index::from_path(&index_path)?;
     if index.entries.is_empty() {
         println!("Nothing to commit, working tree clean");
         return Ok(());
     }
 
     // 2. Get the parent commit hash from HEAD.
-    let parent_hash = repository::get_head_commit_hash(&git_dir)?;
+    let parent_hash = repository::get_head_commit_hash(git_dir)?;
 
-    // 3. Write a tree object from the index.
-    // We will reuse the write_tree logic, but in a real app, this would be a
-    // dedicated function `write_tree_from_index`. For now, `write_tree` from the
-    // filesystem is a close enough approximation if `add` was just run.
-    let tree_hash_bytes = crate::commands::write_tree::write_tree(&Path::new("."))?;
+    // 3. Write the tree objects for exactly what is staged in the index.
+    let store = repo.odb();
+    let tree_hash_bytes = crate::commands::write_tree::write_tree_from_index(&mut index, store)?;
     let tree_hash = hex::encode(tree_hash_bytes);
 
+    // The index persists between commits, so an unchanged tree means nothing
+    // new was staged.
+    if let Some(parent) = &parent_hash
+        && repository::commit_tree_oid(store, &objects::parse_oid(parent)?)? == tree_hash_bytes
+    {
+        println!("Nothing to commit, working tree clean");
+        return Ok(());
+    }
+
     // 4. Create the commit object, using our plumbing command's logic.
+    let reflog_message = match parent_hash {
+        Some(_) => format!("commit: {}", message.lines().next().unwrap_or("")),
+        None => format!("commit (initial): {}", message.lines().next().unwrap_or("")),
+    };
     let commit_hash_bytes =
-        crate::commands::commit_tree::commit_tree(tree_hash, parent_hash, message)?;
+        crate::commands::commit_tree::commit_tree(repo, tree_hash, parent_hash.iter().cloned().collect(), message)?;
     let commit_hash = hex::encode(commit_hash_bytes);
 
-    // 5. Update the current branch (HEAD) to point to the new commit.
-    repository::update_head(&git_dir, &commit_hash)?;
+    // 5. Update the current branch (HEAD) to point to the new commit, provided
+    // nobody else moved it while we were working.
+    repository::update_head(git_dir, &commit_hash, parent_hash.as_deref(), &reflog_message)?;
 
-    println!("Committed to main with hash {}", commit_hash);
+    let target = repository::current_branch(git_dir)?.unwrap_or_else(|| "detached HEAD".to_string());
+    println!("Committed to {} with hash {}", target, commit_hash);
 
-    // Optional: Clear the index after a successful commit.
-    // A real Git implementation does this.
-    let _ = std::fs::remove_file(&index_path);
+    // 6. Keep the index: it now matches the committed tree and is the baseline
+    // the next `add` builds on. Writing it back also saves the refreshed
+    // cached tree.
+    index.write(&index_path)?;
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,108 @@
+// src/commands/config.rs
+
+use crate::config::{self, Config, ConfigLevel};
+use crate::repository::Repository;
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+/// Which config files `config` reads and writes.
+#[derive(Debug)]
+pub enum ConfigScope {
+    /// Read every level; write the repository's config.
+    All,
+    Level(ConfigLevel),
+    File(PathBuf),
+}
+
+#[derive(Debug)]
+pub enum ConfigAction {
+    Get(String),
+    GetAll(String),
+    Set(String, String),
+    Add(String, String),
+    Unset(String),
+    UnsetAll(String),
+    List,
+}
+
+impl ConfigAction {
+    /// Works out what to do from `config`'s flags and arguments:
+    /// `<key>` reads, `<key> <value>` writes.
+    pub fn from_flags(
+        key: Option<String>,
+        value: Option<String>,
+        get_all: bool,
+        add: bool,
+        unset: bool,
+        unset_all: bool,
+        list: bool,
+    ) -> Result<Self> {
+        Ok(match (key, value) {
+            (None, None) if list => ConfigAction::List,
+            (Some(key), None) if get_all => ConfigAction::GetAll(key),
+            (Some(key), None) if unset => ConfigAction::Unset(key),
+            (Some(key), None) if unset_all => ConfigAction::UnsetAll(key),
+            (Some(key), Some(value)) if add => ConfigAction::Add(key, value),
+            (Some(key), None) if !add => ConfigAction::Get(key),
+            (Some(key), Some(value)) if !(get_all || unset || unset_all || list) => {
+                ConfigAction::Set(key, value)
+            }
+            _ => bail!("usage: config [--global | --system | --local | -f <file>] (<key> [<value>] | --get-all <key> | --add <key> <value> | --unset[-all] <key> | -l)"),
+        })
+    }
+}
+
+pub fn config(repo: Option<&Repository>, scope: ConfigScope, action: ConfigAction, show_origin: bool) -> Result<()> {
+    let git_dir = repo.map(|repo| repo.git_dir());
+    let read = || match &scope {
+        ConfigScope::All => Config::load(git_dir),
+        ConfigScope::Level(level) => Config::load_level(*level, git_dir),
+        ConfigScope::File(path) => Config::load_from(path, ConfigLevel::Local, git_dir),
+    };
+    let write_path = || match &scope {
+        ConfigScope::All => config::write_path(ConfigLevel::Local, git_dir),
+        ConfigScope::Level(level) => config::write_path(*level, git_dir),
+        ConfigScope::File(path) => Ok(path.clone()),
+    };
+
+    match action {
+        ConfigAction::Get(key) => match read()?.get(&key) {
+            Some(value) => println!("{}", value),
+            // Like git, a missing key is reported only through the exit status.
+            None => std::process::exit(1),
+        },
+        ConfigAction::GetAll(key) => {
+            let config = read()?;
+            let values = config.get_all(&key);
+            if values.is_empty() {
+                std::process::exit(1);
+            }
+            for value in values {
+                println!("{}", value);
+            }
+        }
+        ConfigAction::Set(key, value) => config::set_value(&write_path()?, &key, &value, false)?,
+        ConfigAction::Add(key, value) => config::set_value(&write_path()?, &key, &value, true)?,
+        ConfigAction::Unset(key) => unset(&write_path()?, &key, false)?,
+        ConfigAction::UnsetAll(key) => unset(&write_path()?, &key, true)?,
+        ConfigAction::List => {
+            for entry in read()?.entries() {
+                if show_origin {
+                    print!("file:{}\t", entry.origin.display());
+                }
+                match &entry.value {
+                    Some(value) => println!("{}={}", entry.key, value),
+                    None => println!("{}", entry.key),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn unset(path: &Path, key: &str, all: bool) -> Result<()> {
+    if config::unset_value(path, key, all)? == 0 {
+        bail!("key not found: {}", key);
+    }
+    Ok(())
+}
@@ -0,0 +1,545 @@
+// src/commands/diff.rs
+
+use crate::diff::rename::{self, BreakOptions, RenameOptions};
+use crate::diff::stat::{self, FileStat};
+use crate::diff::tree::{self, Entry, FileChange, Snapshot, Status};
+use crate::diff::{self, unified, Algorithm};
+use crate::index::{self, Index};
+use crate::objects::{self, GitObject, ObjectKind, Oid};
+use crate::odb::{self, ObjectStore};
+use crate::repository::{self, Repository};
+use crate::revision;
+use anyhow::{bail, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const NULL_OID: Oid = [0; 20];
+
+/// Options for `diff`, mirroring its flags.
+#[derive(Debug)]
+pub struct DiffOptions {
+    pub algorithm: Algorithm,
+    /// Unchanged lines shown around each change.
+    pub context: usize,
+    /// How renamed and copied files are found, or None to show them as
+    /// deleted and added.
+    pub renames: Option<RenameOptions>,
+    /// Whether, and how, modified files that were mostly rewritten are
+    /// broken up, both for rename detection and to show them as rewrites.
+    pub breaks: Option<BreakOptions>,
+    /// A line per file instead of a patch.
+    pub summary: Option<Summary>,
+    /// Show how many lines each file gained and lost, instead of a patch.
+    pub stat: bool,
+    pub numstat: bool,
+    /// `--summary`: a line for each created, deleted, renamed, copied or
+    /// rewritten file and each mode change, instead of a patch.
+    pub condensed_summary: bool,
+}
+
+/// The one-line-per-file formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Summary {
+    /// `--name-only`: just the path.
+    NameOnly,
+    /// `--name-status`: the status letter (A, D, M, T, or R/C with the
+    /// similarity) and the path, or both paths of a rename or copy.
+    NameStatus,
+    /// `--raw`: both modes and abbreviated ids, then the status and paths.
+    Raw,
+}
+
+/// The rename detection `diff.renames` asks for: on unless it is false, and
+/// looking for copies too if it is `copies`.
+pub fn configured_renames(repo: &Repository) -> Result<Option<RenameOptions>> {
+    match repo.config().get("diff.renames") {
+        Some(value) if value.eq_ignore_ascii_case("copies") || value.eq_ignore_ascii_case("copy") => {
+            Ok(Some(RenameOptions { copies: true, ..RenameOptions::default() }))
+        }
+        Some(_) => Ok(repo.config().get_bool("diff.renames")?.unwrap_or(true).then(RenameOptions::default)),
+        None => Ok(Some(RenameOptions::default())),
+    }
+}
+
+/// Shows what changed between two snapshots:
+///
+/// - no revisions: the working tree against the index (what `add` would
+///   stage), or with `cached` the index against HEAD (what `commit` would
+///   record);
+/// - one revision: the working tree, or with `cached` the index, against
+///   that commit;
+/// - two revisions (or `A..B`): the second commit's tree against the
+///   first's, and for `A...B` against where they forked.
+///
+/// Only the working tree files the index tracks are compared. With `paths`,
+/// only files at or below those paths are shown. Deleted and added files
+/// are then paired up into renames and copies as `options` asks, and the
+/// changes shown as a patch or in the formats `options` picks.
+pub fn diff(
+    repo: &Repository,
+    cached: bool,
+    revisions: &[String],
+    paths: &[PathBuf],
+    options: &DiffOptions,
+) -> Result<()> {
+    let store = repo.odb();
+    let paths = paths
+        .iter()
+        .map(|path| Ok(index::path_bytes(&repo.worktree_path(path)?).to_vec()))
+        .collect::<Result<Vec<_>>>()?;
+
+    // 1. Work out what changed. Files read from the working tree are kept by
+    // id, so they aren't read twice.
+    let mut worktree_content = HashMap::new();
+    let mut unmerged = BTreeSet::new();
+    let changes = match (revisions, cached) {
+        ([], false) => {
+            let index;
+            (index, unmerged) = read_index(repo)?;
+            unmerged.retain(|path| tree::is_selected(path, &paths));
+            let worktree = read_worktree(repo, &mut worktree_content)?;
+            tree::diff_snapshots(&index, &worktree, &paths)
+        }
+        ([], true) => tree::diff_snapshots(&head_snapshot(repo)?, &read_index(repo)?.0, &paths),
+        ([rev], _) if rev.contains("..") => {
+            let (from, to) = resolve_range(repo, rev)?;
+            diff_commits(store, &from, &to, &paths)?
+        }
+        ([rev], false) => {
+            let commit = revision::resolve_commit(repo, rev)?;
+            let worktree = read_worktree(repo, &mut worktree_content)?;
+            tree::diff_snapshots(&commit_snapshot(store, &commit)?, &worktree, &paths)
+        }
+        ([rev], true) => {
+            let commit = revision::resolve_commit(repo, rev)?;
+            tree::diff_snapshots(&commit_snapshot(store, &commit)?, &read_index(repo)?.0, &paths)
+        }
+        ([from, to], false) => {
+            let from = revision::resolve_commit(repo, from)?;
+            let to = revision::resolve_commit(repo, to)?;
+            diff_commits(store, &from, &to, &paths)?
+        }
+        ([_, _], true) => bail!("--cached compares the index with a commit, so takes at most one"),
+        _ => bail!("usage: diff [--cached] [<commit> [<commit>]] [-- <path>...]"),
+    };
+    // Git hashes added working tree files when it looks for renames, so
+    // `--raw` then shows their ids.
+    let hashed_added = options.renames.is_some_and(|renames| {
+        let is_source = |change: &FileChange| match change.status {
+            Status::Deleted => true,
+            Status::Modified | Status::TypeChanged => renames.copies,
+            _ => false,
+        };
+        changes.iter().any(is_source) && changes.iter().any(|change| change.status == Status::Added)
+    });
+    let mut read = |entry: &Entry| load(store, entry, &worktree_content);
+    let changes = if options.renames.is_some() || options.breaks.is_some() {
+        rename::detect(changes, options.renames.as_ref(), options.breaks.as_ref(), &mut read)?
+    } else {
+        changes
+    };
+
+    // 2. Show them.
+    let mut out = BufWriter::new(io::stdout().lock());
+    let result = (|| -> Result<()> {
+        let patch = options.summary.is_none() && !options.stat && !options.numstat && !options.condensed_summary;
+        for path in &unmerged {
+            let path = index::quote_path(path);
+            match options.summary {
+                None if patch => writeln!(out, "* Unmerged path {}", path)?,
+                Some(Summary::NameOnly) => writeln!(out, "{}", path)?,
+                Some(Summary::NameStatus) => writeln!(out, "U\t{}", path)?,
+                _ => {}
+            }
+        }
+        if let Some(summary) = options.summary {
+            for change in &changes {
+                write_summary(&mut out, store, summary, change, &worktree_content, hashed_added)?;
+            }
+        }
+        if options.stat || options.numstat {
+            let stats = changes
+                .iter()
+                .map(|change| file_stat(change, options.algorithm, &mut read))
+                .collect::<Result<Vec<_>>>()?;
+            if options.numstat {
+                stat::write_numstat(&mut out, &stats)?;
+            }
+            if options.stat {
+                stat::write_stat(&mut out, &stats, stat::terminal_width())?;
+            }
+        }
+        if options.condensed_summary {
+            for change in &changes {
+                write_condensed_summary(&mut out, change)?;
+            }
+        }
+        if patch {
+            for change in &changes {
+                let file = |entry: &Option<Entry>| -> Result<Option<File>> {
+                    let Some(entry) = entry else {
+                        return Ok(None);
+                    };
+                    Ok(Some(File { entry: entry.clone(), content: read(entry)? }))
+                };
+                let (old, new) = (file(&change.old)?, file(&change.new)?);
+                if change.status == Status::TypeChanged {
+                    // A file replaced by a symlink (or the other way round)
+                    // is shown as one deleted and the other added.
+                    write_patch(&mut out, store, options, Status::Deleted, old, None)?;
+                    write_patch(&mut out, store, options, Status::Added, None, new)?;
+                } else {
+                    write_patch(&mut out, store, options, change.status, old, new)?;
+                }
+            }
+        }
+        out.flush()?;
+        Ok(())
+    })();
+    match result {
+        // Stop quietly when the reader goes away, e.g. `diff | head`.
+        Err(err) if err.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) => Ok(()),
+        result => result,
+    }
+}
+
+/// Compares two commits' trees.
+fn diff_commits(store: &ObjectStore, from: &Oid, to: &Oid, paths: &[Vec<u8>]) -> Result<Vec<FileChange>> {
+    let from = repository::commit_tree_oid(store, from)?;
+    let to = repository::commit_tree_oid(store, to)?;
+    tree::diff_trees(store, Some(&from), Some(&to), paths)
+}
+
+/// Writes one `--name-only`, `--name-status` or `--raw` line.
+fn write_summary(
+    out: &mut dyn Write,
+    store: &ObjectStore,
+    summary: Summary,
+    change: &FileChange,
+    worktree_content: &HashMap<Oid, Vec<u8>>,
+    hashed_added: bool,
+) -> Result<()> {
+    let paths = match (&change.status, &change.old, &change.new) {
+        (Status::Renamed(_) | Status::Copied(_), Some(old), Some(new)) => {
+            format!("{}\t{}", index::quote_path(&old.path), index::quote_path(&new.path))
+        }
+        _ => index::quote_path(change.path()),
+    };
+    match summary {
+        Summary::NameOnly => writeln!(out, "{}", index::quote_path(change.path()))?,
+        Summary::NameStatus => writeln!(out, "{}\t{}", change.status.label(), paths)?,
+        Summary::Raw => {
+            let mode = |entry: &Option<Entry>| entry.as_ref().map_or(0, |entry| entry.mode);
+            let old_oid = change.old.as_ref().map_or(NULL_OID, |entry| entry.oid);
+            // Files read from the working tree have no id to show, unless
+            // rename detection or breaking them up hashed them.
+            let hashed = match change.status {
+                Status::Modified | Status::TypeChanged => false,
+                Status::Added => hashed_added,
+                _ => true,
+            };
+            let new_oid = match &change.new {
+                Some(entry) if hashed || !worktree_content.contains_key(&entry.oid) => entry.oid,
+                _ => NULL_OID,
+            };
+            writeln!(
+                out,
+                ":{:06o} {:06o} {} {} {}\t{}",
+                mode(&change.old),
+                mode(&change.new),
+                abbreviate(store, &old_oid)?,
+                abbreviate(store, &new_oid)?,
+                change.status.label(),
+                paths
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Counts the lines a change adds and removes, or for a binary file its
+/// sizes.
+fn file_stat(
+    change: &FileChange,
+    algorithm: Algorithm,
+    read: &mut dyn FnMut(&Entry) -> Result<Vec<u8>>,
+) -> Result<FileStat> {
+    let name = match (&change.status, &change.old, &change.new) {
+        (Status::Renamed(_) | Status::Copied(_), Some(old), Some(new)) => {
+            stat::rename_name(&index::quote_path(&old.path), &index::quote_path(&new.path))
+        }
+        _ => index::quote_path(change.path()),
+    };
+    let old = change.old.as_ref().map(&mut *read).transpose()?.unwrap_or_default();
+    let new = change.new.as_ref().map(&mut *read).transpose()?.unwrap_or_default();
+    let same = change.old.as_ref().map(|entry| entry.oid) == change.new.as_ref().map(|entry| entry.oid);
+    if diff::is_binary(&old) || diff::is_binary(&new) {
+        let (added, deleted) = if same { (0, 0) } else { (new.len(), old.len()) };
+        return Ok(FileStat { name, added, deleted, binary: true });
+    }
+    let (mut added, mut deleted) = (0, 0);
+    if let Status::Rewritten(_) = change.status {
+        // A rewrite counts as every line removed and added again.
+        return Ok(FileStat { name, added: diff::split_lines(&new).len(), deleted: diff::split_lines(&old).len(), binary: false });
+    }
+    if !same {
+        let (old_lines, new_lines) = (diff::split_lines(&old), diff::split_lines(&new));
+        for change in diff::diff_lines(&old_lines, &new_lines, algorithm) {
+            added += change.new_len;
+            deleted += change.old_len;
+        }
+    }
+    Ok(FileStat { name, added, deleted, binary: false })
+}
+
+/// One side of a changed file.
+struct File {
+    entry: Entry,
+    content: Vec<u8>,
+}
+
+/// Writes the `diff --git` header and hunks for one file. `None` is a side
+/// where the file doesn't exist.
+fn write_patch(
+    out: &mut dyn Write,
+    store: &ObjectStore,
+    options: &DiffOptions,
+    status: Status,
+    old: Option<File>,
+    new: Option<File>,
+) -> Result<()> {
+    // Paths with unusual bytes are quoted whole, prefix included, as git
+    // does: `"a/n\351"`.
+    let old_path = old.as_ref().or(new.as_ref()).map_or(&[][..], |file| file.entry.path.as_slice());
+    let new_path = new.as_ref().or(old.as_ref()).map_or(&[][..], |file| file.entry.path.as_slice());
+    let (old_prefixed, new_prefixed) = (quote_prefixed("a/", old_path), quote_prefixed("b/", new_path));
+    writeln!(out, "diff --git {} {}", old_prefixed, new_prefixed)?;
+    match (&old, &new) {
+        (None, Some(new)) => writeln!(out, "new file mode {:06o}", new.entry.mode)?,
+        (Some(old), None) => writeln!(out, "deleted file mode {:06o}", old.entry.mode)?,
+        (Some(old), Some(new)) if old.entry.mode != new.entry.mode => {
+            writeln!(out, "old mode {:06o}", old.entry.mode)?;
+            writeln!(out, "new mode {:06o}", new.entry.mode)?;
+        }
+        _ => {}
+    }
+    match status {
+        Status::Renamed(similarity) => {
+            writeln!(out, "similarity index {}%", similarity)?;
+            writeln!(out, "rename from {}", index::quote_path(old_path))?;
+            writeln!(out, "rename to {}", index::quote_path(new_path))?;
+        }
+        Status::Copied(similarity) => {
+            writeln!(out, "similarity index {}%", similarity)?;
+            writeln!(out, "copy from {}", index::quote_path(old_path))?;
+            writeln!(out, "copy to {}", index::quote_path(new_path))?;
+        }
+        Status::Rewritten(dissimilarity) => writeln!(out, "dissimilarity index {}%", dissimilarity)?,
+        _ => {}
+    }
+
+    let old_oid = old.as_ref().map_or(NULL_OID, |file| file.entry.oid);
+    let new_oid = new.as_ref().map_or(NULL_OID, |file| file.entry.oid);
+    if old_oid == new_oid {
+        // Only the mode or the path changed.
+        return Ok(());
+    }
+    write!(out, "index {}..{}", abbreviate(store, &old_oid)?, abbreviate(store, &new_oid)?)?;
+    match (&old, &new) {
+        (Some(old), Some(new)) if old.entry.mode == new.entry.mode => writeln!(out, " {:06o}", old.entry.mode)?,
+        _ => writeln!(out)?,
+    }
+
+    let old_name = if old.is_some() { old_prefixed } else { "/dev/null".to_string() };
+    let new_name = if new.is_some() { new_prefixed } else { "/dev/null".to_string() };
+    let old_content = old.as_ref().map_or(&[][..], |file| &file.content);
+    let new_content = new.as_ref().map_or(&[][..], |file| &file.content);
+    if diff::is_binary(old_content) || diff::is_binary(new_content) {
+        writeln!(out, "Binary files {} and {} differ", old_name, new_name)?;
+        return Ok(());
+    }
+
+    let old_lines = diff::split_lines(old_content);
+    let new_lines = diff::split_lines(new_content);
+    let changes = match status {
+        // A rewrite is shown as one hunk replacing every line.
+        Status::Rewritten(_) => vec![diff::Change {
+            old_start: 0,
+            old_len: old_lines.len(),
+            new_start: 0,
+            new_len: new_lines.len(),
+        }],
+        _ => diff::diff_lines(&old_lines, &new_lines, options.algorithm),
+    };
+    if !changes.is_empty() {
+        writeln!(out, "--- {}", old_name)?;
+        writeln!(out, "+++ {}", new_name)?;
+        let context = if let Status::Rewritten(_) = status { 0 } else { options.context };
+        unified::write_hunks(out, &old_lines, &new_lines, &changes, context)?;
+    }
+    Ok(())
+}
+
+/// Writes the `--summary` lines for one change, if it has any:
+/// ` create mode 100644 path`, ` rename old => new (90%)`,
+/// ` rewrite path (75%)`, ` mode change 100644 => 100755 path` and so on.
+fn write_condensed_summary(out: &mut dyn Write, change: &FileChange) -> Result<()> {
+    let mode_change = |out: &mut dyn Write, show_path: bool| -> Result<()> {
+        if let (Some(old), Some(new)) = (&change.old, &change.new)
+            && old.mode != new.mode
+        {
+            write!(out, " mode change {:06o} => {:06o}", old.mode, new.mode)?;
+            if show_path {
+                write!(out, " {}", index::quote_path(&new.path))?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    };
+    match (change.status, &change.old, &change.new) {
+        (Status::Added, _, Some(new)) => writeln!(out, " create mode {:06o} {}", new.mode, index::quote_path(&new.path))?,
+        (Status::Deleted, Some(old), _) => writeln!(out, " delete mode {:06o} {}", old.mode, index::quote_path(&old.path))?,
+        (Status::Renamed(similarity) | Status::Copied(similarity), Some(old), Some(new)) => {
+            let kind = if let Status::Renamed(_) = change.status { "rename" } else { "copy" };
+            let names = stat::rename_name(&index::quote_path(&old.path), &index::quote_path(&new.path));
+            writeln!(out, " {} {} ({}%)", kind, names, similarity)?;
+            mode_change(out, false)?;
+        }
+        (Status::Rewritten(dissimilarity), _, _) => {
+            writeln!(out, " rewrite {} ({}%)", index::quote_path(change.path()), dissimilarity)?;
+            mode_change(out, false)?;
+        }
+        _ => mode_change(out, true)?,
+    }
+    Ok(())
+}
+
+fn quote_prefixed(prefix: &str, path: &[u8]) -> String {
+    index::quote_path(&[prefix.as_bytes(), path].concat())
+}
+
+/// Reads a file's content from the working tree copy if there is one, or
+/// from the object store. A submodule is shown as the commit it is at.
+fn load(store: &ObjectStore, entry: &Entry, worktree: &HashMap<Oid, Vec<u8>>) -> Result<Vec<u8>> {
+    if let Some(content) = worktree.get(&entry.oid) {
+        return Ok(content.clone());
+    }
+    if entry.mode == 0o160000 {
+        return Ok(format!("Subproject commit {}\n", hex::encode(entry.oid)).into_bytes());
+    }
+    match store.read(&entry.oid)? {
+        GitObject::Blob(blob) => Ok(blob.content),
+        other => bail!("{} is a {}, not a blob", String::from_utf8_lossy(&entry.path), other.kind()),
+    }
+}
+
+fn abbreviate(store: &ObjectStore, oid: &Oid) -> Result<String> {
+    if *oid == NULL_OID {
+        return Ok("0".repeat(7));
+    }
+    revision::abbreviate(store, oid, 7)
+}
+
+/// The merged entries of the index, and the paths with conflicts.
+fn read_index(repo: &Repository) -> Result<(Snapshot, BTreeSet<Vec<u8>>)> {
+    let index = Index::from_path(&repo.index_path())?;
+    let mut files = Snapshot::new();
+    let mut unmerged = BTreeSet::new();
+    for entry in &index.entries {
+        let path = index::path_bytes(&entry.path).to_vec();
+        if entry.stage == 0 {
+            files.insert(path, (entry.mode, entry.hash));
+        } else {
+            unmerged.insert(path);
+        }
+    }
+    Ok((files, unmerged))
+}
+
+fn head_snapshot(repo: &Repository) -> Result<Snapshot> {
+    match repository::get_head_commit_hash(repo.git_dir())? {
+        Some(hash) => commit_snapshot(repo.odb(), &objects::parse_oid(&hash)?),
+        None => Ok(Snapshot::new()),
+    }
+}
+
+fn commit_snapshot(store: &ObjectStore, commit: &Oid) -> Result<Snapshot> {
+    let tree = repository::commit_tree_oid(store, commit)?;
+    repository::read_tree_recursive(store, &tree)
+}
+
+/// The two commits `A..B` compares, or for `A...B` the fork point and `B`.
+/// A missing side is HEAD.
+fn resolve_range(repo: &Repository, spec: &str) -> Result<(Oid, Oid)> {
+    let (from, to, symmetric) = match spec.split_once("...") {
+        Some((from, to)) => (from, to, true),
+        None => {
+            let (from, to) = spec.split_once("..").unwrap_or((spec, ""));
+            (from, to, false)
+        }
+    };
+    let resolve = |spec: &str| revision::resolve_commit(repo, if spec.is_empty() { "HEAD" } else { spec });
+    let (from, to) = (resolve(from)?, resolve(to)?);
+    if !symmetric {
+        return Ok((from, to));
+    }
+    match revision::merge_bases(repo.odb(), &from, &to)?.first() {
+        Some(base) => Ok((*base, to)),
+        None => bail!("{}: no merge base", spec),
+    }
+}
+
+/// The working tree copies of the files the index tracks. Files whose stat
+/// data still matches their index entry are taken to be unchanged; the rest
+/// are read and hashed, and their content kept in `content` by id.
+fn read_worktree(repo: &Repository, content: &mut HashMap<Oid, Vec<u8>>) -> Result<Snapshot> {
+    let work_tree = repo.require_work_tree()?;
+    let index = Index::from_path(&repo.index_path())?;
+    let mut files = Snapshot::new();
+    for entry in index.entries.iter().filter(|entry| entry.stage == 0) {
+        let path = index::path_bytes(&entry.path).to_vec();
+        if entry.mode == 0o160000 {
+            files.insert(path, (entry.mode, entry.hash));
+            continue;
+        }
+        let Some((mode, data)) = read_file(&work_tree.join(&entry.path), |metadata| {
+            entry.stat_matches(metadata) && !entry.is_racy(index.mtime)
+        })?
+        else {
+            continue;
+        };
+        match data {
+            None => {
+                files.insert(path, (mode, entry.hash));
+            }
+            Some(data) => {
+                let oid = odb::hash_object(ObjectKind::Blob, &data);
+                files.insert(path, (mode, oid));
+                content.insert(oid, data);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Reads a file or symlink (as its target) from the working tree, returning
+/// its mode and content, or no content if `unchanged` says it can be
+/// skipped. None means the file is gone.
+fn read_file(path: &Path, unchanged: impl Fn(&fs::Metadata) -> bool) -> Result<Option<(u32, Option<Vec<u8>>)>> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) if !metadata.is_dir() => metadata,
+        _ => return Ok(None),
+    };
+    let mode = index::mode_from_metadata(&metadata);
+    if unchanged(&metadata) {
+        return Ok(Some((mode, None)));
+    }
+    let data = if metadata.file_type().is_symlink() {
+        index::path_bytes(&fs::read_link(path)?).to_vec()
+    } else {
+        fs::read(path)?
+    };
+    Ok(Some((mode, Some(data))))
+}
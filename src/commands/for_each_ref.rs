@@ -0,0 +1,218 @@
+// src/commands/for_each_ref.rs
+
+use crate::objects::{GitObject, Oid};
+use crate::odb::ObjectStore;
+use crate::refs;
+use crate::repository::Repository;
+use anyhow::{bail, Context, Result};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// A parsed `--format` string.
+enum Segment {
+    Literal(String),
+    // `deref` is set for `%(*name)`, which looks at what a tag points to.
+    Atom { name: String, deref: bool },
+}
+
+/// Prints refs matching `patterns` (all refs if empty) using `format`.
+///
+/// A pattern matches a ref when it is a prefix of it up to a `/`, or when it
+/// matches the whole name as a glob. `sort` is an atom name, prefixed with
+/// `-` for descending order; refs are sorted by name otherwise.
+pub fn for_each_ref(
+    repo: &Repository,
+    patterns: &[String],
+    format: &str,
+    sort: Option<&str>,
+    count: Option<usize>,
+) -> Result<()> {
+    let segments = parse_format(format)?;
+    let store = repo.odb();
+    let head = refs::resolve_ref_name(repo.git_dir(), "HEAD")?.0;
+
+    let mut matching: Vec<(String, Oid)> = refs::list_refs(repo.git_dir(), "refs/")?
+        .into_iter()
+        .filter(|(name, _)| patterns.is_empty() || patterns.iter().any(|p| pattern_matches(name, p)))
+        .collect();
+
+    if let Some(key) = sort {
+        let (key, descending) = match key.strip_prefix('-') {
+            Some(key) => (key, true),
+            None => (key, false),
+        };
+        let mut keyed = Vec::with_capacity(matching.len());
+        for (name, oid) in matching {
+            let value = atom_value(store, &name, &oid, &head, key, false)?;
+            keyed.push((value, name, oid));
+        }
+        keyed.sort_by(|a, b| {
+            let ordering = compare_values(&a.0, &b.0).then_with(|| a.1.cmp(&b.1));
+            if descending { ordering.reverse() } else { ordering }
+        });
+        matching = keyed.into_iter().map(|(_, name, oid)| (name, oid)).collect();
+    }
+
+    for (name, oid) in matching.into_iter().take(count.unwrap_or(usize::MAX)) {
+        let mut line = String::new();
+        for segment in &segments {
+            match segment {
+                Segment::Literal(text) => line.push_str(text),
+                Segment::Atom { name: atom, deref } => {
+                    line.push_str(&atom_value(store, &name, &oid, &head, atom, *deref)?)
+                }
+            }
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn parse_format(format: &str) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut rest = format;
+    while let Some(pos) = rest.find('%') {
+        literal.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        if let Some(after) = rest.strip_prefix('%') {
+            literal.push('%');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('(') {
+            let end = after.find(')').context("Unterminated %( in format")?;
+            let atom = &after[..end];
+            if !literal.is_empty() {
+                segments.push(Segment::Literal(std::mem::take(&mut literal)));
+            }
+            segments.push(match atom.strip_prefix('*') {
+                Some(name) => Segment::Atom { name: name.to_string(), deref: true },
+                None => Segment::Atom { name: atom.to_string(), deref: false },
+            });
+            rest = &after[end + 1..];
+        } else if rest.len() >= 2 && let Ok(byte) = u8::from_str_radix(&rest[..2], 16) {
+            // `%xx` inserts the byte with that hex value, e.g. `%00` or `%09`.
+            literal.push(byte as char);
+            rest = &rest[2..];
+        } else {
+            literal.push('%');
+        }
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    Ok(segments)
+}
+
+/// Evaluates one atom for a ref. Dates are given in git's raw
+/// `<seconds> <timezone>` form.
+fn atom_value(
+    store: &ObjectStore,
+    refname: &str,
+    oid: &Oid,
+    head: &str,
+    atom: &str,
+    deref: bool,
+) -> Result<String> {
+    match atom {
+        "refname" => return Ok(refname.to_string()),
+        "refname:short" => return Ok(refs::shorten_ref_name(refname).to_string()),
+        "HEAD" => return Ok(if refname == head { "*" } else { " " }.to_string()),
+        _ => {}
+    }
+
+    // Everything else describes the object, or for `*` atoms the object a tag
+    // points to. `*` atoms are empty for refs that aren't annotated tags.
+    let object_id = if deref {
+        let peeled = refs::peel(store, oid)?;
+        if peeled == *oid {
+            return Ok(String::new());
+        }
+        peeled
+    } else {
+        *oid
+    };
+    let object = store.read(&object_id)?;
+    let hash = hex::encode(object_id);
+
+    let value = match atom {
+        "objectname" => hash,
+        "objectname:short" => hash[..7].to_string(),
+        "objecttype" => object.kind().to_string(),
+        "objectsize" => object.content().len().to_string(),
+        "subject" | "contents:subject" => subject(&message(&object)),
+        "body" | "contents:body" => body(&message(&object)).to_string(),
+        "contents" => message(&object).into_owned(),
+        _ => {
+            let Some((role, field)) = split_person_atom(atom) else {
+                bail!("unknown field name: {}", atom);
+            };
+            let ident = match (&object, role) {
+                (GitObject::Commit(commit), "author") => Some(&commit.author),
+                (GitObject::Commit(commit), "committer" | "creator") => Some(&commit.committer),
+                (GitObject::Tag(tag), "tagger" | "creator") => tag.tagger.as_ref(),
+                _ => None,
+            };
+            ident.map(|ident| ident_field(&String::from_utf8_lossy(ident), field)).unwrap_or_default()
+        }
+    };
+    Ok(value)
+}
+
+/// Splits `authorname`, `committeremail`, `taggerdate`, `creatordate` and the
+/// like into the person and the field.
+fn split_person_atom(atom: &str) -> Option<(&str, &str)> {
+    ["author", "committer", "tagger", "creator"].iter().find_map(|role| {
+        let field = atom.strip_prefix(role)?;
+        let field = if field.is_empty() { "ident" } else { field };
+        matches!(field, "ident" | "name" | "email" | "date").then_some((*role, field))
+    })
+}
+
+/// Picks a field out of an identity line: `Name <email> 1700000000 +0100`.
+fn ident_field(ident: &str, field: &str) -> String {
+    let (name, rest) = ident.split_once(" <").unwrap_or((ident, ""));
+    let (email, date) = rest.split_once("> ").unwrap_or((rest, ""));
+    match field {
+        "name" => name.to_string(),
+        "email" => format!("<{}>", email),
+        "date" => date.to_string(),
+        _ => ident.to_string(),
+    }
+}
+
+fn message(object: &GitObject) -> Cow<'_, str> {
+    match object {
+        GitObject::Commit(commit) => String::from_utf8_lossy(&commit.message),
+        GitObject::Tag(tag) => String::from_utf8_lossy(&tag.message),
+        _ => Cow::Borrowed(""),
+    }
+}
+
+/// The first paragraph of a message, joined into one line.
+fn subject(message: &str) -> String {
+    let paragraph = message.split("\n\n").next().unwrap_or("");
+    paragraph.lines().collect::<Vec<_>>().join(" ")
+}
+
+fn body(message: &str) -> &str {
+    message.split_once("\n\n").map(|(_, body)| body).unwrap_or("")
+}
+
+/// Numbers (sizes, dates) compare numerically, everything else as text.
+fn compare_values(a: &str, b: &str) -> Ordering {
+    let number = |value: &str| value.split(' ').next().and_then(|n| n.parse::<i64>().ok());
+    match (number(a), number(b)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        _ => a.cmp(b),
+    }
+}
+
+fn pattern_matches(name: &str, pattern: &str) -> bool {
+    if let Some(rest) = name.strip_prefix(pattern)
+        && (pattern.ends_with('/') || rest.is_empty() || rest.starts_with('/'))
+    {
+        return true;
+    }
+    refs::glob_match(pattern.as_bytes(), name.as_bytes())
+}
@@ -4,10 +4,11 @@ use std::fs;
 use std::path::Path;
 use anyhow::{Context, Result};
 
-pub fn init(path: &Path) -> Result<()> {
-    // 1. Create the main `.git` directory.
-    let git_dir = path.join(".git");
-    fs::create_dir(&git_dir)
+pub fn init(path: &Path, bare: bool) -> Result<()> {
+    // 1. Create the main `.git` directory. A bare repository has no working
+    // tree, so the repository data lives directly in `path`.
+    let git_dir = if bare { path.to_path_buf() } else { path.join(".git") };
+    fs::create_dir_all(&git_dir)
         .with_context(|| format!("Failed to create directory at: {:?}", git_dir))?;
 
     // 2. Create the `objects` directory.
@@ -15,7 +16,7 @@ pub fn init(path: &Path) -> Result<()> {
     fs::create_dir(&objects_dir)
         .with_context(|| format!("Failed to create directory at: {:?}", objects_dir))?;
 
-    // 3. Create the `refs` directory and its subdirectory `heads`.
+    // 3. Create the `refs` directory and its subdirectories `heads` and `tags`.
     let refs_dir = git_dir.join("refs");
     fs::create_dir(&refs_dir)
         .with_context(|| format!("Failed to create directory at: {:?}", refs_dir))?;
@@ -24,6 +25,10 @@ pub fn init(path: &Path) -> Result<()> {
     fs::create_dir(&heads_dir)
         .with_context(|| format!("Failed to create directory at: {:?}", heads_dir))?;
 
+    let tags_dir = refs_dir.join("tags");
+    fs::create_dir(&tags_dir)
+        .with_context(|| format!("Failed to create directory at: {:?}", tags_dir))?;
+
     // 4. Create the `HEAD` file.
     let head_file_path = git_dir.join("HEAD");
     // This file points to the default branch, which doesn't exist yet, but we
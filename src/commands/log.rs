@@ -0,0 +1,450 @@
+// src/commands/log.rs
+
+use crate::date::{self, DateFormat};
+use crate::graph::Graph;
+use crate::identity::Signature;
+use crate::objects::{self, Commit, ObjectKind, Oid};
+use crate::odb::ObjectStore;
+use crate::refs::{self, RefValue};
+use crate::repository::{self, Repository};
+use crate::revision;
+use crate::revwalk::{RevWalk, WalkOptions};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::io::{self, BufWriter, StdoutLock, Write};
+use std::path::PathBuf;
+
+/// How each commit is printed, chosen with `--pretty`, `--format` or
+/// `--oneline`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pretty {
+    /// `<hash> <subject>`
+    Oneline,
+    /// Hash, author and subject.
+    Short,
+    /// Hash, author, date and message. The default.
+    Medium,
+    /// Hash, author, committer and message.
+    Full,
+    /// Hash, author and committer with their dates, and message.
+    Fuller,
+    /// A template of `%` placeholders. With `terminated` (`tformat:`) each
+    /// commit ends with a newline; otherwise (`format:`) newlines only go
+    /// between commits.
+    Format { template: String, terminated: bool },
+}
+
+impl Pretty {
+    /// Parses a `--pretty` value: a format name, `format:<template>`,
+    /// `tformat:<template>`, or a bare template, which is taken as `tformat:`.
+    pub fn parse(value: &str) -> Result<Self> {
+        Ok(match value {
+            "oneline" => Pretty::Oneline,
+            "short" => Pretty::Short,
+            "medium" => Pretty::Medium,
+            "full" => Pretty::Full,
+            "fuller" => Pretty::Fuller,
+            _ => {
+                if let Some(template) = value.strip_prefix("format:") {
+                    Pretty::Format { template: template.to_string(), terminated: false }
+                } else if let Some(template) = value.strip_prefix("tformat:") {
+                    Pretty::Format { template: template.to_string(), terminated: true }
+                } else if value.contains('%') {
+                    Pretty::Format { template: value.to_string(), terminated: true }
+                } else {
+                    bail!("invalid --pretty format: {}", value);
+                }
+            }
+        })
+    }
+
+    // The multi-line formats put a blank line between commits.
+    fn blank_line_between(&self) -> bool {
+        matches!(self, Pretty::Short | Pretty::Medium | Pretty::Full | Pretty::Fuller)
+    }
+}
+
+/// Options for `log`, mirroring its flags.
+#[derive(Debug)]
+pub struct LogOptions {
+    pub pretty: Pretty,
+    /// Show abbreviated commit hashes, as `--oneline` does.
+    pub abbrev_commit: bool,
+    pub max_count: Option<usize>,
+    pub graph: bool,
+    pub first_parent: bool,
+    pub authors: Vec<String>,
+    pub grep: Vec<String>,
+    /// Dates in any form `date::parse_approxidate` understands.
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+/// Shows the commits reachable from `revisions` (HEAD if none), newest first.
+/// Revisions can be ranges like `main..feature`. With `paths`, only commits
+/// that change them are shown.
+pub fn log(repo: &Repository, revisions: &[String], paths: &[PathBuf], options: LogOptions) -> Result<()> {
+    let store = repo.odb();
+    let now = date::now();
+    let paths = paths
+        .iter()
+        .map(|path| match repo.work_tree() {
+            Some(_) => Ok(repo.worktree_path(path)?.to_string_lossy().into_owned()),
+            None => Ok(path.to_string_lossy().into_owned()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut walk = RevWalk::new(
+        store,
+        WalkOptions {
+            first_parent: options.first_parent,
+            paths,
+            since: options.since.as_deref().map(|since| date::parse_approxidate(since, now)).transpose()?,
+            until: options.until.as_deref().map(|until| date::parse_approxidate(until, now)).transpose()?,
+            authors: options.authors.clone(),
+            grep: options.grep.clone(),
+            topo_order: options.graph,
+        },
+    );
+
+    if revisions.is_empty() {
+        let head = repository::get_head_commit_hash(repo.git_dir())?.with_context(|| {
+            let branch = repository::current_branch(repo.git_dir()).ok().flatten().unwrap_or_default();
+            format!("your current branch '{}' does not have any commits yet", branch)
+        })?;
+        walk.push(objects::parse_oid(&head)?)?;
+    }
+    for spec in revisions {
+        let range = revision::parse_range(repo, spec)?;
+        for oid in range.include {
+            walk.push(revision::peel_to(store, &oid, ObjectKind::Commit)?)?;
+        }
+        for oid in range.exclude {
+            walk.hide(oid)?;
+        }
+    }
+
+    let mut printer = Printer {
+        repo,
+        store,
+        options: &options,
+        decorations: None,
+        out: BufWriter::new(io::stdout().lock()),
+        started: false,
+    };
+    match printer.print(&mut walk) {
+        // Stop quietly when the reader goes away, e.g. `log | head`.
+        Err(err) if err.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) => Ok(()),
+        result => result,
+    }
+}
+
+struct Printer<'a> {
+    repo: &'a Repository,
+    store: &'a ObjectStore,
+    options: &'a LogOptions,
+    // Ref names pointing at each commit, for `%d` and `%D`. Read on first use.
+    decorations: Option<HashMap<Oid, Vec<String>>>,
+    out: BufWriter<StdoutLock<'static>>,
+    // Whether anything has been printed, so the next line needs a newline
+    // before it.
+    started: bool,
+}
+
+impl Printer<'_> {
+    fn print(&mut self, walk: &mut RevWalk) -> Result<()> {
+        let mut graph = self.options.graph.then(Graph::new);
+        let mut shown = 0;
+        while self.options.max_count.is_none_or(|max| shown < max) {
+            let Some(oid) = walk.next_commit()? else {
+                break;
+            };
+            let commit = walk.commit(&oid).context("commit missing from walk")?;
+            let text = self.render(&oid, commit)?;
+
+            if let Some(graph) = &mut graph {
+                graph.update(&oid, walk.parents(&oid));
+            }
+            if shown > 0 && self.options.pretty.blank_line_between() {
+                let row = graph.as_mut().map(Graph::separator_row).unwrap_or_default();
+                self.line(&row)?;
+            }
+            let mut lines = text.split('\n');
+            match &mut graph {
+                Some(graph) => {
+                    // Rows making room for the commit come before its own row,
+                    // which its first line goes beside.
+                    loop {
+                        let (row, commit_row) = graph.next_row();
+                        if commit_row {
+                            self.line(&format!("{}{}", row, lines.next().unwrap_or_default()))?;
+                            break;
+                        }
+                        self.line(&row)?;
+                    }
+                    for line in lines {
+                        self.line(&format!("{}{}", graph.next_row().0, line))?;
+                    }
+                    while !graph.is_finished() {
+                        self.line(&graph.next_row().0)?;
+                    }
+                }
+                None => {
+                    for line in lines {
+                        self.line(line)?;
+                    }
+                }
+            }
+            shown += 1;
+        }
+
+        let terminated = !matches!(self.options.pretty, Pretty::Format { terminated: false, .. });
+        if self.started && terminated {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn line(&mut self, text: &str) -> io::Result<()> {
+        if self.started {
+            self.out.write_all(b"\n")?;
+        }
+        self.started = true;
+        self.out.write_all(text.as_bytes())
+    }
+
+    fn hash(&self, oid: &Oid) -> Result<String> {
+        if self.options.abbrev_commit {
+            revision::abbreviate(self.store, oid, 7)
+        } else {
+            Ok(hex::encode(oid))
+        }
+    }
+
+    /// A commit's text in the chosen format, without a final newline.
+    fn render(&mut self, oid: &Oid, commit: &Commit) -> Result<String> {
+        let author = Signature::parse(&String::from_utf8_lossy(&commit.author))?;
+        let committer = Signature::parse(&String::from_utf8_lossy(&commit.committer))?;
+        let message = String::from_utf8_lossy(&commit.message);
+        let person = |who: &Signature| format!("{} <{}>", who.name, who.email);
+        let when = |who: &Signature| date::format_date(who.timestamp, who.offset, DateFormat::Default);
+
+        let mut header = format!("commit {}\n", self.hash(oid)?);
+        if commit.parents.len() > 1 {
+            let parents = commit
+                .parents
+                .iter()
+                .map(|parent| revision::abbreviate(self.store, &objects::parse_oid(parent)?, 7))
+                .collect::<Result<Vec<_>>>()?;
+            header.push_str(&format!("Merge: {}\n", parents.join(" ")));
+        }
+
+        let text = match &self.options.pretty {
+            Pretty::Oneline => format!("{} {}", self.hash(oid)?, subject(&message)),
+            Pretty::Short => format!(
+                "{}Author: {}\n\n{}",
+                header,
+                person(&author),
+                indent(subject_paragraph(&message))
+            ),
+            Pretty::Medium => format!(
+                "{}Author: {}\nDate:   {}\n\n{}",
+                header,
+                person(&author),
+                when(&author),
+                indent(&message)
+            ),
+            Pretty::Full => format!(
+                "{}Author: {}\nCommit: {}\n\n{}",
+                header,
+                person(&author),
+                person(&committer),
+                indent(&message)
+            ),
+            Pretty::Fuller => format!(
+                "{}Author:     {}\nAuthorDate: {}\nCommit:     {}\nCommitDate: {}\n\n{}",
+                header,
+                person(&author),
+                when(&author),
+                person(&committer),
+                when(&committer),
+                indent(&message)
+            ),
+            Pretty::Format { template, .. } => {
+                let template = template.clone();
+                self.expand(&template, oid, commit, &author, &committer)?
+            }
+        };
+        Ok(text)
+    }
+
+    /// Fills in a `--format` template. Unknown placeholders are left as they
+    /// are.
+    fn expand(
+        &mut self,
+        template: &str,
+        oid: &Oid,
+        commit: &Commit,
+        author: &Signature,
+        committer: &Signature,
+    ) -> Result<String> {
+        let mut result = String::new();
+        let mut rest = template;
+        while let Some(pos) = rest.find('%') {
+            result.push_str(&rest[..pos]);
+            rest = &rest[pos + 1..];
+            let (value, used) = self.placeholder(rest, oid, commit, author, committer)?;
+            match value {
+                Some(value) => {
+                    result.push_str(&value);
+                    rest = &rest[used..];
+                }
+                None => result.push('%'),
+            }
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    /// Evaluates the placeholder at the start of `spec` (just after a `%`),
+    /// returning its value and how many bytes of `spec` it took up.
+    fn placeholder(
+        &mut self,
+        spec: &str,
+        oid: &Oid,
+        commit: &Commit,
+        author: &Signature,
+        committer: &Signature,
+    ) -> Result<(Option<String>, usize)> {
+        let abbrev = |hex: &str| -> Result<String> { revision::abbreviate(self.store, &objects::parse_oid(hex)?, 7) };
+        let Some(first) = spec.chars().next() else {
+            return Ok((None, 0));
+        };
+        let value = match first {
+            '%' => "%".to_string(),
+            'n' => "\n".to_string(),
+            'H' => hex::encode(oid),
+            'h' => revision::abbreviate(self.store, oid, 7)?,
+            'T' => commit.tree_hash.clone(),
+            't' => abbrev(&commit.tree_hash)?,
+            'P' => commit.parents.join(" "),
+            'p' => commit.parents.iter().map(|p| abbrev(p)).collect::<Result<Vec<_>>>()?.join(" "),
+            's' => subject(&String::from_utf8_lossy(&commit.message)),
+            'b' => body(&String::from_utf8_lossy(&commit.message)).to_string(),
+            'B' => String::from_utf8_lossy(&commit.message).into_owned(),
+            'd' | 'D' => {
+                let names = self.decorations(oid)?;
+                match (first, names.is_empty()) {
+                    (_, true) => String::new(),
+                    ('d', false) => format!(" ({})", names.join(", ")),
+                    _ => names.join(", "),
+                }
+            }
+            'a' | 'c' => {
+                let who = if first == 'a' { author } else { committer };
+                let Some(field) = spec[1..].chars().next() else {
+                    return Ok((None, 0));
+                };
+                let value = match field {
+                    'n' => who.name.clone(),
+                    'e' => who.email.clone(),
+                    'd' => date::format_date(who.timestamp, who.offset, DateFormat::Default),
+                    'r' => date::format_date(who.timestamp, who.offset, DateFormat::Relative),
+                    't' => date::format_date(who.timestamp, who.offset, DateFormat::Unix),
+                    'i' => date::format_date(who.timestamp, who.offset, DateFormat::Iso),
+                    'I' => date::format_date(who.timestamp, who.offset, DateFormat::IsoStrict),
+                    's' => date::format_date(who.timestamp, who.offset, DateFormat::Short),
+                    _ => return Ok((None, 0)),
+                };
+                return Ok((Some(value), 2));
+            }
+            'x' => {
+                // `%xx` inserts the byte with that hex value, e.g. `%x00`.
+                return Ok(match spec.get(1..3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => (Some((byte as char).to_string()), 3),
+                    None => (None, 0),
+                });
+            }
+            'C' => {
+                const COLORS: [(&str, &str); 4] =
+                    [("red", "\x1b[31m"), ("green", "\x1b[32m"), ("blue", "\x1b[34m"), ("reset", "\x1b[m")];
+                return Ok(COLORS
+                    .iter()
+                    .find(|(name, _)| spec[1..].starts_with(name))
+                    .map(|(name, code)| (Some(code.to_string()), 1 + name.len()))
+                    .unwrap_or((None, 0)));
+            }
+            _ => return Ok((None, 0)),
+        };
+        Ok((Some(value), 1))
+    }
+
+    /// The names of the refs pointing at a commit, the way `%D` shows them:
+    /// `HEAD -> main, tag: v1.0, origin/main`.
+    fn decorations(&mut self, oid: &Oid) -> Result<Vec<String>> {
+        if self.decorations.is_none() {
+            let git_dir = self.repo.git_dir();
+            let mut decorations: HashMap<Oid, Vec<String>> = HashMap::new();
+            let head_target = match refs::read_ref(git_dir, "HEAD")? {
+                Some(RefValue::Symbolic(target)) => Some(target),
+                _ => None,
+            };
+            let mut head_shown_with_branch = false;
+            let mut names = Vec::new();
+            for (name, ref_oid) in refs::list_refs(git_dir, "refs/")? {
+                let target = refs::peel(self.store, &ref_oid)?;
+                let label = if Some(&name) == head_target.as_ref() {
+                    head_shown_with_branch = true;
+                    format!("HEAD -> {}", refs::shorten_ref_name(&name))
+                } else if name.starts_with("refs/tags/") {
+                    format!("tag: {}", refs::shorten_ref_name(&name))
+                } else {
+                    refs::shorten_ref_name(&name).to_string()
+                };
+                // HEAD comes first, then refs by name.
+                let first = label.starts_with("HEAD");
+                names.push((target, !first, label));
+            }
+            if !head_shown_with_branch && let Some(head) = refs::resolve_ref(git_dir, "HEAD")? {
+                names.push((head, false, "HEAD".to_string()));
+            }
+            names.sort_by_key(|name| name.1);
+            for (target, _, label) in names {
+                decorations.entry(target).or_default().push(label);
+            }
+            self.decorations = Some(decorations);
+        }
+        Ok(self.decorations.as_ref().and_then(|d| d.get(oid)).cloned().unwrap_or_default())
+    }
+}
+
+/// The first paragraph of a message, joined into one line.
+fn subject(message: &str) -> String {
+    subject_paragraph(message).lines().collect::<Vec<_>>().join(" ")
+}
+
+fn subject_paragraph(message: &str) -> &str {
+    let message = message.trim_start_matches('\n');
+    message.split("\n\n").next().unwrap_or("")
+}
+
+/// Everything after the first paragraph.
+fn body(message: &str) -> &str {
+    message
+        .trim_start_matches('\n')
+        .split_once("\n\n")
+        .map(|(_, body)| body.trim_start_matches('\n'))
+        .unwrap_or("")
+}
+
+/// A message as the multi-line formats show it: indented by four spaces,
+/// without leading or trailing blank lines.
+fn indent(message: &str) -> String {
+    message
+        .trim_matches('\n')
+        .lines()
+        .map(|line| if line.trim().is_empty() { "    ".to_string() } else { format!("    {}", line) })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -4,4 +4,21 @@ pub mod cat_file;
 pub mod write_tree;
 pub mod commit_tree;
 pub mod add;
-pub mod commit;
\ No newline at end of file
+pub mod commit;
+pub mod tag;
+pub mod pack_objects;
+pub mod repack;
+pub mod status;
+pub mod show_ref;
+pub mod symbolic_ref;
+pub mod for_each_ref;
+pub mod pack_refs;
+pub mod update_ref;
+pub mod reflog;
+pub mod rev_parse;
+pub mod config;
+pub mod log;
+pub mod diff;
+pub mod branch;
+pub mod checkout;
+pub mod restore;
@@ -0,0 +1,34 @@
+// src/commands/pack_objects.rs
+
+use crate::objects;
+use crate::pack_writer::{self, PackInput};
+use crate::repository::Repository;
+use anyhow::Result;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+/// Reads object ids from stdin, one per line and optionally followed by a
+/// path (the `rev-list --objects` format), and writes them to
+/// `<base_name>-<checksum>.pack` and `.idx`. Prints the checksum.
+pub fn pack_objects(repo: &Repository, base_name: &Path) -> Result<()> {
+    let mut inputs = Vec::new();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let (hash, path) = match line.split_once(' ') {
+            Some((hash, path)) => (hash, Some(path.to_string())),
+            None => (line, None),
+        };
+        inputs.push(PackInput {
+            oid: objects::parse_oid(hash)?,
+            path,
+        });
+    }
+
+    let written = pack_writer::write_pack(repo.odb(), &inputs, base_name)?;
+    println!("{}", hex::encode(written.checksum));
+    Ok(())
+}
@@ -0,0 +1,11 @@
+// src/commands/pack_refs.rs
+
+use crate::refs;
+use crate::repository::Repository;
+use anyhow::Result;
+
+/// Moves loose refs into `.git/packed-refs`. Only tags and refs that are
+/// already packed are included, unless `all` is set.
+pub fn pack_refs(repo: &Repository, all: bool, prune: bool) -> Result<()> {
+    refs::pack_refs(repo.git_dir(), repo.odb(), all, prune)
+}
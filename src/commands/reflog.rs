@@ -0,0 +1,91 @@
+// src/commands/reflog.rs
+
+use crate::date;
+use crate::refs;
+use crate::reflog;
+use crate::repository::Repository;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// How long reflog entries are kept by default, as in git.
+const DEFAULT_EXPIRE: &str = "90.days.ago";
+
+/// Prints a ref's log newest first, as `<hash> <name>@{<n>}: <message>`.
+pub fn show(repo: &Repository, name: &str) -> Result<()> {
+    let ref_name = full_name(repo.git_dir(), name)?;
+    let entries = reflog::read(repo.git_dir(), &ref_name)?;
+    for (n, entry) in entries.iter().rev().enumerate() {
+        println!("{} {}@{{{}}}: {}", &hex::encode(entry.new)[..7], name, n, entry.message);
+    }
+    Ok(())
+}
+
+/// Removes entries older than `expire` from the logs of `names` (or of every
+/// ref with `all`).
+pub fn expire(repo: &Repository, names: &[String], all: bool, expire: Option<&str>) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let cutoff = match expire.unwrap_or(DEFAULT_EXPIRE) {
+        "never" => return Ok(()),
+        "all" | "now" => i64::MAX,
+        when => date::parse_approxidate(when, date::now())?,
+    };
+
+    let ref_names = if all {
+        reflog::list(git_dir)?
+    } else if names.is_empty() {
+        bail!("reflog expire needs the refs to expire, or --all");
+    } else {
+        names.iter().map(|name| full_name(git_dir, name)).collect::<Result<_>>()?
+    };
+
+    for ref_name in ref_names {
+        let entries = reflog::read(git_dir, &ref_name)?;
+        let kept: Vec<_> = entries.iter().filter(|entry| entry.timestamp >= cutoff).cloned().collect();
+        if kept.len() != entries.len() {
+            reflog::rewrite(git_dir, &ref_name, &kept)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes single entries given as `<ref>@{<n>}`.
+pub fn delete(repo: &Repository, specs: &[String]) -> Result<()> {
+    let git_dir = repo.git_dir();
+    if specs.is_empty() {
+        bail!("reflog delete needs entries to delete, e.g. HEAD@{{1}}");
+    }
+
+    // Entries are numbered newest first; collect them per ref so that
+    // deleting one doesn't renumber the others.
+    let mut to_delete: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for spec in specs {
+        let (name, n) = spec
+            .strip_suffix('}')
+            .and_then(|rest| rest.split_once("@{"))
+            .with_context(|| format!("Not a reflog entry: {}", spec))?;
+        let n: usize = n.parse().with_context(|| format!("Not a reflog entry: {}", spec))?;
+        let ref_name = full_name(git_dir, if name.is_empty() { "HEAD" } else { name })?;
+        to_delete.entry(ref_name).or_default().push(n);
+    }
+
+    for (ref_name, numbers) in to_delete {
+        let entries = reflog::read(git_dir, &ref_name)?;
+        let count = entries.len();
+        if let Some(n) = numbers.iter().find(|&&n| n >= count) {
+            bail!("reflog entry {}@{{{}}} does not exist", ref_name, n);
+        }
+        let kept: Vec<_> = entries
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !numbers.contains(&(count - 1 - i)))
+            .map(|(_, entry)| entry)
+            .collect();
+        reflog::rewrite(git_dir, &ref_name, &kept)?;
+    }
+    Ok(())
+}
+
+fn full_name(git_dir: &Path, name: &str) -> Result<String> {
+    refs::dwim_ref(git_dir, name)?.with_context(|| format!("No such ref: {}", name))
+}
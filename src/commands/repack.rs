@@ -0,0 +1,136 @@
+// src/commands/repack.rs
+
+use crate::index::Index;
+use crate::objects::{self, GitObject, Oid};
+use crate::odb::ObjectStore;
+use crate::pack_writer::{self, PackInput};
+use crate::reflog;
+use crate::refs;
+use crate::repository::Repository;
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Packs every object reachable from HEAD, the refs, the reflogs and the index.
+///
+/// Without `all`, objects that are already packed are left alone and only the
+/// loose ones go into the new pack. With `delete`, loose objects that are now
+/// in the new pack are removed, and with `all` also every older pack.
+pub fn repack(repo: &Repository, all: bool, delete: bool) -> Result<()> {
+    let store = repo.odb();
+
+    // 1. Find what is reachable.
+    let mut inputs = reachable_objects(repo.git_dir(), store)?;
+    if !all {
+        inputs.retain(|input| !store.packs().iter().any(|pack| pack.contains(&input.oid)));
+    }
+    if inputs.is_empty() {
+        println!("Nothing new to pack.");
+        return Ok(());
+    }
+
+    // 2. Write the new pack.
+    let pack_dir = store.objects_dir().join("pack");
+    let written = pack_writer::write_pack(store, &inputs, &pack_dir.join("pack"))?;
+    println!(
+        "Packed {} objects into pack-{}",
+        inputs.len(),
+        hex::encode(written.checksum)
+    );
+
+    if !delete {
+        return Ok(());
+    }
+
+    // 3. Remove loose copies of everything we just packed.
+    let mut removed = 0;
+    for input in &inputs {
+        let hex = hex::encode(input.oid);
+        let dir = store.objects_dir().join(&hex[0..2]);
+        if fs::remove_file(dir.join(&hex[2..])).is_ok() {
+            removed += 1;
+            // Clean up the fan-out directory once it is empty.
+            let _ = fs::remove_dir(&dir);
+        }
+    }
+
+    // 4. With -a, the new pack holds every reachable object, so older packs
+    // are redundant.
+    if all {
+        for pack in store.packs() {
+            if pack.pack_path() != written.pack_path {
+                fs::remove_file(pack.pack_path().with_extension("idx"))?;
+                fs::remove_file(pack.pack_path())?;
+            }
+        }
+    }
+    println!("Removed {} loose objects.", removed);
+    Ok(())
+}
+
+/// Walks from HEAD, every ref under `refs/`, every commit a reflog has
+/// recorded and every index entry, returning each reachable object once, with
+/// the path it was found at for trees and blobs.
+fn reachable_objects(git_dir: &Path, store: &ObjectStore) -> Result<Vec<PackInput>> {
+    let mut roots = Vec::new();
+    if let Some(head) = crate::repository::get_head_commit_hash(git_dir)? {
+        roots.push(objects::parse_oid(&head)?);
+    }
+    roots.extend(refs::list_refs(git_dir, "refs/")?.into_iter().map(|(_, oid)| oid));
+    // Keep what `HEAD@{n}` and the like can still reach. Entries for objects
+    // that are already gone are skipped, as are the null ids of creations
+    // and deletions.
+    for name in reflog::list(git_dir)? {
+        for entry in reflog::read(git_dir, &name)? {
+            roots.extend([entry.old, entry.new].into_iter().filter(|oid| store.exists(oid)));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut result = Vec::new();
+    let mut pending: Vec<(Oid, Option<String>)> = roots.into_iter().map(|oid| (oid, None)).collect();
+    while let Some((oid, path)) = pending.pop() {
+        if !seen.insert(oid) {
+            continue;
+        }
+        match store.read(&oid)? {
+            GitObject::Commit(commit) => {
+                pending.push((objects::parse_oid(&commit.tree_hash)?, Some(String::new())));
+                for parent in &commit.parents {
+                    pending.push((objects::parse_oid(parent)?, None));
+                }
+            }
+            GitObject::Tree(tree) => {
+                let prefix = path.as_deref().unwrap_or("");
+                for entry in &tree.entries {
+                    // Submodule commits live in another repository.
+                    if entry.mode == "160000" {
+                        continue;
+                    }
+                    let name = String::from_utf8_lossy(&entry.name);
+                    let entry_path = if prefix.is_empty() {
+                        name.into_owned()
+                    } else {
+                        format!("{}/{}", prefix, name)
+                    };
+                    pending.push((entry.hash, Some(entry_path)));
+                }
+            }
+            GitObject::Tag(tag) => pending.push((objects::parse_oid(&tag.object)?, None)),
+            GitObject::Blob(_) => {}
+        }
+        result.push(PackInput { oid, path });
+    }
+
+    let index = Index::from_path(&git_dir.join("index"))?;
+    for entry in index.entries {
+        if seen.insert(entry.hash) && store.exists(&entry.hash) {
+            result.push(PackInput {
+                oid: entry.hash,
+                path: Some(entry.path.to_string_lossy().into_owned()),
+            });
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,128 @@
+// src/commands/restore.rs
+
+use crate::checkout;
+use crate::diff::tree::{self, Snapshot};
+use crate::index::{self, Index, IndexEntry};
+use crate::objects::{self, ObjectKind};
+use crate::repository::{self, Repository};
+use crate::revision;
+use anyhow::{bail, Result};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// Where `restore` takes files from and what it puts them back into.
+#[derive(Debug, Default)]
+pub struct RestoreOptions {
+    /// Reset the index entries. The source defaults to HEAD.
+    pub staged: bool,
+    /// Rewrite the working tree files. The default without `staged`.
+    pub worktree: bool,
+    /// A commit or tree to restore from, instead of the index or HEAD.
+    pub source: Option<String>,
+    /// Leave files the source doesn't have alone, rather than deleting them.
+    pub overlay: bool,
+}
+
+/// Restores the files at or below `paths` from the source: into the working
+/// tree, where the index entry gets fresh stat data if the file now matches
+/// it, and/or into the index. Tracked files the source doesn't have are
+/// removed unless `overlay` is set. Returns how many paths were restored.
+pub fn restore(repo: &Repository, paths: &[PathBuf], options: &RestoreOptions) -> Result<usize> {
+    if paths.is_empty() {
+        bail!("you must specify path(s) to restore");
+    }
+    let work_tree = repo.require_work_tree()?;
+    let store = repo.odb();
+    let worktree = options.worktree || !options.staged;
+    let selected = paths
+        .iter()
+        .map(|path| Ok(index::path_bytes(&repo.worktree_path(path)?).to_vec()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let index_path = repo.index_path();
+    let mut index = Index::from_path(&index_path)?;
+    let from_index = options.source.is_none() && !options.staged;
+    let source: Snapshot = match &options.source {
+        Some(rev) => {
+            let tree = revision::peel_to(store, &revision::resolve(repo, rev)?, ObjectKind::Tree)?;
+            repository::read_tree_recursive(store, &tree)?
+        }
+        None if options.staged => match repository::get_head_commit_hash(repo.git_dir())? {
+            Some(hash) => {
+                let commit = objects::parse_oid(&hash)?;
+                repository::read_tree_recursive(store, &repository::commit_tree_oid(store, &commit)?)?
+            }
+            None => Snapshot::new(),
+        },
+        None => index
+            .entries
+            .iter()
+            .filter(|entry| entry.stage == 0)
+            .map(|entry| (index::path_bytes(&entry.path).to_vec(), (entry.mode, entry.hash)))
+            .collect(),
+    };
+
+    // 1. Every pathspec has to match something the source or index tracks.
+    let tracked: BTreeSet<Vec<u8>> = index.entries.iter().map(|entry| index::path_bytes(&entry.path).to_vec()).collect();
+    let candidates: BTreeSet<&Vec<u8>> = source.keys().chain(tracked.iter()).collect();
+    for (spec, path) in selected.iter().zip(paths) {
+        if !candidates.iter().any(|candidate| tree::is_selected(candidate, std::slice::from_ref(spec))) {
+            bail!("pathspec '{}' did not match any file(s) known to git", path.display());
+        }
+    }
+    let matched: Vec<&Vec<u8>> = candidates
+        .into_iter()
+        .filter(|path| tree::is_selected(path, &selected))
+        .filter(|path| !options.overlay || source.contains_key(*path))
+        .collect();
+    if from_index {
+        let unmerged = index.entries.iter().find(|entry| {
+            entry.stage != 0 && matched.iter().any(|path| path.as_slice() == index::path_bytes(&entry.path))
+        });
+        if let Some(entry) = unmerged {
+            bail!("path '{}' is unmerged", entry.path.display());
+        }
+    }
+
+    // 2. Put each file back.
+    for path in &matched {
+        let in_index = index
+            .entries
+            .iter()
+            .find(|entry| entry.stage == 0 && path.as_slice() == index::path_bytes(&entry.path))
+            .cloned();
+        match source.get(*path) {
+            Some(&(mode, oid)) => {
+                let written = if worktree {
+                    Some(checkout::checkout_entry(store, work_tree, path, mode, &oid)?)
+                } else {
+                    None
+                };
+                let unchanged = in_index.as_ref().is_some_and(|entry| (entry.mode, entry.hash) == (mode, oid));
+                // The index takes the new file's stat data when it describes
+                // the same content; a restaged entry without a file gets none.
+                match written {
+                    Some(entry) if options.staged || unchanged => index.add(entry),
+                    None if options.staged && !unchanged => index.add(IndexEntry {
+                        mode,
+                        hash: oid,
+                        path: index::path_from_bytes(path),
+                        ..Default::default()
+                    }),
+                    _ => {}
+                }
+            }
+            None => {
+                if worktree && in_index.is_some() {
+                    checkout::remove_entry(work_tree, path)?;
+                }
+                if options.staged {
+                    index.remove(&index::path_from_bytes(path));
+                }
+            }
+        }
+    }
+
+    index.write(&index_path)?;
+    Ok(matched.len())
+}
@@ -0,0 +1,58 @@
+// src/commands/rev_parse.rs
+
+use crate::refs;
+use crate::repository::Repository;
+use crate::revision;
+use anyhow::{bail, Result};
+
+/// Options for `rev-parse`, mirroring its flags.
+#[derive(Debug, Default)]
+pub struct RevParseOptions {
+    pub verify: bool,
+    /// Abbreviate hashes to at least this many digits.
+    pub short: Option<usize>,
+    pub abbrev_ref: bool,
+    pub symbolic_full_name: bool,
+}
+
+/// Prints the object id each revision names. Ranges print their included
+/// commits, then the excluded ones prefixed with `^`. With `verify`, exactly
+/// one revision naming an existing object is required.
+pub fn rev_parse(repo: &Repository, specs: &[String], options: &RevParseOptions) -> Result<()> {
+    let store = repo.odb();
+    let format = |oid: &[u8; 20]| match options.short {
+        Some(len) => revision::abbreviate(store, oid, len),
+        None => Ok(hex::encode(oid)),
+    };
+
+    if options.verify {
+        let [spec] = specs else {
+            bail!("Needed a single revision");
+        };
+        let oid = revision::resolve(repo, spec)?;
+        if !store.exists(&oid) {
+            bail!("Needed a single revision");
+        }
+        println!("{}", format(&oid)?);
+        return Ok(());
+    }
+
+    for spec in specs {
+        if options.abbrev_ref || options.symbolic_full_name {
+            if let Some(name) = revision::symbolic_full_name(repo, spec)? {
+                let name = if options.abbrev_ref { refs::shorten_ref_name(&name) } else { &name };
+                println!("{}", name);
+            }
+            continue;
+        }
+
+        let range = revision::parse_range(repo, spec)?;
+        for oid in &range.include {
+            println!("{}", format(oid)?);
+        }
+        for oid in &range.exclude {
+            println!("^{}", format(oid)?);
+        }
+    }
+    Ok(())
+}
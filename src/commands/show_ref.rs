@@ -0,0 +1,78 @@
+// src/commands/show_ref.rs
+
+use crate::refs;
+use crate::repository::Repository;
+use anyhow::{bail, Result};
+
+/// Options for `show-ref`, mirroring its flags.
+#[derive(Debug, Default)]
+pub struct ShowRefOptions {
+    pub head: bool,
+    pub heads: bool,
+    pub tags: bool,
+    pub dereference: bool,
+    pub hash_only: bool,
+    pub verify: bool,
+}
+
+/// Lists refs with the ids they point at. A pattern matches a ref when it
+/// equals the ref's trailing path components, so `main` finds both
+/// `refs/heads/main` and `refs/remotes/origin/main`. With `verify`, each
+/// pattern must instead be an exact ref name.
+pub fn show_ref(repo: &Repository, patterns: &[String], options: &ShowRefOptions) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let mut matches = Vec::new();
+
+    if options.verify {
+        for name in patterns {
+            let found = if name == "HEAD" || name.starts_with("refs/") {
+                refs::resolve_ref(git_dir, name)?
+            } else {
+                None
+            };
+            match found {
+                Some(oid) => matches.push((name.clone(), oid)),
+                None => bail!("'{}' - not a valid ref", name),
+            }
+        }
+    } else {
+        if options.head && let Some(oid) = refs::resolve_ref(git_dir, "HEAD")? {
+            matches.push(("HEAD".to_string(), oid));
+        }
+        for (name, oid) in refs::list_refs(git_dir, "refs/")? {
+            let kind_ok = (!options.heads && !options.tags)
+                || (options.heads && name.starts_with("refs/heads/"))
+                || (options.tags && name.starts_with("refs/tags/"));
+            if kind_ok && (patterns.is_empty() || patterns.iter().any(|p| tail_matches(&name, p))) {
+                matches.push((name, oid));
+            }
+        }
+    }
+
+    if matches.is_empty() {
+        bail!("No matching refs found");
+    }
+    let store = repo.odb();
+    for (name, oid) in matches {
+        print_ref(&hex::encode(oid), &name, options.hash_only);
+        if options.dereference {
+            let peeled = refs::peel(store, &oid)?;
+            if peeled != oid {
+                print_ref(&hex::encode(peeled), &format!("{}^{{}}", name), options.hash_only);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_ref(hash: &str, name: &str, hash_only: bool) {
+    if hash_only {
+        println!("{}", hash);
+    } else {
+        println!("{} {}", hash, name);
+    }
+}
+
+fn tail_matches(name: &str, pattern: &str) -> bool {
+    name == pattern || name.ends_with(&format!("/{}", pattern))
+}
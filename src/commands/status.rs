@@ -0,0 +1,276 @@
+// src/commands/status.rs
+
+use crate::index::{self, Index, IndexEntry};
+use crate::objects::{self, ObjectKind, Oid};
+use crate::odb;
+use crate::repository::{self, Repository};
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// How `status` prints its result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFormat {
+    Long,
+    Short,
+    PorcelainV1,
+    PorcelainV2,
+}
+
+impl StatusFormat {
+    /// Picks the format from the `--short` and `--porcelain[=<version>]` flags.
+    pub fn from_flags(short: bool, porcelain: Option<&str>) -> Result<Self> {
+        match porcelain {
+            Some("v1") | Some("1") => Ok(StatusFormat::PorcelainV1),
+            Some("v2") | Some("2") => Ok(StatusFormat::PorcelainV2),
+            Some(other) => bail!("Unsupported porcelain version: {}", other),
+            None if short => Ok(StatusFormat::Short),
+            None => Ok(StatusFormat::Long),
+        }
+    }
+}
+
+/// The state of one tracked path in HEAD, the index and the working tree.
+#[derive(Debug)]
+struct TrackedPath {
+    path: Vec<u8>,
+    head: Option<(u32, Oid)>,
+    index: Option<(u32, Oid)>,
+    worktree_mode: Option<u32>,
+    // Status letters: ' ' unchanged, 'A' added, 'M' modified, 'D' deleted.
+    staged: char,
+    unstaged: char,
+}
+
+pub fn status(repo: &Repository, format: StatusFormat) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let work_tree = repo.require_work_tree()?;
+    let store = repo.odb();
+    let index = Index::from_path(&repo.index_path())?;
+
+    // 1. Collect the three snapshots.
+    let head_commit = repository::get_head_commit_hash(git_dir)?;
+    let head_files = match &head_commit {
+        Some(hash) => {
+            let tree = repository::commit_tree_oid(store, &objects::parse_oid(hash)?)?;
+            repository::read_tree_recursive(store, &tree)?
+        }
+        None => BTreeMap::new(),
+    };
+    let index_entries: BTreeMap<Vec<u8>, &IndexEntry> = index
+        .entries
+        .iter()
+        .map(|entry| (index::path_bytes(&entry.path).to_vec(), entry))
+        .collect();
+    let index_files: BTreeMap<Vec<u8>, (u32, Oid)> = index_entries
+        .iter()
+        .map(|(path, entry)| (path.clone(), (entry.mode, entry.hash)))
+        .collect();
+    let mut worktree_files = BTreeSet::new();
+    walk_worktree(work_tree, b"", &mut worktree_files)?;
+
+    // 2. HEAD vs index gives the staged changes, index vs working tree the
+    // unstaged ones.
+    let all_paths: BTreeSet<&Vec<u8>> = head_files.keys().chain(index_files.keys()).collect();
+    let index_mtime = index.mtime;
+    let mut tracked = Vec::new();
+    for path in all_paths {
+        let head = head_files.get(path).copied();
+        // An entry added with --intent-to-add has nothing staged yet: it
+        // counts as absent from the index, and its file as not yet added.
+        let intent_to_add = index_entries.get(path).is_some_and(|entry| entry.intent_to_add);
+        let index = index_files.get(path).copied().filter(|_| !intent_to_add);
+        let staged = match (head, index) {
+            (None, Some(_)) => 'A',
+            (Some(_), None) => 'D',
+            (Some(head), Some(index)) if head != index => 'M',
+            _ => ' ',
+        };
+
+        let mut worktree_mode = None;
+        let mut unstaged = ' ';
+        if intent_to_add {
+            match worktree_state(&work_tree.join(index::path_from_bytes(path)), index_entries[path], index_mtime)? {
+                None => unstaged = 'D',
+                Some((actual_mode, _)) => {
+                    worktree_mode = Some(actual_mode);
+                    unstaged = 'A';
+                }
+            }
+        } else if let (Some((mode, hash)), Some(entry)) = (index, index_entries.get(path)) {
+            match worktree_state(&work_tree.join(index::path_from_bytes(path)), entry, index_mtime)? {
+                None => unstaged = 'D',
+                Some((actual_mode, actual_hash)) => {
+                    worktree_mode = Some(actual_mode);
+                    if actual_mode != mode || actual_hash != hash {
+                        unstaged = 'M';
+                    }
+                }
+            }
+        }
+
+        if staged != ' ' || unstaged != ' ' {
+            tracked.push(TrackedPath {
+                path: path.clone(),
+                head,
+                index,
+                worktree_mode,
+                staged,
+                unstaged,
+            });
+        }
+    }
+
+    // 3. Anything on disk the index doesn't know about is untracked. Whole
+    // directories without tracked files are reported once, as `dir/`.
+    let tracked_dirs: BTreeSet<&[u8]> = index_files.keys().flat_map(|path| parent_dirs(path)).collect();
+    let untracked: BTreeSet<Vec<u8>> = worktree_files
+        .iter()
+        .filter(|path| !index_files.contains_key(*path))
+        .map(|path| {
+            parent_dirs(path)
+                .into_iter()
+                .find(|dir| !tracked_dirs.contains(dir))
+                .map_or_else(|| path.clone(), |dir| [dir, b"/"].concat())
+        })
+        .collect();
+
+    // 4. Print.
+    match format {
+        StatusFormat::Long => print_long(git_dir, head_commit.is_none(), &tracked, &untracked)?,
+        StatusFormat::Short | StatusFormat::PorcelainV1 => {
+            for entry in &tracked {
+                println!("{}{} {}", entry.staged, entry.unstaged, index::quote_path(&entry.path));
+            }
+            for path in &untracked {
+                println!("?? {}", index::quote_path(path));
+            }
+        }
+        StatusFormat::PorcelainV2 => {
+            for entry in &tracked {
+                let (head_mode, head_hash) = entry.head.unwrap_or((0, [0; 20]));
+                let (index_mode, index_hash) = entry.index.unwrap_or((0, [0; 20]));
+                println!(
+                    "1 {}{} N... {:06o} {:06o} {:06o} {} {} {}",
+                    dot_if_blank(entry.staged),
+                    dot_if_blank(entry.unstaged),
+                    head_mode,
+                    index_mode,
+                    entry.worktree_mode.unwrap_or(0),
+                    hex::encode(head_hash),
+                    hex::encode(index_hash),
+                    index::quote_path(&entry.path)
+                );
+            }
+            for path in &untracked {
+                println!("? {}", index::quote_path(path));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_long(
+    git_dir: &Path,
+    no_commits: bool,
+    tracked: &[TrackedPath],
+    untracked: &BTreeSet<Vec<u8>>,
+) -> Result<()> {
+    println!("{}", repository::describe_head(git_dir)?);
+    if no_commits {
+        println!("\nNo commits yet");
+    }
+
+    let staged: Vec<&TrackedPath> = tracked.iter().filter(|entry| entry.staged != ' ').collect();
+    if !staged.is_empty() {
+        println!("\nChanges to be committed:");
+        for entry in staged {
+            println!("\t{:<12}{}", describe(entry.staged), index::quote_path(&entry.path));
+        }
+    }
+
+    let unstaged: Vec<&TrackedPath> = tracked.iter().filter(|entry| entry.unstaged != ' ').collect();
+    if !unstaged.is_empty() {
+        println!("\nChanges not staged for commit:");
+        for entry in unstaged {
+            println!("\t{:<12}{}", describe(entry.unstaged), index::quote_path(&entry.path));
+        }
+    }
+
+    if !untracked.is_empty() {
+        println!("\nUntracked files:");
+        for path in untracked {
+            println!("\t{}", index::quote_path(path));
+        }
+    }
+
+    if tracked.iter().all(|entry| entry.staged == ' ') {
+        if !tracked.is_empty() || !untracked.is_empty() {
+            println!("\nno changes added to commit");
+        } else {
+            println!("\nnothing to commit, working tree clean");
+        }
+    }
+    Ok(())
+}
+
+fn describe(status: char) -> &'static str {
+    match status {
+        'A' => "new file:",
+        'D' => "deleted:",
+        _ => "modified:",
+    }
+}
+
+fn dot_if_blank(status: char) -> char {
+    if status == ' ' { '.' } else { status }
+}
+
+/// Returns the mode and blob id of a file in the working tree, or None if it
+/// no longer exists. Files whose stat data still matches their index entry are
+/// not rehashed, unless the entry is racily clean.
+fn worktree_state(
+    path: &Path,
+    entry: &IndexEntry,
+    index_mtime: Option<(u32, u32)>,
+) -> Result<Option<(u32, Oid)>> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) if !metadata.is_dir() => metadata,
+        _ => return Ok(None),
+    };
+    let mode = index::mode_from_metadata(&metadata);
+    if entry.stat_matches(&metadata) && !entry.is_racy(index_mtime) {
+        return Ok(Some((mode, entry.hash)));
+    }
+    let content = if metadata.file_type().is_symlink() {
+        index::path_bytes(&fs::read_link(path)?).to_vec()
+    } else {
+        fs::read(path)?
+    };
+    Ok(Some((mode, odb::hash_object(ObjectKind::Blob, &content))))
+}
+
+/// Collects every file below `dir` as a slash-separated path, skipping `.git`.
+/// Names are kept as the bytes the file system gives, like index paths.
+fn walk_worktree(dir: &Path, prefix: &[u8], files: &mut BTreeSet<Vec<u8>>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name == ".git" {
+            continue;
+        }
+        let path = [prefix, name.as_encoded_bytes()].concat();
+        if entry.file_type()?.is_dir() {
+            walk_worktree(&entry.path(), &[&path[..], b"/"].concat(), files)?;
+        } else {
+            files.insert(path);
+        }
+    }
+    Ok(())
+}
+
+/// `a/b/c.txt` -> `["a", "a/b"]`
+fn parent_dirs(path: &[u8]) -> Vec<&[u8]> {
+    (0..path.len()).filter(|&i| path[i] == b'/').map(|i| &path[..i]).collect()
+}
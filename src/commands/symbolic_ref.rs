@@ -0,0 +1,40 @@
+// src/commands/symbolic_ref.rs
+
+use crate::refs::{self, RefValue};
+use crate::repository::Repository;
+use anyhow::{bail, Result};
+use std::fs;
+
+/// Reads, sets or deletes a symbolic ref such as HEAD.
+///
+/// With only a name, prints the ref it points at (shortened with `short`).
+/// With a target, makes the name point there. With `delete`, removes it.
+pub fn symbolic_ref(
+    repo: &Repository,
+    name: &str,
+    target: Option<&str>,
+    delete: bool,
+    short: bool,
+    quiet: bool,
+) -> Result<()> {
+    let git_dir = repo.git_dir();
+
+    if let Some(target) = target {
+        refs::check_ref_name(target)?;
+        return refs::write_symbolic_ref(git_dir, name, target);
+    }
+
+    match refs::read_ref(git_dir, name)? {
+        Some(RefValue::Symbolic(_)) if delete => {
+            fs::remove_file(git_dir.join(name))?;
+        }
+        Some(RefValue::Symbolic(current)) => {
+            let shown = if short { refs::shorten_ref_name(&current) } else { &current };
+            println!("{}", shown);
+        }
+        // Like git, -q only silences the "not a symbolic ref" case.
+        _ if quiet => std::process::exit(1),
+        _ => bail!("ref {} is not a symbolic ref", name),
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+// src/commands/tag.rs
+
+use crate::identity::{self, Role};
+use crate::objects::{GitObject, Tag};
+use crate::refs;
+use crate::repository::Repository;
+use crate::revision;
+use anyhow::{bail, Context, Result};
+
+/// Creates `refs/tags/<name>`. With a message, an annotated tag object is
+/// written and the ref points at it; otherwise the tag is lightweight and the
+/// ref points straight at the target.
+pub fn create_tag(
+    repo: &Repository,
+    name: &str,
+    rev: Option<&str>,
+    message: Option<String>,
+    force: bool,
+) -> Result<()> {
+    let git_dir = repo.git_dir();
+    refs::check_ref_name(name).with_context(|| format!("'{}' is not a valid tag name.", name))?;
+
+    let ref_name = format!("refs/tags/{}", name);
+    if refs::read_ref(git_dir, &ref_name)?.is_some() && !force {
+        bail!("tag '{}' already exists", name);
+    }
+
+    // 1. Resolve what we are tagging. Defaults to the current commit.
+    let target = revision::resolve(repo, rev.unwrap_or("HEAD"))?;
+    let store = repo.odb();
+
+    // 2. For annotated tags, write the tag object first.
+    let tag_hash = match message {
+        Some(mut message) => {
+            let (kind, _) = store.header(&target)?;
+            // The tagger is whoever would be the committer of a commit.
+            let tagger = identity::signature(repo.config(), Role::Committer)?;
+            if !message.ends_with('\n') {
+                message.push('\n');
+            }
+            let tag = Tag {
+                object: hex::encode(target),
+                kind,
+                tag: name.to_string(),
+                tagger: Some(tagger.into_bytes()),
+                extra_headers: Vec::new(),
+                message: message.into_bytes(),
+                signature: None,
+            };
+            store.write(&GitObject::Tag(tag))?
+        }
+        None => target,
+    };
+
+    // 3. Point the ref at it.
+    refs::write_ref(git_dir, &ref_name, &tag_hash)
+}
+
+/// Prints every tag whose name matches `pattern` (all tags if none is given).
+pub fn list_tags(repo: &Repository, pattern: Option<&str>) -> Result<()> {
+    for (ref_name, _) in refs::list_refs(repo.git_dir(), "refs/tags/")? {
+        let name = refs::shorten_ref_name(&ref_name);
+        if pattern.is_none_or(|pattern| refs::glob_match(pattern.as_bytes(), name.as_bytes())) {
+            println!("{}", name);
+        }
+    }
+    Ok(())
+}
+
+pub fn delete_tag(repo: &Repository, name: &str) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let ref_name = format!("refs/tags/{}", name);
+    let oid = refs::resolve_ref(git_dir, &ref_name)?
+        .with_context(|| format!("tag '{}' not found.", name))?;
+    refs::delete_ref(git_dir, &ref_name)?;
+    println!("Deleted tag '{}' (was {})", name, &hex::encode(oid)[..7]);
+    Ok(())
+}
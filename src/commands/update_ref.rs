@@ -0,0 +1,108 @@
+// src/commands/update_ref.rs
+
+use crate::objects::Oid;
+use crate::refs::RefTransaction;
+use crate::repository::Repository;
+use crate::revision;
+use anyhow::{bail, Context, Result};
+use std::io::{self, Read};
+
+/// `update-ref <ref> <new> [<old>]`, or `update-ref -d <ref> [<old>]`.
+///
+/// With `<old>`, the ref is only changed if it still holds that value; an
+/// all-zero `<old>` means the ref must not exist yet.
+pub fn update_ref(
+    repo: &Repository,
+    name: &str,
+    values: &[String],
+    delete: bool,
+    no_deref: bool,
+    message: Option<&str>,
+) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let mut transaction = RefTransaction::new();
+    transaction.set_deref(!no_deref);
+    transaction.set_message(message.unwrap_or(""));
+
+    match (delete, values) {
+        (true, []) => transaction.delete(name, None),
+        (true, [old]) => {
+            let old = parse_value(repo, old)?.context("delete needs a non-zero old value")?;
+            transaction.delete(name, Some(old));
+        }
+        (false, [new]) => queue_update(&mut transaction, name, parse_value(repo, new)?, None),
+        (false, [new, old]) => {
+            let old = parse_value(repo, old)?;
+            queue_update(&mut transaction, name, parse_value(repo, new)?, Some(old));
+        }
+        _ => bail!("usage: update-ref <ref> <new> [<old>] or update-ref -d <ref> [<old>]"),
+    }
+    transaction.commit(git_dir)
+}
+
+/// `update-ref --stdin`: reads one command per line and applies them all in a
+/// single transaction, so either every ref changes or none does.
+///
+/// ```text
+/// update <ref> <new> [<old>]
+/// create <ref> <new>
+/// delete <ref> [<old>]
+/// verify <ref> [<old>]
+/// option no-deref
+/// ```
+///
+/// `option no-deref` applies to the command that follows it.
+pub fn update_ref_stdin(repo: &Repository, no_deref: bool, message: Option<&str>) -> Result<()> {
+    let git_dir = repo.git_dir();
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+
+    let mut transaction = RefTransaction::new();
+    transaction.set_message(message.unwrap_or(""));
+    let mut next_no_deref = false;
+    for line in input.lines().filter(|line| !line.is_empty()) {
+        transaction.set_deref(!(no_deref || next_no_deref));
+        next_no_deref = false;
+
+        let words: Vec<&str> = line.split(' ').collect();
+        let value = |i: usize| words.get(i).map(|v| parse_value(repo, v)).transpose();
+        match (words[0], words.len()) {
+            ("update", 3 | 4) => {
+                let new = value(2)?.flatten();
+                queue_update(&mut transaction, words[1], new, value(3)?);
+            }
+            ("create", 3) => {
+                let new = value(2)?.flatten().with_context(|| format!("create {}: zero <new>", words[1]))?;
+                transaction.update(words[1], new, Some(None));
+            }
+            ("delete", 2 | 3) => match value(2)? {
+                Some(None) => bail!("delete {}: zero <old>", words[1]),
+                old => transaction.delete(words[1], old.flatten()),
+            },
+            ("verify", 2 | 3) => transaction.verify(words[1], value(2)?.flatten()),
+            ("option", 2) if words[1] == "no-deref" => next_no_deref = true,
+            _ => bail!("Invalid update-ref command: {}", line),
+        }
+    }
+    transaction.commit(git_dir)
+}
+
+/// An update to a zero id is a deletion, as in git.
+fn queue_update(transaction: &mut RefTransaction, name: &str, new: Option<Oid>, old: Option<Option<Oid>>) {
+    match new {
+        Some(new) => transaction.update(name, new, old),
+        None => match old {
+            Some(Some(old)) => transaction.delete(name, Some(old)),
+            _ => transaction.delete(name, None),
+        },
+    }
+}
+
+/// Parses a value given to update-ref: empty or all zeros means "no value",
+/// anything else is resolved as a revision.
+fn parse_value(repo: &Repository, value: &str) -> Result<Option<Oid>> {
+    if value.is_empty() || (value.len() == 40 && value.bytes().all(|b| b == b'0')) {
+        return Ok(None);
+    }
+    Ok(Some(revision::resolve(repo, value)?))
+}
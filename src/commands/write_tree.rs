@@ -1,90 +1,124 @@
 // src/commands/write_tree.rs
 
-use crate::objects::{Blob, GitObject, Tree, TreeEntry};
-use anyhow::{Context, Result};
-use flate2::write::ZlibEncoder;
-use flate2::Compression;
-use sha1::{Digest, Sha1};
-use std::fs;
-use std::io::prelude::*;
-use std::path::Path;
+use crate::index::{self, CacheTree, Index, IndexEntry};
+use crate::objects::{GitObject, Tree, TreeEntry};
+use crate::odb::ObjectStore;
+use crate::repository::Repository;
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
 
-pub fn write_tree(path: &Path) -> Result<[u8; 20]> {
-    let mut entries = Vec::new();
+/// The `write-tree` command: writes the index as a tree and prints its id.
+pub fn write_tree(repo: &Repository) -> Result<()> {
+    let index_path = repo.index_path();
+    let mut index = Index::from_path(&index_path)?;
+    let hash = write_tree_from_index(&mut index, repo.odb())?;
+    // Save the refreshed cached tree so the next write can reuse it.
+    index.write(&index_path)?;
+    println!("{}", hex::encode(hash));
+    Ok(())
+}
 
-    // 1. Iterate over the files and directories in the given path.
-    for entry in fs::read_dir(path)? {
-        let entry = entry?;
-        let entry_path = entry.path();
-        let entry_name = entry
-            .file_name()
-            .into_string()
-            .map_err(|_| anyhow::anyhow!("Invalid UTF-8 in file name"))?;
-
-        // 2. Skip the .git directory itself.
-        if entry_name == ".git" {
-            continue;
-        }
-
-        let file_type = entry.file_type()?;
-        let hash = if file_type.is_dir() {
-            // 3. If it's a directory, recursively call write_tree.
-            write_tree(&entry_path)?
-        } else if file_type.is_file() {
-            // 4. If it's a file, hash it as a blob object.
-            let content = fs::read(&entry_path)?;
-            let blob = Blob { content };
-            let git_object = GitObject::Blob(blob);
-
-            // This is very similar to hash-object's logic.
-            let object_content = format!("blob {}\0{}", git_object.to_string().len(), git_object);
-            let object_bytes = object_content.as_bytes();
-            
-            let mut hasher = Sha1::new();
-            hasher.update(object_bytes);
-            hasher.finalize().into() // Convert GenericArray to [u8; 20]
-        } else {
-            // We don't handle symlinks or other file types.
-            continue;
-        };
-
-        // 5. Determine the file mode. For simplicity, we'll use 100644 for files
-        // and 040000 for directories.
-        let mode = if file_type.is_dir() { "40000" } else { "100644" };
-        entries.push(TreeEntry {
-            mode: mode.to_string(),
-            name: entry_name,
-            hash,
-        });
+/// Writes the tree objects for the staged snapshot in the index and returns
+/// the id of the root tree.
+///
+/// Directories whose cached tree (the index `TREE` extension) is still valid
+/// are reused without being rebuilt. Afterwards the cache is replaced with one
+/// describing every tree just written, so the next call can skip them all.
+pub fn write_tree_from_index(index: &mut Index, store: &ObjectStore) -> Result<[u8; 20]> {
+    // 1. Unmerged entries have no single version to commit.
+    if let Some(entry) = index.entries.iter().find(|entry| entry.stage != 0) {
+        bail!("Cannot write a tree: {} has unresolved conflicts", entry.path.display());
     }
 
-    // 6. Sort the entries by name, as required by Git.
-    entries.sort();
-
-    // 7. Create the Tree object and serialize it.
-    let tree = Tree { entries };
-    let tree_content = tree.as_bytes();
+    // 2. Entries added with --intent-to-add have no content yet.
+    let entries: Vec<(&[u8], &IndexEntry)> = index
+        .entries
+        .iter()
+        .filter(|entry| !entry.intent_to_add)
+        .map(|entry| (index::path_bytes(&entry.path), entry))
+        .collect();
 
-    // 8. Hash the serialized tree content to get the tree's hash.
-    let header = format!("tree {}\0", tree_content.len());
-    let mut full_content = header.as_bytes().to_vec();
-    full_content.extend_from_slice(&tree_content);
+    // 3. Build the trees bottom up.
+    let (hash, cache_tree) = build_tree(&entries, 0, index.cache_tree.as_ref(), store)?;
+    index.cache_tree = Some(cache_tree);
+    Ok(hash)
+}
 
-    let mut hasher = Sha1::new();
-    hasher.update(&full_content);
-    let hash: [u8; 20] = hasher.finalize().into();
+/// Builds the tree for `entries`, which all live in the same directory and
+/// whose paths are relative to it from byte `prefix_len` on.
+fn build_tree(
+    entries: &[(&[u8], &IndexEntry)],
+    prefix_len: usize,
+    cached: Option<&CacheTree>,
+    store: &ObjectStore,
+) -> Result<([u8; 20], CacheTree)> {
+    // 1. A valid cached tree covering the same number of entries is reused.
+    if let Some(cached) = cached
+        && let Some(hash) = cached.valid_oid()
+        && cached.entry_count as usize == entries.len()
+        && store.exists(&hash)
+    {
+        return Ok((hash, cached.clone()));
+    }
 
-    // 9. Write the new tree object to the database.
-    let hash_hex = hex::encode(hash);
-    let object_dir = Path::new(".git/objects").join(&hash_hex[0..2]);
-    fs::create_dir_all(&object_dir)?;
-    let object_path = object_dir.join(&hash_hex[2..]);
+    // 2. The index is sorted by path, so everything inside a subdirectory is
+    // one contiguous run of entries.
+    let mut tree_entries = Vec::new();
+    let mut subtrees = Vec::new();
+    let mut i = 0;
+    while i < entries.len() {
+        let (path, entry) = &entries[i];
+        let relative = &path[prefix_len..];
+        match relative.iter().position(|&b| b == b'/') {
+            Some(slash) => {
+                let dir = &relative[..slash];
+                let dir_prefix = &path[..prefix_len + slash + 1];
+                let end = i + entries[i..]
+                    .iter()
+                    .take_while(|(path, _)| path.starts_with(dir_prefix))
+                    .count();
+                let cached_subtree = cached.and_then(|cached| cached.find(dir));
+                let (hash, cache_tree) =
+                    build_tree(&entries[i..end], dir_prefix.len(), cached_subtree, store)?;
+                tree_entries.push(TreeEntry {
+                    mode: "40000".to_string(),
+                    name: dir.to_vec(),
+                    hash,
+                });
+                subtrees.push((dir.to_vec(), cache_tree));
+                i = end;
+            }
+            None => {
+                tree_entries.push(TreeEntry {
+                    mode: format!("{:o}", entry.mode),
+                    name: relative.to_vec(),
+                    hash: entry.hash,
+                });
+                i += 1;
+            }
+        }
+    }
 
-    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-    encoder.write_all(&full_content)?;
-    let compressed_bytes = encoder.finish()?;
-    fs::write(&object_path, compressed_bytes)?;
+    // 3. Sort the entries as git does and write the tree.
+    tree_entries.sort_by(compare_tree_entries);
+    let hash = store.write(&GitObject::Tree(Tree { entries: tree_entries }))?;
+    let cache_tree = CacheTree {
+        entry_count: entries.len() as i32,
+        oid: Some(hash),
+        subtrees,
+    };
+    Ok((hash, cache_tree))
+}
 
-    // 10. Return the hash of the tree we just created.
-    Ok(hash)
-}
\ No newline at end of file
+/// Git sorts tree entries by name, comparing directory names as if they ended
+/// in `/`, so `foo.txt` comes before the directory `foo`.
+fn compare_tree_entries(a: &TreeEntry, b: &TreeEntry) -> Ordering {
+    let key = |entry: &TreeEntry| {
+        let mut name = entry.name.clone();
+        if entry.is_tree() {
+            name.push(b'/');
+        }
+        name
+    };
+    key(a).cmp(&key(b))
+}
@@ -0,0 +1,833 @@
+// src/config.rs
+
+use crate::lockfile::LockFile;
+use crate::refs::{self, RefValue};
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Includes nested deeper than this are assumed to be a loop.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// Which config file a setting comes from. Later levels override earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigLevel {
+    System,
+    Global,
+    Local,
+}
+
+/// One `key = value` setting.
+#[derive(Debug, Clone)]
+pub struct ConfigEntry {
+    /// `section.name` or `section.subsection.name`. Section and name are
+    /// lowercase; the subsection keeps its case.
+    pub key: String,
+    /// None for a line with just the name, which means `true`.
+    pub value: Option<String>,
+    pub origin: PathBuf,
+    pub level: ConfigLevel,
+}
+
+/// All settings from every config file, in the order they were read.
+#[derive(Debug, Default)]
+pub struct Config {
+    entries: Vec<ConfigEntry>,
+}
+
+impl Config {
+    /// Reads the system, global and (given a git directory) repository config
+    /// files, following `include.path` and `includeIf.<condition>.path`.
+    pub fn load(git_dir: Option<&Path>) -> Result<Self> {
+        let mut config = Config::default();
+        for level in [ConfigLevel::System, ConfigLevel::Global, ConfigLevel::Local] {
+            config.load_level_files(level, git_dir)?;
+        }
+        Ok(config)
+    }
+
+    /// Reads only the files of one level, e.g. for `config --global`.
+    pub fn load_level(level: ConfigLevel, git_dir: Option<&Path>) -> Result<Self> {
+        let mut config = Config::default();
+        config.load_level_files(level, git_dir)?;
+        Ok(config)
+    }
+
+    fn load_level_files(&mut self, level: ConfigLevel, git_dir: Option<&Path>) -> Result<()> {
+        for path in read_paths(level, git_dir) {
+            self.load_file(&path, level, git_dir, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a single file (and what it includes), e.g. for `config --file`.
+    pub fn load_from(path: &Path, level: ConfigLevel, git_dir: Option<&Path>) -> Result<Self> {
+        let mut config = Config::default();
+        config.load_file(path, level, git_dir, 0)?;
+        Ok(config)
+    }
+
+    fn load_file(&mut self, path: &Path, level: ConfigLevel, git_dir: Option<&Path>, depth: usize) -> Result<()> {
+        if !path.is_file() {
+            return Ok(());
+        }
+        if depth > MAX_INCLUDE_DEPTH {
+            bail!("Exceeded maximum include depth ({}) at {:?}", MAX_INCLUDE_DEPTH, path);
+        }
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read config file {:?}", path))?;
+        let parsed = ConfigFile::parse(&text).with_context(|| format!("Invalid config file {:?}", path))?;
+
+        for entry in parsed.entries {
+            let key = entry.key();
+            // Includes take effect where they appear, so later settings in this
+            // file still override what they bring in.
+            let include = match (entry.section.as_str(), &entry.subsection, entry.name.as_str()) {
+                ("include", None, "path") => entry.value.clone(),
+                ("includeif", Some(condition), "path") if include_condition(condition, path, git_dir)? => {
+                    entry.value.clone()
+                }
+                _ => None,
+            };
+            self.entries.push(ConfigEntry {
+                key,
+                value: entry.value,
+                origin: path.to_path_buf(),
+                level,
+            });
+            if let Some(include) = include {
+                let include = expand_path(&include, path)?;
+                self.load_file(&include, level, git_dir, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[ConfigEntry] {
+        &self.entries
+    }
+
+    /// The last value set for `key`. A name without a value reads as empty.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_entry(key).map(|entry| entry.value.as_deref().unwrap_or(""))
+    }
+
+    /// Every value set for a multi-valued key, in order.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        let Ok(key) = normalize_key(key) else {
+            return Vec::new();
+        };
+        self.entries
+            .iter()
+            .filter(|entry| entry.key == key)
+            .map(|entry| entry.value.as_deref().unwrap_or(""))
+            .collect()
+    }
+
+    /// Reads a boolean setting: `true`/`yes`/`on`/non-zero numbers, a bare
+    /// name, or `false`/`no`/`off`/`0`/empty.
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        let Some(entry) = self.get_entry(key) else {
+            return Ok(None);
+        };
+        match &entry.value {
+            None => Ok(Some(true)),
+            Some(value) => parse_bool(value)
+                .map(Some)
+                .with_context(|| format!("bad boolean config value '{}' for '{}'", value, key)),
+        }
+    }
+
+    fn get_entry(&self, key: &str) -> Option<&ConfigEntry> {
+        let key = normalize_key(key).ok()?;
+        self.entries.iter().rev().find(|entry| entry.key == key)
+    }
+}
+
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" => Some(true),
+        "false" | "no" | "off" | "" => Some(false),
+        number => number.parse::<i64>().ok().map(|n| n != 0),
+    }
+}
+
+/// The files read for a level, in order. Global settings come from
+/// `$XDG_CONFIG_HOME/git/config` and then `~/.gitconfig`.
+fn read_paths(level: ConfigLevel, git_dir: Option<&Path>) -> Vec<PathBuf> {
+    match level {
+        ConfigLevel::System => {
+            let disabled = env::var("GIT_CONFIG_NOSYSTEM").is_ok_and(|v| parse_bool(&v) == Some(true));
+            if disabled {
+                return Vec::new();
+            }
+            vec![env::var_os("GIT_CONFIG_SYSTEM").map_or_else(|| PathBuf::from("/etc/gitconfig"), PathBuf::from)]
+        }
+        ConfigLevel::Global => {
+            if let Some(path) = env::var_os("GIT_CONFIG_GLOBAL") {
+                return vec![PathBuf::from(path)];
+            }
+            xdg_config_path().into_iter().chain(home_dir().map(|home| home.join(".gitconfig"))).collect()
+        }
+        ConfigLevel::Local => git_dir.map(|dir| dir.join("config")).into_iter().collect(),
+    }
+}
+
+/// The file `config` writes to for a level. Global settings go to
+/// `~/.gitconfig`, unless only the XDG file exists.
+pub fn write_path(level: ConfigLevel, git_dir: Option<&Path>) -> Result<PathBuf> {
+    match level {
+        ConfigLevel::System => Ok(read_paths(level, git_dir)
+            .pop()
+            .unwrap_or_else(|| PathBuf::from("/etc/gitconfig"))),
+        ConfigLevel::Global => {
+            if let Some(path) = env::var_os("GIT_CONFIG_GLOBAL") {
+                return Ok(PathBuf::from(path));
+            }
+            let home = home_dir().context("$HOME is not set")?.join(".gitconfig");
+            match xdg_config_path() {
+                Some(xdg) if !home.exists() && xdg.exists() => Ok(xdg),
+                _ => Ok(home),
+            }
+        }
+        ConfigLevel::Local => Ok(git_dir.context("Not in a git repository")?.join("config")),
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").filter(|home| !home.is_empty()).map(PathBuf::from)
+}
+
+fn xdg_config_path() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Some(PathBuf::from(dir).join("git").join("config")),
+        None => home_dir().map(|home| home.join(".config").join("git").join("config")),
+    }
+}
+
+/// Resolves an include path: `~/` is the home directory and relative paths
+/// are relative to the including file.
+fn expand_path(path: &str, including_file: &Path) -> Result<PathBuf> {
+    if let Some(rest) = path.strip_prefix("~/") {
+        return Ok(home_dir().context("$HOME is not set")?.join(rest));
+    }
+    let base = including_file.parent().unwrap_or(Path::new("."));
+    Ok(base.join(path))
+}
+
+/// Evaluates an `includeIf` condition: `gitdir:`, `gitdir/i:` or `onbranch:`.
+fn include_condition(condition: &str, including_file: &Path, git_dir: Option<&Path>) -> Result<bool> {
+    let Some(git_dir) = git_dir else {
+        return Ok(false);
+    };
+    if let Some(pattern) = condition.strip_prefix("onbranch:") {
+        let Some(RefValue::Symbolic(head)) = refs::read_ref(git_dir, "HEAD")? else {
+            return Ok(false);
+        };
+        let branch = refs::shorten_ref_name(&head);
+        let pattern = if pattern.ends_with('/') { format!("{}**", pattern) } else { pattern.to_string() };
+        return Ok(wildmatch(pattern.as_bytes(), branch.as_bytes()));
+    }
+
+    let (pattern, ignore_case) = match condition.strip_prefix("gitdir:") {
+        Some(pattern) => (pattern, false),
+        None => match condition.strip_prefix("gitdir/i:") {
+            Some(pattern) => (pattern, true),
+            None => return Ok(false),
+        },
+    };
+
+    // `~/` and `./` are expanded, other relative patterns can match anywhere,
+    // and a trailing `/` matches everything below.
+    let mut pattern = if let Some(rest) = pattern.strip_prefix("./") {
+        let base = including_file.parent().unwrap_or(Path::new("."));
+        format!("{}/{}", base.display(), rest)
+    } else if pattern.starts_with("~/") {
+        expand_path(pattern, including_file)?.display().to_string()
+    } else if !pattern.starts_with('/') {
+        format!("**/{}", pattern)
+    } else {
+        pattern.to_string()
+    };
+    if pattern.ends_with('/') {
+        pattern.push_str("**");
+    }
+
+    let mut candidates = vec![git_dir.display().to_string()];
+    if let Ok(real) = fs::canonicalize(git_dir) {
+        candidates.push(real.display().to_string());
+    }
+    Ok(candidates.iter().any(|dir| {
+        if ignore_case {
+            wildmatch(pattern.to_lowercase().as_bytes(), dir.to_lowercase().as_bytes())
+        } else {
+            wildmatch(pattern.as_bytes(), dir.as_bytes())
+        }
+    }))
+}
+
+/// Path matching where `*` and `?` stay within one path component and `**`
+/// crosses any number of them.
+fn wildmatch(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            // `**/` may also match no directories at all.
+            (rest.first() == Some(&b'/') && wildmatch(&rest[1..], text))
+                || (0..=text.len()).any(|i| wildmatch(rest, &text[i..]))
+        }
+        [b'*', rest @ ..] => {
+            for i in 0..=text.len() {
+                if wildmatch(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&b'/') {
+                    break;
+                }
+            }
+            false
+        }
+        [b'?', rest @ ..] => text.first().is_some_and(|&c| c != b'/') && wildmatch(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && wildmatch(rest, &text[1..]),
+    }
+}
+
+/// Splits `section[.subsection].name` and lowercases the section and name.
+fn split_key(key: &str) -> Result<(String, Option<String>, String)> {
+    let (section, rest) = key.split_once('.').with_context(|| format!("key does not contain a section: {}", key))?;
+    let (subsection, name) = match rest.rsplit_once('.') {
+        Some((subsection, name)) => (Some(subsection.to_string()), name),
+        None => (None, rest),
+    };
+    let valid_section = !section.is_empty() && section.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid_section || !valid_name {
+        bail!("invalid key: {}", key);
+    }
+    Ok((section.to_ascii_lowercase(), subsection, name.to_ascii_lowercase()))
+}
+
+/// The canonical spelling of a key, as stored in `ConfigEntry::key`.
+pub fn normalize_key(key: &str) -> Result<String> {
+    let (section, subsection, name) = split_key(key)?;
+    Ok(match subsection {
+        Some(subsection) => format!("{}.{}.{}", section, subsection, name),
+        None => format!("{}.{}", section, name),
+    })
+}
+
+/// A parsed setting, with where it sits in the file so it can be edited.
+#[derive(Debug)]
+struct FileEntry {
+    section: String,
+    subsection: Option<String>,
+    name: String,
+    value: Option<String>,
+    /// The bytes of its line(s), including the newline. None when it shares
+    /// a line with its section header, which we don't rewrite.
+    span: Option<Range<usize>>,
+    /// Index into `ConfigFile::headers`.
+    header: usize,
+}
+
+impl FileEntry {
+    fn key(&self) -> String {
+        match &self.subsection {
+            Some(subsection) => format!("{}.{}.{}", self.section, subsection, self.name),
+            None => format!("{}.{}", self.section, self.name),
+        }
+    }
+
+    fn matches(&self, section: &str, subsection: Option<&str>, name: &str) -> bool {
+        self.section == section && self.subsection.as_deref() == subsection && self.name == name
+    }
+}
+
+#[derive(Debug)]
+struct FileHeader {
+    section: String,
+    subsection: Option<String>,
+    /// The bracketed header itself, e.g. `[branch "main"]`.
+    span: Range<usize>,
+    /// Just past the end of the header's line.
+    line_end: usize,
+}
+
+/// A config file in git's INI dialect:
+///
+/// ```text
+/// # comment
+/// [section]
+///     name = value        ; comment
+///     flag
+/// [section "Subsection"]
+///     name = "quoted value with \"escapes\"\n" \
+///            continued
+/// ```
+#[derive(Debug, Default)]
+struct ConfigFile {
+    entries: Vec<FileEntry>,
+    headers: Vec<FileHeader>,
+}
+
+impl ConfigFile {
+    fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser { text: text.as_bytes(), pos: 0 };
+        let mut file = ConfigFile::default();
+
+        loop {
+            let line_start = parser.pos;
+            let mut own_line = true;
+            parser.skip_blanks();
+            match parser.peek() {
+                None => break,
+                Some(b'\n' | b'\r') => parser.pos += 1,
+                Some(b'#' | b';') => parser.skip_line(),
+                Some(b'[') => {
+                    let start = parser.pos;
+                    let (section, subsection) = parser.header()?;
+                    let span = start..parser.pos;
+                    let line_end = text[parser.pos..].find('\n').map_or(text.len(), |i| parser.pos + i + 1);
+                    file.headers.push(FileHeader { section, subsection, span, line_end });
+                    // A setting may follow the header on the same line.
+                    parser.skip_blanks();
+                    if parser.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                        own_line = false;
+                        file.entry(&mut parser, line_start, own_line)?;
+                    }
+                }
+                Some(c) if c.is_ascii_alphabetic() => file.entry(&mut parser, line_start, own_line)?,
+                Some(_) => bail!("bad config line {}", parser.line_number()),
+            }
+        }
+        Ok(file)
+    }
+
+    fn entry(&mut self, parser: &mut Parser, line_start: usize, own_line: bool) -> Result<()> {
+        let Some(header) = self.headers.len().checked_sub(1) else {
+            bail!("bad config line {}: setting outside of a section", parser.line_number());
+        };
+        let name = parser.name().to_ascii_lowercase();
+        parser.skip_blanks();
+        let value = match parser.peek() {
+            Some(b'=') => {
+                parser.pos += 1;
+                Some(parser.value()?)
+            }
+            None | Some(b'\n' | b'\r' | b'#' | b';') => {
+                parser.skip_line();
+                None
+            }
+            Some(_) => bail!("bad config line {}", parser.line_number()),
+        };
+        let header_ref = &self.headers[header];
+        self.entries.push(FileEntry {
+            section: header_ref.section.clone(),
+            subsection: header_ref.subsection.clone(),
+            name,
+            value,
+            span: own_line.then_some(line_start..parser.pos),
+            header,
+        });
+        Ok(())
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn line_number(&self) -> usize {
+        self.text[..self.pos.min(self.text.len())].iter().filter(|&&c| c == b'\n').count() + 1
+    }
+
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Moves past the end of the current line.
+    fn skip_line(&mut self) {
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == b'\n' {
+                break;
+            }
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == b'-') {
+            self.pos += 1;
+        }
+        String::from_utf8_lossy(&self.text[start..self.pos]).into_owned()
+    }
+
+    /// Parses `[section]`, `[section "subsection"]` or the old `[section.subsection]`.
+    fn header(&mut self) -> Result<(String, Option<String>)> {
+        self.pos += 1;
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'.') {
+            self.pos += 1;
+        }
+        let name = String::from_utf8_lossy(&self.text[start..self.pos]).to_ascii_lowercase();
+        if name.is_empty() {
+            bail!("bad section header on line {}", self.line_number());
+        }
+
+        let subsection = match self.peek() {
+            Some(b']') => match name.split_once('.') {
+                Some((section, subsection)) => {
+                    self.pos += 1;
+                    return Ok((section.to_string(), Some(subsection.to_string())));
+                }
+                None => None,
+            },
+            Some(b' ' | b'\t') => {
+                self.skip_blanks();
+                if self.peek() != Some(b'"') {
+                    bail!("bad section header on line {}", self.line_number());
+                }
+                self.pos += 1;
+                let mut subsection = Vec::new();
+                loop {
+                    match self.peek() {
+                        None | Some(b'\n') => bail!("bad section header on line {}", self.line_number()),
+                        Some(b'"') => break,
+                        Some(b'\\') => {
+                            self.pos += 1;
+                            subsection.extend(self.peek());
+                        }
+                        Some(c) => subsection.push(c),
+                    }
+                    self.pos += 1;
+                }
+                self.pos += 1;
+                Some(String::from_utf8_lossy(&subsection).into_owned())
+            }
+            _ => bail!("bad section header on line {}", self.line_number()),
+        };
+        if self.peek() != Some(b']') {
+            bail!("bad section header on line {}", self.line_number());
+        }
+        self.pos += 1;
+        Ok((name, subsection))
+    }
+
+    /// Parses a value up to the end of its (possibly continued) line. Quotes
+    /// are removed, escapes are decoded, and unquoted trailing whitespace and
+    /// comments are dropped.
+    fn value(&mut self) -> Result<String> {
+        self.skip_blanks();
+        let mut value = Vec::new();
+        // Length of `value` without trailing unquoted whitespace.
+        let mut kept = 0;
+        let mut quoted = false;
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                b'\n' => {
+                    if quoted {
+                        bail!("unterminated quote on line {}", self.line_number() - 1);
+                    }
+                    break;
+                }
+                b'#' | b';' if !quoted => {
+                    self.skip_line();
+                    break;
+                }
+                b'"' => quoted = !quoted,
+                b'\\' => {
+                    let escaped = self.peek().context("bad escape at end of file")?;
+                    self.pos += 1;
+                    match escaped {
+                        // A backslash at the end of a line continues the value.
+                        b'\n' => continue,
+                        b'\r' if self.peek() == Some(b'\n') => {
+                            self.pos += 1;
+                            continue;
+                        }
+                        b'n' => value.push(b'\n'),
+                        b't' => value.push(b'\t'),
+                        b'b' => value.push(0x08),
+                        b'\\' | b'"' => value.push(escaped),
+                        _ => bail!("bad escape in config value on line {}", self.line_number()),
+                    }
+                    kept = value.len();
+                }
+                b' ' | b'\t' | b'\r' if !quoted => value.push(c),
+                c => {
+                    value.push(c);
+                    kept = value.len();
+                }
+            }
+        }
+        if quoted {
+            bail!("unterminated quote in config value");
+        }
+        value.truncate(kept);
+        Ok(String::from_utf8_lossy(&value).into_owned())
+    }
+}
+
+/// Sets `key` in the config file at `path`, creating the file if needed.
+/// An existing single value is replaced in place; with `add`, another value
+/// is appended instead, for multi-valued keys.
+pub fn set_value(path: &Path, key: &str, value: &str, add: bool) -> Result<()> {
+    let (section, subsection, name) = split_key(key)?;
+    // Written as the user spelled it; matching ignores case.
+    let written_name = key.rsplit('.').next().unwrap_or(&name);
+    let line = format!("\t{} = {}\n", written_name, quote_value(value));
+
+    let mut lock = LockFile::acquire(path)?;
+    let mut text = read_existing(path)?;
+    let file = ConfigFile::parse(&text).with_context(|| format!("Invalid config file {:?}", path))?;
+    let existing: Vec<&FileEntry> = file
+        .entries
+        .iter()
+        .filter(|entry| entry.matches(&section, subsection.as_deref(), &name))
+        .collect();
+
+    match existing.as_slice() {
+        [entry] if !add && entry.span.is_some() => {
+            let span = entry.span.clone().unwrap_or_default();
+            text.replace_range(span, &line);
+        }
+        [_, _, ..] if !add => bail!("{} has multiple values", key),
+        _ => {
+            // Append to the last block for this section, or start a new one.
+            let header = file
+                .headers
+                .iter()
+                .rposition(|h| h.section == section && h.subsection == subsection);
+            match header {
+                Some(index) => {
+                    let end = file
+                        .entries
+                        .iter()
+                        .filter(|entry| entry.header == index)
+                        .filter_map(|entry| entry.span.as_ref().map(|span| span.end))
+                        .max()
+                        .unwrap_or(file.headers[index].line_end);
+                    if end == text.len() && !text.is_empty() && !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                    let end = end.min(text.len());
+                    text.insert_str(end, &line);
+                }
+                None => {
+                    if !text.is_empty() && !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                    text.push_str(&section_header(key, &subsection));
+                    text.push_str(&line);
+                }
+            }
+        }
+    }
+
+    lock.write_all(text.as_bytes())?;
+    lock.commit()
+}
+
+/// Removes `key` from the config file at `path`. Returns how many values were
+/// removed. A key with several values is only removed with `all`.
+pub fn unset_value(path: &Path, key: &str, all: bool) -> Result<usize> {
+    let (section, subsection, name) = split_key(key)?;
+    let mut lock = LockFile::acquire(path)?;
+    let mut text = read_existing(path)?;
+    let file = ConfigFile::parse(&text).with_context(|| format!("Invalid config file {:?}", path))?;
+    let spans: Vec<Range<usize>> = file
+        .entries
+        .iter()
+        .filter(|entry| entry.matches(&section, subsection.as_deref(), &name))
+        .filter_map(|entry| entry.span.clone())
+        .collect();
+
+    if spans.len() > 1 && !all {
+        bail!("{} has multiple values", key);
+    }
+    // Sections left empty go with their settings.
+    let blocks = emptied_sections(&text, &file, &section, subsection.as_deref(), &spans);
+    let mut edits: Vec<Range<usize>> = spans
+        .iter()
+        .filter(|span| !blocks.iter().any(|block| covers(block, span)))
+        .cloned()
+        .chain(blocks.iter().cloned())
+        .collect();
+    edits.sort_by_key(|span| span.start);
+    // Remove from the end so earlier spans stay valid.
+    for span in edits.iter().rev() {
+        text.replace_range(span.clone(), "");
+    }
+    if !spans.is_empty() {
+        lock.write_all(text.as_bytes())?;
+        lock.commit()?;
+    }
+    Ok(spans.len())
+}
+
+/// The blocks of `section.subsection` that removing `spans` leaves without
+/// settings, so their headers can go too. Like git, a block is kept when a
+/// comment sits in it or directly above it, as the comment may be about it.
+/// Each block runs from the end of whatever precedes it, taking the blank
+/// lines above the header, up to the next header of another section.
+fn emptied_sections(
+    text: &str,
+    file: &ConfigFile,
+    section: &str,
+    subsection: Option<&str>,
+    spans: &[Range<usize>],
+) -> Vec<Range<usize>> {
+    let is_ours = |header: &FileHeader| header.section == section && header.subsection.as_deref() == subsection;
+    // Repeated headers of the section don't keep it alive on their own.
+    let mut removable: Vec<Range<usize>> = file
+        .headers
+        .iter()
+        .filter(|header| is_ours(header))
+        .map(|header| header.span.clone())
+        .chain(spans.iter().cloned())
+        .collect();
+    removable.sort_by_key(|span| span.start);
+    let is_blank = |range: &Range<usize>| {
+        let mut rest = range.start;
+        let mut blank = true;
+        for span in removable.iter().filter(|span| covers(range, span)) {
+            blank &= text[rest..span.start].trim().is_empty();
+            rest = span.end;
+        }
+        blank && text[rest..range.end].trim().is_empty()
+    };
+
+    let mut blocks: Vec<Range<usize>> = Vec::new();
+    for (index, header) in file.headers.iter().enumerate() {
+        if !is_ours(header) || blocks.last().is_some_and(|block| block.end > header.span.start) {
+            continue;
+        }
+        let end = file.headers[index..]
+            .iter()
+            .find(|next| !is_ours(next))
+            .map_or(text.len(), |next| next.span.start);
+        let body = header.span.start..end;
+        if !spans.iter().any(|span| covers(&body, span)) || !is_blank(&body) {
+            continue;
+        }
+        // Whatever comes before: a setting, a header line (unless a comment
+        // follows the header on it) or the start of the file.
+        let start = file
+            .entries
+            .iter()
+            .filter_map(|entry| entry.span.as_ref().map(|span| span.end))
+            .chain(file.headers[..index].iter().enumerate().map(|(before, h)| {
+                let inline = file.entries.iter().any(|entry| entry.header == before && entry.span.is_none());
+                if inline || text[h.span.end..h.line_end].trim().is_empty() { h.line_end } else { h.span.end }
+            }))
+            .filter(|&end| end <= header.span.start)
+            .max()
+            .unwrap_or(0);
+        if text[start..header.span.start].trim().is_empty() {
+            blocks.push(start..end);
+        }
+    }
+    blocks
+}
+
+fn covers(outer: &Range<usize>, inner: &Range<usize>) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// Renames every `[section "subsection"]` block named `old` (given as
+/// `section.subsection`) to `new`, or removes the blocks and their settings
+/// when `new` is None. Returns how many blocks were changed.
+pub fn rename_section(path: &Path, old: &str, new: Option<&str>) -> Result<usize> {
+    let (section, subsection) = split_section(old)?;
+    let header = match new {
+        Some(new) => Some(section_header(new, &split_section(new)?.1)),
+        None => None,
+    };
+
+    let mut lock = LockFile::acquire(path)?;
+    let mut text = read_existing(path)?;
+    let file = ConfigFile::parse(&text).with_context(|| format!("Invalid config file {:?}", path))?;
+    let spans: Vec<Range<usize>> = file
+        .headers
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| h.section == section && h.subsection == subsection)
+        .map(|(index, h)| match &header {
+            Some(_) => h.span.clone(),
+            // A block runs up to the next header.
+            None => h.span.start..file.headers.get(index + 1).map_or(text.len(), |next| next.span.start),
+        })
+        .collect();
+
+    // Edit from the end so earlier spans stay valid.
+    for span in spans.iter().rev() {
+        let replacement = header.as_deref().map_or("", |header| header.trim_end());
+        text.replace_range(span.clone(), replacement);
+    }
+    if !spans.is_empty() {
+        lock.write_all(text.as_bytes())?;
+        lock.commit()?;
+    }
+    Ok(spans.len())
+}
+
+/// Splits `section[.subsection]` and lowercases the section.
+fn split_section(name: &str) -> Result<(String, Option<String>)> {
+    let (section, subsection) = match name.split_once('.') {
+        Some((section, subsection)) => (section, Some(subsection.to_string())),
+        None => (name, None),
+    };
+    if section.is_empty() || !section.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        bail!("invalid section name: {}", name);
+    }
+    Ok((section.to_ascii_lowercase(), subsection))
+}
+
+fn read_existing(path: &Path) -> Result<String> {
+    if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("Failed to read config file {:?}", path))
+    } else {
+        Ok(String::new())
+    }
+}
+
+fn section_header(key: &str, subsection: &Option<String>) -> String {
+    let section = key.split('.').next().unwrap_or(key);
+    match subsection {
+        Some(subsection) => {
+            let escaped = subsection.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("[{} \"{}\"]\n", section, escaped)
+        }
+        None => format!("[{}]\n", section),
+    }
+}
+
+/// Escapes a value for writing, quoting it if it would otherwise lose
+/// leading or trailing whitespace or be cut short by a comment character.
+fn quote_value(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    let needs_quotes = value.starts_with([' ', '\t'])
+        || value.ends_with([' ', '\t'])
+        || value.contains(['#', ';']);
+    if needs_quotes {
+        format!("\"{}\"", escaped)
+    } else {
+        escaped
+    }
+}
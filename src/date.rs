@@ -0,0 +1,239 @@
+// src/date.rs
+
+use anyhow::{bail, Context, Result};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the Unix epoch, right now.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Parses the date forms git users type, relative to `now`:
+///
+/// - `now`, `yesterday`
+/// - `<n> <unit>[s] ago`, with spaces or dots (`2.weeks.ago`); units are
+///   seconds, minutes, hours, days, weeks, months (30 days) and years (365 days)
+/// - `@<seconds>` or a bare Unix timestamp
+/// - `YYYY-MM-DD`, optionally followed by `HH:MM[:SS]`, in UTC
+pub fn parse_approxidate(text: &str, now: i64) -> Result<i64> {
+    let text = text.trim().to_ascii_lowercase();
+    let words: Vec<&str> = text.split(['.', ' ']).filter(|w| !w.is_empty()).collect();
+
+    match words.as_slice() {
+        ["now"] => return Ok(now),
+        ["yesterday"] => return Ok(now - 86_400),
+        [count, unit, "ago"] => {
+            let count: i64 = count.parse().with_context(|| format!("Invalid date: {}", text))?;
+            return Ok(now - count * unit_seconds(unit).with_context(|| format!("Invalid date: {}", text))?);
+        }
+        _ => {}
+    }
+
+    let seconds = text.strip_prefix('@').unwrap_or(&text);
+    if !seconds.is_empty() && seconds.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(seconds.parse()?);
+    }
+
+    let (day, time) = text.split_once([' ', 't']).unwrap_or((&text, "00:00:00"));
+    let day: Vec<i64> = day.split('-').map(str::parse).collect::<Result<_, _>>()
+        .with_context(|| format!("Invalid date: {}", text))?;
+    let time: Vec<i64> = time.split(':').map(str::parse).collect::<Result<_, _>>()
+        .with_context(|| format!("Invalid date: {}", text))?;
+    match (day.as_slice(), time.as_slice()) {
+        ([year, month, day], [hour, minute, rest @ ..]) if rest.len() <= 1 => {
+            let second = rest.first().copied().unwrap_or(0);
+            Ok(days_from_civil(*year, *month, *day) * 86_400 + hour * 3600 + minute * 60 + second)
+        }
+        _ => bail!("Invalid date: {}", text),
+    }
+}
+
+fn unit_seconds(unit: &str) -> Option<i64> {
+    let unit = unit.strip_suffix('s').unwrap_or(unit);
+    Some(match unit {
+        "second" | "sec" => 1,
+        "minute" | "min" => 60,
+        "hour" => 3600,
+        "day" => 86_400,
+        "week" => 7 * 86_400,
+        "month" => 30 * 86_400,
+        "year" => 365 * 86_400,
+        _ => return None,
+    })
+}
+
+/// Days since 1970-01-01 for a date in the proleptic Gregorian calendar.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The year, month and day of a day counted from 1970-01-01; the inverse of
+/// `days_from_civil`.
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// How to show a date, as for `log`'s `--date` and `%ad`-style placeholders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateFormat {
+    /// `Wed Nov 15 00:23:20 2023 +0200`
+    Default,
+    /// `3 days ago`
+    Relative,
+    /// `2023-11-15 00:23:20 +0200`
+    Iso,
+    /// `2023-11-15T00:23:20+02:00`
+    IsoStrict,
+    /// `2023-11-15`
+    Short,
+    /// `1700000600`
+    Unix,
+}
+
+/// Formats a timestamp as seen in the timezone it was recorded in.
+pub fn format_date(timestamp: i64, offset: i64, format: DateFormat) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let local = timestamp + offset;
+    let days = local.div_euclid(86_400);
+    let seconds = local.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    let (hour, minute, second) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    let tz = format_offset(offset);
+
+    match format {
+        DateFormat::Default => format!(
+            "{} {} {} {:02}:{:02}:{:02} {} {}",
+            WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+            MONTHS[month as usize - 1],
+            day,
+            hour,
+            minute,
+            second,
+            year,
+            tz
+        ),
+        DateFormat::Relative => format_relative(timestamp, now()),
+        DateFormat::Iso => format!(
+            "{}-{:02}-{:02} {:02}:{:02}:{:02} {}",
+            year, month, day, hour, minute, second, tz
+        ),
+        DateFormat::IsoStrict => format!(
+            "{}-{:02}-{:02}T{:02}:{:02}:{:02}{}:{}",
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            &tz[..3],
+            &tz[3..]
+        ),
+        DateFormat::Short => format!("{}-{:02}-{:02}", year, month, day),
+        DateFormat::Unix => timestamp.to_string(),
+    }
+}
+
+/// Describes how long before `now` a time was, rounding the way git does:
+/// `45 seconds ago`, `3 hours ago`, `2 weeks ago`, `1 year, 2 months ago`.
+fn format_relative(timestamp: i64, now: i64) -> String {
+    let plural = |count: i64, unit: &str| {
+        format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+    };
+    if timestamp > now {
+        return "in the future".to_string();
+    }
+    let seconds = now - timestamp;
+    if seconds < 90 {
+        return format!("{} ago", plural(seconds, "second"));
+    }
+    let minutes = (seconds + 30) / 60;
+    if minutes < 90 {
+        return format!("{} ago", plural(minutes, "minute"));
+    }
+    let hours = (minutes + 30) / 60;
+    if hours < 36 {
+        return format!("{} ago", plural(hours, "hour"));
+    }
+    let days = (hours + 12) / 24;
+    if days < 14 {
+        return format!("{} ago", plural(days, "day"));
+    }
+    if days < 70 {
+        return format!("{} ago", plural((days + 3) / 7, "week"));
+    }
+    if days < 365 {
+        return format!("{} ago", plural((days + 15) / 30, "month"));
+    }
+    if days < 1825 {
+        let total_months = (days * 12 * 2 + 365) / (365 * 2);
+        let (years, months) = (total_months / 12, total_months % 12);
+        if months == 0 {
+            return format!("{} ago", plural(years, "year"));
+        }
+        return format!("{}, {} ago", plural(years, "year"), plural(months, "month"));
+    }
+    format!("{} ago", plural((days + 183) / 365, "year"))
+}
+
+/// The local timezone's offset from UTC in seconds at the given time, as
+/// `localtime_r` reports it (so daylight saving time is accounted for).
+/// Without `localtime_r`, times are taken to be in UTC.
+pub fn local_offset(timestamp: i64) -> i64 {
+    #[cfg(unix)]
+    {
+        let time = timestamp as libc::time_t;
+        // SAFETY: `tm` is plain data that localtime_r fills in; both pointers
+        // are valid for the duration of the call.
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::localtime_r(&time, &mut tm) };
+        if result.is_null() { 0 } else { tm.tm_gmtoff as i64 }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = timestamp;
+        0
+    }
+}
+
+/// Formats an offset in seconds the way git stores it: `+0100`, `-0430`.
+pub fn format_offset(offset: i64) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.abs() / 60;
+    format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// Parses a `+hhmm` / `-hhmm` offset into seconds.
+pub fn parse_offset(text: &str) -> Option<i64> {
+    let (sign, digits) = match text.as_bytes().first()? {
+        b'+' => (1, &text[1..]),
+        b'-' => (-1, &text[1..]),
+        _ => return None,
+    };
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i64 = digits[..2].parse().ok()?;
+    let minutes: i64 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
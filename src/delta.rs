@@ -0,0 +1,218 @@
+// src/delta.rs
+
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Reconstructs an object from its base and a git delta.
+///
+/// A delta starts with the base size and the result size (both as little-endian
+/// base-128 varints), followed by instructions: a byte with the high bit set
+/// copies a range out of the base, any other non-zero byte inserts that many
+/// literal bytes that follow it.
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut pos = 0;
+    let base_size = read_size(delta, &mut pos)?;
+    if base_size != base.len() {
+        bail!(
+            "Delta base size mismatch: expected {} bytes, got {}",
+            base_size,
+            base.len()
+        );
+    }
+    let result_size = read_size(delta, &mut pos)?;
+    let mut result = Vec::with_capacity(result_size);
+
+    while pos < delta.len() {
+        let opcode = delta[pos];
+        pos += 1;
+        if opcode & 0x80 != 0 {
+            // Copy: the low 4 bits say which offset bytes follow, the next 3
+            // which size bytes follow.
+            let mut offset = 0usize;
+            let mut size = 0usize;
+            for i in 0..4 {
+                if opcode & (1 << i) != 0 {
+                    offset |= (*delta.get(pos).ok_or_else(truncated)? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            for i in 0..3 {
+                if opcode & (0x10 << i) != 0 {
+                    size |= (*delta.get(pos).ok_or_else(truncated)? as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+            let end = offset
+                .checked_add(size)
+                .filter(|&end| end <= base.len())
+                .ok_or_else(|| anyhow::anyhow!("Delta copies past the end of its base"))?;
+            result.extend_from_slice(&base[offset..end]);
+        } else if opcode != 0 {
+            let end = pos + opcode as usize;
+            if end > delta.len() {
+                return Err(truncated());
+            }
+            result.extend_from_slice(&delta[pos..end]);
+            pos = end;
+        } else {
+            bail!("Invalid delta opcode 0");
+        }
+    }
+
+    if result.len() != result_size {
+        bail!(
+            "Delta result size mismatch: expected {} bytes, got {}",
+            result_size,
+            result.len()
+        );
+    }
+    Ok(result)
+}
+
+/// Reads only the result size from a delta's header.
+pub fn result_size(delta: &[u8]) -> Result<usize> {
+    let mut pos = 0;
+    read_size(delta, &mut pos)?;
+    read_size(delta, &mut pos)
+}
+
+fn read_size(data: &[u8], pos: &mut usize) -> Result<usize> {
+    let mut size = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos).ok_or_else(truncated)?;
+        *pos += 1;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+        if shift > 63 {
+            bail!("Delta size is too large");
+        }
+    }
+}
+
+fn truncated() -> anyhow::Error {
+    anyhow::anyhow!("Truncated delta")
+}
+
+/// Blocks of this many bytes in the base are indexed when creating a delta.
+const BLOCK_SIZE: usize = 16;
+/// Insert instructions can carry at most this many literal bytes.
+const MAX_INSERT: usize = 0x7f;
+/// Copy instructions can encode sizes up to three bytes long.
+const MAX_COPY: usize = 0xff_ffff;
+
+/// Creates a delta that turns `base` into `target`.
+///
+/// Every block of `BLOCK_SIZE` bytes in the base is indexed; the target is then
+/// scanned for those blocks, and each hit is extended as far as it matches in
+/// both directions and emitted as a copy. Everything else becomes inserts.
+pub fn create_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_size(&mut delta, base.len());
+    write_size(&mut delta, target.len());
+
+    let mut blocks: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    if base.len() >= BLOCK_SIZE {
+        for offset in (0..=base.len() - BLOCK_SIZE).step_by(BLOCK_SIZE) {
+            blocks
+                .entry(&base[offset..offset + BLOCK_SIZE])
+                .or_default()
+                .push(offset);
+        }
+    }
+
+    let mut pending = Vec::new();
+    let mut pos = 0;
+    while pos < target.len() {
+        let candidates = target
+            .get(pos..pos + BLOCK_SIZE)
+            .and_then(|block| blocks.get(block));
+        let Some(candidates) = candidates else {
+            pending.push(target[pos]);
+            pos += 1;
+            continue;
+        };
+
+        // Pick the candidate with the longest forward match.
+        let (mut base_start, mut length) = candidates
+            .iter()
+            .map(|&offset| (offset, common_prefix(&base[offset..], &target[pos..])))
+            .max_by_key(|&(_, length)| length)
+            .expect("block index entries are never empty");
+
+        // Extend backwards into bytes we were about to insert.
+        let mut target_start = pos;
+        while base_start > 0
+            && !pending.is_empty()
+            && base[base_start - 1] == target[target_start - 1]
+        {
+            base_start -= 1;
+            target_start -= 1;
+            length += 1;
+            pending.pop();
+        }
+
+        flush_inserts(&mut delta, &mut pending);
+        let mut copied = 0;
+        while copied < length {
+            let size = (length - copied).min(MAX_COPY);
+            write_copy(&mut delta, base_start + copied, size);
+            copied += size;
+        }
+        pos = target_start + length;
+    }
+    flush_inserts(&mut delta, &mut pending);
+    delta
+}
+
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+fn flush_inserts(delta: &mut Vec<u8>, pending: &mut Vec<u8>) {
+    for chunk in pending.chunks(MAX_INSERT) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+    pending.clear();
+}
+
+fn write_copy(delta: &mut Vec<u8>, offset: usize, size: usize) {
+    let opcode_pos = delta.len();
+    let mut opcode = 0x80u8;
+    delta.push(0);
+    // Only non-zero bytes of the offset and size are stored.
+    for i in 0..4 {
+        let byte = (offset >> (8 * i)) as u8;
+        if byte != 0 {
+            opcode |= 1 << i;
+            delta.push(byte);
+        }
+    }
+    for i in 0..3 {
+        let byte = (size >> (8 * i)) as u8;
+        if byte != 0 {
+            opcode |= 0x10 << i;
+            delta.push(byte);
+        }
+    }
+    delta[opcode_pos] = opcode;
+}
+
+fn write_size(delta: &mut Vec<u8>, mut size: usize) {
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+        if size == 0 {
+            delta.push(byte);
+            return;
+        }
+        delta.push(byte | 0x80);
+    }
+}
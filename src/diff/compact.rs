@@ -0,0 +1,281 @@
+// src/diff/compact.rs
+
+// Indents are measured up to this many columns, and blank lines counted up
+// to this many.
+const MAX_INDENT: i32 = 200;
+const MAX_BLANKS: i32 = 20;
+// How far a run of changes is slid looking for the best place for it.
+const MAX_SLIDING: usize = 100;
+
+// The weights git's indent heuristic gives each feature of a split.
+const START_OF_FILE_PENALTY: i32 = 1;
+const END_OF_FILE_PENALTY: i32 = 21;
+const TOTAL_BLANK_WEIGHT: i32 = -30;
+const POST_BLANK_WEIGHT: i32 = 6;
+const RELATIVE_INDENT_PENALTY: i32 = -4;
+const RELATIVE_INDENT_WITH_BLANK_PENALTY: i32 = 10;
+const RELATIVE_OUTDENT_PENALTY: i32 = 24;
+const RELATIVE_OUTDENT_WITH_BLANK_PENALTY: i32 = 17;
+const RELATIVE_DEDENT_PENALTY: i32 = 23;
+const RELATIVE_DEDENT_WITH_BLANK_PENALTY: i32 = 17;
+const INDENT_WEIGHT: i32 = 60;
+
+/// A run of changed lines, `start..end`. Between two runs there is always
+/// at least one unchanged line.
+#[derive(Debug, Clone, Copy)]
+struct Group {
+    start: usize,
+    end: usize,
+}
+
+/// Slides each run of changed lines in one file up or down to where git
+/// would put it, keeping `other` (the changes marked in the other file) in
+/// step.
+///
+/// A run can move down a line when the line after it equals its first line,
+/// and up when the line before it equals its last. Runs that meet are merged.
+/// Each run then goes next to a run of changes in the other file if it can
+/// line up with one, so that a replacement reads as one hunk; otherwise the
+/// indent heuristic picks the position whose edges fall most naturally
+/// between blocks of code.
+pub(super) fn compact(lines: &[&[u8]], ids: &[usize], changed: &mut [bool], other: &[bool]) {
+    let mut group = first_group(changed);
+    let mut other_group = first_group(other);
+    loop {
+        if group.end != group.start {
+            let (mut size, mut earliest_end, mut end_matching_other);
+            loop {
+                size = group.end - group.start;
+                end_matching_other = None;
+
+                // As far up as it goes...
+                while slide_up(ids, changed, &mut group) {
+                    previous_group(other, &mut other_group);
+                }
+                earliest_end = group.end;
+                if other_group.end > other_group.start {
+                    end_matching_other = Some(group.end);
+                }
+
+                // ...then as far down, noting where it lines up with changes
+                // in the other file.
+                while slide_down(ids, changed, &mut group) {
+                    next_group(other, &mut other_group);
+                    if other_group.end > other_group.start {
+                        end_matching_other = Some(group.end);
+                    }
+                }
+                // Merging with a neighbour may let it move further.
+                if size == group.end - group.start {
+                    break;
+                }
+            }
+
+            if group.end == earliest_end {
+                // It can't move.
+            } else if end_matching_other.is_some() {
+                while other_group.end == other_group.start {
+                    slide_up(ids, changed, &mut group);
+                    previous_group(other, &mut other_group);
+                }
+            } else {
+                let lowest = earliest_end.max((group.end - size).saturating_sub(1)).max(group.end.saturating_sub(MAX_SLIDING));
+                let mut best: Option<(usize, Score)> = None;
+                for shift in lowest..=group.end {
+                    let mut score = Score::default();
+                    score.add(&Split::measure(lines, shift));
+                    score.add(&Split::measure(lines, shift - size));
+                    if best.as_ref().is_none_or(|(_, best)| score.compare(best) <= 0) {
+                        best = Some((shift, score));
+                    }
+                }
+                let best_end = best.map_or(group.end, |(shift, _)| shift);
+                while group.end > best_end {
+                    slide_up(ids, changed, &mut group);
+                    previous_group(other, &mut other_group);
+                }
+            }
+        }
+
+        if !next_group(changed, &mut group) {
+            break;
+        }
+        next_group(other, &mut other_group);
+    }
+}
+
+fn is_changed(changed: &[bool], line: usize) -> bool {
+    changed.get(line).copied().unwrap_or(false)
+}
+
+fn first_group(changed: &[bool]) -> Group {
+    let mut end = 0;
+    while is_changed(changed, end) {
+        end += 1;
+    }
+    Group { start: 0, end }
+}
+
+/// Moves to the next run, which may be empty (between two unchanged lines).
+fn next_group(changed: &[bool], group: &mut Group) -> bool {
+    if group.end == changed.len() {
+        return false;
+    }
+    group.start = group.end + 1;
+    group.end = group.start;
+    while is_changed(changed, group.end) {
+        group.end += 1;
+    }
+    true
+}
+
+fn previous_group(changed: &[bool], group: &mut Group) -> bool {
+    if group.start == 0 {
+        return false;
+    }
+    group.end = group.start - 1;
+    group.start = group.end;
+    while group.start > 0 && changed[group.start - 1] {
+        group.start -= 1;
+    }
+    true
+}
+
+fn slide_down(ids: &[usize], changed: &mut [bool], group: &mut Group) -> bool {
+    if group.end < changed.len() && ids[group.start] == ids[group.end] {
+        changed[group.start] = false;
+        changed[group.end] = true;
+        group.start += 1;
+        group.end += 1;
+        while is_changed(changed, group.end) {
+            group.end += 1;
+        }
+        true
+    } else {
+        false
+    }
+}
+
+fn slide_up(ids: &[usize], changed: &mut [bool], group: &mut Group) -> bool {
+    if group.start > 0 && ids[group.start - 1] == ids[group.end - 1] {
+        group.start -= 1;
+        group.end -= 1;
+        changed[group.start] = true;
+        changed[group.end] = false;
+        while group.start > 0 && changed[group.start - 1] {
+            group.start -= 1;
+        }
+        true
+    } else {
+        false
+    }
+}
+
+/// What surrounds the boundary just before line `split`. Indents of -1 mean
+/// a blank line (or none at all).
+#[derive(Debug)]
+struct Split {
+    end_of_file: bool,
+    indent: i32,
+    pre_blank: i32,
+    pre_indent: i32,
+    post_blank: i32,
+    post_indent: i32,
+}
+
+impl Split {
+    fn measure(lines: &[&[u8]], split: usize) -> Self {
+        let end_of_file = split >= lines.len();
+        let indent = if end_of_file { -1 } else { line_indent(lines[split]) };
+
+        let (mut pre_blank, mut pre_indent) = (0, -1);
+        for line in lines[..split.min(lines.len())].iter().rev() {
+            pre_indent = line_indent(line);
+            if pre_indent != -1 {
+                break;
+            }
+            pre_blank += 1;
+            if pre_blank == MAX_BLANKS {
+                pre_indent = 0;
+                break;
+            }
+        }
+
+        let (mut post_blank, mut post_indent) = (0, -1);
+        for line in lines.iter().skip(split + 1) {
+            post_indent = line_indent(line);
+            if post_indent != -1 {
+                break;
+            }
+            post_blank += 1;
+            if post_blank == MAX_BLANKS {
+                post_indent = 0;
+                break;
+            }
+        }
+
+        Split { end_of_file, indent, pre_blank, pre_indent, post_blank, post_indent }
+    }
+}
+
+/// How bad a position for a run of changes is, from the splits at both of
+/// its ends. Lower is better.
+#[derive(Debug, Default)]
+struct Score {
+    effective_indent: i32,
+    penalty: i32,
+}
+
+impl Score {
+    fn add(&mut self, split: &Split) {
+        if split.pre_indent == -1 && split.pre_blank == 0 {
+            self.penalty += START_OF_FILE_PENALTY;
+        }
+        if split.end_of_file {
+            self.penalty += END_OF_FILE_PENALTY;
+        }
+
+        let post_blank = if split.indent == -1 { 1 + split.post_blank } else { 0 };
+        let total_blank = split.pre_blank + post_blank;
+        self.penalty += TOTAL_BLANK_WEIGHT * total_blank;
+        self.penalty += POST_BLANK_WEIGHT * post_blank;
+
+        let indent = if split.indent != -1 { split.indent } else { split.post_indent };
+        let any_blanks = total_blank != 0;
+        self.effective_indent += indent;
+
+        if indent == -1 || split.pre_indent == -1 || indent == split.pre_indent {
+            // Nothing more to weigh.
+        } else if indent > split.pre_indent {
+            self.penalty += if any_blanks { RELATIVE_INDENT_WITH_BLANK_PENALTY } else { RELATIVE_INDENT_PENALTY };
+        } else if split.post_indent != -1 && split.post_indent > indent {
+            self.penalty += if any_blanks { RELATIVE_OUTDENT_WITH_BLANK_PENALTY } else { RELATIVE_OUTDENT_PENALTY };
+        } else {
+            self.penalty += if any_blanks { RELATIVE_DEDENT_WITH_BLANK_PENALTY } else { RELATIVE_DEDENT_PENALTY };
+        }
+    }
+
+    fn compare(&self, other: &Score) -> i32 {
+        let indents = (self.effective_indent > other.effective_indent) as i32
+            - (self.effective_indent < other.effective_indent) as i32;
+        INDENT_WEIGHT * indents + (self.penalty - other.penalty)
+    }
+}
+
+/// The width of a line's leading whitespace, with tabs to multiples of 8, or
+/// -1 if the line is blank.
+fn line_indent(line: &[u8]) -> i32 {
+    let mut width = 0;
+    for &byte in line {
+        match byte {
+            b' ' => width += 1,
+            b'\t' => width += 8 - width % 8,
+            b'\n' | b'\r' | 0x0b | 0x0c => {}
+            _ => return width,
+        }
+        if width >= MAX_INDENT {
+            return MAX_INDENT;
+        }
+    }
+    -1
+}
@@ -0,0 +1,173 @@
+// src/diff/histogram.rs
+
+use super::myers;
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Lines occurring more often than this on the old side are never used as
+// anchors; if they are all there is in common, Myers takes over.
+const MAX_CHAIN_LENGTH: usize = 64;
+
+/// Where a line occurs on the old side: its first position, and how often.
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    first: usize,
+    count: usize,
+}
+
+/// A run of lines equal on both sides, `old_start..=old_end` and
+/// `new_start..=new_end`.
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    old_start: usize,
+    old_end: usize,
+    new_start: usize,
+    new_end: usize,
+}
+
+enum Lcs {
+    Found(Region),
+    // Nothing at all in common.
+    None,
+    // Only lines too common to anchor on.
+    TooCommon,
+}
+
+/// Marks the lines that differ between `old` and `new` (given as line ids)
+/// with the histogram algorithm, as git implements it.
+///
+/// Of the runs of lines the two sides share, the one whose rarest line
+/// occurs least often on the old side (the longest, among equally rare ones)
+/// is kept, and what comes before and after it is diffed the same way.
+pub(super) fn diff(old: &[usize], new: &[usize], old_changed: &mut [bool], new_changed: &mut [bool]) {
+    let mut histogram = Histogram { old, new, old_changed, new_changed };
+    histogram.diff(0..old.len(), 0..new.len());
+}
+
+struct Histogram<'a> {
+    old: &'a [usize],
+    new: &'a [usize],
+    old_changed: &'a mut [bool],
+    new_changed: &'a mut [bool],
+}
+
+impl Histogram<'_> {
+    fn diff(&mut self, mut old: Range<usize>, mut new: Range<usize>) {
+        loop {
+            if old.is_empty() || new.is_empty() {
+                self.old_changed[old].fill(true);
+                self.new_changed[new].fill(true);
+                return;
+            }
+            match self.find_lcs(old.clone(), new.clone()) {
+                Lcs::Found(region) => {
+                    self.diff(old.start..region.old_start, new.start..region.new_start);
+                    old = region.old_end + 1..old.end;
+                    new = region.new_end + 1..new.end;
+                }
+                Lcs::None => {
+                    self.old_changed[old].fill(true);
+                    self.new_changed[new].fill(true);
+                    return;
+                }
+                Lcs::TooCommon => {
+                    myers::diff(
+                        &self.old[old.clone()],
+                        &self.new[new.clone()],
+                        &mut self.old_changed[old],
+                        &mut self.new_changed[new],
+                    );
+                    return;
+                }
+            }
+        }
+    }
+
+    fn find_lcs(&self, old: Range<usize>, new: Range<usize>) -> Lcs {
+        // Index the old side: each line's first occurrence and count, and
+        // from each position the next occurrence of the same line.
+        let mut records: HashMap<usize, Record> = HashMap::new();
+        let mut next: Vec<Option<usize>> = vec![None; old.len()];
+        for line in old.clone().rev() {
+            match records.get_mut(&self.old[line]) {
+                Some(record) => {
+                    next[line - old.start] = Some(record.first);
+                    record.first = line;
+                    record.count += 1;
+                }
+                None => {
+                    records.insert(self.old[line], Record { first: line, count: 1 });
+                }
+            }
+        }
+        let count_at = |line: usize| records[&self.old[line]].count;
+
+        let mut best: Option<Region> = None;
+        let mut best_count = MAX_CHAIN_LENGTH + 1;
+        let mut has_common = false;
+        let mut b_ptr = new.start;
+        while b_ptr < new.end {
+            let mut b_next = b_ptr + 1;
+            let Some(record) = records.get(&self.new[b_ptr]) else {
+                b_ptr = b_next;
+                continue;
+            };
+            has_common = true;
+            if record.count > best_count {
+                b_ptr = b_next;
+                continue;
+            }
+
+            // Try each occurrence on the old side, growing the match both
+            // ways and tracking the rarest line in it.
+            let mut a_ptr = record.first;
+            loop {
+                let following = next[a_ptr - old.start];
+                let (mut a_start, mut b_start, mut a_end, mut b_end) = (a_ptr, b_ptr, a_ptr, b_ptr);
+                let mut rarest = record.count;
+                while old.start < a_start && new.start < b_start && self.old[a_start - 1] == self.new[b_start - 1] {
+                    a_start -= 1;
+                    b_start -= 1;
+                    if rarest > 1 {
+                        rarest = rarest.min(count_at(a_start));
+                    }
+                }
+                while a_end + 1 < old.end && b_end + 1 < new.end && self.old[a_end + 1] == self.new[b_end + 1] {
+                    a_end += 1;
+                    b_end += 1;
+                    if rarest > 1 {
+                        rarest = rarest.min(count_at(a_end));
+                    }
+                }
+
+                if b_next <= b_end {
+                    b_next = b_end + 1;
+                }
+                let best_len = best.map_or(0, |region| region.old_end - region.old_start);
+                if best_len < a_end - a_start || rarest < best_count {
+                    best = Some(Region { old_start: a_start, old_end: a_end, new_start: b_start, new_end: b_end });
+                    best_count = rarest;
+                }
+
+                // Skip occurrences inside the match just found.
+                let mut following = following;
+                while let Some(position) = following
+                    && position <= a_end
+                {
+                    following = next[position - old.start];
+                }
+                match following {
+                    Some(position) => a_ptr = position,
+                    None => break,
+                }
+            }
+            b_ptr = b_next;
+        }
+
+        match best {
+            _ if has_common && best_count > MAX_CHAIN_LENGTH => Lcs::TooCommon,
+            Some(region) => Lcs::Found(region),
+            None => Lcs::None,
+        }
+    }
+}
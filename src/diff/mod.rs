@@ -0,0 +1,108 @@
+// src/diff/mod.rs
+
+mod compact;
+mod histogram;
+mod myers;
+mod patience;
+pub mod rename;
+pub mod stat;
+pub mod tree;
+pub mod unified;
+
+use std::collections::HashMap;
+
+/// How the lines that changed between two files are chosen when more than
+/// one answer is possible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// The shortest edit script, found with Myers' O(ND) algorithm.
+    #[default]
+    Myers,
+    /// Anchors on lines that occur exactly once in both files, which keeps
+    /// moved blocks and braces from being matched up with the wrong code.
+    Patience,
+    /// Like patience, but anchors on the rarest lines rather than only
+    /// unique ones.
+    Histogram,
+}
+
+/// A run of lines removed from the old file and the lines that replaced
+/// them in the new one, either of which may be empty. Positions are 0-based
+/// line numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+}
+
+/// Splits a file into lines, each keeping its `\n` so that a last line
+/// without one differs from the same line with it.
+pub fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|&byte| byte == b'\n').collect()
+}
+
+/// Git treats a file as binary if a NUL byte appears in its first 8000
+/// bytes.
+pub fn is_binary(data: &[u8]) -> bool {
+    data[..data.len().min(8000)].contains(&0)
+}
+
+/// Compares two files line by line and returns what changed, in order.
+///
+/// Once the algorithm has marked the changed lines, runs of changes that
+/// could equally be drawn a few lines up or down are slid to where git puts
+/// them, so the hunks match its output.
+pub fn diff_lines<'a>(old: &[&'a [u8]], new: &[&'a [u8]], algorithm: Algorithm) -> Vec<Change> {
+    // Equal lines get equal ids, so the algorithms compare numbers.
+    let mut ids: HashMap<&'a [u8], usize> = HashMap::new();
+    let old_ids = intern(old, &mut ids);
+    let new_ids = intern(new, &mut ids);
+
+    let mut old_changed = vec![false; old.len()];
+    let mut new_changed = vec![false; new.len()];
+    match algorithm {
+        Algorithm::Myers => myers::diff(&old_ids, &new_ids, &mut old_changed, &mut new_changed),
+        Algorithm::Patience => patience::diff(&old_ids, &new_ids, &mut old_changed, &mut new_changed),
+        Algorithm::Histogram => histogram::diff(&old_ids, &new_ids, &mut old_changed, &mut new_changed),
+    }
+
+    compact::compact(old, &old_ids, &mut old_changed, &new_changed);
+    compact::compact(new, &new_ids, &mut new_changed, &old_changed);
+    changes(&old_changed, &new_changed)
+}
+
+fn intern<'a>(lines: &[&'a [u8]], ids: &mut HashMap<&'a [u8], usize>) -> Vec<usize> {
+    lines
+        .iter()
+        .map(|line| {
+            let next = ids.len();
+            *ids.entry(line).or_insert(next)
+        })
+        .collect()
+}
+
+/// Turns the lines marked as changed on each side into runs. Unchanged lines
+/// pair up one to one, so the two sides are walked together.
+fn changes(old_changed: &[bool], new_changed: &[bool]) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let (mut old, mut new) = (0, 0);
+    while old < old_changed.len() || new < new_changed.len() {
+        let is_changed = |changed: &[bool], line: usize| changed.get(line).copied().unwrap_or(false);
+        if !is_changed(old_changed, old) && !is_changed(new_changed, new) {
+            old += 1;
+            new += 1;
+            continue;
+        }
+        let (old_start, new_start) = (old, new);
+        while is_changed(old_changed, old) {
+            old += 1;
+        }
+        while is_changed(new_changed, new) {
+            new += 1;
+        }
+        changes.push(Change { old_start, old_len: old - old_start, new_start, new_len: new - new_start });
+    }
+    changes
+}
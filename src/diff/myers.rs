@@ -0,0 +1,244 @@
+// src/diff/myers.rs
+
+use std::collections::HashMap;
+
+// Lines occurring at least this often (or the square root of the file's
+// length, if smaller) on the other side are only kept if they aren't
+// surrounded by lines that have no match at all.
+const MAX_EQUAL_LIMIT: usize = 1024;
+// How far either side of such a line is looked at.
+const SCAN_WINDOW: usize = 100;
+// A frequent line is discarded when fewer than one in this many of the lines
+// around it are also frequent.
+const KEEP_RUN: usize = 4;
+
+/// Marks the lines that differ between `old` and `new` (given as line ids),
+/// the way git's xdiff does.
+///
+/// Lines the two files start or end with are skipped, and lines with no
+/// match on the other side are marked straight away, as are frequent lines
+/// among them. The rest is compared with Myers' algorithm, which repeatedly
+/// finds the middle snake of the edit graph, searching forwards from the
+/// start and backwards from the end, and splits the problem around it.
+pub(super) fn diff(old: &[usize], new: &[usize], old_changed: &mut [bool], new_changed: &mut [bool]) {
+    let mut counts: HashMap<usize, (usize, usize)> = HashMap::new();
+    for id in old {
+        counts.entry(*id).or_default().0 += 1;
+    }
+    for id in new {
+        counts.entry(*id).or_default().1 += 1;
+    }
+
+    // 1. Skip the common start and end.
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let limit = old.len().min(new.len()) - prefix;
+    let suffix = old.iter().rev().zip(new.iter().rev()).take(limit).take_while(|(a, b)| a == b).count();
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+
+    // 2. Drop lines that can't be matched, keeping the positions of the rest.
+    let old_kept = discard(old, prefix, old_end, |id| counts[id].1, old_changed);
+    let new_kept = discard(new, prefix, new_end, |id| counts[id].0, new_changed);
+    let a: Vec<usize> = old_kept.iter().map(|&line| old[line]).collect();
+    let b: Vec<usize> = new_kept.iter().map(|&line| new[line]).collect();
+
+    // 3. Divide and conquer what is left.
+    let offset = b.len() as isize + 1;
+    let size = a.len() + b.len() + 3;
+    let mut forward = vec![0isize; size];
+    let mut backward = vec![0isize; size];
+    let mut pending = vec![(0, a.len(), 0, b.len())];
+    while let Some((mut off1, mut lim1, mut off2, mut lim2)) = pending.pop() {
+        while off1 < lim1 && off2 < lim2 && a[off1] == b[off2] {
+            off1 += 1;
+            off2 += 1;
+        }
+        while off1 < lim1 && off2 < lim2 && a[lim1 - 1] == b[lim2 - 1] {
+            lim1 -= 1;
+            lim2 -= 1;
+        }
+
+        if off1 == lim1 {
+            for line in &new_kept[off2..lim2] {
+                new_changed[*line] = true;
+            }
+        } else if off2 == lim2 {
+            for line in &old_kept[off1..lim1] {
+                old_changed[*line] = true;
+            }
+        } else {
+            let mut boxes = Boxes { a: &a, b: &b, forward: &mut forward, backward: &mut backward, offset };
+            let (mid1, mid2) = boxes.split(off1, lim1, off2, lim2);
+            pending.push((mid1, lim1, mid2, lim2));
+            pending.push((off1, mid1, off2, mid2));
+        }
+    }
+}
+
+/// Marks lines in `start..end` with no counterpart on the other side as
+/// changed, and returns the positions of the lines left to compare.
+/// `matches` counts a line id's occurrences on the other side.
+fn discard(
+    lines: &[usize],
+    start: usize,
+    end: usize,
+    matches: impl Fn(&usize) -> usize,
+    changed: &mut [bool],
+) -> Vec<usize> {
+    let limit = bogosqrt(lines.len()).min(MAX_EQUAL_LIMIT);
+    // 0: no match, 1: matched, 2: matched many times.
+    let kinds: Vec<u8> = (0..lines.len())
+        .map(|line| match matches(&lines[line]) {
+            0 => 0,
+            count if count >= limit => 2,
+            _ => 1,
+        })
+        .collect();
+
+    let mut kept = Vec::new();
+    for line in start..end {
+        if kinds[line] == 1 || (kinds[line] == 2 && !among_unmatched(&kinds, line, start, end - 1)) {
+            kept.push(line);
+        } else {
+            changed[line] = true;
+        }
+    }
+    kept
+}
+
+/// Whether a frequent line sits in a run of lines that are mostly
+/// unmatched, making it more likely a coincidence than a real match.
+fn among_unmatched(kinds: &[u8], line: usize, start: usize, end: usize) -> bool {
+    let start = start.max(line.saturating_sub(SCAN_WINDOW));
+    let end = end.min(line + SCAN_WINDOW);
+
+    let run = |lines: &mut dyn Iterator<Item = usize>| {
+        let (mut unmatched, mut frequent) = (0, 0);
+        for other in lines {
+            match kinds[other] {
+                0 => unmatched += 1,
+                2 => frequent += 1,
+                _ => break,
+            }
+        }
+        (unmatched, frequent)
+    };
+    let (before_unmatched, before_frequent) = run(&mut (start..line).rev());
+    if before_unmatched == 0 {
+        return false;
+    }
+    let (after_unmatched, after_frequent) = run(&mut (line + 1..=end));
+    if after_unmatched == 0 {
+        return false;
+    }
+    // The line itself counts on both sides, as git counts it.
+    let frequent = before_frequent + after_frequent + 2;
+    let unmatched = before_unmatched + after_unmatched;
+    frequent * KEEP_RUN < frequent + unmatched
+}
+
+/// An integer approximation of the square root, by halving the bits.
+fn bogosqrt(mut n: usize) -> usize {
+    let mut root = 1;
+    while n > 0 {
+        root <<= 1;
+        n >>= 2;
+    }
+    root
+}
+
+/// The two sequences being compared and the furthest point reached on each
+/// diagonal, searching forwards and backwards. Diagonal `k` holds the points
+/// where `x - y == k`, stored at `k + offset`.
+struct Boxes<'a> {
+    a: &'a [usize],
+    b: &'a [usize],
+    forward: &'a mut [isize],
+    backward: &'a mut [isize],
+    offset: isize,
+}
+
+impl Boxes<'_> {
+    fn fwd(&mut self, diagonal: isize) -> &mut isize {
+        &mut self.forward[(diagonal + self.offset) as usize]
+    }
+
+    fn bwd(&mut self, diagonal: isize) -> &mut isize {
+        &mut self.backward[(diagonal + self.offset) as usize]
+    }
+
+    /// Finds where the forward and backward searches through the box
+    /// `off1..lim1` by `off2..lim2` first overlap, which lies on a shortest
+    /// edit path. Ties go the way git's `xdl_split` breaks them.
+    fn split(&mut self, off1: usize, lim1: usize, off2: usize, lim2: usize) -> (usize, usize) {
+        let (off1, lim1, off2, lim2) = (off1 as isize, lim1 as isize, off2 as isize, lim2 as isize);
+        let (dmin, dmax) = (off1 - lim2, lim1 - off2);
+        let (fmid, bmid) = (off1 - off2, lim1 - lim2);
+        let odd = (fmid - bmid) & 1 != 0;
+        let (mut fmin, mut fmax, mut bmin, mut bmax) = (fmid, fmid, bmid, bmid);
+        *self.fwd(fmid) = off1;
+        *self.bwd(bmid) = lim1;
+
+        loop {
+            // Widen the range of diagonals by one each way, bouncing off the
+            // edges of the box; the diagonals just outside it are marked
+            // unreachable.
+            if fmin > dmin {
+                fmin -= 1;
+                *self.fwd(fmin - 1) = -1;
+            } else {
+                fmin += 1;
+            }
+            if fmax < dmax {
+                fmax += 1;
+                *self.fwd(fmax + 1) = -1;
+            } else {
+                fmax -= 1;
+            }
+
+            let mut diagonal = fmax;
+            while diagonal >= fmin {
+                let (below, above) = (*self.fwd(diagonal - 1), *self.fwd(diagonal + 1));
+                let mut i1 = if below >= above { below + 1 } else { above };
+                let mut i2 = i1 - diagonal;
+                while i1 < lim1 && i2 < lim2 && self.a[i1 as usize] == self.b[i2 as usize] {
+                    i1 += 1;
+                    i2 += 1;
+                }
+                *self.fwd(diagonal) = i1;
+                if odd && bmin <= diagonal && diagonal <= bmax && *self.bwd(diagonal) <= i1 {
+                    return (i1 as usize, i2 as usize);
+                }
+                diagonal -= 2;
+            }
+
+            if bmin > dmin {
+                bmin -= 1;
+                *self.bwd(bmin - 1) = isize::MAX;
+            } else {
+                bmin += 1;
+            }
+            if bmax < dmax {
+                bmax += 1;
+                *self.bwd(bmax + 1) = isize::MAX;
+            } else {
+                bmax -= 1;
+            }
+
+            let mut diagonal = bmax;
+            while diagonal >= bmin {
+                let (below, above) = (*self.bwd(diagonal - 1), *self.bwd(diagonal + 1));
+                let mut i1 = if below < above { below } else { above - 1 };
+                let mut i2 = i1 - diagonal;
+                while i1 > off1 && i2 > off2 && self.a[i1 as usize - 1] == self.b[i2 as usize - 1] {
+                    i1 -= 1;
+                    i2 -= 1;
+                }
+                *self.bwd(diagonal) = i1;
+                if !odd && fmin <= diagonal && diagonal <= fmax && i1 <= *self.fwd(diagonal) {
+                    return (i1 as usize, i2 as usize);
+                }
+                diagonal -= 2;
+            }
+        }
+    }
+}
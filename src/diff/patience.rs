@@ -0,0 +1,168 @@
+// src/diff/patience.rs
+
+use super::myers;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// How often a line from the old side occurs on the new side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Occurrence {
+    Absent,
+    Once(usize),
+    // Twice or more on either side.
+    Many,
+}
+
+/// A line that occurs on the old side, at its first position there.
+#[derive(Debug)]
+struct Entry {
+    old: usize,
+    new: Occurrence,
+}
+
+/// Marks the lines that differ between `old` and `new` (given as line ids)
+/// with the patience algorithm.
+///
+/// Lines that occur exactly once on each side are matched up, the longest
+/// sequence of them that is in the same order on both sides is kept as
+/// anchors, and the stretches between anchors are diffed the same way.
+/// Stretches without any unique common line fall back to Myers.
+pub(super) fn diff(old: &[usize], new: &[usize], old_changed: &mut [bool], new_changed: &mut [bool]) {
+    let mut patience = Patience { old, new, old_changed, new_changed };
+    patience.diff(0..old.len(), 0..new.len());
+}
+
+struct Patience<'a> {
+    old: &'a [usize],
+    new: &'a [usize],
+    old_changed: &'a mut [bool],
+    new_changed: &'a mut [bool],
+}
+
+impl Patience<'_> {
+    fn diff(&mut self, old: Range<usize>, new: Range<usize>) {
+        if old.is_empty() || new.is_empty() {
+            self.mark(old, new);
+            return;
+        }
+
+        let mut positions: HashMap<usize, usize> = HashMap::new();
+        let mut entries: Vec<Entry> = Vec::new();
+        for line in old.clone() {
+            match positions.get(&self.old[line]) {
+                Some(&entry) => entries[entry].new = Occurrence::Many,
+                None => {
+                    positions.insert(self.old[line], entries.len());
+                    entries.push(Entry { old: line, new: Occurrence::Absent });
+                }
+            }
+        }
+        let mut has_matches = false;
+        for line in new.clone() {
+            if let Some(&entry) = positions.get(&self.new[line]) {
+                has_matches = true;
+                let entry = &mut entries[entry];
+                entry.new = match entry.new {
+                    Occurrence::Absent => Occurrence::Once(line),
+                    _ => Occurrence::Many,
+                };
+            }
+        }
+        if !has_matches {
+            self.mark(old, new);
+            return;
+        }
+
+        let anchors = longest_common_sequence(&entries);
+        if anchors.is_empty() {
+            myers::diff(
+                &self.old[old.clone()],
+                &self.new[new.clone()],
+                &mut self.old_changed[old],
+                &mut self.new_changed[new],
+            );
+            return;
+        }
+        self.walk(&anchors, old, new);
+    }
+
+    /// Diffs the stretches between anchors, after growing each stretch of
+    /// matching lines around them as far as it goes.
+    fn walk(&mut self, anchors: &[(usize, usize)], old: Range<usize>, new: Range<usize>) {
+        let (mut line1, mut line2) = (old.start, new.start);
+        let mut anchor = 0;
+        loop {
+            let (mut next1, mut next2) = match anchors.get(anchor) {
+                Some(&(next1, next2)) => (next1, next2),
+                None => (old.end, new.end),
+            };
+            if anchor < anchors.len() {
+                while next1 > line1 && next2 > line2 && self.old[next1 - 1] == self.new[next2 - 1] {
+                    next1 -= 1;
+                    next2 -= 1;
+                }
+            }
+            while line1 < next1 && line2 < next2 && self.old[line1] == self.new[line2] {
+                line1 += 1;
+                line2 += 1;
+            }
+
+            if next1 > line1 || next2 > line2 {
+                self.diff(line1..next1, line2..next2);
+            }
+            if anchor >= anchors.len() {
+                return;
+            }
+
+            // Step over anchors that directly follow each other.
+            while anchor + 1 < anchors.len()
+                && anchors[anchor + 1] == (anchors[anchor].0 + 1, anchors[anchor].1 + 1)
+            {
+                anchor += 1;
+            }
+            line1 = anchors[anchor].0 + 1;
+            line2 = anchors[anchor].1 + 1;
+            anchor += 1;
+        }
+    }
+
+    fn mark(&mut self, old: Range<usize>, new: Range<usize>) {
+        self.old_changed[old].fill(true);
+        self.new_changed[new].fill(true);
+    }
+}
+
+/// The longest run of unique common lines that appear in the same order on
+/// both sides, as (old, new) positions, found by patience sorting.
+fn longest_common_sequence(entries: &[Entry]) -> Vec<(usize, usize)> {
+    let unique: Vec<(usize, usize)> = entries
+        .iter()
+        .filter_map(|entry| match entry.new {
+            Occurrence::Once(new) => Some((entry.old, new)),
+            _ => None,
+        })
+        .collect();
+
+    // The top of each pile, and for each line the top of the pile to its
+    // left when it was placed.
+    let mut piles: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; unique.len()];
+    for (index, &(_, new)) in unique.iter().enumerate() {
+        let pile = piles.partition_point(|&top| unique[top].1 < new);
+        previous[index] = pile.checked_sub(1).map(|left| piles[left]);
+        if pile == piles.len() {
+            piles.push(index);
+        } else {
+            piles[pile] = index;
+        }
+    }
+
+    let mut sequence = Vec::new();
+    let mut next = piles.last().copied();
+    while let Some(index) = next {
+        sequence.push(unique[index]);
+        next = previous[index];
+    }
+    sequence.reverse();
+    sequence
+}
@@ -0,0 +1,503 @@
+// src/diff/rename.rs
+
+use super::tree::{Entry, FileChange, Status};
+use crate::objects::Oid;
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Similarity scores run from 0 to this, as in git.
+pub const MAX_SCORE: u32 = 60000;
+/// Files at least half the same are renames unless told otherwise.
+pub const DEFAULT_SCORE: u32 = MAX_SCORE / 2;
+/// A modified file at least half rewritten is broken up for `-B`...
+pub const DEFAULT_BREAK_SCORE: u32 = MAX_SCORE / 2;
+/// ...and shown as a rewrite if at least 60% of it is gone.
+pub const DEFAULT_MERGE_SCORE: u32 = MAX_SCORE * 3 / 5;
+
+// Files smaller than this many bytes are never broken up.
+const MINIMUM_BREAK_SIZE: u64 = 400;
+
+// How many of the most similar sources are kept for each added file.
+const CANDIDATES_PER_DESTINATION: usize = 4;
+// Files are compared in spans of at most this many bytes, ending at newlines.
+const MAX_SPAN: usize = 64;
+const SPAN_HASH_BASE: u32 = 107927;
+
+/// How `detect` pairs files up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenameOptions {
+    /// Also look for added files copied from ones that were modified, or
+    /// from a deleted file already used for a rename.
+    pub copies: bool,
+    /// How similar two files must be to pair them, out of `MAX_SCORE`.
+    pub minimum_score: u32,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        RenameOptions { copies: false, minimum_score: DEFAULT_SCORE }
+    }
+}
+
+/// What `-B` does with modified files that were mostly rewritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakOptions {
+    /// How much of a file must have changed, out of `MAX_SCORE`, for its
+    /// old and new versions to be paired up as if deleted and added.
+    pub break_score: u32,
+    /// How much of the old file must be gone for a broken file that nothing
+    /// was paired with to show as a rewrite, rather than a modification.
+    pub merge_score: u32,
+}
+
+impl Default for BreakOptions {
+    fn default() -> Self {
+        BreakOptions { break_score: DEFAULT_BREAK_SCORE, merge_score: DEFAULT_MERGE_SCORE }
+    }
+}
+
+/// Parses what `-B` takes: `<break>[/<merge>]`, either of which may be
+/// left out for the default, e.g. `70%` or `/50%`.
+pub fn parse_break(text: &str) -> Result<BreakOptions> {
+    let (break_score, merge_score) = text.split_once('/').unwrap_or((text, ""));
+    let score = |text: &str, default: u32| if text.is_empty() { Ok(default) } else { parse_score(text) };
+    Ok(BreakOptions {
+        break_score: score(break_score, DEFAULT_BREAK_SCORE)?,
+        merge_score: score(merge_score, DEFAULT_MERGE_SCORE)?,
+    })
+}
+
+/// Parses the threshold `-M` and `-C` take, as git does: `90%`, or digits
+/// read as a fraction, so `9` and `90` are both 90% and `05` is 5%.
+pub fn parse_score(text: &str) -> Result<u32> {
+    let (mut number, mut scale, mut dot) = (0u64, 1u64, false);
+    let mut chars = text.chars();
+    for ch in chars.by_ref() {
+        match ch {
+            '.' if !dot => {
+                scale = 1;
+                dot = true;
+            }
+            '%' => {
+                scale = if dot { scale * 100 } else { 100 };
+                break;
+            }
+            '0'..='9' if scale < 100000 => {
+                scale *= 10;
+                number = number * 10 + u64::from(ch as u8 - b'0');
+            }
+            '0'..='9' => {}
+            _ => bail!("invalid similarity '{}'", text),
+        }
+    }
+    if chars.next().is_some() {
+        bail!("invalid similarity '{}'", text);
+    }
+    Ok(if number >= scale { MAX_SCORE } else { (u64::from(MAX_SCORE) * number / scale) as u32 })
+}
+
+/// A deleted (or, when looking for copies, modified) file an added file may
+/// have come from, and how many added files have been paired with it.
+struct Source {
+    change: usize,
+    used: u32,
+}
+
+/// An added file, and the source and score it has been paired with.
+struct Destination {
+    change: usize,
+    pair: Option<(usize, u32)>,
+}
+
+/// What the similarity of two files is worked out from.
+struct Signature {
+    size: u64,
+    spans: HashMap<u32, u64>,
+}
+
+/// The signatures of the files compared so far, by blob id, and how to
+/// read the ones not seen yet.
+struct Signatures<'a> {
+    load: &'a mut dyn FnMut(&Entry) -> Result<Vec<u8>>,
+    cache: HashMap<Oid, Signature>,
+}
+
+/// A possible pairing in the similarity matrix. `destination` is None for
+/// an empty slot.
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    destination: Option<usize>,
+    source: usize,
+    score: u32,
+    same_basename: bool,
+}
+
+/// Pairs up deleted and added files in `changes` that are renames (or with
+/// `copies`, copies) of each other, replacing them with `Renamed` or
+/// `Copied` changes. `load` reads a file's content.
+///
+/// With `breaks`, modified regular files that changed enough are first
+/// split into a deletion and an addition, as git's diffcore-break does, so
+/// that either half can be paired. A file whose halves are both left over is
+/// put back together, as a `Rewritten` change if enough of it is gone.
+/// Without `renames`, that is all that happens.
+///
+/// This follows git's diffcore-rename. Files with identical content are
+/// paired first, preferring a source with the same file name. Then, for
+/// renames only, a deleted and an added file that are the only ones left
+/// with their file name are paired if they are at least halfway from the
+/// threshold to identical. The rest are scored against every remaining
+/// source by how many of their line spans they share, and the best pairs at
+/// or above the threshold are taken. Only regular files are paired unless
+/// they are identical.
+///
+/// When a deleted file is the source of several added ones, the last of
+/// them is the rename and the others copies.
+pub fn detect(
+    changes: Vec<FileChange>,
+    renames: Option<&RenameOptions>,
+    breaks: Option<&BreakOptions>,
+    load: &mut dyn FnMut(&Entry) -> Result<Vec<u8>>,
+) -> Result<Vec<FileChange>> {
+    let mut signatures = Signatures { load, cache: HashMap::new() };
+
+    // Broken files, by how much of the old file is gone; 0 for those that
+    // go back to being plain modifications.
+    let mut broken: HashMap<usize, u32> = HashMap::new();
+    if let Some(breaks) = breaks {
+        for (index, change) in changes.iter().enumerate() {
+            if let (Status::Modified, Some(old), Some(new)) = (change.status, &change.old, &change.new)
+                && let Some(score) = signatures.break_score(old, new, breaks.break_score)?
+            {
+                broken.insert(index, if score < breaks.merge_score { 0 } else { score });
+            }
+        }
+    }
+    let Some(options) = renames else {
+        return Ok(pair_up(changes, &[], &[], &broken));
+    };
+
+    let mut sources = Vec::new();
+    let mut destinations = Vec::new();
+    for (index, change) in changes.iter().enumerate() {
+        match change.status {
+            Status::Added => destinations.push(Destination { change: index, pair: None }),
+            Status::Deleted => sources.push(Source { change: index, used: 0 }),
+            // A broken file that wasn't rewritten enough stays where it is
+            // unless something else is paired into its place, so counts as
+            // used, like a modified one.
+            Status::Modified if broken.contains_key(&index) => {
+                sources.push(Source { change: index, used: u32::from(broken[&index] == 0) });
+                destinations.push(Destination { change: index, pair: None });
+            }
+            // A modified file stays where it is, so counts as already used:
+            // anything paired with it is a copy.
+            Status::Modified | Status::TypeChanged if options.copies => {
+                sources.push(Source { change: index, used: 1 })
+            }
+            _ => {}
+        }
+    }
+    if sources.is_empty() || destinations.is_empty() {
+        return Ok(pair_up(changes, &sources, &destinations, &broken));
+    }
+    let old = |source: &Source| changes[source.change].old.as_ref().expect("a source has an old side");
+    let new = |destination: &Destination| changes[destination.change].new.as_ref().expect("a destination has a new side");
+
+    // 1. Identical files. The two halves of a broken file are never paired
+    // with each other, here or below.
+    for destination in destinations.iter_mut() {
+        let target = new(destination);
+        let mut best: Option<(usize, u32)> = None;
+        for (index, source) in sources.iter().enumerate() {
+            let candidate = old(source);
+            if source.change == destination.change
+                || candidate.oid != target.oid
+                || ((!is_regular(candidate.mode) || !is_regular(target.mode)) && candidate.mode != target.mode)
+                || (source.used > 0 && !options.copies)
+            {
+                continue;
+            }
+            let score = u32::from(source.used == 0) + u32::from(same_basename(&candidate.path, &target.path));
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((index, score));
+                if score == 2 {
+                    break;
+                }
+            }
+        }
+        if let Some((index, _)) = best {
+            destination.pair = Some((index, MAX_SCORE));
+            sources[index].used += 1;
+        }
+    }
+    if options.minimum_score >= MAX_SCORE {
+        return Ok(pair_up(changes, &sources, &destinations, &broken));
+    }
+
+    // Sources already renamed away can't be renamed again (they can still
+    // be copied).
+    let mut remaining: Vec<usize> =
+        (0..sources.len()).filter(|&index| options.copies || sources[index].used == 0).collect();
+
+    // 2. For renames, files that are the only ones left with their name.
+    if !options.copies {
+        let minimum_score = options.minimum_score + (MAX_SCORE - options.minimum_score) / 2;
+        let source_names = unique_names(remaining.iter().map(|&index| basename(&old(&sources[index]).path)));
+        let unpaired: Vec<usize> = (0..destinations.len()).filter(|&index| destinations[index].pair.is_none()).collect();
+        let destination_names = unique_names(unpaired.iter().map(|&index| basename(&new(&destinations[index]).path)));
+
+        for &source in &remaining {
+            let name = basename(&old(&sources[source]).path);
+            let (Some(Some(_)), Some(&Some(destination_position))) =
+                (source_names.get(name), destination_names.get(name))
+            else {
+                continue;
+            };
+            let destination = unpaired[destination_position];
+            if destinations[destination].pair.is_some() || destinations[destination].change == sources[source].change {
+                continue;
+            }
+            let score = signatures.similarity(old(&sources[source]), new(&destinations[destination]), minimum_score)?;
+            if score >= minimum_score {
+                destinations[destination].pair = Some((source, score));
+                sources[source].used += 1;
+            }
+        }
+        remaining.retain(|&index| sources[index].used == 0);
+    }
+
+    // 3. Score every remaining pair, keeping each added file's best few.
+    let mut matrix = Vec::new();
+    for (index, destination) in destinations.iter().enumerate().filter(|(_, destination)| destination.pair.is_none()) {
+        let empty = Candidate { destination: None, source: 0, score: 0, same_basename: false };
+        let mut best = [empty; CANDIDATES_PER_DESTINATION];
+        for &source in remaining.iter().filter(|&&source| sources[source].change != destination.change) {
+            let (from, to) = (old(&sources[source]), new(destination));
+            let candidate = Candidate {
+                destination: Some(index),
+                source,
+                score: signatures.similarity(from, to, options.minimum_score)?,
+                same_basename: same_basename(&from.path, &to.path),
+            };
+            let worst = (1..best.len()).fold(0, |worst, slot| {
+                if compare(&best[slot], &best[worst]) == Ordering::Greater { slot } else { worst }
+            });
+            if compare(&best[worst], &candidate) == Ordering::Greater {
+                best[worst] = candidate;
+            }
+        }
+        matrix.extend(best);
+    }
+    matrix.sort_by(compare);
+
+    // Take the most similar pairs first: renames, then (if wanted) copies.
+    for allow_used in [false, true] {
+        if allow_used && !options.copies {
+            break;
+        }
+        for candidate in &matrix {
+            let Some(destination) = candidate.destination else {
+                break;
+            };
+            if candidate.score < options.minimum_score {
+                break;
+            }
+            if destinations[destination].pair.is_some() || (!allow_used && sources[candidate.source].used > 0) {
+                continue;
+            }
+            destinations[destination].pair = Some((candidate.source, candidate.score));
+            sources[candidate.source].used += 1;
+        }
+    }
+
+    Ok(pair_up(changes, &sources, &destinations, &broken))
+}
+
+/// Replaces each paired added (or broken) file with the rename or copy,
+/// and drops the deleted files that were renamed. Of the files paired with
+/// one source, all but the last are copies. A broken file that nothing was
+/// paired into is put back together, and stays, so anything paired with it
+/// is a copy.
+fn pair_up(
+    changes: Vec<FileChange>,
+    sources: &[Source],
+    destinations: &[Destination],
+    broken: &HashMap<usize, u32>,
+) -> Vec<FileChange> {
+    let mut uses: Vec<u32> = sources.iter().map(|source| source.used).collect();
+    let pairs: HashMap<usize, (usize, u32)> =
+        destinations.iter().filter_map(|destination| Some((destination.change, destination.pair?))).collect();
+    let renamed: Vec<usize> = sources.iter().filter(|source| source.used > 0).map(|source| source.change).collect();
+    for (index, source) in sources.iter().enumerate() {
+        if broken.contains_key(&source.change) && !pairs.contains_key(&source.change) {
+            uses[index] += 1;
+        }
+    }
+
+    let mut paired = Vec::new();
+    for (index, change) in changes.iter().enumerate() {
+        if let Some(&(source, score)) = pairs.get(&index) {
+            uses[source] -= 1;
+            let similarity = score * 100 / MAX_SCORE;
+            let status = if uses[source] > 0 { Status::Copied(similarity) } else { Status::Renamed(similarity) };
+            let old = changes[sources[source].change].old.clone();
+            paired.push(FileChange { status, old, new: change.new.clone() });
+        } else if let Some(&score) = broken.get(&index) {
+            let status = if score > 0 { Status::Rewritten(score * 100 / MAX_SCORE) } else { Status::Modified };
+            paired.push(FileChange { status, ..change.clone() });
+        } else if !(change.status == Status::Deleted && renamed.contains(&index)) {
+            paired.push(change.clone());
+        }
+    }
+    paired
+}
+
+/// Orders candidates best first: by score, then those keeping their file
+/// name, with empty slots last.
+fn compare(a: &Candidate, b: &Candidate) -> Ordering {
+    match (a.destination, b.destination) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        _ => b.score.cmp(&a.score).then(b.same_basename.cmp(&a.same_basename)),
+    }
+}
+
+/// Maps each name to its position, or to None if it occurs more than once.
+fn unique_names<'a>(names: impl Iterator<Item = &'a [u8]>) -> HashMap<&'a [u8], Option<usize>> {
+    let mut unique: HashMap<&[u8], Option<usize>> = HashMap::new();
+    for (index, name) in names.enumerate() {
+        unique.entry(name).and_modify(|slot| *slot = None).or_insert(Some(index));
+    }
+    unique
+}
+
+fn is_regular(mode: u32) -> bool {
+    mode & 0o170000 == 0o100000
+}
+
+fn basename(path: &[u8]) -> &[u8] {
+    path.rsplit(|&byte| byte == b'/').next().unwrap_or(path)
+}
+
+fn same_basename(a: &[u8], b: &[u8]) -> bool {
+    basename(a) == basename(b)
+}
+
+impl Signatures<'_> {
+    /// How similar two files are, out of `MAX_SCORE`. Only regular files
+    /// are compared; anything else scores 0.
+    fn similarity(&mut self, source: &Entry, destination: &Entry, minimum_score: u32) -> Result<u32> {
+        if !is_regular(source.mode) || !is_regular(destination.mode) {
+            return Ok(0);
+        }
+        for entry in [source, destination] {
+            if !self.cache.contains_key(&entry.oid) {
+                let signature = Signature::of(&(self.load)(entry)?);
+                self.cache.insert(entry.oid, signature);
+            }
+        }
+        Ok(self.cache[&source.oid].similarity(&self.cache[&destination.oid], minimum_score))
+    }
+
+    /// Whether a modified file changed enough to be broken up, and if so how
+    /// much of the old file is gone, out of `MAX_SCORE`. Only regular files
+    /// are broken.
+    fn break_score(&mut self, old: &Entry, new: &Entry, break_score: u32) -> Result<Option<u32>> {
+        if !is_regular(old.mode) || !is_regular(new.mode) || old.oid == new.oid {
+            return Ok(None);
+        }
+        for entry in [old, new] {
+            if !self.cache.contains_key(&entry.oid) {
+                let signature = Signature::of(&(self.load)(entry)?);
+                self.cache.insert(entry.oid, signature);
+            }
+        }
+        Ok(self.cache[&old.oid].break_score(&self.cache[&new.oid], break_score))
+    }
+}
+
+impl Signature {
+    /// Cuts a file into spans that end at a newline or after `MAX_SPAN`
+    /// bytes, and counts the bytes in spans with each hash. As in git, a last
+    /// line without a newline isn't counted, and in text the CR of a CRLF is
+    /// skipped.
+    fn of(data: &[u8]) -> Self {
+        let text = !super::is_binary(data);
+        let mut spans: HashMap<u32, u64> = HashMap::new();
+        let (mut accum1, mut accum2, mut length) = (0u32, 0u32, 0u64);
+        for (index, &byte) in data.iter().enumerate() {
+            if text && byte == b'\r' && data.get(index + 1) == Some(&b'\n') {
+                continue;
+            }
+            let old1 = accum1;
+            accum1 = (accum1 << 7) ^ (accum2 >> 25);
+            accum2 = (accum2 << 7) ^ (old1 >> 25);
+            accum1 = accum1.wrapping_add(u32::from(byte));
+            length += 1;
+            if length < MAX_SPAN as u64 && byte != b'\n' {
+                continue;
+            }
+            let hash = accum1.wrapping_add(accum2.wrapping_mul(0x61)) % SPAN_HASH_BASE;
+            *spans.entry(hash).or_default() += length;
+            (accum1, accum2, length) = (0, 0, 0);
+        }
+        Signature { size: data.len() as u64, spans }
+    }
+
+    /// How much of the larger file is made of spans both share, out of
+    /// `MAX_SCORE`. Files whose sizes differ too much for `minimum_score` to
+    /// be reached score 0 without being compared.
+    fn similarity(&self, destination: &Signature, minimum_score: u32) -> u32 {
+        let max_size = self.size.max(destination.size);
+        let delta_size = max_size - self.size.min(destination.size);
+        if max_size * u64::from(MAX_SCORE - minimum_score) < delta_size * u64::from(MAX_SCORE) {
+            return 0;
+        }
+        if destination.size == 0 {
+            return 0;
+        }
+        let copied: u64 = self
+            .spans
+            .iter()
+            .map(|(hash, &count)| count.min(destination.spans.get(hash).copied().unwrap_or(0)))
+            .sum();
+        (copied * u64::from(MAX_SCORE) / max_size) as u32
+    }
+
+    /// git's `should_break`: a file is broken up if more than `break_score`
+    /// of it was removed, or if what was removed and added together make up
+    /// that much of the larger version, unless it mostly grew. Small and
+    /// empty files are left alone.
+    fn break_score(&self, destination: &Signature, break_score: u32) -> Option<u32> {
+        let max_size = self.size.max(destination.size);
+        if max_size < MINIMUM_BREAK_SIZE || self.size == 0 {
+            return None;
+        }
+        let (mut copied, mut added) = (0, 0);
+        for (hash, &count) in &destination.spans {
+            let source_count = self.spans.get(hash).copied().unwrap_or(0);
+            copied += count.min(source_count);
+            added += count.saturating_sub(source_count);
+        }
+        let copied = copied.min(self.size);
+        if destination.size < added + copied {
+            added = destination.size.saturating_sub(copied);
+        }
+        let removed = self.size - copied;
+        let (max_score, break_score) = (u64::from(MAX_SCORE), u64::from(break_score));
+        let merge_score = removed * max_score / self.size;
+        if merge_score > break_score {
+            return Some(merge_score as u32);
+        }
+        if (removed + added) * max_score / max_size < break_score {
+            return None;
+        }
+        if self.size * break_score < removed * max_score && added * 20 < removed && added * 20 < copied {
+            return None;
+        }
+        Some(merge_score as u32)
+    }
+}
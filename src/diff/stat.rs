@@ -0,0 +1,204 @@
+// src/diff/stat.rs
+
+use std::io::{self, Write};
+
+/// How many lines one file gained and lost, for `--stat` and `--numstat`.
+/// For a binary file the counts are its sizes in bytes after and before.
+#[derive(Debug, Clone)]
+pub struct FileStat {
+    /// The path, or for a rename both paths as `rename_name` shows them.
+    pub name: String,
+    pub added: usize,
+    pub deleted: usize,
+    pub binary: bool,
+}
+
+/// Shows a renamed file's two paths with what they share written once,
+/// e.g. `src/{old => new}/lib.rs`.
+pub fn rename_name(old: &str, new: &str) -> String {
+    let (a, b) = (old.as_bytes(), new.as_bytes());
+
+    // The common prefix, up to and including a slash.
+    let mut prefix = 0;
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        if x != y {
+            break;
+        }
+        if *x == b'/' {
+            prefix = i + 1;
+        }
+    }
+
+    // The common suffix, from a slash. The end of each string compares
+    // equal, and with a prefix the search may reach back to its slash.
+    let byte_at = |bytes: &[u8], i: isize| if i as usize == bytes.len() { 0 } else { bytes[i as usize] };
+    let limit = prefix as isize - isize::from(prefix > 0);
+    let (mut i, mut j) = (a.len() as isize, b.len() as isize);
+    let mut suffix = 0;
+    while limit <= i && limit <= j && byte_at(a, i) == byte_at(b, j) {
+        if byte_at(a, i) == b'/' {
+            suffix = a.len() - i as usize;
+        }
+        i -= 1;
+        j -= 1;
+    }
+
+    let a_middle = a.len().saturating_sub(prefix + suffix);
+    let b_middle = b.len().saturating_sub(prefix + suffix);
+    let mut name = String::new();
+    if prefix + suffix > 0 {
+        name.push_str(&old[..prefix]);
+        name.push('{');
+    }
+    name.push_str(&old[prefix..prefix + a_middle]);
+    name.push_str(" => ");
+    name.push_str(&new[prefix..prefix + b_middle]);
+    if prefix + suffix > 0 {
+        name.push('}');
+        name.push_str(&old[old.len() - suffix..]);
+    }
+    name
+}
+
+/// Writes `--numstat` lines: lines added, lines deleted and the name,
+/// separated by tabs, with `-` for the counts of a binary file.
+pub fn write_numstat(out: &mut dyn Write, files: &[FileStat]) -> io::Result<()> {
+    for file in files {
+        if file.binary {
+            writeln!(out, "-\t-\t{}", file.name)?;
+        } else {
+            writeln!(out, "{}\t{}\t{}", file.added, file.deleted, file.name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the `--stat` table, laid out the way git lays it out in `width`
+/// columns, followed by the totals line.
+///
+/// Each file gets its name, its number of changed lines and a bar of `+`
+/// and `-` scaled to fit. When everything doesn't fit, names get up to 5/8
+/// of the width and are shortened from the front, and bars the rest.
+pub fn write_stat(out: &mut dyn Write, files: &[FileStat], width: usize) -> io::Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+    let text_width = |text: &str| text.chars().count() as i64;
+
+    let (mut max_len, mut max_change, mut bin_width, mut number_width) = (0, 0, 0, 0);
+    for file in files {
+        max_len = max_len.max(text_width(&file.name));
+        if file.binary {
+            // "Bin XXX -> YYY bytes"
+            bin_width = bin_width.max(14 + decimal_width(file.added) + decimal_width(file.deleted));
+            number_width = 3;
+            continue;
+        }
+        max_change = max_change.max((file.added + file.deleted) as i64);
+    }
+
+    let mut width = width as i64;
+    number_width = number_width.max(decimal_width(max_change as usize));
+    width = width.max(16 + 6 + number_width);
+    let mut graph_width = if max_change + 4 > bin_width { max_change } else { bin_width - 4 };
+    let mut name_width = max_len;
+    if name_width + number_width + 6 + graph_width > width {
+        if graph_width > width * 3 / 8 - number_width - 6 {
+            graph_width = (width * 3 / 8 - number_width - 6).max(6);
+        }
+        if name_width > width - number_width - 6 - graph_width {
+            name_width = width - number_width - 6 - graph_width;
+        } else {
+            graph_width = width - number_width - 6 - name_width;
+        }
+    }
+
+    let number_width = number_width as usize;
+    let (mut insertions, mut deletions) = (0, 0);
+    for file in files {
+        let mut prefix = "";
+        let mut name = file.name.as_str();
+        let mut len = name_width;
+        if name_width < text_width(name) {
+            prefix = "...";
+            len = (len - 3).max(0);
+            let skip = (text_width(name) - len) as usize;
+            name = &name[name.char_indices().nth(skip).map_or(name.len(), |(i, _)| i)..];
+            if let Some(slash) = name.find('/') {
+                name = &name[slash..];
+            }
+        }
+        let padding = (len - text_width(name)).max(0) as usize;
+        write!(out, " {}{}{} | ", prefix, name, " ".repeat(padding))?;
+
+        if file.binary {
+            write!(out, "{:>width$}", "Bin", width = number_width)?;
+            if file.added != 0 || file.deleted != 0 {
+                write!(out, " {} -> {} bytes", file.deleted, file.added)?;
+            }
+            writeln!(out)?;
+            continue;
+        }
+
+        insertions += file.added;
+        deletions += file.deleted;
+        let (mut add, mut delete) = (file.added as i64, file.deleted as i64);
+        if graph_width <= max_change {
+            let mut total = scale_linear(add + delete, graph_width, max_change);
+            if total < 2 && add > 0 && delete > 0 {
+                total = 2;
+            }
+            if add < delete {
+                add = scale_linear(add, graph_width, max_change);
+                delete = total - add;
+            } else {
+                delete = scale_linear(delete, graph_width, max_change);
+                add = total - delete;
+            }
+        }
+        let changed = file.added + file.deleted;
+        write!(out, "{:>width$}{}", changed, if changed > 0 { " " } else { "" }, width = number_width)?;
+        writeln!(out, "{}{}", "+".repeat(add as usize), "-".repeat(delete as usize))?;
+    }
+
+    let plural = |count: usize, one: &str, many: &str| format!("{} {}", count, if count == 1 { one } else { many });
+    write!(out, " {}", plural(files.len(), "file changed", "files changed"))?;
+    if insertions > 0 || deletions == 0 {
+        write!(out, ", {}", plural(insertions, "insertion(+)", "insertions(+)"))?;
+    }
+    if deletions > 0 || insertions == 0 {
+        write!(out, ", {}", plural(deletions, "deletion(-)", "deletions(-)"))?;
+    }
+    writeln!(out)
+}
+
+/// Scales a count to a bar of at most `width` characters, where `max` fills
+/// it. Any change gets at least one character.
+fn scale_linear(count: i64, width: i64, max: i64) -> i64 {
+    if count == 0 { 0 } else { 1 + count * (width - 1) / max }
+}
+
+fn decimal_width(number: usize) -> i64 {
+    number.to_string().len() as i64
+}
+
+/// The width `--stat` lays itself out in: $COLUMNS, or the terminal's
+/// width if standard output is one, or 80.
+pub fn terminal_width() -> usize {
+    if let Some(columns) = std::env::var("COLUMNS").ok().and_then(|value| value.parse().ok())
+        && columns > 0
+    {
+        return columns;
+    }
+    #[cfg(unix)]
+    {
+        // SAFETY: TIOCGWINSZ fills in the winsize struct, which is plain data,
+        // and fails harmlessly when stdout isn't a terminal.
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+        if result == 0 && size.ws_col > 0 {
+            return usize::from(size.ws_col);
+        }
+    }
+    80
+}
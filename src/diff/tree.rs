@@ -0,0 +1,205 @@
+// src/diff/tree.rs
+
+use crate::objects::{GitObject, Oid, TreeEntry};
+use crate::odb::ObjectStore;
+use anyhow::{bail, Context, Result};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The files on one side of a comparison, by slash-separated path, with
+/// their mode and blob id. This is what `repository::read_tree_recursive`
+/// returns, and what the index and working tree are read into. Paths are
+/// kept as the bytes git stores, which needn't be UTF-8, and so sort as git
+/// sorts them.
+pub type Snapshot = BTreeMap<Vec<u8>, (u32, Oid)>;
+
+/// A file on one side of a change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: Vec<u8>,
+    pub mode: u32,
+    pub oid: Oid,
+}
+
+/// What happened to a file. Renames and copies carry how similar the two
+/// files are, as a percentage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Added,
+    Deleted,
+    Modified,
+    /// A file became a symlink or a submodule, or the other way round.
+    TypeChanged,
+    Renamed(u32),
+    Copied(u32),
+    /// Modified, but so much that `-B` broke it up, with how much of the
+    /// old file is gone, as a percentage.
+    Rewritten(u32),
+}
+
+impl Status {
+    /// The letter `--name-status` and `--raw` show, with the similarity for
+    /// renames and copies (e.g. `R100`), or how much was rewritten.
+    pub fn label(&self) -> String {
+        match self {
+            Status::Added => "A".to_string(),
+            Status::Deleted => "D".to_string(),
+            Status::Modified => "M".to_string(),
+            Status::TypeChanged => "T".to_string(),
+            Status::Renamed(similarity) => format!("R{:03}", similarity),
+            Status::Copied(similarity) => format!("C{:03}", similarity),
+            Status::Rewritten(dissimilarity) => format!("M{:03}", dissimilarity),
+        }
+    }
+}
+
+/// One file that differs between two trees. `old` is missing for an added
+/// file and `new` for a deleted one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub status: Status,
+    pub old: Option<Entry>,
+    pub new: Option<Entry>,
+}
+
+impl FileChange {
+    /// The path the file ends up at, or had if it was deleted.
+    pub fn path(&self) -> &[u8] {
+        match (&self.new, &self.old) {
+            (Some(entry), _) | (None, Some(entry)) => &entry.path,
+            (None, None) => &[],
+        }
+    }
+
+    /// The change between two versions of the same path, if they differ.
+    fn between(path: &[u8], old: Option<(u32, Oid)>, new: Option<(u32, Oid)>) -> Option<Self> {
+        let entry = |(mode, oid)| Entry { path: path.to_vec(), mode, oid };
+        let status = match (old, new) {
+            (Some(old), Some(new)) if old == new => return None,
+            (Some((old_mode, _)), Some((new_mode, _))) if old_mode & 0o170000 != new_mode & 0o170000 => {
+                Status::TypeChanged
+            }
+            (Some(_), Some(_)) => Status::Modified,
+            (None, Some(_)) => Status::Added,
+            (Some(_), None) => Status::Deleted,
+            (None, None) => return None,
+        };
+        Some(FileChange { status, old: old.map(entry), new: new.map(entry) })
+    }
+}
+
+/// Whether `path` is one of `paths` or below one of them. No paths selects
+/// everything.
+pub fn is_selected(path: &[u8], paths: &[Vec<u8>]) -> bool {
+    paths.is_empty()
+        || paths.iter().any(|selected| {
+            selected.is_empty()
+                || path == selected.as_slice()
+                || path.strip_prefix(selected.as_slice()).is_some_and(|rest| rest.starts_with(b"/"))
+        })
+}
+
+/// Whether the directory `dir` could hold something `paths` selects.
+fn may_contain_selected(dir: &[u8], paths: &[Vec<u8>]) -> bool {
+    is_selected(dir, paths)
+        || paths
+            .iter()
+            .any(|selected| selected.strip_prefix(dir).is_some_and(|rest| rest.starts_with(b"/")))
+}
+
+/// Compares two trees (a missing one is empty) and returns the files that
+/// differ, in path order. Added and deleted files are not paired up here;
+/// `rename::detect` does that.
+///
+/// The trees are walked together, entries matched by name, and a subtree
+/// whose id is the same on both sides is skipped without being read. A file
+/// replaced by a directory of the same name shows as the file deleted and the
+/// directory's files added. With `paths`, only files at or below them are
+/// compared, and other subtrees aren't read.
+pub fn diff_trees(store: &ObjectStore, old: Option<&Oid>, new: Option<&Oid>, paths: &[Vec<u8>]) -> Result<Vec<FileChange>> {
+    let mut changes = Vec::new();
+    walk(store, old, new, b"", paths, &mut changes)?;
+    Ok(changes)
+}
+
+fn walk(
+    store: &ObjectStore,
+    old: Option<&Oid>,
+    new: Option<&Oid>,
+    prefix: &[u8],
+    paths: &[Vec<u8>],
+    changes: &mut Vec<FileChange>,
+) -> Result<()> {
+    let old_entries = read_entries(store, old)?;
+    let new_entries = read_entries(store, new)?;
+    let (mut old_iter, mut new_iter) = (old_entries.iter().peekable(), new_entries.iter().peekable());
+    loop {
+        // Entries are in tree order, where a directory sorts as if its name
+        // ended in '/', so both sides can be merged like sorted lists.
+        let (old_entry, new_entry) = match (old_iter.peek(), new_iter.peek()) {
+            (None, None) => break,
+            (Some(_), None) => (old_iter.next(), None),
+            (None, Some(_)) => (None, new_iter.next()),
+            (Some(old_entry), Some(new_entry)) => match compare_entries(old_entry, new_entry) {
+                Ordering::Less => (old_iter.next(), None),
+                Ordering::Greater => (None, new_iter.next()),
+                Ordering::Equal => (old_iter.next(), new_iter.next()),
+            },
+        };
+        let name = old_entry.or(new_entry).map_or(&[][..], |entry| entry.name.as_slice());
+        let path = [prefix, name].concat();
+
+        if old_entry.or(new_entry).is_some_and(|entry| entry.is_tree()) {
+            if old_entry.map(|entry| entry.hash) == new_entry.map(|entry| entry.hash)
+                || !may_contain_selected(&path, paths)
+            {
+                continue;
+            }
+            let (old_tree, new_tree) = (old_entry.map(|entry| &entry.hash), new_entry.map(|entry| &entry.hash));
+            walk(store, old_tree, new_tree, &[&path[..], b"/"].concat(), paths, changes)?;
+        } else if is_selected(&path, paths) {
+            let file = |entry: Option<&TreeEntry>| -> Result<Option<(u32, Oid)>> {
+                entry.map(|entry| Ok((parse_mode(entry, &path)?, entry.hash))).transpose()
+            };
+            changes.extend(FileChange::between(&path, file(old_entry)?, file(new_entry)?));
+        }
+    }
+    Ok(())
+}
+
+fn read_entries(store: &ObjectStore, tree: Option<&Oid>) -> Result<Vec<TreeEntry>> {
+    let Some(tree) = tree else {
+        return Ok(Vec::new());
+    };
+    match store.read(tree)? {
+        GitObject::Tree(tree) => Ok(tree.entries),
+        other => bail!("{} is a {}, not a tree", hex::encode(tree), other.kind()),
+    }
+}
+
+fn compare_entries(a: &TreeEntry, b: &TreeEntry) -> Ordering {
+    let key = |entry: &TreeEntry| {
+        let mut key = entry.name.clone();
+        if entry.is_tree() {
+            key.push(b'/');
+        }
+        key
+    };
+    key(a).cmp(&key(b))
+}
+
+fn parse_mode(entry: &TreeEntry, path: &[u8]) -> Result<u32> {
+    u32::from_str_radix(&entry.mode, 8)
+        .with_context(|| format!("Invalid mode {} for {}", entry.mode, String::from_utf8_lossy(path)))
+}
+
+/// Compares two snapshots, such as the index and the working tree, the same
+/// way `diff_trees` compares trees.
+pub fn diff_snapshots(old: &Snapshot, new: &Snapshot, paths: &[Vec<u8>]) -> Vec<FileChange> {
+    let all_paths: BTreeSet<&Vec<u8>> = old.keys().chain(new.keys()).collect();
+    all_paths
+        .into_iter()
+        .filter(|path| is_selected(path, paths))
+        .filter_map(|path| FileChange::between(path, old.get(path).copied(), new.get(path).copied()))
+        .collect()
+}
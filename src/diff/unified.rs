@@ -0,0 +1,118 @@
+// src/diff/unified.rs
+
+use super::Change;
+use std::io::{self, Write};
+
+// Function names in hunk headers are cut to this many bytes.
+const MAX_FUNCTION_NAME: usize = 80;
+
+/// Writes the `@@` hunks of a unified diff with `context` unchanged lines
+/// around each change. Changes closer together than twice that are shown in
+/// one hunk.
+///
+/// Each hunk header ends with the nearest line above the hunk that looks
+/// like the start of a function (one starting with a letter, `_` or `$`),
+/// as git's default does. When there is none between this hunk and the
+/// previous one, the previous hunk's is repeated.
+pub fn write_hunks(
+    out: &mut dyn Write,
+    old: &[&[u8]],
+    new: &[&[u8]],
+    changes: &[Change],
+    context: usize,
+) -> io::Result<()> {
+    let mut function: &[u8] = b"";
+    // Lines above this were searched for the previous hunk.
+    let mut searched = 0;
+    let mut rest = changes;
+    while let Some(first) = rest.first() {
+        let mut count = 1;
+        while let Some(change) = rest.get(count) {
+            let previous = &rest[count - 1];
+            if change.old_start - (previous.old_start + previous.old_len) > 2 * context {
+                break;
+            }
+            count += 1;
+        }
+        let (hunk, remaining) = rest.split_at(count);
+        rest = remaining;
+        let last = &hunk[count - 1];
+
+        let old_start = first.old_start.saturating_sub(context);
+        let new_start = first.new_start.saturating_sub(context);
+        let old_end = (last.old_start + last.old_len + context).min(old.len());
+        let new_end = (last.new_start + last.new_len + context).min(new.len());
+
+        if let Some(line) = old[searched.min(old_start)..old_start].iter().rev().find_map(|line| function_name(line)) {
+            function = line;
+        }
+        searched = old_start;
+
+        write!(
+            out,
+            "@@ -{} +{} @@",
+            range(old_start, old_end - old_start),
+            range(new_start, new_end - new_start)
+        )?;
+        if !function.is_empty() {
+            out.write_all(b" ")?;
+            out.write_all(function)?;
+        }
+        out.write_all(b"\n")?;
+
+        // Context comes from the new side, though it is the same on both.
+        let mut new_line = new_start;
+        for change in hunk {
+            for line in &new[new_line..change.new_start] {
+                write_line(out, b' ', line)?;
+            }
+            for line in &old[change.old_start..change.old_start + change.old_len] {
+                write_line(out, b'-', line)?;
+            }
+            for line in &new[change.new_start..change.new_start + change.new_len] {
+                write_line(out, b'+', line)?;
+            }
+            new_line = change.new_start + change.new_len;
+        }
+        for line in &new[new_line..new_end] {
+            write_line(out, b' ', line)?;
+        }
+    }
+    Ok(())
+}
+
+/// A hunk's start line and length as the header shows them: 1-based, the
+/// length left out when it is 1, and an empty range given as the line
+/// before it.
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+fn write_line(out: &mut dyn Write, prefix: u8, line: &[u8]) -> io::Result<()> {
+    out.write_all(&[prefix])?;
+    out.write_all(line)?;
+    if !line.ends_with(b"\n") {
+        out.write_all(b"\n\\ No newline at end of file\n")?;
+    }
+    Ok(())
+}
+
+/// The text a hunk header shows for a line that starts a function, without
+/// trailing whitespace.
+fn function_name(line: &[u8]) -> Option<&[u8]> {
+    let first = *line.first()?;
+    if !(first.is_ascii_alphabetic() || first == b'_' || first == b'$') {
+        return None;
+    }
+    let mut name = &line[..line.len().min(MAX_FUNCTION_NAME)];
+    while let Some((last, rest)) = name.split_last()
+        && matches!(last, b' ' | b'\t' | b'\n' | b'\r' | 0x0b | 0x0c)
+    {
+        name = rest;
+    }
+    Some(name)
+}
@@ -0,0 +1,456 @@
+// src/graph.rs
+
+use crate::objects::Oid;
+
+/// Draws the ASCII history graph `log --graph` shows beside each commit,
+/// following the layout of git's `graph.c` so the output matches it.
+///
+/// Each column is waiting for one commit: the parent of whatever was last
+/// drawn in it. A commit is drawn as `*` in its column, which then waits for
+/// its first parent; other parents of a merge branch off beside it (`|\`),
+/// and a column waiting for a commit another column already waits for is
+/// folded into it (`|/`). Columns sit at even positions of the text, with the
+/// lines between them at odd ones.
+///
+/// Each commit is drawn over several rows: any rows making room for an
+/// octopus merge, the commit's own row, the row where a merge's parents
+/// branch off, then rows moving lines left until every column is in place.
+/// Rows left over once the commit's text is used up are printed on their own.
+#[derive(Debug)]
+pub struct Graph {
+    commit: Oid,
+    parents: Vec<Oid>,
+    // The columns before and after the current commit.
+    columns: Vec<Oid>,
+    new_columns: Vec<Oid>,
+    // For each text position, the new column the line there is heading for.
+    mapping: Vec<Option<usize>>,
+    // `mapping` as it was after the last collapsing row.
+    old_mapping: Vec<Option<usize>>,
+    // The width every row for the current commit is padded to.
+    width: usize,
+    state: State,
+    prev_state: State,
+    commit_index: usize,
+    prev_commit_index: usize,
+    // Whether a merge's first parent is to the left of it (0) or below it
+    // (1); -1 until it is known.
+    merge_layout: isize,
+    // How many columns a merge adds to the right of it.
+    edges_added: isize,
+    prev_edges_added: isize,
+    expansion_row: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Padding,
+    PreCommit,
+    Commit,
+    PostMerge,
+    Collapsing,
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self {
+            commit: [0; 20],
+            parents: Vec::new(),
+            columns: Vec::new(),
+            new_columns: Vec::new(),
+            mapping: Vec::new(),
+            old_mapping: Vec::new(),
+            width: 0,
+            state: State::Padding,
+            prev_state: State::Padding,
+            commit_index: 0,
+            prev_commit_index: 0,
+            merge_layout: -1,
+            edges_added: 0,
+            prev_edges_added: 0,
+            expansion_row: 0,
+        }
+    }
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lays out the next commit, given the parents it will be drawn with.
+    pub fn update(&mut self, oid: &Oid, parents: &[Oid]) {
+        self.commit = *oid;
+        self.parents = parents.to_vec();
+        self.prev_commit_index = self.commit_index;
+        self.update_columns();
+        self.expansion_row = 0;
+        // The previous commit always finishes its rows before this one starts,
+        // and no row has been printed yet, so `prev_state` stays as it is.
+        self.state = if self.needs_pre_commit_row() { State::PreCommit } else { State::Commit };
+    }
+
+    /// Whether every row of the current commit has been drawn.
+    pub fn is_finished(&self) -> bool {
+        self.state == State::Padding
+    }
+
+    /// The next row of the current commit, and whether it is the commit's own
+    /// row (the one its first line of text goes beside). Once the commit is
+    /// finished, rows just continue each column down.
+    pub fn next_row(&mut self) -> (String, bool) {
+        let mut row = String::new();
+        let commit_row = self.state == State::Commit;
+        match self.state {
+            State::Padding => {
+                for _ in &self.new_columns {
+                    row.push_str("| ");
+                }
+            }
+            State::PreCommit => self.pre_commit_row(&mut row),
+            State::Commit => self.commit_row(&mut row),
+            State::PostMerge => self.post_merge_row(&mut row),
+            State::Collapsing => self.collapsing_row(&mut row),
+        }
+        (self.pad(row), commit_row)
+    }
+
+    /// The row beside a blank line printed between commits.
+    pub fn separator_row(&mut self) -> String {
+        if self.state != State::Commit {
+            return self.next_row().0;
+        }
+        let mut row = String::new();
+        for column in &self.columns {
+            row.push('|');
+            if *column == self.commit && self.parents.len() > 2 {
+                row.push_str(&" ".repeat((self.parents.len() - 2) * 2));
+            } else {
+                row.push(' ');
+            }
+        }
+        self.prev_state = State::Padding;
+        self.pad(row)
+    }
+
+    fn set_state(&mut self, state: State) {
+        self.prev_state = self.state;
+        self.state = state;
+    }
+
+    fn pad(&self, mut row: String) -> String {
+        while row.len() < self.width {
+            row.push(' ');
+        }
+        row
+    }
+
+    /// Works out the columns after the current commit, and where each line
+    /// drawn for it will end up.
+    fn update_columns(&mut self) {
+        self.columns = std::mem::take(&mut self.new_columns);
+        self.mapping = vec![None; 2 * (self.columns.len() + self.parents.len() + 1)];
+        self.width = 0;
+        self.prev_edges_added = self.edges_added;
+        self.edges_added = 0;
+
+        // A commit no column waits for (a branch tip) goes after the others.
+        let mut seen_this = false;
+        for index in 0..=self.columns.len() {
+            let column = match self.columns.get(index) {
+                Some(column) => *column,
+                None if seen_this => break,
+                None => self.commit,
+            };
+            if column == self.commit {
+                seen_this = true;
+                self.commit_index = index;
+                self.merge_layout = -1;
+                for parent in self.parents.clone() {
+                    self.insert_column(parent, Some(index));
+                }
+                // The commit takes up room even when nothing continues below.
+                if self.parents.is_empty() {
+                    self.width += 2;
+                }
+            } else {
+                self.insert_column(column, None);
+            }
+        }
+
+        while self.mapping.len() > 1 && self.mapping.last() == Some(&None) {
+            self.mapping.pop();
+        }
+    }
+
+    /// Adds `oid` to the new columns unless it is already there, and maps the
+    /// next text position to it. `index` is the commit's column when `oid` is
+    /// one of its parents.
+    fn insert_column(&mut self, oid: Oid, index: Option<usize>) {
+        let column = match self.new_columns.iter().position(|other| *other == oid) {
+            Some(column) => column,
+            None => {
+                self.new_columns.push(oid);
+                self.new_columns.len() - 1
+            }
+        };
+
+        let position = match index {
+            Some(index) if self.parents.len() > 1 && self.merge_layout == -1 => {
+                // The first parent of a merge decides whether its lines lean
+                // left, when that parent is already in a column to the left,
+                // or branch off to the right.
+                let distance = index as isize - column as isize;
+                let shift = if distance > 1 { 2 * distance - 3 } else { 1 };
+                self.merge_layout = if distance > 0 { 0 } else { 1 };
+                self.edges_added = self.parents.len() as isize + self.merge_layout - 2;
+                let position = self.width as isize + (self.merge_layout - 1) * shift;
+                self.width += 2 * self.merge_layout as usize;
+                position as usize
+            }
+            _ if self.edges_added > 0
+                && self.width >= 2
+                && self.mapping[self.width - 2] == Some(column) =>
+            {
+                // Joins the edge just drawn for the merge straight away.
+                self.edges_added = -1;
+                self.width - 2
+            }
+            _ => {
+                self.width += 2;
+                self.width - 2
+            }
+        };
+        if position >= self.mapping.len() {
+            self.mapping.resize(position + 1, None);
+        }
+        self.mapping[position] = Some(column);
+    }
+
+    fn dashed_parents(&self) -> usize {
+        (self.parents.len() as isize + self.merge_layout - 3).max(0) as usize
+    }
+
+    /// An octopus merge with columns to its right first pushes them along to
+    /// make room for its parents.
+    fn needs_pre_commit_row(&self) -> bool {
+        self.parents.len() >= 3
+            && self.commit_index + 1 < self.columns.len()
+            && self.expansion_row < self.dashed_parents() * 2
+    }
+
+    fn pre_commit_row(&mut self, row: &mut String) {
+        let mut seen_this = false;
+        for (index, column) in self.columns.iter().enumerate() {
+            if *column == self.commit {
+                seen_this = true;
+                row.push('|');
+                row.push_str(&" ".repeat(self.expansion_row));
+            } else if seen_this && self.expansion_row == 0 {
+                // Lines the previous merge pushed along keep leaning.
+                if self.prev_state == State::PostMerge && self.prev_commit_index < index {
+                    row.push('\\');
+                } else {
+                    row.push('|');
+                }
+            } else if seen_this {
+                row.push('\\');
+            } else {
+                row.push('|');
+            }
+            row.push(' ');
+        }
+
+        self.expansion_row += 1;
+        if !self.needs_pre_commit_row() {
+            self.set_state(State::Commit);
+        }
+    }
+
+    fn commit_row(&mut self, row: &mut String) {
+        let mut seen_this = false;
+        for index in 0..=self.columns.len() {
+            let column = match self.columns.get(index) {
+                Some(column) => *column,
+                None if seen_this => break,
+                None => self.commit,
+            };
+            if column == self.commit {
+                seen_this = true;
+                row.push('*');
+                for dash in 0..self.dashed_parents() {
+                    row.push('-');
+                    row.push(if dash + 1 == self.dashed_parents() { '.' } else { '-' });
+                }
+            } else if seen_this && self.edges_added > 1 {
+                row.push('\\');
+            } else if seen_this && self.edges_added == 1 {
+                // Right after a merge's branching row, lines it pushed along
+                // are still leaning.
+                if self.prev_state == State::PostMerge
+                    && self.prev_edges_added > 0
+                    && self.prev_commit_index < index
+                {
+                    row.push('\\');
+                } else {
+                    row.push('|');
+                }
+            } else if self.prev_state == State::Collapsing
+                && self.old_mapping.get(2 * index + 1) == Some(&Some(index))
+                && self.mapping.get(2 * index).copied().flatten().is_none_or(|target| target < index)
+            {
+                // A line still moving left from the previous commit's rows.
+                row.push('/');
+            } else {
+                row.push('|');
+            }
+            row.push(' ');
+        }
+
+        if self.parents.len() > 1 {
+            self.set_state(State::PostMerge);
+        } else if self.is_mapping_correct() {
+            self.set_state(State::Padding);
+        } else {
+            self.set_state(State::Collapsing);
+        }
+    }
+
+    fn post_merge_row(&mut self, row: &mut String) {
+        const MERGE_CHARS: [char; 3] = ['/', '|', '\\'];
+        let first_parent = self.parents[0];
+        let mut after_first_parent = false;
+        let mut seen_this = false;
+        for index in 0..=self.columns.len() {
+            let column = match self.columns.get(index) {
+                Some(column) => *column,
+                None if seen_this => break,
+                None => self.commit,
+            };
+            if column == self.commit {
+                seen_this = true;
+                let mut char_index = self.merge_layout as usize;
+                for parent in 0..self.parents.len() {
+                    row.push(MERGE_CHARS[char_index]);
+                    if char_index == 2 {
+                        if self.edges_added > 0 || parent + 1 < self.parents.len() {
+                            row.push(' ');
+                        }
+                    } else {
+                        char_index += 1;
+                    }
+                }
+                if self.edges_added == 0 {
+                    row.push(' ');
+                }
+            } else if seen_this {
+                row.push(if self.edges_added > 0 { '\\' } else { '|' });
+                row.push(' ');
+            } else {
+                row.push('|');
+                // A first parent far to the left is reached along the bottom
+                // of the columns in between.
+                if self.merge_layout != 0 || index + 1 != self.commit_index {
+                    row.push(if after_first_parent { '_' } else { ' ' });
+                }
+            }
+            if column == first_parent {
+                after_first_parent = true;
+            }
+        }
+
+        if self.is_mapping_correct() {
+            self.set_state(State::Padding);
+        } else {
+            self.set_state(State::Collapsing);
+        }
+    }
+
+    /// Moves every line one position left towards its column. A line crossing
+    /// the one to its left is drawn beside it, and one line per row can run
+    /// along the bottom (`_`) to catch up.
+    fn collapsing_row(&mut self, row: &mut String) {
+        let size = self.mapping.len();
+        let old = std::mem::replace(&mut self.mapping, vec![None; size]);
+        // The first position a line runs along the bottom from, and its
+        // column.
+        let mut horizontal: Option<(usize, usize)> = None;
+        let mut used_horizontal = false;
+
+        for (position, target) in old.iter().enumerate() {
+            let Some(target) = *target else {
+                continue;
+            };
+            if 2 * target == position {
+                // Already in place.
+                self.mapping[position] = Some(target);
+            } else if self.mapping[position - 1].is_none() {
+                self.mapping[position - 1] = Some(target);
+                if horizontal.is_none() {
+                    horizontal = Some((position, target));
+                    self.run_along_bottom(target, position);
+                }
+            } else if self.mapping[position - 1] != Some(target) {
+                // Cross the line just to the left.
+                self.mapping[position - 2] = Some(target);
+                if horizontal.is_none() {
+                    horizontal = Some((position - 1, target));
+                    self.run_along_bottom(target, position);
+                }
+            }
+        }
+
+        self.old_mapping = self.mapping.clone();
+        if self.mapping.last() == Some(&None) {
+            self.mapping.pop();
+        }
+
+        for position in 0..self.mapping.len() {
+            let Some(target) = self.mapping[position] else {
+                row.push(' ');
+                continue;
+            };
+            if 2 * target == position {
+                row.push('|');
+            } else if let Some((edge, edge_target)) = horizontal
+                && target == edge_target
+                && position + 1 != edge
+            {
+                // Only the first segment carries on into the next row.
+                if position != 2 * target + 3 {
+                    self.mapping[position] = None;
+                }
+                used_horizontal = true;
+                row.push('_');
+            } else {
+                if used_horizontal && horizontal.is_some_and(|(edge, _)| position < edge) {
+                    self.mapping[position] = None;
+                }
+                row.push('/');
+            }
+        }
+
+        if self.is_mapping_correct() {
+            self.set_state(State::Padding);
+        }
+    }
+
+    /// Maps the odd positions between `target`'s column and `position` to it,
+    /// so the line runs along the bottom (`_`) as far as it can this row.
+    fn run_along_bottom(&mut self, target: usize, position: usize) {
+        let mut between = 2 * target + 3;
+        while between + 2 < position {
+            self.mapping[between] = Some(target);
+            between += 2;
+        }
+    }
+
+    /// Whether every line is in its column, or just to the right of it where
+    /// the `/` already drawn joins it.
+    fn is_mapping_correct(&self) -> bool {
+        self.mapping
+            .iter()
+            .enumerate()
+            .all(|(position, target)| target.is_none_or(|target| target == position / 2))
+    }
+}
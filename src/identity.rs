@@ -0,0 +1,148 @@
+// src/identity.rs
+
+use crate::config::Config;
+use crate::date;
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::fs;
+
+/// Whose identity is wanted: commits record both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Author,
+    Committer,
+}
+
+impl Role {
+    fn env_prefix(self) -> &'static str {
+        match self {
+            Role::Author => "GIT_AUTHOR",
+            Role::Committer => "GIT_COMMITTER",
+        }
+    }
+
+    fn config_section(self) -> &'static str {
+        match self {
+            Role::Author => "author",
+            Role::Committer => "committer",
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Role::Author => "Author",
+            Role::Committer => "Committer",
+        }
+    }
+}
+
+/// An identity line read back from a commit or tag, split into its parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    pub timestamp: i64,
+    /// Offset from UTC in seconds.
+    pub offset: i64,
+}
+
+impl Signature {
+    /// Parses `Name <email> <timestamp> <+hhmm>`.
+    pub fn parse(line: &str) -> Result<Self> {
+        let invalid = || format!("Invalid identity line: {}", line);
+        let (name, rest) = line.split_once('<').with_context(invalid)?;
+        let (email, date) = rest.split_once('>').with_context(invalid)?;
+        let (timestamp, offset) = date.trim().split_once(' ').unwrap_or((date.trim(), "+0000"));
+        Ok(Signature {
+            name: name.trim().to_string(),
+            email: email.to_string(),
+            timestamp: timestamp.parse().with_context(invalid)?,
+            offset: date::parse_offset(offset).unwrap_or(0),
+        })
+    }
+}
+
+/// The `Name <email> <timestamp> <timezone>` line recorded in commits and
+/// tags. Fails if no name or email is configured.
+pub fn signature(config: &Config, role: Role) -> Result<String> {
+    let (name, email) = name_and_email(config, role)?;
+    let (timestamp, offset) = when(role)?;
+    Ok(format!("{} <{}> {} {}", name, email, timestamp, date::format_offset(offset)))
+}
+
+/// Like `signature` without the date, and never failing: reflogs fall back to
+/// the login name when no identity is configured, as git does.
+pub fn reflog_identity(config: &Config) -> String {
+    let (name, email) = name_and_email(config, Role::Committer).unwrap_or_else(|_| {
+        let user = env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+        let host = fs::read_to_string("/etc/hostname")
+            .map(|host| host.trim().to_string())
+            .unwrap_or_else(|_| "localhost".to_string());
+        (user.clone(), format!("{}@{}", user, host))
+    });
+    format!("{} <{}>", name, email)
+}
+
+/// Looks up the name and email, in order of precedence, from
+/// `GIT_AUTHOR_NAME` / `GIT_COMMITTER_NAME` (and `_EMAIL`), then
+/// `author.name` / `committer.name`, then `user.name` / `user.email`, and for
+/// the email finally `$EMAIL`.
+pub fn name_and_email(config: &Config, role: Role) -> Result<(String, String)> {
+    let lookup = |field: &str| {
+        env::var(format!("{}_{}", role.env_prefix(), field.to_ascii_uppercase()))
+            .ok()
+            .or_else(|| config.get(&format!("{}.{}", role.config_section(), field)).map(str::to_string))
+            .or_else(|| config.get(&format!("user.{}", field)).map(str::to_string))
+            .filter(|value| !value.trim().is_empty())
+    };
+    let name = lookup("name");
+    let email = lookup("email").or_else(|| env::var("EMAIL").ok().filter(|e| !e.is_empty()));
+
+    match (name, email) {
+        (Some(name), Some(email)) => Ok((sanitize(&name), sanitize(&email))),
+        _ => bail!(
+            "{} identity unknown\n\n*** Please tell me who you are.\n\nRun\n\n  \
+             rusgit config --global user.email \"you@example.com\"\n  \
+             rusgit config --global user.name \"Your Name\"\n\nto set your account's default identity.",
+            role.title()
+        ),
+    }
+}
+
+/// Names and emails can't contain the characters that delimit them.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !matches!(c, '<' | '>' | '\n'))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// The time to record: `GIT_AUTHOR_DATE` / `GIT_COMMITTER_DATE` if set
+/// (`<timestamp> <+hhmm>`, `@<timestamp>` or any date `parse_approxidate`
+/// understands, optionally followed by an offset), otherwise now, in the local
+/// timezone.
+pub fn when(role: Role) -> Result<(i64, i64)> {
+    let now = date::now();
+    let Ok(text) = env::var(format!("{}_DATE", role.env_prefix())) else {
+        return Ok((now, date::local_offset(now)));
+    };
+
+    let text = text.trim();
+    let (rest, offset) = match text.rsplit_once(' ') {
+        Some((rest, tz)) => match date::parse_offset(tz) {
+            Some(offset) => (rest, Some(offset)),
+            None => (text, None),
+        },
+        None => (text, None),
+    };
+    let bare = rest.strip_prefix('@').unwrap_or(rest);
+    let timestamp = if bare.bytes().all(|b| b.is_ascii_digit()) {
+        bare.parse().with_context(|| format!("Invalid date: {}", text))?
+    } else {
+        // Dates with a time of day are wall-clock times in the given zone.
+        date::parse_approxidate(rest, now)? - offset.unwrap_or(0)
+    };
+    Ok((timestamp, offset.unwrap_or_else(|| date::local_offset(timestamp))))
+}
@@ -1,26 +1,357 @@
 // src/index.rs
 
+use crate::lockfile::LockFile;
 use anyhow::{bail, Context, Result};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 use sha1::{Digest, Sha1};
 
+/// The index mode for a file in the working tree: a symlink, an executable
+/// or a regular file.
+pub fn mode_from_metadata(metadata: &fs::Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.file_type().is_symlink() {
+            return 0o120000;
+        }
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return 0o100755;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+    0o100644
+}
+
+// Bits of the 16-bit entry flags.
+const FLAG_ASSUME_VALID: u16 = 0x8000;
+const FLAG_EXTENDED: u16 = 0x4000;
+const STAGE_MASK: u16 = 0x3000;
+const NAME_MASK: u16 = 0x0fff;
+// Bits of the extended flags that follow them in version 3 and later.
+const FLAG_SKIP_WORKTREE: u16 = 0x4000;
+const FLAG_INTENT_TO_ADD: u16 = 0x2000;
+
 // Represents a single entry in the index file.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct IndexEntry {
-    // We'll simplify and only store what's essential for now.
-    // Real Git index entries have more metadata (timestamps, etc.).
+    // The stat data lets us tell whether a file changed without rehashing it.
+    pub ctime_secs: u32,
+    pub ctime_nanos: u32,
+    pub mtime_secs: u32,
+    pub mtime_nanos: u32,
+    pub dev: u32,
+    pub ino: u32,
     pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    // The on-disk size, truncated to 32 bits like git does.
+    pub size: u32,
     pub hash: [u8; 20],
     pub path: PathBuf,
+    // Merge stage: 0 for normal entries, 1-3 for the sides of a conflict.
+    pub stage: u8,
+    pub assume_valid: bool,
+    // Extended flags, which need index version 3 or later.
+    pub skip_worktree: bool,
+    pub intent_to_add: bool,
+}
+
+impl IndexEntry {
+    /// Builds an entry for a working tree file, taking the mode and stat data
+    /// from its metadata.
+    pub fn from_metadata(path: PathBuf, hash: [u8; 20], metadata: &fs::Metadata) -> Self {
+        let mut entry = IndexEntry {
+            mode: mode_from_metadata(metadata),
+            hash,
+            path,
+            ..Default::default()
+        };
+        entry.update_stat(metadata);
+        entry
+    }
+
+    /// Records the stat data of the file this entry was hashed from.
+    pub fn update_stat(&mut self, metadata: &fs::Metadata) {
+        let stat = StatData::from_metadata(metadata);
+        self.ctime_secs = stat.ctime_secs;
+        self.ctime_nanos = stat.ctime_nanos;
+        self.mtime_secs = stat.mtime_secs;
+        self.mtime_nanos = stat.mtime_nanos;
+        self.dev = stat.dev;
+        self.ino = stat.ino;
+        self.uid = stat.uid;
+        self.gid = stat.gid;
+        self.size = stat.size;
+    }
+
+    /// True if the file's metadata is unchanged since this entry was recorded.
+    /// That only proves the content is unchanged if the entry is not racy.
+    pub fn stat_matches(&self, metadata: &fs::Metadata) -> bool {
+        let stat = StatData::from_metadata(metadata);
+        self.mode == mode_from_metadata(metadata)
+            && self.mtime_secs == stat.mtime_secs
+            && self.mtime_nanos == stat.mtime_nanos
+            && self.ctime_secs == stat.ctime_secs
+            && self.ctime_nanos == stat.ctime_nanos
+            && self.dev == stat.dev
+            && self.ino == stat.ino
+            && self.uid == stat.uid
+            && self.gid == stat.gid
+            && self.size == stat.size
+    }
+
+    /// git's racy-git rule: a file modified in the same instant the index was
+    /// written may have changed again after it was hashed without its mtime
+    /// moving, so its stat data cannot be trusted and the content must be checked.
+    pub fn is_racy(&self, index_mtime: Option<(u32, u32)>) -> bool {
+        match index_mtime {
+            Some(index_mtime) => (self.mtime_secs, self.mtime_nanos) >= index_mtime,
+            None => true,
+        }
+    }
+}
+
+/// The subset of `stat(2)` that the index records, in the widths it uses.
+struct StatData {
+    ctime_secs: u32,
+    ctime_nanos: u32,
+    mtime_secs: u32,
+    mtime_nanos: u32,
+    dev: u32,
+    ino: u32,
+    uid: u32,
+    gid: u32,
+    size: u32,
+}
+
+impl StatData {
+    #[cfg(unix)]
+    fn from_metadata(metadata: &fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        StatData {
+            ctime_secs: metadata.ctime() as u32,
+            ctime_nanos: metadata.ctime_nsec() as u32,
+            mtime_secs: metadata.mtime() as u32,
+            mtime_nanos: metadata.mtime_nsec() as u32,
+            dev: metadata.dev() as u32,
+            ino: metadata.ino() as u32,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            size: metadata.len() as u32,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from_metadata(metadata: &fs::Metadata) -> Self {
+        let (mtime_secs, mtime_nanos) = metadata.modified().map(system_time_parts).unwrap_or((0, 0));
+        StatData {
+            ctime_secs: mtime_secs,
+            ctime_nanos: mtime_nanos,
+            mtime_secs,
+            mtime_nanos,
+            dev: 0,
+            ino: 0,
+            uid: 0,
+            gid: 0,
+            size: metadata.len() as u32,
+        }
+    }
+}
+
+fn system_time_parts(time: SystemTime) -> (u32, u32) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    (since_epoch.as_secs() as u32, since_epoch.subsec_nanos())
 }
 
 // Represents the entire index.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Index {
+    // 2 is the baseline format, 3 adds extended entry flags and 4 compresses
+    // paths against the previous entry. Written back in the version it was read.
+    pub version: u32,
     pub entries: Vec<IndexEntry>,
+    // When the index file was last written, used to detect racily clean entries.
+    pub mtime: Option<(u32, u32)>,
+    // The `TREE` extension: tree ids of directories whose entries are unchanged
+    // since they were last written as trees.
+    pub cache_tree: Option<CacheTree>,
+    // The `REUC` extension: the conflicted stages of paths that have since been
+    // resolved, so a merge resolution can be undone.
+    pub resolve_undo: Vec<ResolveUndo>,
+    // Extensions we don't interpret (`UNTR`, `link`, `FSMN`, ...), written back
+    // as they were read.
+    pub extensions: Vec<RawExtension>,
+}
+
+/// An index extension kept as raw bytes.
+#[derive(Debug, Clone)]
+pub struct RawExtension {
+    pub signature: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+/// One node of the cached tree: a directory, how many index entries it covers
+/// and, if still valid, the id of the tree object those entries produce.
+#[derive(Debug, Clone, Default)]
+pub struct CacheTree {
+    // -1 when the node has been invalidated.
+    pub entry_count: i32,
+    pub oid: Option<[u8; 20]>,
+    // Subdirectories by name, in the order git writes them.
+    pub subtrees: Vec<(Vec<u8>, CacheTree)>,
+}
+
+impl CacheTree {
+    /// Parses the `TREE` extension. Nodes are stored depth first as
+    /// `<name>\0<entry_count> <subtree_count>\n`, followed by the tree id
+    /// when the node is valid, then its subtrees.
+    pub fn parse(mut data: &[u8]) -> Result<Self> {
+        let (name, tree) = Self::parse_node(&mut data)?;
+        if !name.is_empty() || !data.is_empty() {
+            bail!("Invalid TREE extension");
+        }
+        Ok(tree)
+    }
+
+    fn parse_node(data: &mut &[u8]) -> Result<(Vec<u8>, Self)> {
+        let name = read_until_nul(data)?.to_vec();
+        let newline = data.iter().position(|&b| b == b'\n').context("Invalid TREE extension")?;
+        let counts = std::str::from_utf8(&data[..newline])?;
+        *data = &data[newline + 1..];
+        let (entry_count, subtree_count) = counts.split_once(' ').context("Invalid TREE extension")?;
+        let entry_count: i32 = entry_count.parse().context("Invalid TREE extension")?;
+        let subtree_count: usize = subtree_count.parse().context("Invalid TREE extension")?;
+
+        let oid = if entry_count >= 0 {
+            let mut oid = [0u8; 20];
+            data.read_exact(&mut oid)?;
+            Some(oid)
+        } else {
+            None
+        };
+        let mut subtrees = Vec::with_capacity(subtree_count);
+        for _ in 0..subtree_count {
+            subtrees.push(Self::parse_node(data)?);
+        }
+        Ok((
+            name,
+            CacheTree {
+                entry_count,
+                oid,
+                subtrees,
+            },
+        ))
+    }
+
+    fn write(&self, name: &[u8], out: &mut Vec<u8>) {
+        out.extend_from_slice(name);
+        out.push(0);
+        out.extend_from_slice(format!("{} {}\n", self.entry_count, self.subtrees.len()).as_bytes());
+        if let Some(oid) = self.valid_oid() {
+            out.extend_from_slice(&oid);
+        }
+        for (name, subtree) in &self.subtrees {
+            subtree.write(name, out);
+        }
+    }
+
+    /// Marks the root and every directory leading to `path` as changed.
+    pub fn invalidate(&mut self, path: &[u8]) {
+        self.entry_count = -1;
+        self.oid = None;
+        if let Some(slash) = path.iter().position(|&b| b == b'/')
+            && let Some((_, subtree)) = self.subtrees.iter_mut().find(|(name, _)| name[..] == path[..slash])
+        {
+            subtree.invalidate(&path[slash + 1..]);
+        }
+    }
+
+    /// Returns the node for a slash-separated directory path (`""` for the root).
+    pub fn find(&self, dir: &[u8]) -> Option<&CacheTree> {
+        if dir.is_empty() {
+            return Some(self);
+        }
+        let (name, rest) = match dir.iter().position(|&b| b == b'/') {
+            Some(slash) => (&dir[..slash], &dir[slash + 1..]),
+            None => (dir, &[][..]),
+        };
+        self.subtrees
+            .iter()
+            .find(|(subtree_name, _)| subtree_name == name)
+            .and_then(|(_, subtree)| subtree.find(rest))
+    }
+
+    /// The tree id for this node, if it is still valid.
+    pub fn valid_oid(&self) -> Option<[u8; 20]> {
+        if self.entry_count >= 0 { self.oid } else { None }
+    }
+}
+
+/// One `REUC` record: the modes and blob ids of stages 1-3 of a path whose
+/// conflict was resolved. A mode of 0 means the stage was absent.
+#[derive(Debug, Clone)]
+pub struct ResolveUndo {
+    pub path: Vec<u8>,
+    pub modes: [u32; 3],
+    pub hashes: [Option<[u8; 20]>; 3],
+}
+
+impl ResolveUndo {
+    fn parse_all(mut data: &[u8]) -> Result<Vec<Self>> {
+        let mut entries = Vec::new();
+        while !data.is_empty() {
+            let path = read_until_nul(&mut data)?.to_vec();
+            let mut modes = [0u32; 3];
+            for mode in modes.iter_mut() {
+                let text = std::str::from_utf8(read_until_nul(&mut data)?)?;
+                *mode = u32::from_str_radix(text, 8).context("Invalid REUC mode")?;
+            }
+            let mut hashes = [None; 3];
+            for (hash, mode) in hashes.iter_mut().zip(modes) {
+                if mode != 0 {
+                    let mut oid = [0u8; 20];
+                    data.read_exact(&mut oid)?;
+                    *hash = Some(oid);
+                }
+            }
+            entries.push(ResolveUndo { path, modes, hashes });
+        }
+        Ok(entries)
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.path);
+        out.push(0);
+        for mode in self.modes {
+            out.extend_from_slice(format!("{:o}", mode).as_bytes());
+            out.push(0);
+        }
+        for (hash, mode) in self.hashes.iter().zip(self.modes) {
+            if mode != 0
+                && let Some(hash) = hash
+            {
+                out.extend_from_slice(hash);
+            }
+        }
+    }
+}
+
+impl Default for Index {
+    fn default() -> Self {
+        Index {
+            version: 2,
+            entries: Vec::new(),
+            mtime: None,
+            cache_tree: None,
+            resolve_undo: Vec::new(),
+            extensions: Vec::new(),
+        }
+    }
 }
 
 impl Index {
@@ -33,109 +364,371 @@ impl Index {
         }
 
         let mut file = File::open(path)?;
+        index.mtime = Some(system_time_parts(file.metadata()?.modified()?));
         let mut data = Vec::new();
         file.read_to_end(&mut data)?;
-        let mut data = &data[..]; // Create a slice to read from
 
-        // 1. Read the header (12 bytes).
+        // 1. Verify the trailing checksum before trusting anything else.
+        if data.len() < 12 + 20 {
+            bail!("Index file is too short: {:?}", path);
+        }
+        let (data, checksum) = data.split_at(data.len() - 20);
+        if Sha1::digest(data)[..] != checksum[..] {
+            bail!("Index file is corrupt (checksum mismatch): {:?}", path);
+        }
+        let mut data = data; // Create a slice to read from
+
+        // 2. Read the header (12 bytes).
         let signature = data.read_u32::<BigEndian>()?;
         if signature != 0x44495243 { // "DIRC" in ASCII
             bail!("Invalid index signature");
         }
         let version = data.read_u32::<BigEndian>()?;
-        if version != 2 {
+        if !(2..=4).contains(&version) {
             bail!("Unsupported index version: {}", version);
         }
+        index.version = version;
         let entry_count = data.read_u32::<BigEndian>()?;
 
-        // 2. Read the entries.
+        // 3. Read the entries.
+        let mut previous_path: Vec<u8> = Vec::new();
         for _ in 0..entry_count {
-            // We'll skip ctime, mtime, dev, ino, uid, gid, size (60 bytes total)
-            // A real implementation would parse these.
-            data.read_exact(&mut [0u8; 8])?; // ctime
-            data.read_exact(&mut [0u8; 8])?; // mtime
-            data.read_exact(&mut [0u8; 4])?; // dev
-            data.read_exact(&mut [0u8; 4])?; // ino
+            let ctime_secs = data.read_u32::<BigEndian>()?;
+            let ctime_nanos = data.read_u32::<BigEndian>()?;
+            let mtime_secs = data.read_u32::<BigEndian>()?;
+            let mtime_nanos = data.read_u32::<BigEndian>()?;
+            let dev = data.read_u32::<BigEndian>()?;
+            let ino = data.read_u32::<BigEndian>()?;
             let mode = data.read_u32::<BigEndian>()?;
-            data.read_exact(&mut [0u8; 4])?; // uid
-            data.read_exact(&mut [0u8; 4])?; // gid
-            data.read_exact(&mut [0u8; 4])?; // size
+            let uid = data.read_u32::<BigEndian>()?;
+            let gid = data.read_u32::<BigEndian>()?;
+            let size = data.read_u32::<BigEndian>()?;
 
             let mut hash = [0u8; 20];
             data.read_exact(&mut hash)?;
 
             let flags = data.read_u16::<BigEndian>()?;
-            let path_len = (flags & 0x0FFF) as usize;
+            let mut header_len = 62;
+            let mut extended_flags = 0;
+            if flags & FLAG_EXTENDED != 0 {
+                if version < 3 {
+                    bail!("Extended index entry flags in a version {} index", version);
+                }
+                extended_flags = data.read_u16::<BigEndian>()?;
+                header_len += 2;
+            }
 
-            let mut path_bytes = vec![0u8; path_len];
-            data.read_exact(&mut path_bytes)?;
-            let path_str = std::str::from_utf8(&path_bytes)?;
-            let path = PathBuf::from(path_str);
+            let path_bytes = if version == 4 {
+                // Version 4 stores how many bytes to drop from the end of the
+                // previous path, then the new suffix, NUL-terminated, and
+                // doesn't pad entries.
+                let strip = read_offset_varint(&mut data)?;
+                if strip > previous_path.len() {
+                    bail!("Invalid path prefix compression in index");
+                }
+                let mut path = previous_path[..previous_path.len() - strip].to_vec();
+                path.extend_from_slice(read_until_nul(&mut data)?);
+                path
+            } else {
+                // Names of 0xFFF bytes or more don't fit in the flags and are
+                // found by looking for the terminating NUL instead.
+                let name_len = (flags & NAME_MASK) as usize;
+                let path = if name_len < NAME_MASK as usize {
+                    let (path, rest) = data.split_at_checked(name_len).context("Truncated index entry")?;
+                    data = rest;
+                    path.to_vec()
+                } else {
+                    let path = &data[..data.iter().position(|&b| b == 0).context("Truncated index entry")?];
+                    data = &data[path.len()..];
+                    path.to_vec()
+                };
+                // Entries are padded with 1-8 null bytes to align to 8-byte boundaries.
+                let entry_len = header_len + path.len();
+                data.read_exact(&mut vec![0u8; entry_padding(entry_len)])?;
+                path
+            };
 
-            index.entries.push(IndexEntry { mode, hash, path });
+            let path = path_from_bytes(&path_bytes);
 
-            // Entries are padded with null bytes to align to 8-byte boundaries.
-            let entry_len = 62 + path_len; // 62 bytes of metadata + path
-            let padding = (8 - (entry_len % 8)) % 8;
-            data.read_exact(&mut vec![0u8; padding])?;
+            index.entries.push(IndexEntry {
+                ctime_secs,
+                ctime_nanos,
+                mtime_secs,
+                mtime_nanos,
+                dev,
+                ino,
+                mode,
+                uid,
+                gid,
+                size,
+                hash,
+                path,
+                stage: ((flags & STAGE_MASK) >> 12) as u8,
+                assume_valid: flags & FLAG_ASSUME_VALID != 0,
+                skip_worktree: extended_flags & FLAG_SKIP_WORKTREE != 0,
+                intent_to_add: extended_flags & FLAG_INTENT_TO_ADD != 0,
+            });
+            previous_path = path_bytes;
+        }
+
+        // 4. Whatever is left before the checksum is a sequence of extensions:
+        // a 4-byte signature, a 32-bit size and that many bytes of data.
+        while !data.is_empty() {
+            let mut signature = [0u8; 4];
+            data.read_exact(&mut signature)?;
+            let size = data.read_u32::<BigEndian>()? as usize;
+            let (ext_data, rest) = data
+                .split_at_checked(size)
+                .with_context(|| format!("Truncated index extension {}", String::from_utf8_lossy(&signature)))?;
+            data = rest;
+
+            match &signature {
+                b"TREE" => index.cache_tree = Some(CacheTree::parse(ext_data)?),
+                b"REUC" => index.resolve_undo = ResolveUndo::parse_all(ext_data)?,
+                // The end-of-entries marker and entry offset table only describe
+                // the layout of this particular file, so they go stale as soon
+                // as we rewrite it. They are optional; drop them.
+                b"EOIE" | b"IEOT" => {}
+                _ => index.extensions.push(RawExtension {
+                    signature,
+                    data: ext_data.to_vec(),
+                }),
+            }
         }
 
-        // We're skipping the checksum at the end for simplicity.
         Ok(index)
     }
 
-    /// Write the Index struct back to a binary file on disk.
+    /// Write the Index struct back to a binary file on disk. The new index
+    /// goes into `index.lock` and is renamed over the old one, so concurrent
+    /// writers are refused and a crash never leaves a partial index.
     pub fn write(&self, path: &Path) -> Result<()> {
+        let mut lock = LockFile::acquire(path)?;
         let mut file_content = Vec::new();
 
+        // Extended flags need at least version 3.
+        let has_extended_flags = self.entries.iter().any(|e| e.skip_worktree || e.intent_to_add);
+        let version = if has_extended_flags { self.version.max(3) } else { self.version };
+
         // 1. Write the header.
         file_content.write_u32::<BigEndian>(0x44495243)?; // "DIRC"
-        file_content.write_u32::<BigEndian>(2)?; // Version 2
+        file_content.write_u32::<BigEndian>(version)?;
         file_content.write_u32::<BigEndian>(self.entries.len() as u32)?;
 
+        // Entries modified in the same second as this write are racily clean:
+        // a later change might not move their mtime. Smudging the recorded size
+        // makes the next reader compare their content instead of trusting stat.
+        let now = system_time_parts(SystemTime::now());
+
         // 2. Write the entries.
+        let mut previous_path: &[u8] = &[];
         for entry in &self.entries {
-            // Again, we're writing zero for most metadata fields.
-            file_content.write_u32::<BigEndian>(0)?; // ctime seconds
-            file_content.write_u32::<BigEndian>(0)?; // ctime nanoseconds
-            file_content.write_u32::<BigEndian>(0)?; // mtime seconds
-            file_content.write_u32::<BigEndian>(0)?; // mtime nanoseconds
-            file_content.write_u32::<BigEndian>(0)?; // dev
-            file_content.write_u32::<BigEndian>(0)?; // ino
+            let size = if (entry.mtime_secs, entry.mtime_nanos) >= (now.0, 0) { 0 } else { entry.size };
+            file_content.write_u32::<BigEndian>(entry.ctime_secs)?;
+            file_content.write_u32::<BigEndian>(entry.ctime_nanos)?;
+            file_content.write_u32::<BigEndian>(entry.mtime_secs)?;
+            file_content.write_u32::<BigEndian>(entry.mtime_nanos)?;
+            file_content.write_u32::<BigEndian>(entry.dev)?;
+            file_content.write_u32::<BigEndian>(entry.ino)?;
             file_content.write_u32::<BigEndian>(entry.mode)?;
-            file_content.write_u32::<BigEndian>(0)?; // uid
-            file_content.write_u32::<BigEndian>(0)?; // gid
-            file_content.write_u32::<BigEndian>(0)?; // size
+            file_content.write_u32::<BigEndian>(entry.uid)?;
+            file_content.write_u32::<BigEndian>(entry.gid)?;
+            file_content.write_u32::<BigEndian>(size)?;
 
             file_content.write_all(&entry.hash)?;
 
-            let path_bytes = entry.path.to_str().context("Non-UTF8 path")?.as_bytes();
-            file_content.write_u16::<BigEndian>(path_bytes.len() as u16)?;
-            file_content.write_all(path_bytes)?;
+            let path_bytes = path_bytes(&entry.path);
+            let extended = entry.skip_worktree || entry.intent_to_add;
+            let mut flags = (path_bytes.len().min(NAME_MASK as usize)) as u16;
+            flags |= ((entry.stage as u16) << 12) & STAGE_MASK;
+            if entry.assume_valid {
+                flags |= FLAG_ASSUME_VALID;
+            }
+            if extended {
+                flags |= FLAG_EXTENDED;
+            }
+            file_content.write_u16::<BigEndian>(flags)?;
+            if extended {
+                let mut extended_flags = 0;
+                if entry.skip_worktree {
+                    extended_flags |= FLAG_SKIP_WORKTREE;
+                }
+                if entry.intent_to_add {
+                    extended_flags |= FLAG_INTENT_TO_ADD;
+                }
+                file_content.write_u16::<BigEndian>(extended_flags)?;
+            }
 
-            // Pad with null bytes.
-            let entry_len = 62 + path_bytes.len();
-            let padding = (8 - (entry_len % 8)) % 8;
-            file_content.write_all(&vec![0u8; padding])?;
+            if version == 4 {
+                let common = previous_path
+                    .iter()
+                    .zip(path_bytes)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                write_offset_varint(&mut file_content, previous_path.len() - common);
+                file_content.write_all(&path_bytes[common..])?;
+                file_content.push(0);
+            } else {
+                file_content.write_all(path_bytes)?;
+                // Pad with null bytes.
+                let entry_len = if extended { 64 } else { 62 } + path_bytes.len();
+                file_content.write_all(&vec![0u8; entry_padding(entry_len)])?;
+            }
+            previous_path = path_bytes;
+        }
+
+        // 3. Write the extensions.
+        if let Some(cache_tree) = &self.cache_tree {
+            let mut ext_data = Vec::new();
+            cache_tree.write(b"", &mut ext_data);
+            write_extension(&mut file_content, b"TREE", &ext_data)?;
+        }
+        if !self.resolve_undo.is_empty() {
+            let mut ext_data = Vec::new();
+            for entry in &self.resolve_undo {
+                entry.write(&mut ext_data);
+            }
+            write_extension(&mut file_content, b"REUC", &ext_data)?;
+        }
+        for extension in &self.extensions {
+            write_extension(&mut file_content, &extension.signature, &extension.data)?;
         }
 
-        // 3. Calculate and write the checksum.
+        // 4. Calculate and write the checksum.
         let mut hasher = sha1::Sha1::new();
         hasher.update(&file_content);
         let checksum = hasher.finalize();
         file_content.write_all(&checksum)?;
-        
-        fs::write(path, &file_content)?;
-        Ok(())
+
+        lock.write_all(&file_content)?;
+        lock.commit()
     }
 
     /// A helper to add or update an entry in the index.
-    pub fn add(&mut self, path: PathBuf, hash: [u8; 20], mode: u32) {
+    pub fn add(&mut self, entry: IndexEntry) {
+        // The cached trees of every directory above this path are now stale.
+        if let Some(cache_tree) = &mut self.cache_tree {
+            cache_tree.invalidate(path_bytes(&entry.path));
+        }
         // Remove the old entry if it exists.
-        self.entries.retain(|e| e.path != path);
+        self.entries.retain(|e| e.path != entry.path);
         // Add the new entry.
-        self.entries.push(IndexEntry { path, hash, mode });
+        self.entries.push(entry);
         // Keep the index sorted by path, as Git requires.
-        self.entries.sort_by(|a, b| a.path.cmp(&b.path));
+        self.entries.sort_by(compare_entries);
     }
-}
\ No newline at end of file
+
+    /// Removes every entry for a path, at any stage. Returns whether there was one.
+    pub fn remove(&mut self, path: &Path) -> bool {
+        let before = self.entries.len();
+        self.entries.retain(|e| e.path != path);
+        if self.entries.len() == before {
+            return false;
+        }
+        if let Some(cache_tree) = &mut self.cache_tree {
+            cache_tree.invalidate(path_bytes(path));
+        }
+        true
+    }
+}
+
+/// Git orders entries by the raw bytes of their path, then by stage. This is
+/// not the same as comparing `Path`s component by component: `a.c` sorts
+/// before `a/b` because `.` is less than `/`.
+pub fn compare_entries(a: &IndexEntry, b: &IndexEntry) -> std::cmp::Ordering {
+    path_bytes(&a.path).cmp(path_bytes(&b.path)).then(a.stage.cmp(&b.stage))
+}
+
+/// The bytes an index path is stored as. Git doesn't require paths to be
+/// UTF-8, so they are taken from the OS string as they are.
+pub fn path_bytes(path: &Path) -> &[u8] {
+    path.as_os_str().as_encoded_bytes()
+}
+
+/// The inverse of `path_bytes`. Outside Unix, file names have to be valid
+/// Unicode, so anything else is replaced.
+pub fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Shows a path as git does in its output: unchanged if it is plain
+/// printable ASCII, otherwise in double quotes with control characters,
+/// quotes, backslashes and bytes from 0x7f up escaped C-style, e.g.
+/// `"n\351"` for a Latin-1 `né`.
+pub fn quote_path(path: &[u8]) -> String {
+    let needs_quoting = |byte: u8| !(0x20..0x7f).contains(&byte) || byte == b'"' || byte == b'\\';
+    if !path.iter().any(|&byte| needs_quoting(byte)) {
+        return String::from_utf8_lossy(path).into_owned();
+    }
+    let mut quoted = String::from("\"");
+    for &byte in path {
+        match byte {
+            0x07 => quoted.push_str("\\a"),
+            0x08 => quoted.push_str("\\b"),
+            b'\t' => quoted.push_str("\\t"),
+            b'\n' => quoted.push_str("\\n"),
+            0x0b => quoted.push_str("\\v"),
+            0x0c => quoted.push_str("\\f"),
+            b'\r' => quoted.push_str("\\r"),
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            byte if needs_quoting(byte) => quoted.push_str(&format!("\\{:03o}", byte)),
+            byte => quoted.push(byte as char),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn write_extension(out: &mut Vec<u8>, signature: &[u8; 4], data: &[u8]) -> Result<()> {
+    out.write_all(signature)?;
+    out.write_u32::<BigEndian>(data.len() as u32)?;
+    out.write_all(data)?;
+    Ok(())
+}
+
+/// Reads git's offset varint, where each continuation byte adds one before
+/// shifting (the same encoding as pack `OFS_DELTA` offsets).
+fn read_offset_varint(data: &mut &[u8]) -> Result<usize> {
+    let mut byte = data.read_u8()?;
+    let mut value = (byte & 0x7f) as usize;
+    while byte & 0x80 != 0 {
+        byte = data.read_u8()?;
+        value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+    }
+    Ok(value)
+}
+
+fn write_offset_varint(out: &mut Vec<u8>, mut value: usize) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        value -= 1;
+        bytes.push(0x80 | (value & 0x7f) as u8);
+        value >>= 7;
+    }
+    bytes.reverse();
+    out.extend_from_slice(&bytes);
+}
+
+/// Returns the bytes up to the next NUL and advances past the NUL.
+fn read_until_nul<'a>(data: &mut &'a [u8]) -> Result<&'a [u8]> {
+    let nul = data.iter().position(|&b| b == 0).context("Truncated index entry")?;
+    let bytes = &data[..nul];
+    *data = &data[nul + 1..];
+    Ok(bytes)
+}
+
+/// The number of null bytes after an entry: at least one, and enough to reach
+/// a multiple of 8 bytes.
+fn entry_padding(entry_len: usize) -> usize {
+    8 - (entry_len % 8)
+}
@@ -1,7 +1,22 @@
 // src/lib.rs
 
 pub mod objects;
+pub mod odb;
+pub mod pack;
+pub mod delta;
+pub mod pack_writer;
 pub mod commands;
 pub mod cli;
 pub mod index;
-pub mod repository;
\ No newline at end of file
+pub mod repository;
+pub mod refs;
+pub mod lockfile;
+pub mod reflog;
+pub mod date;
+pub mod revision;
+pub mod config;
+pub mod identity;
+pub mod revwalk;
+pub mod graph;
+pub mod diff;
+pub mod checkout;
\ No newline at end of file
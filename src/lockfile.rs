@@ -0,0 +1,80 @@
+// src/lockfile.rs
+
+use anyhow::{bail, Context, Result};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// An exclusive lock on a file, held by creating `<path>.lock` next to it.
+///
+/// Only one process can create the lock file, so whoever holds it may
+/// rewrite the file: the new content goes into the lock file, which
+/// `commit` renames over the original. If the lock is dropped without being
+/// committed, the lock file is removed and the original is left untouched.
+#[derive(Debug)]
+pub struct LockFile {
+    path: PathBuf,
+    lock_path: PathBuf,
+    file: Option<File>,
+}
+
+impl LockFile {
+    pub fn acquire(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut lock_name = OsString::from(path.as_os_str());
+        lock_name.push(".lock");
+        let lock_path = PathBuf::from(lock_name);
+
+        let file = match OpenOptions::new().write(true).create_new(true).open(&lock_path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => bail!(
+                "Unable to create '{}': File exists.\n\nAnother rusgit process seems to be running in this repository. \
+                 If none is, remove the file manually to continue.",
+                lock_path.display()
+            ),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Unable to create '{}'", lock_path.display()));
+            }
+        };
+        Ok(LockFile {
+            path: path.to_path_buf(),
+            lock_path,
+            file: Some(file),
+        })
+    }
+
+    /// The file this lock protects.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes the new content and flushes it to disk, so that a crash after
+    /// `commit` can't leave an empty or partial file behind.
+    pub fn write_all(&mut self, content: &[u8]) -> Result<()> {
+        let file = self.file.as_mut().context("Lock file already closed")?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Moves the new content into place and releases the lock.
+    pub fn commit(mut self) -> Result<()> {
+        self.file = None;
+        fs::rename(&self.lock_path, &self.path)
+            .with_context(|| format!("Failed to rename '{}' into place", self.lock_path.display()))?;
+        // Nothing is left for Drop to clean up.
+        self.lock_path = PathBuf::new();
+        Ok(())
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        if !self.lock_path.as_os_str().is_empty() {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+    }
+}
@@ -1,43 +1,346 @@
 // src/main.rs
 
 mod cli;
-// mod commands; 
-// use std::path;
 use rusgit::commands;
+use rusgit::repository::Repository;
 use anyhow::Result;
-use clap::Parser;
-use cli::{Cli, Commands};
-use std::path::Path;
+use cli::{Cli, Commands, ReflogAction};
+use std::env;
 
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    // Rust ignores SIGPIPE, which makes `println!` panic once a reader such as
+    // `head` goes away. Restore the default so we stop quietly, like git.
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+    let cli = Cli::parse_args();
     match cli.command {
-        Commands::Init { path } => {
-            commands::init::init(&path)?;
+        // `init` creates a repository and `hash-object` can run without one.
+        Commands::Init { path, bare } => {
+            commands::init::init(&path, bare)?;
+        }
+        Commands::HashObject { file, write } => {
+            let repo = Repository::discover(&env::current_dir()?).ok();
+            commands::hash_object::hash_object(repo.as_ref(), &file, write)?;
+        }
+        Commands::Config {
+            key,
+            value,
+            global,
+            system,
+            local,
+            file,
+            get: _,
+            get_all,
+            add,
+            unset,
+            unset_all,
+            list,
+            show_origin,
+        } => {
+            use commands::config::{ConfigAction, ConfigScope};
+            use rusgit::config::ConfigLevel;
+            // --global, --system and --file work outside a repository.
+            let repo = Repository::discover(&env::current_dir()?).ok();
+            let scope = match (file, global, system, local) {
+                (Some(file), ..) => ConfigScope::File(file),
+                (None, true, _, _) => ConfigScope::Level(ConfigLevel::Global),
+                (None, _, true, _) => ConfigScope::Level(ConfigLevel::System),
+                (None, _, _, true) => ConfigScope::Level(ConfigLevel::Local),
+                _ => ConfigScope::All,
+            };
+            let action = ConfigAction::from_flags(key, value, get_all, add, unset, unset_all, list)?;
+            commands::config::config(repo.as_ref(), scope, action, show_origin)?;
         }
-        Commands::HashObject { file, write } => { // <-- Add this match arm
-            commands::hash_object::hash_object(&file, write)?;
+        // Everything else runs against the repository found from here.
+        command => {
+            let repo = Repository::discover(&env::current_dir()?)?;
+            run(&repo, command)?;
         }
-        Commands::CatFile { hash, pretty_print } => { // <-- Add this
-            commands::cat_file::cat_file(&hash, pretty_print)?;
+    }
+    Ok(())
+}
+
+fn run(repo: &Repository, command: Commands) -> Result<()> {
+    match command {
+        Commands::Init { .. } | Commands::HashObject { .. } | Commands::Config { .. } => unreachable!(),
+        Commands::CatFile { hash, pretty_print } => {
+            commands::cat_file::cat_file(repo, &hash, pretty_print)?;
         }
         Commands::WriteTree => {
-            let hash = rusgit::commands::write_tree::write_tree(&Path::new("."))?;
-            println!("{}", hex::encode(hash));
+            commands::write_tree::write_tree(repo)?;
         }
         Commands::CommitTree {
             tree_hash,
             parent_hash,
             message,
         } => {
-            rusgit::commands::commit_tree::commit_tree(tree_hash, parent_hash, message)?;
+            rusgit::commands::commit_tree::commit_tree(repo, tree_hash, parent_hash, message)?;
         }
         Commands::Add { files } => {
-            rusgit::commands::add::add(files)?;
+            rusgit::commands::add::add(repo, files)?;
         }
         Commands::Commit { message } => {
-            rusgit::commands::commit::commit(message)?;
+            rusgit::commands::commit::commit(repo, message)?;
+        }
+        Commands::Status { short, porcelain } => {
+            let format = commands::status::StatusFormat::from_flags(short, porcelain.as_deref())?;
+            commands::status::status(repo, format)?;
+        }
+        Commands::PackObjects { base_name } => {
+            commands::pack_objects::pack_objects(repo, &base_name)?;
+        }
+        Commands::Repack { all, delete } => {
+            commands::repack::repack(repo, all, delete)?;
+        }
+        Commands::Branch {
+            names,
+            delete,
+            force_delete,
+            rename,
+            list,
+            set_upstream_to,
+            contains,
+            merged,
+        } => {
+            use commands::branch::{self, BranchFilter};
+            let listing = list || contains.is_some() || merged.is_some();
+            if delete || force_delete {
+                branch::delete_branches(repo, &names, force_delete)?;
+            } else if rename {
+                match names.as_slice() {
+                    [new] => branch::rename_branch(repo, None, new)?,
+                    [old, new] => branch::rename_branch(repo, Some(old), new)?,
+                    [] => anyhow::bail!("branch name required"),
+                    _ => anyhow::bail!("too many arguments for a rename operation"),
+                }
+            } else if let Some(upstream) = set_upstream_to {
+                match names.as_slice() {
+                    [] => branch::set_upstream(repo, &upstream, None)?,
+                    [name] => branch::set_upstream(repo, &upstream, Some(name))?,
+                    _ => anyhow::bail!("too many arguments to set new upstream"),
+                }
+            } else if listing || names.is_empty() {
+                branch::list_branches(repo, &BranchFilter { patterns: names, contains, merged })?;
+            } else {
+                match names.as_slice() {
+                    [name] => branch::create_branch(repo, name, None)?,
+                    [name, start] => branch::create_branch(repo, name, Some(start))?,
+                    _ => anyhow::bail!("too many arguments"),
+                }
+            }
+        }
+        Commands::Checkout { target, paths, .. } if !paths.is_empty() => {
+            commands::checkout::checkout_paths(repo, target.as_deref(), &paths)?;
+        }
+        Commands::Checkout { target, new_branch, detach, force, .. } => {
+            let options = commands::checkout::SwitchOptions { force, detach, new_branch, branch_only: false };
+            commands::checkout::switch(repo, target.as_deref(), &options)?;
+        }
+        Commands::Switch { target, new_branch, detach, force } => {
+            let options = commands::checkout::SwitchOptions { force, detach, new_branch, branch_only: true };
+            commands::checkout::switch(repo, target.as_deref(), &options)?;
+        }
+        Commands::Restore { paths, staged, worktree, source } => {
+            let options = commands::restore::RestoreOptions { staged, worktree, source, overlay: false };
+            commands::restore::restore(repo, &paths, &options)?;
+        }
+        Commands::Tag {
+            name,
+            rev,
+            annotate,
+            message,
+            list,
+            delete,
+            force,
+        } => match name {
+            None if delete => anyhow::bail!("tag -d needs a tag name"),
+            Some(name) if delete => commands::tag::delete_tag(repo, &name)?,
+            Some(name) if !list => {
+                if annotate && message.is_none() {
+                    anyhow::bail!("An annotated tag needs a message (-m)");
+                }
+                commands::tag::create_tag(repo, &name, rev.as_deref(), message, force)?;
+            }
+            pattern => commands::tag::list_tags(repo, pattern.as_deref())?,
+        },
+        Commands::ShowRef {
+            patterns,
+            head,
+            heads,
+            tags,
+            dereference,
+            hash_only,
+            verify,
+        } => {
+            let options = commands::show_ref::ShowRefOptions {
+                head,
+                heads,
+                tags,
+                dereference,
+                hash_only,
+                verify,
+            };
+            commands::show_ref::show_ref(repo, &patterns, &options)?;
+        }
+        Commands::SymbolicRef {
+            name,
+            target,
+            delete,
+            short,
+            quiet,
+        } => {
+            commands::symbolic_ref::symbolic_ref(repo, &name, target.as_deref(), delete, short, quiet)?;
+        }
+        Commands::ForEachRef {
+            patterns,
+            format,
+            sort,
+            count,
+        } => {
+            commands::for_each_ref::for_each_ref(repo, &patterns, &format, sort.as_deref(), count)?;
+        }
+        Commands::PackRefs { all, no_prune } => {
+            commands::pack_refs::pack_refs(repo, all, !no_prune)?;
+        }
+        Commands::UpdateRef {
+            ref_name,
+            values,
+            delete,
+            no_deref,
+            message,
+            stdin,
+        } => match ref_name {
+            Some(name) if !stdin => commands::update_ref::update_ref(
+                repo,
+                &name,
+                &values,
+                delete,
+                no_deref,
+                message.as_deref(),
+            )?,
+            _ => commands::update_ref::update_ref_stdin(repo, no_deref, message.as_deref())?,
+        },
+        Commands::RevParse {
+            revisions,
+            verify,
+            short,
+            abbrev_ref,
+            symbolic_full_name,
+        } => {
+            let options = commands::rev_parse::RevParseOptions {
+                verify,
+                short,
+                abbrev_ref,
+                symbolic_full_name,
+            };
+            commands::rev_parse::rev_parse(repo, &revisions, &options)?;
+        }
+        Commands::Log {
+            revisions,
+            paths,
+            oneline,
+            max_count,
+            pretty,
+            format,
+            graph,
+            author,
+            grep,
+            since,
+            until,
+            first_parent,
+        } => {
+            use commands::log::{LogOptions, Pretty};
+            let pretty = match (format, pretty) {
+                (Some(template), _) => Pretty::Format { template, terminated: true },
+                (None, Some(pretty)) => Pretty::parse(&pretty)?,
+                (None, None) if oneline => Pretty::Oneline,
+                (None, None) => Pretty::Medium,
+            };
+            let options = LogOptions {
+                pretty,
+                abbrev_commit: oneline,
+                max_count,
+                graph,
+                first_parent,
+                authors: author,
+                grep,
+                since,
+                until,
+            };
+            commands::log::log(repo, &revisions, &paths, options)?;
+        }
+        Commands::Diff {
+            cached,
+            revisions,
+            paths,
+            unified,
+            patience,
+            histogram,
+            find_renames,
+            find_copies,
+            no_renames,
+            break_rewrites,
+            name_only,
+            name_status,
+            raw,
+            stat,
+            numstat,
+            summary: condensed_summary,
+        } => {
+            use commands::diff::{DiffOptions, Summary};
+            use rusgit::diff::rename::{self, RenameOptions};
+            use rusgit::diff::Algorithm;
+            let algorithm = if patience {
+                Algorithm::Patience
+            } else if histogram {
+                Algorithm::Histogram
+            } else {
+                Algorithm::Myers
+            };
+            let score = |threshold: &str| match threshold {
+                "" => Ok(rename::DEFAULT_SCORE),
+                threshold => rename::parse_score(threshold),
+            };
+            let renames = match (find_copies, find_renames) {
+                _ if no_renames => None,
+                (Some(threshold), _) => Some(RenameOptions { copies: true, minimum_score: score(&threshold)? }),
+                (None, Some(threshold)) => Some(RenameOptions { copies: false, minimum_score: score(&threshold)? }),
+                (None, None) => commands::diff::configured_renames(repo)?,
+            };
+            let breaks = break_rewrites.as_deref().map(rename::parse_break).transpose()?;
+            let summary = if name_only {
+                Some(Summary::NameOnly)
+            } else if name_status {
+                Some(Summary::NameStatus)
+            } else if raw {
+                Some(Summary::Raw)
+            } else {
+                None
+            };
+            let options = DiffOptions {
+                algorithm,
+                context: unified,
+                renames,
+                breaks,
+                summary,
+                stat,
+                numstat,
+                condensed_summary,
+            };
+            commands::diff::diff(repo, cached, &revisions, &paths, &options)?;
         }
+        Commands::Reflog { action, ref_name } => match action {
+            None => commands::reflog::show(repo, ref_name.as_deref().unwrap_or("HEAD"))?,
+            Some(ReflogAction::Show { ref_name }) => {
+                commands::reflog::show(repo, ref_name.as_deref().unwrap_or("HEAD"))?
+            }
+            Some(ReflogAction::Expire { expire, all, refs }) => {
+                commands::reflog::expire(repo, &refs, all, expire.as_deref())?
+            }
+            Some(ReflogAction::Delete { entries }) => commands::reflog::delete(repo, &entries)?,
+        },
     }
     Ok(())
 }
\ No newline at end of file
@@ -1,13 +1,67 @@
 // src/objects.rs
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use std::fmt;
+use std::str::FromStr;
+
+/// A raw SHA-1 object id.
+pub type Oid = [u8; 20];
+
+/// Parses a full 40-character hex object id.
+pub fn parse_oid(hex_str: &str) -> Result<Oid> {
+    let mut oid = [0u8; 20];
+    if hex_str.len() != 40 || hex::decode_to_slice(hex_str, &mut oid).is_err() {
+        bail!("Not a valid object name: {}", hex_str);
+    }
+    Ok(oid)
+}
+
+/// The type recorded in an object's header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+    Blob,
+    Tree,
+    Commit,
+    Tag,
+}
+
+impl ObjectKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectKind::Blob => "blob",
+            ObjectKind::Tree => "tree",
+            ObjectKind::Commit => "commit",
+            ObjectKind::Tag => "tag",
+        }
+    }
+}
+
+impl fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ObjectKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "blob" => Ok(ObjectKind::Blob),
+            "tree" => Ok(ObjectKind::Tree),
+            "commit" => Ok(ObjectKind::Commit),
+            "tag" => Ok(ObjectKind::Tag),
+            _ => bail!("Unknown object type: {}", s),
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TreeEntry {
     // We use a string for mode for simplicity. Git uses octal.
     pub mode: String,
-    pub name: String,
+    // Names are raw bytes: git doesn't require them to be UTF-8.
+    pub name: Vec<u8>,
     // The hash is stored as raw bytes for efficiency.
     pub hash: [u8; 20],
 }
@@ -22,25 +76,155 @@ pub struct Commit {
     pub tree_hash: String,
     // A commit can have zero or more parents.
     pub parents: Vec<String>,
-    pub author: String,
-    pub committer: String,
-    pub message: String,
+    // The identities and the message are raw bytes: with an `encoding`
+    // header they are in that encoding, not UTF-8.
+    pub author: Vec<u8>,
+    pub committer: Vec<u8>,
+    // Only present when the message is not UTF-8.
+    pub encoding: Option<String>,
+    // Headers we don't interpret (e.g. `mergetag`), kept in order so the
+    // commit serializes back to the same bytes.
+    pub extra_headers: Vec<(String, Vec<u8>)>,
+    pub gpgsig: Option<Vec<u8>>,
+    pub message: Vec<u8>,
+}
+
+// An annotated tag: a named, signed-off pointer to another object.
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub object: String,
+    pub kind: ObjectKind,
+    pub tag: String,
+    // Very old tags were created without a tagger line.
+    pub tagger: Option<Vec<u8>>,
+    // Headers we don't interpret, kept so the tag serializes back the same.
+    pub extra_headers: Vec<(String, Vec<u8>)>,
+    pub message: Vec<u8>,
+    // An ASCII-armored PGP or SSH signature appended after the message.
+    pub signature: Option<Vec<u8>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum GitObject {
     Blob(Blob),
     Tree(Tree),
     Commit(Commit),
+    Tag(Tag),
 }
 
 // A Blob is just a wrapper around a byte vector. It represents file content.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Blob {
     pub content: Vec<u8>,
 }
 
+/// Parses a `<type> <size>\0` header, returning the type, size and header length.
+pub fn parse_header(data: &[u8]) -> Result<(ObjectKind, usize, usize)> {
+    let nul = data
+        .iter()
+        .position(|&b| b == 0)
+        .context("Invalid object format: missing null byte")?;
+    let header = std::str::from_utf8(&data[..nul]).context("Invalid object header")?;
+    let (kind, size) = header
+        .split_once(' ')
+        .context("Invalid object header: missing size")?;
+    let kind: ObjectKind = kind.parse()?;
+    let size: usize = size.parse().context("Invalid object header: bad size")?;
+    Ok((kind, size, nul + 1))
+}
+
+impl GitObject {
+    /// Decodes a full object (`type size\0content`), checking that the size in
+    /// the header matches the content.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let (kind, size, header_len) = parse_header(data)?;
+        let content = &data[header_len..];
+        if content.len() != size {
+            bail!(
+                "Object size mismatch: header says {} but content is {} bytes",
+                size,
+                content.len()
+            );
+        }
+        GitObject::parse(kind, content)
+    }
+
+    /// Decodes the content of an object of the given type.
+    pub fn parse(kind: ObjectKind, content: &[u8]) -> Result<Self> {
+        match kind {
+            ObjectKind::Blob => Ok(GitObject::Blob(Blob {
+                content: content.to_vec(),
+            })),
+            ObjectKind::Tree => Ok(GitObject::Tree(Tree::parse(content)?)),
+            ObjectKind::Commit => Ok(GitObject::Commit(Commit::parse(content)?)),
+            ObjectKind::Tag => Ok(GitObject::Tag(Tag::parse(content)?)),
+        }
+    }
+
+    pub fn kind(&self) -> ObjectKind {
+        match self {
+            GitObject::Blob(_) => ObjectKind::Blob,
+            GitObject::Tree(_) => ObjectKind::Tree,
+            GitObject::Commit(_) => ObjectKind::Commit,
+            GitObject::Tag(_) => ObjectKind::Tag,
+        }
+    }
+
+    /// The serialized object content, without the `type size\0` header.
+    pub fn content(&self) -> Vec<u8> {
+        match self {
+            GitObject::Blob(blob) => blob.content.clone(),
+            GitObject::Tree(tree) => tree.as_bytes(),
+            GitObject::Commit(commit) => commit.as_bytes(),
+            GitObject::Tag(tag) => tag.as_bytes(),
+        }
+    }
+}
+
+impl TreeEntry {
+    pub fn is_tree(&self) -> bool {
+        self.mode == "40000"
+    }
+
+    /// The object type this entry points at, as shown by `ls-tree`.
+    pub fn kind_name(&self) -> &'static str {
+        match self.mode.as_str() {
+            "40000" => "tree",
+            "160000" => "commit",
+            _ => "blob",
+        }
+    }
+}
+
 impl Tree {
+    /// Parses the binary tree format: repeated `mode<space>name\0<20 byte hash>`.
+    pub fn parse(mut data: &[u8]) -> Result<Self> {
+        let mut entries = Vec::new();
+        while !data.is_empty() {
+            let space = data
+                .iter()
+                .position(|&b| b == b' ')
+                .context("Invalid tree entry: missing mode")?;
+            let nul = data
+                .iter()
+                .position(|&b| b == 0)
+                .context("Invalid tree entry: missing null byte")?;
+            if nul < space || data.len() < nul + 21 {
+                bail!("Invalid tree entry: truncated");
+            }
+            let mode = std::str::from_utf8(&data[..space]).context("Invalid tree entry mode")?;
+            let mut hash = [0u8; 20];
+            hash.copy_from_slice(&data[nul + 1..nul + 21]);
+            entries.push(TreeEntry {
+                mode: mode.to_string(),
+                name: data[space + 1..nul].to_vec(),
+                hash,
+            });
+            data = &data[nul + 21..];
+        }
+        Ok(Tree { entries })
+    }
+
     // This function will convert the Tree struct into the byte format for hashing.
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
@@ -48,7 +232,7 @@ impl Tree {
             // Format is: `mode<space>name\0<raw_hash_bytes>`
             bytes.extend_from_slice(entry.mode.as_bytes());
             bytes.push(b' '); // space
-            bytes.extend_from_slice(entry.name.as_bytes());
+            bytes.extend_from_slice(&entry.name);
             bytes.push(b'\0'); // null byte
             bytes.extend_from_slice(&entry.hash);
         }
@@ -57,6 +241,40 @@ impl Tree {
 }
 
 impl Commit {
+    /// Parses a commit: header lines up to the first blank line, then the message.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let (fields, message) = parse_headers(data)?;
+        let mut tree_hash = None;
+        let mut parents = Vec::new();
+        let mut author = None;
+        let mut committer = None;
+        let mut encoding = None;
+        let mut gpgsig = None;
+        let mut extra_headers = Vec::new();
+        for (key, value) in fields {
+            match key.as_str() {
+                "tree" if tree_hash.is_none() => tree_hash = Some(header_text(value, "tree")?),
+                "parent" => parents.push(header_text(value, "parent")?),
+                "author" if author.is_none() => author = Some(value),
+                "committer" if committer.is_none() => committer = Some(value),
+                "encoding" if encoding.is_none() => encoding = Some(header_text(value, "encoding")?),
+                "gpgsig" if gpgsig.is_none() => gpgsig = Some(value),
+                _ => extra_headers.push((key, value)),
+            }
+        }
+
+        Ok(Commit {
+            tree_hash: tree_hash.context("Invalid commit: missing tree")?,
+            parents,
+            author: author.context("Invalid commit: missing author")?,
+            committer: committer.context("Invalid commit: missing committer")?,
+            encoding,
+            extra_headers,
+            gpgsig,
+            message: message.to_vec(),
+        })
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
         // Format is:
@@ -64,51 +282,187 @@ impl Commit {
         // parent <parent_hash>\n  (optional, repeated for multiple parents)
         // author <author_info>\n
         // committer <committer_info>\n
+        // encoding, extra headers and gpgsig (optional)
         // \n
         // <commit_message>
         bytes.extend_from_slice(format!("tree {}\n", self.tree_hash).as_bytes());
         for parent in &self.parents {
             bytes.extend_from_slice(format!("parent {}\n", parent).as_bytes());
         }
-        bytes.extend_from_slice(format!("author {}\n", self.author).as_bytes());
-        bytes.extend_from_slice(format!("committer {}\n", self.committer).as_bytes());
+        write_header(&mut bytes, "author", &self.author);
+        write_header(&mut bytes, "committer", &self.committer);
+        if let Some(encoding) = &self.encoding {
+            write_header(&mut bytes, "encoding", encoding.as_bytes());
+        }
+        for (key, value) in &self.extra_headers {
+            write_header(&mut bytes, key, value);
+        }
+        if let Some(gpgsig) = &self.gpgsig {
+            write_header(&mut bytes, "gpgsig", gpgsig);
+        }
         bytes.push(b'\n');
-        bytes.extend_from_slice(self.message.as_bytes());
+        bytes.extend_from_slice(&self.message);
         bytes
     }
 }
 
+/// Lines that start a signature block at the end of a tag message.
+const SIGNATURE_MARKERS: [&[u8]; 2] = [
+    b"-----BEGIN PGP SIGNATURE-----",
+    b"-----BEGIN SSH SIGNATURE-----",
+];
+
+impl Tag {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let (fields, body) = parse_headers(data)?;
+        let mut object = None;
+        let mut kind = None;
+        let mut tag = None;
+        let mut tagger = None;
+        let mut extra_headers = Vec::new();
+        for (key, value) in fields {
+            match key.as_str() {
+                "object" if object.is_none() => object = Some(header_text(value, "object")?),
+                "type" if kind.is_none() => kind = Some(header_text(value, "type")?.parse()?),
+                "tag" if tag.is_none() => tag = Some(header_text(value, "tag")?),
+                "tagger" if tagger.is_none() => tagger = Some(value),
+                _ => extra_headers.push((key, value)),
+            }
+        }
+
+        // The signature, if any, is the trailing block that starts on its own line.
+        let signature_start = SIGNATURE_MARKERS
+            .iter()
+            .filter_map(|marker| {
+                if body.starts_with(marker) {
+                    Some(0)
+                } else {
+                    body.windows(marker.len() + 1)
+                        .position(|window| window[0] == b'\n' && &window[1..] == *marker)
+                        .map(|pos| pos + 1)
+                }
+            })
+            .min();
+        let (message, signature) = match signature_start {
+            Some(pos) => (&body[..pos], Some(body[pos..].to_vec())),
+            None => (body, None),
+        };
+
+        Ok(Tag {
+            object: object.context("Invalid tag: missing object")?,
+            kind: kind.context("Invalid tag: missing type")?,
+            tag: tag.context("Invalid tag: missing tag name")?,
+            tagger,
+            extra_headers,
+            message: message.to_vec(),
+            signature,
+        })
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        // Format is:
+        // object <hash>\n
+        // type <object type>\n
+        // tag <name>\n
+        // tagger <tagger_info>\n  (optional)
+        // extra headers (optional)
+        // \n
+        // <message><signature>
+        bytes.extend_from_slice(format!("object {}\n", self.object).as_bytes());
+        bytes.extend_from_slice(format!("type {}\n", self.kind).as_bytes());
+        bytes.extend_from_slice(format!("tag {}\n", self.tag).as_bytes());
+        if let Some(tagger) = &self.tagger {
+            write_header(&mut bytes, "tagger", tagger);
+        }
+        for (key, value) in &self.extra_headers {
+            write_header(&mut bytes, key, value);
+        }
+        bytes.push(b'\n');
+        bytes.extend_from_slice(&self.message);
+        if let Some(signature) = &self.signature {
+            bytes.extend_from_slice(signature);
+        }
+        bytes
+    }
+}
+
+/// Header names and values of a commit or tag, in order.
+type Headers = Vec<(String, Vec<u8>)>;
+
+/// Splits a commit or tag into its header fields and the message after the
+/// first blank line. Header values may span several lines, with continuation
+/// lines starting with a single space (this is how `gpgsig` and `mergetag`
+/// are stored); the value keeps the newlines without the spaces.
+fn parse_headers(data: &[u8]) -> Result<(Headers, &[u8])> {
+    let (mut headers, message) = match data.windows(2).position(|window| window == b"\n\n") {
+        Some(pos) => (&data[..pos], &data[pos + 2..]),
+        None => (data, &data[data.len()..]),
+    };
+    while let Some(rest) = headers.strip_suffix(b"\n") {
+        headers = rest;
+    }
+
+    let mut fields: Headers = Vec::new();
+    for line in headers.split(|&b| b == b'\n').filter(|line| !line.is_empty()) {
+        if let Some(continuation) = line.strip_prefix(b" ") {
+            let (_, value) = fields
+                .last_mut()
+                .context("Invalid object: continuation line without a header")?;
+            value.push(b'\n');
+            value.extend_from_slice(continuation);
+        } else {
+            let space = line.iter().position(|&b| b == b' ').unwrap_or(line.len());
+            let key = std::str::from_utf8(&line[..space]).context("Invalid object header name")?;
+            fields.push((key.to_string(), line[(space + 1).min(line.len())..].to_vec()));
+        }
+    }
+    Ok((fields, message))
+}
+
+/// The value of a header that has to be text, such as an object id.
+fn header_text(value: Vec<u8>, key: &str) -> Result<String> {
+    String::from_utf8(value).with_context(|| format!("Invalid {} header", key))
+}
+
+/// Writes a header line, continuing multi-line values with a leading space.
+fn write_header(bytes: &mut Vec<u8>, key: &str, value: &[u8]) {
+    bytes.extend_from_slice(key.as_bytes());
+    bytes.push(b' ');
+    for &byte in value {
+        bytes.push(byte);
+        if byte == b'\n' {
+            bytes.push(b' ');
+        }
+    }
+    bytes.push(b'\n');
+}
 
 impl fmt::Display for GitObject {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             GitObject::Blob(blob) => write!(f, "{}", String::from_utf8_lossy(&blob.content)),
             GitObject::Tree(tree) => {
-                // For a tree, "displaying" it means printing its entries, similar to `ls -l`.
+                // For a tree, "displaying" it means printing its entries the way
+                // `git ls-tree` does: `<mode> <type> <hash>\t<name>`.
                 for entry in &tree.entries {
                     writeln!(
                         f,
-                        "{} {} {}",
+                        "{:0>6} {} {}\t{}",
                         entry.mode,
+                        entry.kind_name(),
                         // Convert the raw hash bytes to a hex string for display.
-                        hex::encode(&entry.hash),
-                        entry.name
+                        hex::encode(entry.hash),
+                        String::from_utf8_lossy(&entry.name)
                     )?;
                 }
                 Ok(())
             }
             GitObject::Commit(commit) => {
-                // For a commit, "displaying" it means printing its metadata and message.
-                writeln!(f, "tree {}", commit.tree_hash)?;
-                for parent in &commit.parents {
-                    writeln!(f, "parent {}", parent)?;
-                }
-                writeln!(f, "author {}", commit.author)?;
-                writeln!(f, "committer {}", commit.committer)?;
-                writeln!(f, "")?; // Blank line
-                writeln!(f, "{}", commit.message)?;
-                Ok(())
+                // A commit is displayed verbatim, exactly as it is stored.
+                write!(f, "{}", String::from_utf8_lossy(&commit.as_bytes()))
             }
+            GitObject::Tag(tag) => write!(f, "{}", String::from_utf8_lossy(&tag.as_bytes())),
         }
     }
-}
\ No newline at end of file
+}
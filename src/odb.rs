@@ -0,0 +1,223 @@
+// src/odb.rs
+
+use crate::objects::{self, GitObject, ObjectKind, Oid};
+use crate::pack::Pack;
+use anyhow::{bail, Context, Result};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Used to give every temporary object file written by this process a unique name.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The object database. Every command reads and writes objects through this type,
+/// so it is the single place where storage backends are plugged in. Objects are
+/// looked up as loose files first and then in each packfile.
+#[derive(Debug)]
+pub struct ObjectStore {
+    objects_dir: PathBuf,
+    packs: Vec<Pack>,
+}
+
+impl ObjectStore {
+    /// Opens the object database rooted at `objects_dir` (usually `.git/objects`),
+    /// loading the index of every pack in `objects/pack`.
+    pub fn new(objects_dir: impl Into<PathBuf>) -> Result<Self> {
+        let objects_dir = objects_dir.into();
+        let mut packs = Vec::new();
+        let pack_dir = objects_dir.join("pack");
+        if pack_dir.is_dir() {
+            let mut idx_paths = Vec::new();
+            for entry in fs::read_dir(&pack_dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "idx")
+                    && path.with_extension("pack").is_file()
+                {
+                    idx_paths.push(path);
+                }
+            }
+            idx_paths.sort();
+            for idx_path in idx_paths {
+                packs.push(Pack::open(&idx_path)?);
+            }
+        }
+        Ok(ObjectStore { objects_dir, packs })
+    }
+
+    pub fn packs(&self) -> &[Pack] {
+        &self.packs
+    }
+
+    pub fn objects_dir(&self) -> &Path {
+        &self.objects_dir
+    }
+
+    /// Reads and decodes the object with the given id.
+    pub fn read(&self, oid: &Oid) -> Result<GitObject> {
+        let (kind, content) = self.read_raw(oid)?;
+        GitObject::parse(kind, &content)
+    }
+
+    /// Reads an object and returns its type together with its undecoded content.
+    pub fn read_raw(&self, oid: &Oid) -> Result<(ObjectKind, Vec<u8>)> {
+        if !self.loose_path(oid).is_file() {
+            for pack in &self.packs {
+                if let Some(object) = pack.read(oid, self)? {
+                    return Ok(object);
+                }
+            }
+        }
+
+        let data = self.read_loose(oid)?;
+        let (kind, size, header_len) = objects::parse_header(&data)
+            .with_context(|| format!("Corrupt object {}", hex::encode(oid)))?;
+        let content = data[header_len..].to_vec();
+        if content.len() != size {
+            bail!(
+                "Object {} has size {} but its header says {}",
+                hex::encode(oid),
+                content.len(),
+                size
+            );
+        }
+        Ok((kind, content))
+    }
+
+    /// Reads only the type and size of an object.
+    pub fn header(&self, oid: &Oid) -> Result<(ObjectKind, usize)> {
+        let path = self.loose_path(oid);
+        if !path.is_file() && self.packs.iter().any(|pack| pack.contains(oid)) {
+            // Finding the size of a deltified object means resolving the delta.
+            let (kind, content) = self.read_raw(oid)?;
+            return Ok((kind, content.len()));
+        }
+        let file = fs::File::open(&path)
+            .with_context(|| format!("Object {} not found", hex::encode(oid)))?;
+
+        // The header is tiny, so only inflate as much as we need to find the NUL.
+        let mut decoder = ZlibDecoder::new(file);
+        let mut header = Vec::new();
+        let mut byte = [0u8; 1];
+        loop {
+            decoder.read_exact(&mut byte)?;
+            header.push(byte[0]);
+            if byte[0] == 0 {
+                break;
+            }
+            if header.len() > 32 {
+                bail!("Corrupt object header in {}", hex::encode(oid));
+            }
+        }
+        let (kind, size, _) = objects::parse_header(&header)?;
+        Ok((kind, size))
+    }
+
+    /// Returns true if the object is present in the database.
+    pub fn exists(&self, oid: &Oid) -> bool {
+        self.loose_path(oid).is_file() || self.packs.iter().any(|pack| pack.contains(oid))
+    }
+
+    /// Finds every object whose hex id starts with `prefix`, which must be at
+    /// least two lowercase hex digits. Used to expand abbreviated hashes.
+    pub fn find_by_prefix(&self, prefix: &str) -> Result<Vec<Oid>> {
+        let mut found = BTreeSet::new();
+
+        let dir = self.objects_dir.join(&prefix[..2]);
+        if dir.is_dir() {
+            for entry in fs::read_dir(&dir)? {
+                let hex = format!("{}{}", &prefix[..2], entry?.file_name().to_string_lossy());
+                if hex.starts_with(prefix)
+                    && let Ok(oid) = objects::parse_oid(&hex)
+                {
+                    found.insert(oid);
+                }
+            }
+        }
+
+        // Pack indexes are sorted, and hex order is byte order.
+        for pack in &self.packs {
+            let oids = pack.index.oids();
+            let start = oids.partition_point(|oid| hex::encode(oid).as_str() < prefix);
+            found.extend(
+                oids[start..]
+                    .iter()
+                    .take_while(|oid| hex::encode(oid).starts_with(prefix)),
+            );
+        }
+        Ok(found.into_iter().collect())
+    }
+
+    /// Serializes and stores an object, returning its id.
+    pub fn write(&self, object: &GitObject) -> Result<Oid> {
+        self.write_raw(object.kind(), &object.content())
+    }
+
+    /// Stores already-serialized object content of the given type.
+    pub fn write_raw(&self, kind: ObjectKind, content: &[u8]) -> Result<Oid> {
+        let oid = hash_object(kind, content);
+        // Objects are immutable, so an existing file already holds these bytes.
+        if self.exists(&oid) {
+            return Ok(oid);
+        }
+
+        let path = self.loose_path(&oid);
+        let dir = path.parent().expect("loose object paths have a parent");
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create object directory: {:?}", dir))?;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(format!("{} {}\0", kind, content.len()).as_bytes())?;
+        encoder.write_all(content)?;
+        let compressed = encoder.finish()?;
+
+        // Write to a temporary file first and rename it into place, so a crash can
+        // never leave a truncated object behind under its final name.
+        let temp_path = dir.join(format!(
+            "tmp_obj_{}_{}",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp_path, &compressed)
+            .with_context(|| format!("Failed to write object file: {:?}", temp_path))?;
+        if let Err(err) = fs::rename(&temp_path, &path) {
+            let _ = fs::remove_file(&temp_path);
+            // Another process may have won the race to write the same object.
+            if !path.is_file() {
+                return Err(err)
+                    .with_context(|| format!("Failed to write object file: {:?}", path));
+            }
+        }
+        Ok(oid)
+    }
+
+    fn read_loose(&self, oid: &Oid) -> Result<Vec<u8>> {
+        let path = self.loose_path(oid);
+        let compressed = fs::read(&path)
+            .with_context(|| format!("Object {} not found", hex::encode(oid)))?;
+        let mut decoder = ZlibDecoder::new(&compressed[..]);
+        let mut data = Vec::new();
+        decoder
+            .read_to_end(&mut data)
+            .with_context(|| format!("Failed to inflate object file: {:?}", path))?;
+        Ok(data)
+    }
+
+    fn loose_path(&self, oid: &Oid) -> PathBuf {
+        let hex = hex::encode(oid);
+        self.objects_dir.join(&hex[0..2]).join(&hex[2..])
+    }
+}
+
+/// Computes the id an object would have, without storing it.
+pub fn hash_object(kind: ObjectKind, content: &[u8]) -> Oid {
+    use sha1::{Digest, Sha1};
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{} {}\0", kind, content.len()).as_bytes());
+    hasher.update(content);
+    hasher.finalize().into()
+}
@@ -0,0 +1,291 @@
+// src/pack.rs
+
+use crate::delta;
+use crate::objects::{ObjectKind, Oid};
+use crate::odb::ObjectStore;
+use anyhow::{bail, Context, Result};
+use byteorder::{BigEndian, ReadBytesExt};
+use flate2::bufread::ZlibDecoder;
+use sha1::{Digest, Sha1};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// Object type numbers used in pack entry headers.
+pub const OBJ_COMMIT: u8 = 1;
+pub const OBJ_TREE: u8 = 2;
+pub const OBJ_BLOB: u8 = 3;
+pub const OBJ_TAG: u8 = 4;
+pub const OBJ_OFS_DELTA: u8 = 6;
+pub const OBJ_REF_DELTA: u8 = 7;
+
+/// Delta chains longer than this are treated as corrupt rather than followed.
+const MAX_DELTA_DEPTH: usize = 4096;
+
+/// A parsed version 2 `.idx` file: a sorted list of object ids with the
+/// offset of each object in the matching `.pack`.
+#[derive(Debug)]
+pub struct PackIndex {
+    fanout: [u32; 256],
+    oids: Vec<Oid>,
+    crcs: Vec<u32>,
+    offsets: Vec<u64>,
+}
+
+impl PackIndex {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let data = fs::read(path).with_context(|| format!("Failed to read pack index: {:?}", path))?;
+        Self::parse(&data).with_context(|| format!("Invalid pack index: {:?}", path))
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        // 1. Header: magic "\377tOc" and version.
+        if data.len() < 8 + 256 * 4 + 40 {
+            bail!("File is too short");
+        }
+        let checksum_start = data.len() - 20;
+        let checksum: [u8; 20] = Sha1::digest(&data[..checksum_start]).into();
+        if checksum[..] != data[checksum_start..] {
+            bail!("Checksum mismatch");
+        }
+
+        let mut reader = &data[..checksum_start];
+        if reader.read_u32::<BigEndian>()? != 0xff744f63 {
+            bail!("Only version 2 pack indexes are supported");
+        }
+        let version = reader.read_u32::<BigEndian>()?;
+        if version != 2 {
+            bail!("Unsupported pack index version: {}", version);
+        }
+
+        // 2. The fanout table: entry i counts objects whose first byte is <= i.
+        let mut fanout = [0u32; 256];
+        for slot in fanout.iter_mut() {
+            *slot = reader.read_u32::<BigEndian>()?;
+        }
+        let count = fanout[255] as usize;
+
+        // 3. Sorted object ids, then CRC32s, then 32-bit offsets.
+        let mut oids = Vec::with_capacity(count);
+        for _ in 0..count {
+            let mut oid = [0u8; 20];
+            reader.read_exact(&mut oid)?;
+            oids.push(oid);
+        }
+        let mut crcs = Vec::with_capacity(count);
+        for _ in 0..count {
+            crcs.push(reader.read_u32::<BigEndian>()?);
+        }
+        let mut small_offsets = Vec::with_capacity(count);
+        for _ in 0..count {
+            small_offsets.push(reader.read_u32::<BigEndian>()?);
+        }
+
+        // 4. Offsets with the high bit set index into a table of 64-bit offsets
+        // that follows, used for packs larger than 2GiB.
+        let large_count = small_offsets.iter().filter(|&&o| o & 0x8000_0000 != 0).count();
+        let mut large_offsets = Vec::with_capacity(large_count);
+        for _ in 0..large_count {
+            large_offsets.push(reader.read_u64::<BigEndian>()?);
+        }
+        let offsets = small_offsets
+            .into_iter()
+            .map(|offset| {
+                if offset & 0x8000_0000 != 0 {
+                    large_offsets
+                        .get((offset & 0x7fff_ffff) as usize)
+                        .copied()
+                        .context("Large offset index out of range")
+                } else {
+                    Ok(offset as u64)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // 5. What remains is the checksum of the pack itself.
+        if reader.len() != 20 {
+            bail!("Unexpected trailing data");
+        }
+
+        Ok(PackIndex {
+            fanout,
+            oids,
+            crcs,
+            offsets,
+        })
+    }
+
+    /// Returns the pack offset of the object, if this index contains it.
+    pub fn find(&self, oid: &Oid) -> Option<u64> {
+        let first = oid[0] as usize;
+        let start = if first == 0 { 0 } else { self.fanout[first - 1] as usize };
+        let end = self.fanout[first] as usize;
+        self.oids[start..end]
+            .binary_search(oid)
+            .ok()
+            .map(|i| self.offsets[start + i])
+    }
+
+    pub fn len(&self) -> usize {
+        self.oids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.oids.is_empty()
+    }
+
+    /// The object ids in this index, in sorted order.
+    pub fn oids(&self) -> &[Oid] {
+        &self.oids
+    }
+
+    /// The CRC32 of the packed (compressed) data of the i-th object.
+    pub fn crc32(&self, i: usize) -> u32 {
+        self.crcs[i]
+    }
+}
+
+/// A packfile together with its index.
+#[derive(Debug)]
+pub struct Pack {
+    pub index: PackIndex,
+    pack_path: PathBuf,
+}
+
+impl Pack {
+    /// Opens `pack-<hash>.idx` and the `pack-<hash>.pack` next to it.
+    pub fn open(idx_path: &Path) -> Result<Self> {
+        let index = PackIndex::from_path(idx_path)?;
+        let pack_path = idx_path.with_extension("pack");
+
+        let mut file = File::open(&pack_path)
+            .with_context(|| format!("Failed to open packfile: {:?}", pack_path))?;
+        let mut signature = [0u8; 4];
+        file.read_exact(&mut signature)?;
+        if &signature != b"PACK" {
+            bail!("Invalid packfile signature: {:?}", pack_path);
+        }
+        let version = file.read_u32::<BigEndian>()?;
+        if version != 2 && version != 3 {
+            bail!("Unsupported packfile version {}: {:?}", version, pack_path);
+        }
+        let count = file.read_u32::<BigEndian>()? as usize;
+        if count != index.len() {
+            bail!(
+                "Packfile {:?} has {} objects but its index lists {}",
+                pack_path,
+                count,
+                index.len()
+            );
+        }
+
+        Ok(Pack { index, pack_path })
+    }
+
+    pub fn pack_path(&self) -> &Path {
+        &self.pack_path
+    }
+
+    pub fn contains(&self, oid: &Oid) -> bool {
+        self.index.find(oid).is_some()
+    }
+
+    /// Reads an object out of the pack, resolving delta chains. `store` is used
+    /// to look up `REF_DELTA` bases that live outside this pack.
+    pub fn read(&self, oid: &Oid, store: &ObjectStore) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+        let Some(offset) = self.index.find(oid) else {
+            return Ok(None);
+        };
+        let mut file = BufReader::new(File::open(&self.pack_path)?);
+        self.read_at(&mut file, offset, store, 0)
+            .with_context(|| format!("Failed to read {} from {:?}", hex::encode(oid), self.pack_path))
+            .map(Some)
+    }
+
+    fn read_at(
+        &self,
+        file: &mut BufReader<File>,
+        offset: u64,
+        store: &ObjectStore,
+        depth: usize,
+    ) -> Result<(ObjectKind, Vec<u8>)> {
+        if depth > MAX_DELTA_DEPTH {
+            bail!("Delta chain is too deep");
+        }
+
+        file.seek(SeekFrom::Start(offset))?;
+        let (type_id, size) = read_entry_header(file)?;
+
+        match type_id {
+            OBJ_OFS_DELTA => {
+                let distance = read_ofs_distance(file)?;
+                let delta = inflate(file, size)?;
+                let base_offset = offset
+                    .checked_sub(distance)
+                    .context("Delta base offset points before the start of the pack")?;
+                let (kind, base) = self.read_at(file, base_offset, store, depth + 1)?;
+                Ok((kind, delta::apply_delta(&base, &delta)?))
+            }
+            OBJ_REF_DELTA => {
+                let mut base_oid = [0u8; 20];
+                file.read_exact(&mut base_oid)?;
+                let delta = inflate(file, size)?;
+                let (kind, base) = match self.index.find(&base_oid) {
+                    Some(base_offset) => self.read_at(file, base_offset, store, depth + 1)?,
+                    None => store.read_raw(&base_oid)?,
+                };
+                Ok((kind, delta::apply_delta(&base, &delta)?))
+            }
+            _ => Ok((kind_from_type_id(type_id)?, inflate(file, size)?)),
+        }
+    }
+}
+
+/// Maps a pack entry type number to the object type it stores.
+pub fn kind_from_type_id(type_id: u8) -> Result<ObjectKind> {
+    match type_id {
+        OBJ_COMMIT => Ok(ObjectKind::Commit),
+        OBJ_TREE => Ok(ObjectKind::Tree),
+        OBJ_BLOB => Ok(ObjectKind::Blob),
+        OBJ_TAG => Ok(ObjectKind::Tag),
+        _ => bail!("Invalid pack object type: {}", type_id),
+    }
+}
+
+/// Reads the variable-length entry header: 3 bits of type and the
+/// uncompressed size, 4 bits in the first byte and 7 in each following one.
+fn read_entry_header(reader: &mut impl Read) -> Result<(u8, usize)> {
+    let mut byte = reader.read_u8()?;
+    let type_id = (byte >> 4) & 0x7;
+    let mut size = (byte & 0x0f) as usize;
+    let mut shift = 4;
+    while byte & 0x80 != 0 {
+        byte = reader.read_u8()?;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+    }
+    Ok((type_id, size))
+}
+
+/// Reads the backwards distance to an `OFS_DELTA` base. Each continuation
+/// byte adds one before shifting, so there is exactly one encoding per value.
+fn read_ofs_distance(reader: &mut impl Read) -> Result<u64> {
+    let mut byte = reader.read_u8()?;
+    let mut distance = (byte & 0x7f) as u64;
+    while byte & 0x80 != 0 {
+        byte = reader.read_u8()?;
+        distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+    }
+    Ok(distance)
+}
+
+fn inflate(reader: &mut impl BufRead, size: usize) -> Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(size);
+    ZlibDecoder::new(reader)
+        .take(size as u64)
+        .read_to_end(&mut data)?;
+    if data.len() != size {
+        bail!("Pack entry is truncated");
+    }
+    Ok(data)
+}
@@ -0,0 +1,303 @@
+// src/pack_writer.rs
+
+use crate::delta;
+use crate::objects::{ObjectKind, Oid};
+use crate::odb::ObjectStore;
+use crate::pack::{OBJ_BLOB, OBJ_COMMIT, OBJ_OFS_DELTA, OBJ_TAG, OBJ_TREE};
+use anyhow::{Context, Result};
+use byteorder::{BigEndian, WriteBytesExt};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// How many preceding objects each object is compared against for a delta base.
+const WINDOW: usize = 10;
+/// Deltas are never chained deeper than this, matching git's default.
+const MAX_DEPTH: usize = 50;
+/// Objects smaller than this are not worth deltifying.
+const MIN_DELTA_SIZE: usize = 64;
+
+/// An object to be packed, with the path it was reached through (if any).
+/// The path is only a hint used to pair up likely delta bases.
+#[derive(Debug, Clone)]
+pub struct PackInput {
+    pub oid: Oid,
+    pub path: Option<String>,
+}
+
+struct Entry {
+    oid: Oid,
+    kind: ObjectKind,
+    data: Vec<u8>,
+    name_hash: u32,
+    // Index of the base entry and the delta against it, when one was chosen.
+    delta: Option<(usize, Vec<u8>)>,
+    depth: usize,
+}
+
+/// The files produced by `write_pack`.
+#[derive(Debug)]
+pub struct WrittenPack {
+    pub checksum: Oid,
+    pub pack_path: PathBuf,
+    pub idx_path: PathBuf,
+}
+
+/// Writes the given objects to `<prefix>-<checksum>.pack` and a matching
+/// version 2 `.idx`, deltifying objects against each other along the way.
+pub fn write_pack(store: &ObjectStore, inputs: &[PackInput], prefix: &Path) -> Result<WrittenPack> {
+    // 1. Load every object once; duplicates in the input are dropped.
+    let mut seen = HashMap::new();
+    let mut entries = Vec::new();
+    for input in inputs {
+        if seen.insert(input.oid, entries.len()).is_some() {
+            continue;
+        }
+        let (kind, data) = store.read_raw(&input.oid)?;
+        entries.push(Entry {
+            oid: input.oid,
+            kind,
+            data,
+            name_hash: input.path.as_deref().map_or(0, name_hash),
+            delta: None,
+            depth: 0,
+        });
+    }
+
+    // 2. Choose delta bases.
+    find_deltas(&mut entries);
+
+    // 3. Write the pack, making sure every base is written before the
+    // objects that are deltified against it.
+    let mut pack = Vec::new();
+    pack.extend_from_slice(b"PACK");
+    pack.write_u32::<BigEndian>(2)?;
+    pack.write_u32::<BigEndian>(entries.len() as u32)?;
+
+    let mut offsets: Vec<Option<u64>> = vec![None; entries.len()];
+    let mut crcs = vec![0u32; entries.len()];
+    for i in 0..entries.len() {
+        let mut chain = vec![i];
+        while let Some((base, _)) = &entries[*chain.last().unwrap()].delta {
+            if offsets[*base].is_some() {
+                break;
+            }
+            chain.push(*base);
+        }
+        for &entry_index in chain.iter().rev() {
+            if offsets[entry_index].is_none() {
+                let offset = pack.len() as u64;
+                crcs[entry_index] = write_entry(&mut pack, &entries, entry_index, &offsets)?;
+                offsets[entry_index] = Some(offset);
+            }
+        }
+    }
+    let checksum: Oid = Sha1::digest(&pack).into();
+    pack.extend_from_slice(&checksum);
+
+    // 4. Build the index, sorted by object id.
+    let mut sorted: Vec<(Oid, u64, u32)> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| (entry.oid, offsets[i].expect("every entry was written"), crcs[i]))
+        .collect();
+    sorted.sort();
+    let idx = build_index(&sorted, &checksum)?;
+
+    // 5. Write both files via temporary names. The .pack goes first so a
+    // reader never sees an index without its pack.
+    let name = format!(
+        "{}-{}",
+        prefix.file_name().context("Invalid pack prefix")?.to_string_lossy(),
+        hex::encode(checksum)
+    );
+    let dir = prefix.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let pack_path = dir.join(format!("{}.pack", name));
+    let idx_path = dir.join(format!("{}.idx", name));
+    write_atomically(&pack_path, &pack)?;
+    write_atomically(&idx_path, &idx)?;
+
+    Ok(WrittenPack {
+        checksum,
+        pack_path,
+        idx_path,
+    })
+}
+
+/// Picks a delta base for each object using a sliding window over the objects
+/// sorted by type, path hash and decreasing size, the same heuristic git uses:
+/// versions of the same file end up next to each other, largest first.
+fn find_deltas(entries: &mut [Entry]) {
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&entries[a], &entries[b]);
+        type_id(a.kind)
+            .cmp(&type_id(b.kind))
+            .then(a.name_hash.cmp(&b.name_hash))
+            .then(b.data.len().cmp(&a.data.len()))
+    });
+
+    for (position, &target) in order.iter().enumerate() {
+        let target_len = entries[target].data.len();
+        if target_len < MIN_DELTA_SIZE {
+            continue;
+        }
+        let mut best: Option<(usize, Vec<u8>)> = None;
+        for &base in order[position.saturating_sub(WINDOW)..position].iter().rev() {
+            let base_entry = &entries[base];
+            if base_entry.kind != entries[target].kind
+                || base_entry.depth >= MAX_DEPTH
+                || target_len < base_entry.data.len() / 32
+            {
+                continue;
+            }
+            // A delta has to save a meaningful amount of space to be worth it,
+            // and deeper chains must save more since they cost more to read.
+            let limit = best.as_ref().map_or_else(
+                || (target_len / 2).saturating_sub(20) * (MAX_DEPTH - base_entry.depth) / MAX_DEPTH,
+                |(_, delta)| delta.len(),
+            );
+            // Only growth counts: a larger base can still hold everything the
+            // target needs, which is how files that grow over time get deltified.
+            if target_len.saturating_sub(base_entry.data.len()) >= limit {
+                continue;
+            }
+            let delta = delta::create_delta(&base_entry.data, &entries[target].data);
+            if delta.len() < limit {
+                best = Some((base, delta));
+            }
+        }
+        if let Some((base, delta)) = best {
+            entries[target].depth = entries[base].depth + 1;
+            entries[target].delta = Some((base, delta));
+        }
+    }
+}
+
+/// Appends one pack entry and returns the CRC32 of its bytes.
+fn write_entry(
+    pack: &mut Vec<u8>,
+    entries: &[Entry],
+    index: usize,
+    offsets: &[Option<u64>],
+) -> Result<u32> {
+    let start = pack.len();
+    let entry = &entries[index];
+    let data = match &entry.delta {
+        Some((base, delta)) => {
+            write_entry_header(pack, OBJ_OFS_DELTA, delta.len());
+            let base_offset = offsets[*base].expect("bases are written first");
+            write_ofs_distance(pack, start as u64 - base_offset);
+            delta
+        }
+        None => {
+            write_entry_header(pack, type_id(entry.kind), entry.data.len());
+            &entry.data
+        }
+    };
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    pack.extend_from_slice(&encoder.finish()?);
+    Ok(crc32fast::hash(&pack[start..]))
+}
+
+fn write_entry_header(pack: &mut Vec<u8>, type_id: u8, size: usize) {
+    let mut byte = (type_id << 4) | (size & 0x0f) as u8;
+    let mut size = size >> 4;
+    while size > 0 {
+        pack.push(byte | 0x80);
+        byte = (size & 0x7f) as u8;
+        size >>= 7;
+    }
+    pack.push(byte);
+}
+
+/// The inverse of the reader's offset decoding: each continuation byte stores
+/// one less than its value, so the bytes are produced from the end.
+fn write_ofs_distance(pack: &mut Vec<u8>, mut distance: u64) {
+    let mut bytes = vec![(distance & 0x7f) as u8];
+    distance >>= 7;
+    while distance > 0 {
+        distance -= 1;
+        bytes.push(0x80 | (distance & 0x7f) as u8);
+        distance >>= 7;
+    }
+    bytes.reverse();
+    pack.extend_from_slice(&bytes);
+}
+
+fn build_index(sorted: &[(Oid, u64, u32)], pack_checksum: &Oid) -> Result<Vec<u8>> {
+    let mut idx = Vec::new();
+    idx.write_u32::<BigEndian>(0xff744f63)?; // "\377tOc"
+    idx.write_u32::<BigEndian>(2)?;
+
+    let mut fanout = [0u32; 256];
+    for (oid, _, _) in sorted {
+        fanout[oid[0] as usize] += 1;
+    }
+    let mut total = 0;
+    for count in fanout.iter_mut() {
+        total += *count;
+        *count = total;
+    }
+    for count in fanout {
+        idx.write_u32::<BigEndian>(count)?;
+    }
+
+    for (oid, _, _) in sorted {
+        idx.extend_from_slice(oid);
+    }
+    for (_, _, crc) in sorted {
+        idx.write_u32::<BigEndian>(*crc)?;
+    }
+    let mut large_offsets = Vec::new();
+    for (_, offset, _) in sorted {
+        if *offset < 0x8000_0000 {
+            idx.write_u32::<BigEndian>(*offset as u32)?;
+        } else {
+            idx.write_u32::<BigEndian>(0x8000_0000 | large_offsets.len() as u32)?;
+            large_offsets.push(*offset);
+        }
+    }
+    for offset in large_offsets {
+        idx.write_u64::<BigEndian>(offset)?;
+    }
+
+    idx.extend_from_slice(pack_checksum);
+    let checksum: Oid = Sha1::digest(&idx).into();
+    idx.extend_from_slice(&checksum);
+    Ok(idx)
+}
+
+fn write_atomically(path: &Path, data: &[u8]) -> Result<()> {
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, data)
+        .with_context(|| format!("Failed to write pack file: {:?}", temp_path))?;
+    fs::rename(&temp_path, path)
+        .with_context(|| format!("Failed to write pack file: {:?}", path))?;
+    Ok(())
+}
+
+fn type_id(kind: ObjectKind) -> u8 {
+    match kind {
+        ObjectKind::Commit => OBJ_COMMIT,
+        ObjectKind::Tree => OBJ_TREE,
+        ObjectKind::Blob => OBJ_BLOB,
+        ObjectKind::Tag => OBJ_TAG,
+    }
+}
+
+/// git's path hash: mostly determined by the last characters of the path, so
+/// files with the same name (or extension) in different directories sort together.
+fn name_hash(path: &str) -> u32 {
+    let mut hash = 0u32;
+    for c in path.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        hash = (hash >> 2).wrapping_add((c as u32) << 24);
+    }
+    hash
+}
@@ -0,0 +1,193 @@
+// src/reflog.rs
+
+use crate::config::Config;
+use crate::date;
+use crate::identity::{self, Role};
+use crate::lockfile::LockFile;
+use crate::objects::{self, Oid};
+use anyhow::{Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const ZERO_OID: Oid = [0; 20];
+
+/// One line of a reflog: the ref moved from `old` to `new`, by whom, when and why.
+#[derive(Debug, Clone)]
+pub struct ReflogEntry {
+    pub old: Oid,
+    pub new: Oid,
+    /// `Name <email>`
+    pub identity: String,
+    pub timestamp: i64,
+    /// The committer's timezone, e.g. `+0100`.
+    pub timezone: String,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    /// Parses `<old> <new> <name> <<email>> <timestamp> <tz>\t<message>`.
+    pub fn parse(line: &str) -> Result<Self> {
+        let (header, message) = line.split_once('\t').unwrap_or((line, ""));
+        let invalid = || format!("Invalid reflog line: {}", line);
+        let (old, rest) = header.split_once(' ').with_context(invalid)?;
+        let (new, rest) = rest.split_once(' ').with_context(invalid)?;
+        let (identity, date) = rest.rsplit_once("> ").with_context(invalid)?;
+        let (timestamp, timezone) = date.split_once(' ').with_context(invalid)?;
+        Ok(ReflogEntry {
+            old: objects::parse_oid(old)?,
+            new: objects::parse_oid(new)?,
+            identity: format!("{}>", identity),
+            timestamp: timestamp.parse().with_context(invalid)?,
+            timezone: timezone.to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} {} {} {} {}\t{}\n",
+            hex::encode(self.old),
+            hex::encode(self.new),
+            self.identity,
+            self.timestamp,
+            self.timezone,
+            self.message
+        )
+    }
+}
+
+pub fn log_path(git_dir: &Path, name: &str) -> PathBuf {
+    git_dir.join("logs").join(name)
+}
+
+/// Whether updates to `name` are logged: always if it already has a reflog,
+/// and by default for HEAD and branches, like git's `core.logAllRefUpdates`.
+pub fn should_log(git_dir: &Path, name: &str) -> bool {
+    name == "HEAD"
+        || ["refs/heads/", "refs/remotes/", "refs/notes/"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        || log_path(git_dir, name).is_file()
+}
+
+/// Records that `name` moved from `old` to `new`. Callers hold the ref's lock.
+pub fn append(git_dir: &Path, name: &str, old: Option<Oid>, new: Option<Oid>, message: &str) -> Result<()> {
+    let config = Config::load(Some(git_dir))?;
+    // Dated like a commit made now, so GIT_COMMITTER_DATE applies here too.
+    let (timestamp, offset) = identity::when(Role::Committer)?;
+    let entry = ReflogEntry {
+        old: old.unwrap_or(ZERO_OID),
+        new: new.unwrap_or(ZERO_OID),
+        identity: identity::reflog_identity(&config),
+        timestamp,
+        timezone: date::format_offset(offset),
+        // The message is a single line.
+        message: message.lines().next().unwrap_or("").to_string(),
+    };
+
+    let path = log_path(git_dir, name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open reflog {:?}", path))?;
+    file.write_all(entry.to_line().as_bytes())?;
+    Ok(())
+}
+
+/// Reads a ref's reflog, oldest entry first. A ref without one has an empty log.
+pub fn read(git_dir: &Path, name: &str) -> Result<Vec<ReflogEntry>> {
+    let path = log_path(git_dir, name);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    fs::read_to_string(&path)?
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(ReflogEntry::parse)
+        .collect()
+}
+
+/// Replaces a ref's reflog, holding the ref's lock so no update appends to it
+/// meanwhile.
+pub fn rewrite(git_dir: &Path, name: &str, entries: &[ReflogEntry]) -> Result<()> {
+    let _ref_lock = LockFile::acquire(&git_dir.join(name))?;
+    let mut lock = LockFile::acquire(&log_path(git_dir, name))?;
+    let content: String = entries.iter().map(ReflogEntry::to_line).collect();
+    lock.write_all(content.as_bytes())?;
+    lock.commit()
+}
+
+/// Removes a ref's reflog, if it has one.
+pub fn delete(git_dir: &Path, name: &str) -> Result<()> {
+    let path = log_path(git_dir, name);
+    if path.is_file() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Every ref that has a reflog, e.g. `HEAD` and `refs/heads/main`.
+pub fn list(git_dir: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let logs = git_dir.join("logs");
+    if logs.join("HEAD").is_file() {
+        names.push("HEAD".to_string());
+    }
+    collect(&logs.join("refs"), "refs/", &mut names)?;
+    names.sort();
+    Ok(names)
+}
+
+fn collect(dir: &Path, prefix: &str, names: &mut Vec<String>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            collect(&entry.path(), &format!("{}/", name), names)?;
+        } else if !name.ends_with(".lock") {
+            names.push(name);
+        }
+    }
+    Ok(())
+}
+
+/// Resolves `<ref>@{<n>}` (`n` moves back) or `<ref>@{<date>}` (where the ref
+/// was at that time) through the ref's reflog.
+pub fn lookup(git_dir: &Path, name: &str, selector: &str) -> Result<Oid> {
+    let entries = read(git_dir, name)?;
+    if entries.is_empty() {
+        anyhow::bail!("Log for '{}' is empty", name);
+    }
+
+    if let Ok(n) = selector.parse::<usize>() {
+        let entry = entries
+            .len()
+            .checked_sub(n + 1)
+            .map(|i| &entries[i])
+            .with_context(|| format!("Log for '{}' only has {} entries", name, entries.len()))?;
+        return Ok(entry.new);
+    }
+
+    let time = date::parse_approxidate(selector, date::now())?;
+    // The newest entry at or before `time` says where the ref was then. Before
+    // the first entry, the ref was at that entry's old value.
+    match entries.iter().rev().find(|entry| entry.timestamp <= time) {
+        Some(entry) => Ok(entry.new),
+        None => {
+            let first = &entries[0];
+            if first.old == ZERO_OID {
+                Ok(first.new)
+            } else {
+                Ok(first.old)
+            }
+        }
+    }
+}
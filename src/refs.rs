@@ -0,0 +1,520 @@
+// src/refs.rs
+
+use crate::lockfile::LockFile;
+use crate::objects::{self, GitObject, Oid};
+use crate::odb::ObjectStore;
+use crate::reflog;
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Symbolic refs are followed at most this many levels deep, like git.
+const MAX_SYMREF_DEPTH: usize = 5;
+
+/// What a ref file holds: an object id, or the name of another ref.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefValue {
+    Direct(Oid),
+    Symbolic(String),
+}
+
+/// One line of `packed-refs`, with the peeled target for annotated tags.
+#[derive(Debug, Clone)]
+pub struct PackedRef {
+    pub oid: Oid,
+    pub peeled: Option<Oid>,
+}
+
+/// The contents of `.git/packed-refs`, keyed by ref name.
+#[derive(Debug, Default)]
+pub struct PackedRefs {
+    pub refs: BTreeMap<String, PackedRef>,
+}
+
+impl PackedRefs {
+    pub fn read(git_dir: &Path) -> Result<Self> {
+        let path = git_dir.join("packed-refs");
+        let mut packed = PackedRefs::default();
+        if !path.exists() {
+            return Ok(packed);
+        }
+
+        let content = fs::read_to_string(&path)?;
+        let mut last: Option<String> = None;
+        for line in content.lines() {
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            // `^<oid>` lines give the peeled target of the ref just before.
+            if let Some(peeled) = line.strip_prefix('^') {
+                let name = last.as_ref().context("Invalid packed-refs: peeled line without a ref")?;
+                if let Some(entry) = packed.refs.get_mut(name) {
+                    entry.peeled = Some(objects::parse_oid(peeled)?);
+                }
+                continue;
+            }
+            let (hash, name) = line.split_once(' ').context("Invalid packed-refs line")?;
+            packed.refs.insert(
+                name.to_string(),
+                PackedRef {
+                    oid: objects::parse_oid(hash)?,
+                    peeled: None,
+                },
+            );
+            last = Some(name.to_string());
+        }
+        Ok(packed)
+    }
+
+    fn serialize(&self) -> String {
+        let mut content = String::from("# pack-refs with: peeled fully-peeled sorted \n");
+        for (name, entry) in &self.refs {
+            content.push_str(&format!("{} {}\n", hex::encode(entry.oid), name));
+            if let Some(peeled) = entry.peeled {
+                content.push_str(&format!("^{}\n", hex::encode(peeled)));
+            }
+        }
+        content
+    }
+}
+
+/// Reads a ref without following it: the loose file if there is one,
+/// otherwise its `packed-refs` entry.
+pub fn read_ref(git_dir: &Path, name: &str) -> Result<Option<RefValue>> {
+    let path = git_dir.join(name);
+    if path.is_file() {
+        let content = fs::read_to_string(&path)?;
+        let content = content.trim();
+        if let Some(target) = content.strip_prefix("ref: ") {
+            return Ok(Some(RefValue::Symbolic(target.to_string())));
+        }
+        let oid = objects::parse_oid(content).with_context(|| format!("Invalid reference: {}", name))?;
+        return Ok(Some(RefValue::Direct(oid)));
+    }
+    Ok(PackedRefs::read(git_dir)?
+        .refs
+        .remove(name)
+        .map(|entry| RefValue::Direct(entry.oid)))
+}
+
+/// Follows symbolic refs starting at `name` and returns the name of the ref
+/// at the end of the chain with the id it holds (None if it doesn't exist yet,
+/// like the branch of a freshly initialized repository).
+pub fn resolve_ref_name(git_dir: &Path, name: &str) -> Result<(String, Option<Oid>)> {
+    let mut current = name.to_string();
+    let mut seen = HashSet::new();
+    loop {
+        if !seen.insert(current.clone()) || seen.len() > MAX_SYMREF_DEPTH + 1 {
+            bail!("Symbolic ref loop or chain too deep at {}", name);
+        }
+        match read_ref(git_dir, &current)? {
+            Some(RefValue::Symbolic(target)) => current = target,
+            Some(RefValue::Direct(oid)) => return Ok((current, Some(oid))),
+            None => return Ok((current, None)),
+        }
+    }
+}
+
+/// Resolves a ref to an object id, following symbolic refs.
+pub fn resolve_ref(git_dir: &Path, name: &str) -> Result<Option<Oid>> {
+    Ok(resolve_ref_name(git_dir, name)?.1)
+}
+
+/// Expands a short name the way git does, trying `<name>`, `refs/<name>`,
+/// `refs/tags/<name>`, `refs/heads/<name>`, `refs/remotes/<name>` and
+/// `refs/remotes/<name>/HEAD` in that order. Returns the first that exists.
+pub fn dwim_ref(git_dir: &Path, name: &str) -> Result<Option<String>> {
+    let candidates = [
+        name.to_string(),
+        format!("refs/{}", name),
+        format!("refs/tags/{}", name),
+        format!("refs/heads/{}", name),
+        format!("refs/remotes/{}", name),
+        format!("refs/remotes/{}/HEAD", name),
+    ];
+    for candidate in candidates {
+        // Outside refs/, only HEAD-like names are refs; `index` or `config` are not.
+        let is_ref_name = candidate.starts_with("refs/") || check_updatable_name(&candidate).is_ok();
+        if is_ref_name && read_ref(git_dir, &candidate)?.is_some() {
+            return Ok(Some(candidate));
+        }
+    }
+    Ok(None)
+}
+
+/// Writes a ref holding an object id. Symbolic refs are not followed.
+pub fn write_ref(git_dir: &Path, name: &str, oid: &Oid) -> Result<()> {
+    let mut transaction = RefTransaction::new();
+    transaction.set_deref(false);
+    transaction.update(name, *oid, None);
+    transaction.commit(git_dir)
+}
+
+/// Makes `name` a symbolic ref pointing at `target`.
+pub fn write_symbolic_ref(git_dir: &Path, name: &str, target: &str) -> Result<()> {
+    if !target.starts_with("refs/") {
+        bail!("Refusing to point {} outside of refs/: {}", name, target);
+    }
+    let mut lock = LockFile::acquire(&git_dir.join(name))?;
+    lock.write_all(format!("ref: {}\n", target).as_bytes())?;
+    lock.commit()
+}
+
+/// Deletes a ref, both its loose file and its `packed-refs` entry.
+pub fn delete_ref(git_dir: &Path, name: &str) -> Result<()> {
+    if read_ref(git_dir, name)?.is_none() {
+        bail!("No such ref: {}", name);
+    }
+    let mut transaction = RefTransaction::new();
+    transaction.set_deref(false);
+    transaction.delete(name, None);
+    transaction.commit(git_dir)
+}
+
+/// What a transaction does to one ref.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefChange {
+    Set(Oid),
+    Delete,
+    // Only checks the expected value.
+    Verify,
+}
+
+#[derive(Debug)]
+struct RefUpdate {
+    name: String,
+    change: RefChange,
+    // None skips the check, Some(None) requires that the ref doesn't exist.
+    expected: Option<Option<Oid>>,
+    deref: bool,
+}
+
+/// A set of ref updates that are applied together or not at all.
+///
+/// Committing takes `<ref>.lock` for every ref involved, then, with all the
+/// locks held, checks that each ref still holds the value the caller expected
+/// before writing anything. Two processes racing to move the same branch can't
+/// both succeed: one fails to lock, or finds the ref already moved.
+#[derive(Debug)]
+pub struct RefTransaction {
+    updates: Vec<RefUpdate>,
+    deref: bool,
+    message: String,
+}
+
+impl Default for RefTransaction {
+    fn default() -> Self {
+        RefTransaction {
+            updates: Vec::new(),
+            deref: true,
+            message: String::new(),
+        }
+    }
+}
+
+impl RefTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether updates queued from now on follow symbolic refs (the default),
+    /// changing the ref at the end of the chain rather than the symbolic ref.
+    pub fn set_deref(&mut self, deref: bool) {
+        self.deref = deref;
+    }
+
+    /// The reason recorded in the reflog of every ref this transaction moves,
+    /// e.g. `commit: Fix the build`.
+    pub fn set_message(&mut self, message: &str) {
+        self.message = message.to_string();
+    }
+
+    /// Points `name` at `new`. With `expected`, only if the ref currently
+    /// holds that value (`Some(None)`: only if it doesn't exist yet).
+    pub fn update(&mut self, name: &str, new: Oid, expected: Option<Option<Oid>>) {
+        self.push(name, RefChange::Set(new), expected);
+    }
+
+    /// Deletes `name`, optionally only if it currently holds `expected`.
+    pub fn delete(&mut self, name: &str, expected: Option<Oid>) {
+        self.push(name, RefChange::Delete, expected.map(Some));
+    }
+
+    /// Fails the transaction unless `name` holds `expected`.
+    pub fn verify(&mut self, name: &str, expected: Option<Oid>) {
+        self.push(name, RefChange::Verify, Some(expected));
+    }
+
+    fn push(&mut self, name: &str, change: RefChange, expected: Option<Option<Oid>>) {
+        self.updates.push(RefUpdate {
+            name: name.to_string(),
+            change,
+            expected,
+            deref: self.deref,
+        });
+    }
+
+    pub fn commit(&self, git_dir: &Path) -> Result<()> {
+        // 1. Work out which files are actually changing.
+        let mut targets = Vec::with_capacity(self.updates.len());
+        for update in &self.updates {
+            let name = if update.deref {
+                resolve_ref_name(git_dir, &update.name)?.0
+            } else {
+                update.name.clone()
+            };
+            check_updatable_name(&name)?;
+            targets.push((name, update));
+        }
+        targets.sort_by(|a, b| a.0.cmp(&b.0));
+        if let Some(pair) = targets.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            bail!("Multiple updates for ref '{}' not allowed", pair[0].0);
+        }
+
+        // 2. Lock everything. If any lock is taken, the ones we already hold
+        // are released when `locks` is dropped.
+        let mut locks = Vec::with_capacity(targets.len());
+        for (name, _) in &targets {
+            locks.push(
+                LockFile::acquire(&git_dir.join(name))
+                    .with_context(|| format!("cannot lock ref '{}'", name))?,
+            );
+        }
+
+        // 3. Nobody else can move these refs now, so check them.
+        let mut current_values = Vec::with_capacity(targets.len());
+        for (name, update) in &targets {
+            let current = match read_ref(git_dir, name)? {
+                Some(RefValue::Symbolic(_)) => resolve_ref(git_dir, name)?,
+                Some(RefValue::Direct(oid)) => Some(oid),
+                None => None,
+            };
+            if let Some(expected) = update.expected
+                && expected != current
+            {
+                bail!("cannot lock ref '{}': {}", name, describe_mismatch(current, expected));
+            }
+            current_values.push(current);
+        }
+
+        // 4. Stage the new values in the lock files.
+        for ((_, update), lock) in targets.iter().zip(locks.iter_mut()) {
+            if let RefChange::Set(oid) = update.change {
+                lock.write_all(format!("{}\n", hex::encode(oid)).as_bytes())?;
+            }
+        }
+
+        // 5. Deleted refs must also leave packed-refs, which has its own lock.
+        let mut packed_lock = None;
+        if targets.iter().any(|(_, update)| update.change == RefChange::Delete) {
+            let mut lock = LockFile::acquire(&git_dir.join("packed-refs"))?;
+            let mut packed = PackedRefs::read(git_dir)?;
+            let mut changed = false;
+            for (name, update) in &targets {
+                if update.change == RefChange::Delete {
+                    changed |= packed.refs.remove(name).is_some();
+                }
+            }
+            if changed {
+                lock.write_all(packed.serialize().as_bytes())?;
+                packed_lock = Some(lock);
+            }
+        }
+
+        // 6. Log the moves before making them, so that a reflog that can't be
+        // written fails the transaction while every ref is still untouched.
+        // Moving the branch HEAD is on also shows up in HEAD's reflog.
+        let head_target = resolve_ref_name(git_dir, "HEAD")?.0;
+        for ((name, update), old) in targets.iter().zip(&current_values) {
+            if let RefChange::Set(new) = update.change {
+                if reflog::should_log(git_dir, name) {
+                    reflog::append(git_dir, name, *old, Some(new), &self.message)?;
+                }
+                if name != "HEAD" && *name == head_target {
+                    reflog::append(git_dir, "HEAD", *old, Some(new), &self.message)?;
+                }
+            }
+        }
+
+        // 7. Everything is checked and written: move it into place.
+        if let Some(lock) = packed_lock {
+            lock.commit()?;
+        }
+        for ((name, update), lock) in targets.iter().zip(locks) {
+            match update.change {
+                RefChange::Set(_) => lock.commit()?,
+                RefChange::Delete => {
+                    let path = git_dir.join(name);
+                    if path.is_file() {
+                        fs::remove_file(&path)?;
+                    }
+                    reflog::delete(git_dir, name)?;
+                }
+                RefChange::Verify => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn describe_mismatch(current: Option<Oid>, expected: Option<Oid>) -> String {
+    match (current, expected) {
+        (Some(_), None) => "reference already exists".to_string(),
+        (None, Some(_)) => "unable to resolve reference".to_string(),
+        (Some(current), Some(expected)) => format!(
+            "is at {} but expected {}",
+            hex::encode(current),
+            hex::encode(expected)
+        ),
+        (None, None) => unreachable!(),
+    }
+}
+
+/// Refs live under `refs/`, apart from HEAD-like names at the top of the git
+/// directory (`HEAD`, `ORIG_HEAD`, ...).
+fn check_updatable_name(name: &str) -> Result<()> {
+    if name.starts_with("refs/") {
+        return check_ref_name(name);
+    }
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_uppercase() || c == '_') {
+        bail!("Refusing to update ref with bad name '{}'", name);
+    }
+    Ok(())
+}
+
+/// Lists every ref under `prefix` (e.g. `refs/tags/`) with the id it holds,
+/// sorted by name. Loose refs take precedence over packed ones; symbolic
+/// refs are resolved, and dangling ones are left out.
+pub fn list_refs(git_dir: &Path, prefix: &str) -> Result<Vec<(String, Oid)>> {
+    let mut refs: BTreeMap<String, Oid> = PackedRefs::read(git_dir)?
+        .refs
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(prefix))
+        .map(|(name, entry)| (name, entry.oid))
+        .collect();
+
+    let mut loose = Vec::new();
+    collect_loose(&git_dir.join("refs"), "refs/", &mut loose)?;
+    for name in loose.into_iter().filter(|name| name.starts_with(prefix)) {
+        if let Some(oid) = resolve_ref(git_dir, &name)? {
+            refs.insert(name, oid);
+        }
+    }
+    Ok(refs.into_iter().collect())
+}
+
+fn collect_loose(dir: &Path, prefix: &str, names: &mut Vec<String>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            collect_loose(&entry.path(), &format!("{}/", name), names)?;
+        } else if !name.ends_with(".lock") {
+            names.push(name);
+        }
+    }
+    Ok(())
+}
+
+/// Moves loose refs into `packed-refs`. Tags are always packed (they rarely
+/// move), branches only with `all`. With `prune`, the loose files are removed.
+pub fn pack_refs(git_dir: &Path, store: &ObjectStore, all: bool, prune: bool) -> Result<()> {
+    let mut lock = LockFile::acquire(&git_dir.join("packed-refs"))?;
+    let mut packed = PackedRefs::read(git_dir)?;
+    let mut loose = Vec::new();
+    collect_loose(&git_dir.join("refs"), "refs/", &mut loose)?;
+
+    let mut packed_refs = Vec::new();
+    for name in loose {
+        if !all && !name.starts_with("refs/tags/") && !packed.refs.contains_key(&name) {
+            continue;
+        }
+        // Symbolic refs can't be packed.
+        let Some(RefValue::Direct(oid)) = read_ref(git_dir, &name)? else {
+            continue;
+        };
+        packed.refs.insert(name.clone(), PackedRef { oid, peeled: None });
+        packed_refs.push((name, oid));
+    }
+
+    // Record what annotated tags peel to, so readers don't have to open them.
+    for entry in packed.refs.values_mut() {
+        let peeled = peel(store, &entry.oid)?;
+        entry.peeled = (peeled != entry.oid).then_some(peeled);
+    }
+    lock.write_all(packed.serialize().as_bytes())?;
+    lock.commit()?;
+
+    if prune {
+        // A ref that moved since we read it has to stay loose, as the packed
+        // copy is already out of date.
+        for (name, oid) in packed_refs {
+            let _ref_lock = LockFile::acquire(&git_dir.join(&name))?;
+            if read_ref(git_dir, &name)? == Some(RefValue::Direct(oid)) {
+                fs::remove_file(git_dir.join(&name))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Follows annotated tags until reaching an object that isn't a tag.
+pub fn peel(store: &ObjectStore, oid: &Oid) -> Result<Oid> {
+    let mut current = *oid;
+    for _ in 0..MAX_SYMREF_DEPTH * 10 {
+        if !store.exists(&current) {
+            return Ok(current);
+        }
+        match store.read(&current)? {
+            GitObject::Tag(tag) => current = objects::parse_oid(&tag.object)?,
+            _ => return Ok(current),
+        }
+    }
+    bail!("Tag chain too deep at {}", hex::encode(oid));
+}
+
+/// The shortest unambiguous-by-convention form of a ref name:
+/// `refs/heads/main` -> `main`, `refs/tags/v1` -> `v1`.
+pub fn shorten_ref_name(name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
+/// Checks a ref name (or a component of one, like a tag name) against git's
+/// rules for what a ref may be called.
+pub fn check_ref_name(name: &str) -> Result<()> {
+    let invalid = name.is_empty()
+        || name.starts_with('-')
+        || name.starts_with('/')
+        || name.ends_with('/')
+        || name.ends_with('.')
+        || name.ends_with(".lock")
+        || name.contains("..")
+        || name.contains("//")
+        || name.contains("@{")
+        || name == "@"
+        || name.split('/').any(|component| component.starts_with('.'))
+        || name
+            .chars()
+            .any(|c| c.is_ascii_control() || " ~^:?*[\\".contains(c));
+    if invalid {
+        bail!("'{}' is not a valid ref name", name);
+    }
+    Ok(())
+}
+
+/// Shell-style matching with `*` and `?`, as used for ref patterns.
+pub fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        Some((b'?', rest)) => !text.is_empty() && glob_match(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
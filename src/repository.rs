@@ -1,48 +1,269 @@
 // src/repository.rs
 
+use crate::config::Config;
+use crate::diff::tree::Snapshot;
+use crate::objects::{self, GitObject, Oid};
+use crate::odb::ObjectStore;
+use crate::refs::{self, RefTransaction, RefValue};
 use anyhow::{bail, Context, Result};
+use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
-/// Reads the .git/HEAD file to find the hash of the latest commit.
+/// A handle to an opened repository: where its git directory and working tree
+/// are, and its object database. Every command is given one of these instead
+/// of assuming `.git` in the current directory.
+#[derive(Debug)]
+pub struct Repository {
+    git_dir: PathBuf,
+    // None for bare repositories.
+    work_tree: Option<PathBuf>,
+    odb: ObjectStore,
+    config: Config,
+}
+
+impl Repository {
+    /// Opens a repository whose git directory and working tree are known.
+    pub fn open(git_dir: impl Into<PathBuf>, work_tree: Option<PathBuf>) -> Result<Self> {
+        let git_dir = git_dir.into();
+        if !is_git_dir(&git_dir) {
+            bail!("Not a git repository: {:?}", git_dir);
+        }
+        let odb = ObjectStore::new(git_dir.join("objects"))?;
+        let config = Config::load(Some(&git_dir))?;
+        Ok(Repository {
+            git_dir,
+            work_tree,
+            odb,
+            config,
+        })
+    }
+
+    /// Finds the repository the way git does. `GIT_DIR` (with `GIT_WORK_TREE`
+    /// or the current directory as the working tree) wins; otherwise `start`
+    /// and its parents are searched for a `.git` directory, a `.git` file with
+    /// a `gitdir:` pointer, or a bare repository, without moving up into any of
+    /// the directories listed in `GIT_CEILING_DIRECTORIES`.
+    pub fn discover(start: &Path) -> Result<Self> {
+        let start = absolute(start)?;
+        let env_work_tree = env::var_os("GIT_WORK_TREE").map(|dir| start.join(dir));
+
+        if let Some(git_dir) = env::var_os("GIT_DIR") {
+            let work_tree = env_work_tree.or_else(|| Some(start.clone()));
+            return Self::open(start.join(git_dir), work_tree);
+        }
+
+        let ceilings: Vec<PathBuf> = env::var_os("GIT_CEILING_DIRECTORIES")
+            .map(|dirs| {
+                env::split_paths(&dirs)
+                    .filter(|dir| dir.is_absolute())
+                    .map(|dir| normalize(&dir))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut dir = start.as_path();
+        loop {
+            let dot_git = dir.join(".git");
+            if dot_git.is_file() {
+                let git_dir = read_gitdir_file(&dot_git)?;
+                return Self::open(git_dir, env_work_tree.or_else(|| Some(dir.to_path_buf())));
+            }
+            if is_git_dir(&dot_git) {
+                return Self::open(dot_git, env_work_tree.or_else(|| Some(dir.to_path_buf())));
+            }
+            if is_git_dir(dir) {
+                // A bare repository has no working tree unless one is forced.
+                return Self::open(dir, env_work_tree);
+            }
+
+            match dir.parent() {
+                Some(parent) if !ceilings.iter().any(|ceiling| ceiling == parent) => dir = parent,
+                _ => bail!(
+                    "Not a git repository (or any of the parent directories): {:?}",
+                    start
+                ),
+            }
+        }
+    }
+
+    pub fn git_dir(&self) -> &Path {
+        &self.git_dir
+    }
+
+    pub fn work_tree(&self) -> Option<&Path> {
+        self.work_tree.as_deref()
+    }
+
+    /// The working tree, or an error for bare repositories.
+    pub fn require_work_tree(&self) -> Result<&Path> {
+        self.work_tree()
+            .context("This operation must be run in a work tree")
+    }
+
+    pub fn is_bare(&self) -> bool {
+        self.work_tree.is_none()
+    }
+
+    pub fn odb(&self) -> &ObjectStore {
+        &self.odb
+    }
+
+    /// The system, global and repository settings, as they were when the
+    /// repository was opened.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn index_path(&self) -> PathBuf {
+        self.git_dir.join("index")
+    }
+
+    /// Turns a path given on the command line (relative to the current
+    /// directory) into a path relative to the root of the working tree.
+    pub fn worktree_path(&self, path: &Path) -> Result<PathBuf> {
+        let work_tree = normalize(&absolute(self.require_work_tree()?)?);
+        let path = normalize(&absolute(path)?);
+        path.strip_prefix(&work_tree)
+            .map(Path::to_path_buf)
+            .map_err(|_| anyhow::anyhow!("{:?} is outside repository at {:?}", path, work_tree))
+    }
+}
+
+/// A directory is a git directory if it has `HEAD`, `objects` and `refs`.
+fn is_git_dir(dir: &Path) -> bool {
+    dir.join("HEAD").is_file() && dir.join("objects").is_dir() && dir.join("refs").is_dir()
+}
+
+/// Follows a `.git` file (used by worktrees and submodules), which contains
+/// `gitdir: <path>`, relative to the file's directory if not absolute.
+fn read_gitdir_file(path: &Path) -> Result<PathBuf> {
+    let content = fs::read_to_string(path)?;
+    let target = content
+        .trim()
+        .strip_prefix("gitdir: ")
+        .with_context(|| format!("Invalid gitfile format: {:?}", path))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    Ok(normalize(&base.join(target)))
+}
+
+fn absolute(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(env::current_dir()?.join(path))
+    }
+}
+
+/// Resolves `.` and `..` components lexically, without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            other => result.push(other),
+        }
+    }
+    result
+}
+
+/// Reads HEAD, following it through its branch, to find the hash of the
+/// latest commit. None means there are no commits yet.
 pub fn get_head_commit_hash(git_dir: &Path) -> Result<Option<String>> {
-    let head_path = git_dir.join("HEAD");
-    if !head_path.exists() {
+    if !git_dir.join("HEAD").exists() {
         // No HEAD means no commits yet. This is a valid state for the first commit.
         return Ok(None);
     }
+    Ok(refs::resolve_ref(git_dir, "HEAD")?.map(hex::encode))
+}
 
-    let head_content = fs::read_to_string(&head_path)?
-        .trim().to_string();
+/// Returns the name of the branch HEAD points to (e.g. `main`), whether or
+/// not it has any commits yet, or None if HEAD is detached.
+pub fn current_branch(git_dir: &Path) -> Result<Option<String>> {
+    match refs::read_ref(git_dir, "HEAD")? {
+        Some(RefValue::Symbolic(target)) => Ok(Some(refs::shorten_ref_name(&target).to_string())),
+        _ => Ok(None),
+    }
+}
 
-    // The content of HEAD should be "ref: refs/heads/main"
-    if let Some(ref_path_str) = head_content.strip_prefix("ref: ") {
-        let ref_path = git_dir.join(ref_path_str);
-        if ref_path.exists() {
-            // Read the actual hash from the branch file (e.g., .git/refs/heads/main)
-            let commit_hash = fs::read_to_string(ref_path)?.trim().to_string();
-            Ok(Some(commit_hash))
-        } else {
-            // The branch file doesn't exist yet, so there are no commits.
-            Ok(None)
+/// Describes where HEAD is, the way `status` reports it: `On branch main` or
+/// `HEAD detached at 1a2b3c4`.
+pub fn describe_head(git_dir: &Path) -> Result<String> {
+    match current_branch(git_dir)? {
+        Some(branch) => Ok(format!("On branch {}", branch)),
+        None => {
+            let hash = get_head_commit_hash(git_dir)?.context("Invalid HEAD")?;
+            Ok(format!("HEAD detached at {}", &hash[..7]))
         }
-    } else {
-        // This would be a "detached HEAD" state, which we won't support for now.
-        bail!("Detached HEAD state is not supported");
     }
 }
 
-/// Updates the current branch file to point to a new commit hash.
-pub fn update_head(git_dir: &Path, commit_hash: &str) -> Result<()> {
-    let head_path = git_dir.join("HEAD");
-    let head_content = fs::read_to_string(&head_path)?.trim().to_string();
+/// Moves the current branch to a new commit hash, or HEAD itself when it is
+/// detached. `old_hash` is where the caller saw the branch (None if it had no
+/// commits yet): if it has moved since, nothing is changed and an error is
+/// returned, so a concurrent commit is never silently overwritten. `message`
+/// is recorded in the reflog.
+pub fn update_head(git_dir: &Path, commit_hash: &str, old_hash: Option<&str>, message: &str) -> Result<()> {
+    let oid = objects::parse_oid(commit_hash)?;
+    let expected = old_hash.map(objects::parse_oid).transpose()?;
+    let mut transaction = RefTransaction::new();
+    transaction.set_message(message);
+    transaction.update("HEAD", oid, Some(expected));
+    transaction.commit(git_dir)
+}
 
-    if let Some(ref_path_str) = head_content.strip_prefix("ref: ") {
-        let ref_path = git_dir.join(ref_path_str);
-        fs::write(ref_path, commit_hash.as_bytes())
-            .context("Failed to update branch reference")?;
-        Ok(())
-    } else {
-        bail!("Cannot update in detached HEAD state");
+/// Points HEAD directly at a commit rather than at a branch.
+pub fn detach_head(git_dir: &Path, commit_hash: &str, message: &str) -> Result<()> {
+    let oid = objects::parse_oid(commit_hash)?;
+    let mut transaction = RefTransaction::new();
+    transaction.set_deref(false);
+    transaction.set_message(message);
+    transaction.update("HEAD", oid, None);
+    transaction.commit(git_dir).context("Failed to update HEAD")
+}
+
+/// Reads a commit and returns the id of its tree.
+pub fn commit_tree_oid(store: &ObjectStore, commit: &Oid) -> Result<Oid> {
+    match store.read(commit)? {
+        GitObject::Commit(commit) => objects::parse_oid(&commit.tree_hash),
+        other => bail!("{} is a {}, not a commit", hex::encode(commit), other.kind()),
     }
-}
\ No newline at end of file
+}
+
+/// Flattens a tree into every file below it, keyed by slash-separated path
+/// and mapped to the entry's mode and blob id. Paths are the names' bytes as
+/// stored, so names that aren't UTF-8 come through unchanged.
+pub fn read_tree_recursive(store: &ObjectStore, tree: &Oid) -> Result<Snapshot> {
+    let mut files = Snapshot::new();
+    read_tree_into(store, tree, b"", &mut files)?;
+    Ok(files)
+}
+
+fn read_tree_into(
+    store: &ObjectStore,
+    tree: &Oid,
+    prefix: &[u8],
+    files: &mut Snapshot,
+) -> Result<()> {
+    let GitObject::Tree(tree) = store.read(tree)? else {
+        bail!("{} is not a tree", hex::encode(tree));
+    };
+    for entry in tree.entries {
+        // A name with a slash would pass for a path through a subtree.
+        if entry.name.is_empty() || entry.name.contains(&b'/') {
+            bail!("invalid tree entry name '{}'", String::from_utf8_lossy(&entry.name));
+        }
+        let path = [prefix, &entry.name].concat();
+        if entry.is_tree() {
+            read_tree_into(store, &entry.hash, &[&path[..], b"/"].concat(), files)?;
+        } else {
+            let mode = u32::from_str_radix(&entry.mode, 8)
+                .with_context(|| format!("Invalid mode {} for {}", entry.mode, String::from_utf8_lossy(&path)))?;
+            files.insert(path, (mode, entry.hash));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,408 @@
+// src/revision.rs
+
+use crate::config::Config;
+use crate::index::Index;
+use crate::objects::{self, GitObject, ObjectKind, Oid};
+use crate::odb::ObjectStore;
+use crate::reflog;
+use crate::refs;
+use crate::repository::Repository;
+use anyhow::{bail, Context, Result};
+use std::collections::{HashSet, VecDeque};
+use std::path::Path;
+
+/// Abbreviated hashes need at least this many hex digits.
+const MIN_ABBREV: usize = 4;
+
+/// The commits a revision argument selects: walk from `include`, leaving out
+/// everything reachable from `exclude`. `A..B` is `B` and `^A`; `A...B` is
+/// `A` and `B` without their merge bases.
+#[derive(Debug, Default)]
+pub struct RevisionRange {
+    pub include: Vec<Oid>,
+    pub exclude: Vec<Oid>,
+}
+
+/// Parses a revision that may be a range: `A..B`, `A...B` or `^A` (either
+/// side of a range defaults to HEAD), or else a single revision.
+pub fn parse_range(repo: &Repository, spec: &str) -> Result<RevisionRange> {
+    let store = repo.odb();
+    if let Some(excluded) = spec.strip_prefix('^')
+        && !excluded.is_empty()
+    {
+        return Ok(RevisionRange {
+            include: Vec::new(),
+            exclude: vec![resolve_commit(repo, excluded)?],
+        });
+    }
+    if let Some((left, right)) = split_range(spec, "...") {
+        let left = resolve_commit(repo, or_head(left))?;
+        let right = resolve_commit(repo, or_head(right))?;
+        return Ok(RevisionRange {
+            include: vec![right, left],
+            exclude: merge_bases(store, &left, &right)?,
+        });
+    }
+    if let Some((left, right)) = split_range(spec, "..") {
+        return Ok(RevisionRange {
+            include: vec![resolve_commit(repo, or_head(right))?],
+            exclude: vec![resolve_commit(repo, or_head(left))?],
+        });
+    }
+    Ok(RevisionRange {
+        include: vec![resolve(repo, spec)?],
+        exclude: Vec::new(),
+    })
+}
+
+fn split_range<'a>(spec: &'a str, operator: &str) -> Option<(&'a str, &'a str)> {
+    let (left, right) = spec.split_once(operator)?;
+    // In `rev:path` the dots belong to the path.
+    (!left.contains(':')).then_some((left, right))
+}
+
+fn or_head(spec: &str) -> &str {
+    if spec.is_empty() { "HEAD" } else { spec }
+}
+
+/// Resolves a revision and peels it to a commit.
+pub fn resolve_commit(repo: &Repository, spec: &str) -> Result<Oid> {
+    peel_to(repo.odb(), &resolve(repo, spec)?, ObjectKind::Commit)
+}
+
+/// Resolves a single revision to an object id:
+///
+/// - a full or abbreviated (4+ digits) hash, or a ref name, short or full
+/// - `@` for HEAD, `<ref>@{<n>}` and `<ref>@{<date>}` through the reflog,
+///   `@{-<n>}` for the branch checked out n switches ago, `<branch>@{upstream}`
+/// - followed by any of `~<n>` (first parents), `^<n>` (n-th parent),
+///   `^{<type>}` (peel to a commit, tree, blob or tag) and `^{}` (peel tags)
+/// - `<rev>:<path>` for a tree or blob inside a commit, `:<path>` or
+///   `:<stage>:<path>` for a blob in the index
+pub fn resolve(repo: &Repository, spec: &str) -> Result<Oid> {
+    let store = repo.odb();
+    if let Some(rest) = spec.strip_prefix(':') {
+        return resolve_index_path(repo, rest);
+    }
+    if let Some(pos) = find_outside_braces(spec, |c| c == ':') {
+        let (rev, path) = (&spec[..pos], &spec[pos + 1..]);
+        let tree = peel_to(store, &resolve(repo, or_head(rev))?, ObjectKind::Tree)?;
+        return lookup_path(store, &tree, path)
+            .with_context(|| format!("path '{}' does not exist in '{}'", path, rev));
+    }
+
+    let split = find_outside_braces(spec, |c| c == '~' || c == '^').unwrap_or(spec.len());
+    let (base, operators) = spec.split_at(split);
+    // `~` and `^<n>` need commits, which settles ambiguous short hashes.
+    let wants_commit = !operators.is_empty() && !operators.starts_with("^{");
+    let mut oid = resolve_base(repo, base, wants_commit)?;
+
+    let mut rest = operators;
+    while let Some(op) = rest.chars().next() {
+        rest = &rest[1..];
+        if op == '^' && let Some(inner) = rest.strip_prefix('{') {
+            let end = inner.find('}').with_context(|| format!("Invalid revision: {}", spec))?;
+            oid = peel_spec(store, &oid, &inner[..end])?;
+            rest = &inner[end + 1..];
+            continue;
+        }
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let n: usize = if digits == 0 { 1 } else { rest[..digits].parse()? };
+        rest = &rest[digits..];
+
+        let commit = peel_to(store, &oid, ObjectKind::Commit)?;
+        oid = match (op, n) {
+            ('^', 0) => commit,
+            ('^', n) => *commit_parents(store, &commit)?
+                .get(n - 1)
+                .with_context(|| format!("Invalid revision: {} ({} has no parent {})", spec, hex::encode(commit), n))?,
+            _ => {
+                let mut current = commit;
+                for _ in 0..n {
+                    current = *commit_parents(store, &current)?
+                        .first()
+                        .with_context(|| format!("Invalid revision: {} (not enough history)", spec))?;
+                }
+                current
+            }
+        };
+    }
+    Ok(oid)
+}
+
+/// Finds the first character matching `pred` that isn't inside `{...}`.
+fn find_outside_braces(spec: &str, pred: impl Fn(char) -> bool) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            c if depth == 0 && pred(c) => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn resolve_base(repo: &Repository, base: &str, wants_commit: bool) -> Result<Oid> {
+    let git_dir = repo.git_dir();
+    let base = if base == "@" { "HEAD" } else { base };
+
+    if let Some((name, selector)) = base.split_once("@{")
+        && let Some(selector) = selector.strip_suffix('}')
+    {
+        if let Some(ref_name) = selector_ref_name(git_dir, name, selector)? {
+            return refs::resolve_ref(git_dir, &ref_name)?
+                .with_context(|| format!("{} does not point to a commit yet", ref_name));
+        }
+        let ref_name = match name {
+            // A bare `@{n}` is the current branch's log, not HEAD's.
+            "" => refs::resolve_ref_name(git_dir, "HEAD")?.0,
+            name => refs::dwim_ref(git_dir, name)?
+                .with_context(|| format!("Not a valid object name: {}", base))?,
+        };
+        return reflog::lookup(git_dir, &ref_name, selector);
+    }
+
+    if base.len() == 40
+        && let Ok(oid) = objects::parse_oid(base)
+    {
+        return Ok(oid);
+    }
+    if !base.is_empty()
+        && let Some(ref_name) = refs::dwim_ref(git_dir, base)?
+    {
+        return refs::resolve_ref(git_dir, &ref_name)?
+            .with_context(|| format!("{} does not point to a commit yet", ref_name));
+    }
+    if base.len() >= MIN_ABBREV
+        && base.bytes().all(|b| b.is_ascii_hexdigit())
+        && let Some(oid) = resolve_prefix(repo.odb(), base, wants_commit)?
+    {
+        return Ok(oid);
+    }
+    bail!("Not a valid object name: {}", base);
+}
+
+/// The ref named by `@{upstream}` and `@{-n}`, which select another ref
+/// rather than a reflog entry.
+fn selector_ref_name(git_dir: &Path, name: &str, selector: &str) -> Result<Option<String>> {
+    if matches!(selector.to_ascii_lowercase().as_str(), "upstream" | "u") {
+        return Ok(Some(upstream_ref(git_dir, name)?));
+    }
+    if let Some(n) = selector.strip_prefix('-') {
+        if !name.is_empty() {
+            bail!("{}@{{{}}} is not a valid revision", name, selector);
+        }
+        let n: usize = n.parse().with_context(|| format!("Invalid revision: @{{{}}}", selector))?;
+        let branch = previous_checkout(git_dir, n)?;
+        return Ok(Some(refs::dwim_ref(git_dir, &branch)?.unwrap_or(branch)));
+    }
+    Ok(None)
+}
+
+/// The remote-tracking ref a branch follows, from its `branch.<name>.remote`
+/// and `branch.<name>.merge` settings.
+fn upstream_ref(git_dir: &Path, name: &str) -> Result<String> {
+    let branch = match name {
+        "" | "HEAD" => refs::resolve_ref_name(git_dir, "HEAD")?.0,
+        name => refs::dwim_ref(git_dir, name)?.unwrap_or_else(|| format!("refs/heads/{}", name)),
+    };
+    let short = branch
+        .strip_prefix("refs/heads/")
+        .context("HEAD does not point to a branch")?;
+    let config = Config::load(Some(git_dir))?;
+    let remote = config.get(&format!("branch.{}.remote", short));
+    let merge = config.get(&format!("branch.{}.merge", short));
+    let (Some(remote), Some(merge)) = (remote, merge) else {
+        bail!("no upstream configured for branch '{}'", short);
+    };
+    if remote == "." {
+        return Ok(merge.to_string());
+    }
+    Ok(format!(
+        "refs/remotes/{}/{}",
+        remote,
+        merge.strip_prefix("refs/heads/").unwrap_or(merge)
+    ))
+}
+
+/// The branch that was checked out `n` switches ago, from HEAD's reflog.
+fn previous_checkout(git_dir: &Path, n: usize) -> Result<String> {
+    reflog::read(git_dir, "HEAD")?
+        .iter()
+        .rev()
+        .filter_map(|entry| {
+            let moves = entry.message.strip_prefix("checkout: moving from ")?;
+            moves.split_once(" to ").map(|(from, _)| from.to_string())
+        })
+        .nth(n.saturating_sub(1))
+        .with_context(|| format!("@{{-{}}}: no such checkout in HEAD's reflog", n))
+}
+
+/// Expands an abbreviated hash. Ambiguity is an error listing the candidates,
+/// unless exactly one of them is a commit and a commit is wanted.
+fn resolve_prefix(store: &ObjectStore, prefix: &str, wants_commit: bool) -> Result<Option<Oid>> {
+    let prefix = prefix.to_ascii_lowercase();
+    let mut candidates = store.find_by_prefix(&prefix)?;
+    if candidates.len() > 1 && wants_commit {
+        let commits: Vec<Oid> = candidates
+            .iter()
+            .filter(|oid| peel_to(store, oid, ObjectKind::Commit).is_ok())
+            .copied()
+            .collect();
+        if !commits.is_empty() {
+            candidates = commits;
+        }
+    }
+    match candidates.as_slice() {
+        [] => Ok(None),
+        [oid] => Ok(Some(*oid)),
+        _ => {
+            let mut message = format!("short object ID {} is ambiguous\nThe candidates are:", prefix);
+            for oid in &candidates {
+                let (kind, _) = store.header(oid)?;
+                message.push_str(&format!("\n  {} {}", abbreviate(store, oid, 7)?, kind));
+            }
+            bail!(message);
+        }
+    }
+}
+
+/// The shortest prefix of `oid`, at least `min_len` digits long, that no other
+/// object shares.
+pub fn abbreviate(store: &ObjectStore, oid: &Oid, min_len: usize) -> Result<String> {
+    let hex = hex::encode(oid);
+    for len in min_len.clamp(MIN_ABBREV, 40)..40 {
+        if store.find_by_prefix(&hex[..len])?.len() <= 1 {
+            return Ok(hex[..len].to_string());
+        }
+    }
+    Ok(hex)
+}
+
+fn resolve_index_path(repo: &Repository, spec: &str) -> Result<Oid> {
+    let (stage, path) = match spec.split_once(':') {
+        Some((stage @ ("0" | "1" | "2" | "3"), path)) => (stage.parse::<u8>()?, path),
+        _ => (0, spec),
+    };
+    let index = Index::from_path(&repo.index_path())?;
+    let mut stages = index.entries.iter().filter(|entry| entry.path == Path::new(path));
+    match stages.clone().find(|entry| entry.stage == stage) {
+        Some(entry) => Ok(entry.hash),
+        None if stages.next().is_some() => {
+            bail!("path '{}' is in the index, but not at stage {}", path, stage)
+        }
+        None => bail!("path '{}' does not exist in the index", path),
+    }
+}
+
+fn lookup_path(store: &ObjectStore, tree: &Oid, path: &str) -> Result<Oid> {
+    let mut current = *tree;
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        let GitObject::Tree(tree) = store.read(&current)? else {
+            bail!("{} is not a tree", hex::encode(current));
+        };
+        current = tree
+            .entries
+            .iter()
+            .find(|entry| entry.name == component.as_bytes())
+            .map(|entry| entry.hash)
+            .with_context(|| format!("no entry named '{}'", component))?;
+    }
+    Ok(current)
+}
+
+/// Applies a `^{...}` suffix.
+fn peel_spec(store: &ObjectStore, oid: &Oid, kind: &str) -> Result<Oid> {
+    match kind {
+        "" => refs::peel(store, oid),
+        "object" => {
+            store.header(oid)?;
+            Ok(*oid)
+        }
+        kind => peel_to(store, oid, kind.parse()?),
+    }
+}
+
+/// Follows tags (and from a commit, its tree) until reaching an object of the
+/// given kind.
+pub fn peel_to(store: &ObjectStore, oid: &Oid, kind: ObjectKind) -> Result<Oid> {
+    let mut current = *oid;
+    loop {
+        let object = store.read(&current)?;
+        if object.kind() == kind {
+            return Ok(current);
+        }
+        current = match (&object, kind) {
+            (GitObject::Tag(tag), _) => objects::parse_oid(&tag.object)?,
+            (GitObject::Commit(commit), ObjectKind::Tree) => objects::parse_oid(&commit.tree_hash)?,
+            _ => bail!(
+                "object {} is a {}, not a {}",
+                hex::encode(oid),
+                object.kind(),
+                kind
+            ),
+        };
+    }
+}
+
+/// The parents of a commit, in order.
+pub fn commit_parents(store: &ObjectStore, oid: &Oid) -> Result<Vec<Oid>> {
+    match store.read(oid)? {
+        GitObject::Commit(commit) => commit.parents.iter().map(|p| objects::parse_oid(p)).collect(),
+        other => bail!("object {} is a {}, not a commit", hex::encode(oid), other.kind()),
+    }
+}
+
+/// Every commit reachable from `start`, including itself.
+pub fn ancestors(store: &ObjectStore, start: &Oid) -> Result<HashSet<Oid>> {
+    let mut seen = HashSet::new();
+    let mut pending = VecDeque::from([*start]);
+    while let Some(oid) = pending.pop_front() {
+        if seen.insert(oid) {
+            pending.extend(commit_parents(store, &oid)?);
+        }
+    }
+    Ok(seen)
+}
+
+/// The best common ancestors of two commits: those shared by both that aren't
+/// themselves ancestors of another shared commit. Sorted by id.
+pub fn merge_bases(store: &ObjectStore, a: &Oid, b: &Oid) -> Result<Vec<Oid>> {
+    let from_a = ancestors(store, a)?;
+    let common: HashSet<Oid> = ancestors(store, b)?.intersection(&from_a).copied().collect();
+
+    // Anything strictly below a common commit is redundant.
+    let mut below = HashSet::new();
+    let mut pending = VecDeque::new();
+    for oid in &common {
+        pending.extend(commit_parents(store, oid)?);
+    }
+    while let Some(oid) = pending.pop_front() {
+        if below.insert(oid) {
+            pending.extend(commit_parents(store, &oid)?);
+        }
+    }
+
+    let mut bases: Vec<Oid> = common.difference(&below).copied().collect();
+    bases.sort();
+    Ok(bases)
+}
+
+/// The full name of the ref a revision names, following symbolic refs:
+/// `HEAD` gives `refs/heads/main`, `@{u}` gives `refs/remotes/origin/main`.
+/// None if the revision isn't a ref, like a hash. A detached HEAD is `HEAD`.
+pub fn symbolic_full_name(repo: &Repository, spec: &str) -> Result<Option<String>> {
+    let git_dir = repo.git_dir();
+    let spec = if spec == "@" { "HEAD" } else { spec };
+    if let Some((name, selector)) = spec.split_once("@{")
+        && let Some(selector) = selector.strip_suffix('}')
+    {
+        return selector_ref_name(git_dir, name, selector);
+    }
+    match refs::dwim_ref(git_dir, spec)? {
+        Some(ref_name) => Ok(Some(refs::resolve_ref_name(git_dir, &ref_name)?.0)),
+        None => Ok(None),
+    }
+}
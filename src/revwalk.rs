@@ -0,0 +1,400 @@
+// src/revwalk.rs
+
+use crate::identity::Signature;
+use crate::objects::{self, Commit, GitObject, Oid};
+use crate::odb::ObjectStore;
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+/// Which of the commits reachable from a walk's starting points it shows, and
+/// in what order.
+#[derive(Debug, Default)]
+pub struct WalkOptions {
+    /// Follow only the first parent of merges.
+    pub first_parent: bool,
+    /// Only show commits that change one of these paths (slash-separated, from
+    /// the root of the tree; a directory covers everything below it). A merge
+    /// that matches one of its parents at these paths is skipped along with
+    /// the history it brought in.
+    pub paths: Vec<String>,
+    /// Stop at commits made before this time.
+    pub since: Option<i64>,
+    /// Skip commits made after this time.
+    pub until: Option<i64>,
+    /// Only show commits whose author name or email contains one of these.
+    pub authors: Vec<String>,
+    /// Only show commits whose message contains one of these.
+    pub grep: Vec<String>,
+    /// Never show a commit before one of its children, keep each line of
+    /// history together, and rewrite parents to the nearest shown commits:
+    /// what `--graph` needs.
+    pub topo_order: bool,
+}
+
+// Reachable from a hidden commit, so never shown.
+const UNINTERESTING: u8 = 1 << 0;
+// In the queue, or already taken out of it.
+const QUEUED: u8 = 1 << 1;
+// Taken out of the queue, with its parents queued.
+const PROCESSED: u8 = 1 << 2;
+// Doesn't change any of the paths the walk is limited to.
+const TREESAME: u8 = 1 << 3;
+
+struct Node {
+    commit: Commit,
+    // The committer date, which orders the walk.
+    time: i64,
+    parents: Vec<Oid>,
+    flags: u8,
+}
+
+/// A queue entry: the newest commit comes out first, and commits made at the
+/// same time in the order they were queued.
+#[derive(PartialEq, Eq)]
+struct Queued {
+    time: i64,
+    sequence: u64,
+    oid: Oid,
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.time.cmp(&other.time).then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Walks history from a set of commits, newest first, by keeping the commits
+/// still to visit in a queue ordered by commit date.
+///
+/// Without hidden commits or `topo_order`, commits are produced as they come
+/// out of the queue, so asking for a few recent commits only reads those.
+/// Otherwise the walk first runs until only hidden history is left, so a
+/// commit that turns out to be reachable from a hidden one is never shown.
+pub struct RevWalk<'a> {
+    store: &'a ObjectStore,
+    options: WalkOptions,
+    nodes: HashMap<Oid, Node>,
+    queue: BinaryHeap<Queued>,
+    sequence: u64,
+    has_hidden: bool,
+    // The commits to show, once a limited walk has worked them out.
+    prepared: Option<VecDeque<Oid>>,
+}
+
+impl<'a> RevWalk<'a> {
+    pub fn new(store: &'a ObjectStore, options: WalkOptions) -> Self {
+        RevWalk {
+            store,
+            options,
+            nodes: HashMap::new(),
+            queue: BinaryHeap::new(),
+            sequence: 0,
+            has_hidden: false,
+            prepared: None,
+        }
+    }
+
+    /// Starts walking from a commit.
+    pub fn push(&mut self, oid: Oid) -> Result<()> {
+        self.enqueue(oid, false)
+    }
+
+    /// Leaves out a commit and everything reachable from it.
+    pub fn hide(&mut self, oid: Oid) -> Result<()> {
+        self.has_hidden = true;
+        self.enqueue(oid, true)
+    }
+
+    /// A commit the walk has read.
+    pub fn commit(&self, oid: &Oid) -> Option<&Commit> {
+        self.nodes.get(oid).map(|node| &node.commit)
+    }
+
+    /// The parents of a shown commit as the walk sees them: only the first one
+    /// with `first_parent`, only the one a merge matches when limited to
+    /// paths, and with `topo_order` the nearest shown commits instead.
+    pub fn parents(&self, oid: &Oid) -> &[Oid] {
+        let parents = self.nodes.get(oid).map(|node| node.parents.as_slice()).unwrap_or_default();
+        if self.options.first_parent { &parents[..parents.len().min(1)] } else { parents }
+    }
+
+    /// The next commit to show, or None once the walk is over.
+    pub fn next_commit(&mut self) -> Result<Option<Oid>> {
+        if self.prepared.is_none() && (self.has_hidden || self.options.topo_order) {
+            self.prepare()?;
+        }
+        if let Some(prepared) = &mut self.prepared {
+            return Ok(prepared.pop_front());
+        }
+        while let Some(entry) = self.queue.pop() {
+            self.process(entry.oid)?;
+            if self.is_shown(&entry.oid) {
+                return Ok(Some(entry.oid));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs the walk to the end of the interesting history, then works out
+    /// what to show.
+    fn prepare(&mut self) -> Result<()> {
+        let mut visited = Vec::new();
+        while let Some(entry) = self.queue.pop() {
+            self.process(entry.oid)?;
+            if !self.has_flag(&entry.oid, UNINTERESTING) {
+                visited.push(entry.oid);
+            }
+            if self.queue.iter().all(|queued| self.has_flag(&queued.oid, UNINTERESTING)) {
+                break;
+            }
+        }
+
+        // A commit can be found to be reachable from a hidden one after it
+        // was visited, so this is only decided now.
+        visited.retain(|oid| !self.has_flag(oid, UNINTERESTING));
+        if self.options.topo_order {
+            // Sorting all of them, not just those shown, keeps the lines of
+            // history in the order their tips have.
+            visited = self.sort_topologically(visited);
+        }
+        let shown: Vec<Oid> = visited.iter().copied().filter(|oid| self.is_shown(oid)).collect();
+        if self.options.topo_order {
+            self.rewrite_parents(&visited, &shown);
+        }
+        self.prepared = Some(shown.into());
+        Ok(())
+    }
+
+    /// Points each shown commit's parents at the nearest shown commits, going
+    /// through any that are left out. `visited` is in topological order, so
+    /// walking it backwards sees each parent's result before it is needed.
+    fn rewrite_parents(&mut self, visited: &[Oid], shown: &[Oid]) {
+        let shown_set: HashSet<Oid> = shown.iter().copied().collect();
+        let mut nearest: HashMap<Oid, Vec<Oid>> = HashMap::new();
+        let resolve = |nearest: &HashMap<Oid, Vec<Oid>>, parents: &[Oid]| {
+            let mut result: Vec<Oid> = Vec::new();
+            for parent in parents {
+                let found = match nearest.get(parent) {
+                    Some(found) => found.clone(),
+                    None if shown_set.contains(parent) => vec![*parent],
+                    None => Vec::new(),
+                };
+                for oid in found {
+                    if !result.contains(&oid) {
+                        result.push(oid);
+                    }
+                }
+            }
+            result
+        };
+
+        for oid in visited.iter().rev() {
+            let found = if shown_set.contains(oid) {
+                vec![*oid]
+            } else {
+                resolve(&nearest, self.parents(oid))
+            };
+            nearest.insert(*oid, found);
+        }
+        for oid in shown {
+            let parents = resolve(&nearest, self.parents(oid));
+            self.nodes.get_mut(oid).expect("shown commits are loaded").parents = parents;
+        }
+    }
+
+    /// Orders commits so that each comes before its parents. Starting from the
+    /// tips in walk order, a commit is emitted once all its children have
+    /// been, most recently freed first, which keeps each branch's commits
+    /// together instead of interleaving them by date. Parents that
+    /// `first_parent` doesn't follow still count.
+    fn sort_topologically(&self, commits: Vec<Oid>) -> Vec<Oid> {
+        let mut children: HashMap<Oid, usize> = commits.iter().map(|oid| (*oid, 0)).collect();
+        for oid in &commits {
+            for parent in &self.nodes[oid].parents {
+                if let Some(count) = children.get_mut(parent) {
+                    *count += 1;
+                }
+            }
+        }
+
+        let mut stack: Vec<Oid> = commits.iter().copied().filter(|oid| children[oid] == 0).collect();
+        stack.reverse();
+        let mut sorted = Vec::with_capacity(commits.len());
+        while let Some(oid) = stack.pop() {
+            for parent in &self.nodes[&oid].parents {
+                if let Some(count) = children.get_mut(parent) {
+                    *count -= 1;
+                    if *count == 0 {
+                        stack.push(*parent);
+                    }
+                }
+            }
+            sorted.push(oid);
+        }
+        sorted
+    }
+
+    fn has_flag(&self, oid: &Oid, flag: u8) -> bool {
+        self.nodes.get(oid).is_some_and(|node| node.flags & flag != 0)
+    }
+
+    fn load(&mut self, oid: &Oid) -> Result<()> {
+        if self.nodes.contains_key(oid) {
+            return Ok(());
+        }
+        let GitObject::Commit(commit) = self.store.read(oid)? else {
+            bail!("object {} is not a commit", hex::encode(oid));
+        };
+        let time = Signature::parse(&String::from_utf8_lossy(&commit.committer)).map(|s| s.timestamp).unwrap_or(0);
+        let parents = commit
+            .parents
+            .iter()
+            .map(|parent| objects::parse_oid(parent))
+            .collect::<Result<_>>()?;
+        self.nodes.insert(*oid, Node { commit, time, parents, flags: 0 });
+        Ok(())
+    }
+
+    fn enqueue(&mut self, oid: Oid, uninteresting: bool) -> Result<()> {
+        self.load(&oid)?;
+        if uninteresting {
+            self.mark_uninteresting(oid);
+        }
+        let node = self.nodes.get_mut(&oid).expect("just loaded");
+        if node.flags & QUEUED != 0 {
+            return Ok(());
+        }
+        node.flags |= QUEUED;
+        self.sequence += 1;
+        self.queue.push(Queued { time: node.time, sequence: self.sequence, oid });
+        Ok(())
+    }
+
+    /// Marks a commit as hidden, along with the ancestors of it the walk has
+    /// already gone through.
+    fn mark_uninteresting(&mut self, oid: Oid) {
+        let mut pending = vec![oid];
+        while let Some(oid) = pending.pop() {
+            let Some(node) = self.nodes.get_mut(&oid) else {
+                continue;
+            };
+            if node.flags & UNINTERESTING != 0 {
+                continue;
+            }
+            node.flags |= UNINTERESTING;
+            if node.flags & PROCESSED != 0 {
+                pending.extend(node.commit.parents.iter().filter_map(|p| objects::parse_oid(p).ok()));
+            }
+        }
+    }
+
+    /// Queues a commit's parents, simplifying them first when the walk is
+    /// limited to paths.
+    fn process(&mut self, oid: Oid) -> Result<()> {
+        let node = &self.nodes[&oid];
+        let mut parents = node.parents.clone();
+        let (time, flags) = (node.time, node.flags);
+
+        if flags & UNINTERESTING != 0 {
+            self.nodes.get_mut(&oid).expect("queued commits are loaded").flags |= PROCESSED;
+            for parent in parents {
+                self.enqueue(parent, true)?;
+            }
+            return Ok(());
+        }
+        // Older history on this line is out of range.
+        if self.options.since.is_some_and(|since| time < since) {
+            parents.clear();
+        }
+
+        let mut flags = PROCESSED;
+        if !self.options.paths.is_empty() {
+            let tree = objects::parse_oid(&node.commit.tree_hash)?;
+            let mut same = None;
+            // With first_parent only the first parent counts.
+            let considered = if self.options.first_parent { parents.len().min(1) } else { parents.len() };
+            for parent in &parents[..considered] {
+                self.load(parent)?;
+                let parent_tree = objects::parse_oid(&self.nodes[parent].commit.tree_hash)?;
+                if !self.paths_differ(Some(&parent_tree), &tree)? {
+                    same = Some(*parent);
+                    break;
+                }
+            }
+            match same {
+                // A merge that matches a parent keeps only that line of history.
+                Some(parent) => {
+                    parents = vec![parent];
+                    flags |= TREESAME;
+                }
+                None if parents.is_empty() && !self.paths_differ(None, &tree)? => flags |= TREESAME,
+                None => {}
+            }
+        }
+
+        let node = self.nodes.get_mut(&oid).expect("queued commits are loaded");
+        node.flags |= flags;
+        node.parents = parents;
+        for parent in self.parents(&oid).to_vec() {
+            self.enqueue(parent, false)?;
+        }
+        Ok(())
+    }
+
+    /// Whether a processed commit passes every filter.
+    fn is_shown(&self, oid: &Oid) -> bool {
+        let node = &self.nodes[oid];
+        if node.flags & (UNINTERESTING | TREESAME) != 0 {
+            return false;
+        }
+        if self.options.since.is_some_and(|since| node.time < since)
+            || self.options.until.is_some_and(|until| node.time > until)
+        {
+            return false;
+        }
+        let ident = String::from_utf8_lossy(&node.commit.author);
+        let author = ident.rsplitn(3, ' ').nth(2).unwrap_or(&ident);
+        if !self.options.authors.is_empty() && !self.options.authors.iter().any(|a| author.contains(a.as_str())) {
+            return false;
+        }
+        self.options.grep.is_empty()
+            || self.options.grep.iter().any(|text| String::from_utf8_lossy(&node.commit.message).contains(text.as_str()))
+    }
+
+    /// Whether two trees hold different content at any of the walk's paths.
+    /// A missing tree is empty.
+    fn paths_differ(&self, old: Option<&Oid>, new: &Oid) -> Result<bool> {
+        for path in &self.options.paths {
+            let before = match old {
+                Some(tree) => self.entry_at(tree, path)?,
+                None => None,
+            };
+            if before != self.entry_at(new, path)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The object at a path inside a tree, if there is one.
+    fn entry_at(&self, tree: &Oid, path: &str) -> Result<Option<Oid>> {
+        let mut current = *tree;
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            let GitObject::Tree(tree) = self.store.read(&current)? else {
+                return Ok(None);
+            };
+            match tree.entries.iter().find(|entry| entry.name == component.as_bytes()) {
+                Some(entry) => current = entry.hash,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(current))
+    }
+}
@@ -0,0 +1,42 @@
+// tests/add.rs
+
+mod common;
+
+use common::TestRepo;
+use rusgit::index::Index;
+use std::path::Path;
+
+#[cfg(unix)]
+#[test]
+fn add_stages_symlinks_as_links() {
+    let repo = TestRepo::new();
+    repo.write("target", b"content\n");
+    std::os::unix::fs::symlink("target", repo.dir.join("link")).unwrap();
+    std::os::unix::fs::symlink("missing", repo.dir.join("dangling")).unwrap();
+    repo.rusgit(["add", "target", "link", "dangling"]);
+
+    // The ids `git add` gives: blobs holding "target" and "missing".
+    let index = Index::from_path(&repo.git_dir().join("index")).unwrap();
+    let entry = |path: &str| index.entries.iter().find(|entry| entry.path == Path::new(path)).unwrap();
+    assert_eq!(entry("link").mode, 0o120000);
+    assert_eq!(hex::encode(entry("link").hash), "1de565933b05f74c75ff9a6520af5f9f8a5a2f1d");
+    assert_eq!(entry("dangling").mode, 0o120000);
+    assert_eq!(hex::encode(entry("dangling").hash), "6eab79a6ce25b19851f591e3e974e192c6858cf6");
+    assert_eq!(entry("target").mode, 0o100644);
+    assert_eq!(repo.rusgit_line(["write-tree"]), "f6745b83631ffa67cd78734d5b7db9a81b2f7404");
+}
+
+#[test]
+fn add_refuses_to_write_a_locked_index() {
+    let repo = TestRepo::new();
+    repo.write("a", b"a\n");
+    repo.rusgit(["add", "a"]);
+    let before = std::fs::read(repo.git_dir().join("index")).unwrap();
+
+    std::fs::write(repo.git_dir().join("index.lock"), b"").unwrap();
+    repo.write("b", b"b\n");
+    let output = repo.run(["add", "b"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("index.lock': File exists"));
+    assert_eq!(std::fs::read(repo.git_dir().join("index")).unwrap(), before);
+}
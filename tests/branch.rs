@@ -0,0 +1,119 @@
+// tests/branch.rs
+
+mod common;
+
+use common::TestRepo;
+
+fn commit(repo: &TestRepo, file: &str, message: &str) -> String {
+    repo.write(file, format!("{}\n", message).as_bytes());
+    repo.rusgit(["add", file]);
+    repo.rusgit(["commit", "-m", message]);
+    repo.rusgit_line(["rev-parse", "HEAD"])
+}
+
+/// main has two commits; `old` stops at the first, and `topic` adds a
+/// third on top of main that main doesn't have.
+fn history() -> TestRepo {
+    let repo = TestRepo::new();
+    commit(&repo, "f", "one");
+    commit(&repo, "f", "two");
+    repo.rusgit(["branch", "old", "HEAD~1"]);
+    repo.rusgit(["branch", "topic"]);
+    repo.rusgit(["switch", "topic"]);
+    commit(&repo, "g", "three");
+    repo.rusgit(["switch", "main"]);
+    repo
+}
+
+fn branch(repo: &TestRepo, args: &[&str]) -> String {
+    String::from_utf8(repo.rusgit([&["branch"], args].concat())).unwrap()
+}
+
+/// Runs `branch <args>`, expecting it to fail, and returns the error.
+fn branch_error(repo: &TestRepo, args: &[&str]) -> String {
+    let output = repo.run([&["branch"], args].concat());
+    assert!(!output.status.success(), "branch {:?} succeeded", args);
+    String::from_utf8(output.stderr).unwrap()
+}
+
+// The expected output in these tests is git's for the same commands.
+
+#[test]
+fn branches_are_listed_with_the_current_one_marked() {
+    let repo = history();
+    assert_eq!(branch(&repo, &[]), "* main\n  old\n  topic\n");
+    assert_eq!(branch(&repo, &["--list", "t*"]), "  topic\n");
+    assert_eq!(repo.rusgit_line(["rev-parse", "old"]), repo.rusgit_line(["rev-parse", "main~1"]));
+}
+
+#[test]
+fn contains_and_merged_filter_the_list() {
+    let repo = history();
+    assert_eq!(branch(&repo, &["--contains", "HEAD~1"]), "* main\n  old\n  topic\n");
+    assert_eq!(branch(&repo, &["--contains"]), "* main\n  topic\n");
+    assert_eq!(branch(&repo, &["--merged"]), "* main\n  old\n");
+    assert_eq!(branch(&repo, &["--merged", "topic"]), "* main\n  old\n  topic\n");
+}
+
+#[test]
+fn rename_moves_the_reflog_and_config() {
+    let repo = history();
+    let topic = repo.rusgit_line(["rev-parse", "topic"]);
+    assert_eq!(branch(&repo, &["-u", "main", "topic"]), "branch 'topic' set up to track 'main'.\n");
+
+    branch(&repo, &["-m", "topic", "renamed"]);
+
+    assert_eq!(branch(&repo, &[]), "* main\n  old\n  renamed\n");
+    assert_eq!(repo.rusgit_line(["rev-parse", "renamed"]), topic);
+    assert!(!repo.git_dir().join("logs/refs/heads/topic").exists());
+    let log = String::from_utf8(repo.rusgit(["reflog", "show", "renamed"])).unwrap();
+    let messages: Vec<&str> = log.lines().map(|line| line.split_once(": ").unwrap().1).collect();
+    assert_eq!(
+        messages,
+        [
+            "Branch: renamed refs/heads/topic to refs/heads/renamed",
+            "commit: three",
+            "branch: Created from HEAD",
+        ]
+    );
+    assert_eq!(repo.rusgit_line(["config", "branch.renamed.merge"]), "refs/heads/main");
+    assert!(!repo.run(["config", "branch.topic.merge"]).status.success());
+}
+
+#[test]
+fn delete_refuses_unmerged_branches_unless_forced() {
+    let repo = history();
+    let topic = repo.rusgit_line(["rev-parse", "--short", "topic"]);
+    let old = repo.rusgit_line(["rev-parse", "--short", "old"]);
+    branch(&repo, &["-u", "main", "topic"]);
+
+    assert!(branch_error(&repo, &["-d", "topic"]).contains("The branch 'topic' is not fully merged."));
+    assert!(branch_error(&repo, &["-d", "main"]).contains("Cannot delete branch 'main' checked out at"));
+    assert!(branch_error(&repo, &["-d", "nope"]).contains("branch 'nope' not found."));
+    assert_eq!(branch(&repo, &[]), "* main\n  old\n  topic\n");
+
+    assert_eq!(branch(&repo, &["-d", "old"]), format!("Deleted branch old (was {}).\n", old));
+    assert_eq!(branch(&repo, &["-D", "topic"]), format!("Deleted branch topic (was {}).\n", topic));
+    assert_eq!(branch(&repo, &[]), "* main\n");
+    assert!(!repo.git_dir().join("logs/refs/heads/topic").exists());
+    assert!(!repo.run(["config", "branch.topic.merge"]).status.success());
+}
+
+#[test]
+fn set_upstream_records_the_remote_and_branch() {
+    let repo = history();
+    repo.rusgit(["config", "remote.origin.url", "/nowhere"]);
+    repo.rusgit(["config", "remote.origin.fetch", "+refs/heads/*:refs/remotes/origin/*"]);
+    repo.rusgit(["update-ref", "refs/remotes/origin/main", "HEAD~1"]);
+
+    assert_eq!(
+        branch(&repo, &["-u", "origin/main"]),
+        "branch 'main' set up to track 'origin/main'.\n"
+    );
+    assert_eq!(repo.rusgit_line(["config", "branch.main.remote"]), "origin");
+    assert_eq!(repo.rusgit_line(["config", "branch.main.merge"]), "refs/heads/main");
+    assert_eq!(
+        repo.rusgit_line(["rev-parse", "--symbolic-full-name", "@{u}"]),
+        "refs/remotes/origin/main"
+    );
+}
@@ -0,0 +1,91 @@
+// tests/checkout.rs
+
+mod common;
+
+use common::TestRepo;
+use rusgit::objects::{Blob, GitObject, Oid, Tree, TreeEntry};
+use rusgit::index::Index;
+use rusgit::odb::ObjectStore;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+
+fn tree(store: &ObjectStore, entries: Vec<(&str, &[u8], Oid)>) -> Oid {
+    let entries = entries
+        .into_iter()
+        .map(|(mode, name, hash)| TreeEntry {
+            mode: mode.to_string(),
+            name: name.to_vec(),
+            hash,
+        })
+        .collect();
+    store.write(&GitObject::Tree(Tree { entries })).unwrap()
+}
+
+#[test]
+fn checkout_refuses_trees_that_escape_the_work_tree() {
+    let repo = TestRepo::new();
+    repo.write("file", b"content\n");
+    repo.rusgit(["add", "file"]);
+    repo.rusgit(["commit", "-m", "initial"]);
+    let head = repo.rusgit_line(["rev-parse", "HEAD"]);
+    let config = fs::read(repo.git_dir().join("config")).unwrap_or_default();
+
+    let store = ObjectStore::new(repo.git_dir().join("objects")).unwrap();
+    let blob = store.write(&GitObject::Blob(Blob { content: b"pwned\n".to_vec() })).unwrap();
+    let escape = format!("rusgit-escape-{}", std::process::id());
+    let inner = tree(&store, vec![("100644", escape.as_bytes(), blob)]);
+    let git_inner = tree(&store, vec![("100644", b"config", blob)]);
+    let malicious = [
+        tree(&store, vec![("40000", b"..", inner)]),
+        tree(&store, vec![("40000", b".GIT", git_inner)]),
+        tree(&store, vec![("100644", format!("a/../../{}", escape).as_bytes(), blob)]),
+    ];
+
+    for tree in malicious {
+        let commit = repo.rusgit_line(["commit-tree", &hex::encode(tree), "-p", "HEAD", "-m", "evil"]);
+        for args in [vec!["checkout", commit.as_str()], vec!["restore", "--source", commit.as_str(), "--", "."]] {
+            let output = repo.run(&args);
+            assert!(!output.status.success(), "{:?} succeeded", args);
+            assert!(String::from_utf8_lossy(&output.stderr).contains("invalid"));
+        }
+    }
+
+    assert!(!repo.dir.parent().unwrap().join(&escape).exists());
+    assert_eq!(fs::read(repo.git_dir().join("config")).unwrap_or_default(), config);
+    assert_eq!(repo.rusgit_line(["rev-parse", "HEAD"]), head);
+    assert_eq!(fs::read(repo.dir.join("file")).unwrap(), b"content\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn switch_moves_files_whose_names_are_not_utf8() {
+    let repo = TestRepo::new();
+    let kept = common::non_utf8_name(b"d\xe9/kept");
+    let added = common::non_utf8_name(b"n\xe9");
+    repo.write(&kept, b"one\n");
+    repo.rusgit([OsStr::new("add"), kept.as_os_str()]);
+    repo.rusgit(["commit", "-m", "initial"]);
+    repo.rusgit(["branch", "other"]);
+    repo.write(&kept, b"two\n");
+    repo.write(&added, b"new\n");
+    repo.rusgit([OsStr::new("add"), kept.as_os_str(), added.as_os_str()]);
+    repo.rusgit(["commit", "-m", "second"]);
+    let index_paths = || -> Vec<PathBuf> {
+        let index = Index::from_path(&repo.git_dir().join("index")).unwrap();
+        index.entries.into_iter().map(|entry| entry.path).collect()
+    };
+
+    repo.rusgit(["switch", "other"]);
+    assert_eq!(fs::read(repo.dir.join(&kept)).unwrap(), b"one\n");
+    assert!(!repo.dir.join(&added).exists());
+    assert_eq!(index_paths(), [kept.as_path()]);
+
+    repo.rusgit(["switch", "-"]);
+    assert_eq!(fs::read(repo.dir.join(&kept)).unwrap(), b"two\n");
+    assert_eq!(fs::read(repo.dir.join(&added)).unwrap(), b"new\n");
+    assert_eq!(index_paths(), [kept.as_path(), added.as_path()]);
+    let mut names: Vec<_> = fs::read_dir(&repo.dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    names.sort();
+    assert_eq!(names, [OsStr::new(".git"), kept.parent().unwrap().as_os_str(), added.as_os_str()]);
+}
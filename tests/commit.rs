@@ -0,0 +1,32 @@
+// tests/commit.rs
+
+mod common;
+
+use common::TestRepo;
+
+/// The paths in a commit's tree, as `<mode> <type> <id>\t<path>` lines.
+fn tree_paths(repo: &TestRepo, rev: &str) -> Vec<String> {
+    let commit = String::from_utf8(repo.rusgit(["cat-file", "-p", rev])).unwrap();
+    let tree = commit.lines().next().unwrap().strip_prefix("tree ").unwrap().to_string();
+    String::from_utf8(repo.rusgit(["cat-file", "-p", &tree]))
+        .unwrap()
+        .lines()
+        .map(|line| line.split_once('\t').unwrap().1.to_string())
+        .collect()
+}
+
+#[test]
+fn consecutive_commits_keep_files_staged_earlier() {
+    let repo = TestRepo::new();
+    repo.write("first.txt", b"first\n");
+    repo.rusgit(["add", "first.txt"]);
+    repo.rusgit(["commit", "-m", "first"]);
+
+    repo.write("second.txt", b"second\n");
+    repo.rusgit(["add", "second.txt"]);
+    repo.rusgit(["commit", "-m", "second"]);
+
+    assert_eq!(tree_paths(&repo, "HEAD~1"), ["first.txt"]);
+    assert_eq!(tree_paths(&repo, "HEAD"), ["first.txt", "second.txt"]);
+    assert!(repo.git_dir().join("index").is_file());
+}
@@ -0,0 +1,136 @@
+// tests/common/mod.rs
+
+#![allow(dead_code)]
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static REPO_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// The author and committer date of every commit, unless a test picks another.
+pub const DATE: &str = "1112911993 -0700";
+
+/// A scratch repository in the temp directory, created with `rusgit init`
+/// and removed again when dropped. Commands run the built binary inside it,
+/// isolated from the user's configuration.
+pub struct TestRepo {
+    pub dir: PathBuf,
+}
+
+impl TestRepo {
+    pub fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "rusgit-test-{}-{}",
+            std::process::id(),
+            REPO_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let repo = TestRepo { dir };
+        repo.rusgit(["init", "."]);
+        repo
+    }
+
+    pub fn git_dir(&self) -> PathBuf {
+        self.dir.join(".git")
+    }
+
+    /// Runs rusgit with `args` and returns its output, whether it succeeded or not.
+    pub fn run<I, S>(&self, args: I) -> Output
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.run_at(DATE, args)
+    }
+
+    /// Like `run`, with `date` as the author and committer date.
+    pub fn run_at<I, S>(&self, date: &str, args: I) -> Output
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.command(date, args).output().unwrap()
+    }
+
+    /// The rusgit command `run_at` would run, for tests that need to spawn it
+    /// themselves.
+    pub fn command<I, S>(&self, date: &str, args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rusgit"));
+        command
+            .args(args)
+            .current_dir(&self.dir)
+            .env_remove("GIT_DIR")
+            .env_remove("GIT_WORK_TREE")
+            .env("HOME", &self.dir)
+            .env("XDG_CONFIG_HOME", self.dir.join(".config"))
+            .env("GIT_CONFIG_NOSYSTEM", "true")
+            .env("GIT_AUTHOR_NAME", "A U Thor")
+            .env("GIT_AUTHOR_EMAIL", "author@example.com")
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_NAME", "C O Mitter")
+            .env("GIT_COMMITTER_EMAIL", "committer@example.com")
+            .env("GIT_COMMITTER_DATE", date);
+        command
+    }
+
+    /// Runs rusgit with `args`, expecting it to succeed, and returns its stdout.
+    pub fn rusgit<I, S>(&self, args: I) -> Vec<u8>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.rusgit_at(DATE, args)
+    }
+
+    /// Like `rusgit`, with `date` as the author and committer date.
+    pub fn rusgit_at<I, S>(&self, date: &str, args: I) -> Vec<u8>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let output = self.run_at(date, args);
+        assert!(
+            output.status.success(),
+            "rusgit failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        output.stdout
+    }
+
+    /// Like `rusgit`, for commands that print one line such as an object id.
+    pub fn rusgit_line<I, S>(&self, args: I) -> String
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        String::from_utf8(self.rusgit(args)).unwrap().trim_end().to_string()
+    }
+
+    /// Writes a file in the working tree, creating its directory.
+    pub fn write(&self, path: impl AsRef<Path>, content: &[u8]) {
+        let path = self.dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A file name that is not valid UTF-8.
+#[cfg(unix)]
+pub fn non_utf8_name(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
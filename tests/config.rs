@@ -0,0 +1,182 @@
+// tests/config.rs
+
+mod common;
+
+use common::TestRepo;
+use std::fs;
+
+/// Values in a file with quoting, escapes, continuations and comments, and
+/// names spelled in mixed case.
+const QUOTED: &str = "[Core]
+\tEditor = \"vim -c \\\"set tw=72\\\"\" ; inline comment
+\tpager = less \\
+ -R # another comment
+\tpath = \"C:\\\\tmp\\ttab\"
+\tflag
+[Remote \"Origin\"]
+\tURL = one
+[remote \"origin\"]
+\turl = two
+\turl = three
+[multi]
+\tvalue = a
+\tValue = b # c
+\tvalue = \"with ; semicolon\"
+";
+
+/// `config -f quoted <args>` over `QUOTED`.
+fn quoted(args: &[&str]) -> String {
+    let repo = TestRepo::new();
+    repo.write("quoted", QUOTED.as_bytes());
+    String::from_utf8(repo.rusgit([&["config", "-f", "quoted"], args].concat())).unwrap()
+}
+
+// The expected output in these tests is git's for the same files.
+
+#[test]
+fn values_are_unquoted_and_unescaped() {
+    assert_eq!(quoted(&["core.editor"]), "vim -c \"set tw=72\"\n");
+    assert_eq!(quoted(&["core.path"]), "C:\\tmp\ttab\n");
+    assert_eq!(quoted(&["multi.value"]), "with ; semicolon\n");
+}
+
+#[test]
+fn continued_lines_join_and_comments_are_dropped() {
+    assert_eq!(quoted(&["core.pager"]), "less  -R\n");
+    assert_eq!(quoted(&["core.flag"]), "\n");
+}
+
+#[test]
+fn sections_and_names_ignore_case_but_subsections_do_not() {
+    assert_eq!(quoted(&["CORE.PAGER"]), "less  -R\n");
+    assert_eq!(quoted(&["remote.Origin.url"]), "one\n");
+    assert_eq!(quoted(&["REMOTE.origin.URL"]), "three\n");
+
+    let repo = TestRepo::new();
+    repo.write("quoted", QUOTED.as_bytes());
+    let output = repo.run(["config", "-f", "quoted", "remote.ORIGIN.url"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn get_all_lists_every_value_in_order() {
+    assert_eq!(quoted(&["--get-all", "multi.value"]), "a\nb\nwith ; semicolon\n");
+    assert_eq!(quoted(&["--get-all", "remote.origin.url"]), "two\nthree\n");
+    assert_eq!(
+        quoted(&["-l"]),
+        "core.editor=vim -c \"set tw=72\"
+core.pager=less  -R
+core.path=C:\\tmp\ttab
+core.flag
+remote.Origin.url=one
+remote.origin.url=two
+remote.origin.url=three
+multi.value=a
+multi.value=b
+multi.value=with ; semicolon
+"
+    );
+}
+
+#[test]
+fn includes_are_followed_when_their_condition_holds() {
+    let repo = TestRepo::new();
+    repo.write("included", b"[user]\n\tname = Included\n");
+    repo.write("matched", b"[user]\n\temail = matched@example.com\n");
+    repo.write("missed", b"[user]\n\temail = missed@example.com\n");
+    repo.write("globbed", b"[core]\n\tx = globbed\n");
+    let name = repo.dir.file_name().unwrap().to_str().unwrap();
+    let mut config = fs::read_to_string(repo.git_dir().join("config")).unwrap_or_default();
+    config.push_str(&format!(
+        "[include]
+\tpath = ../included
+[includeIf \"gitdir:{}/\"]
+\tpath = ../matched
+[includeIf \"gitdir:/nowhere/\"]
+\tpath = ../missed
+[includeIf \"gitdir:{}/\"]
+\tpath = ../globbed
+",
+        repo.dir.display(),
+        name
+    ));
+    fs::write(repo.git_dir().join("config"), config).unwrap();
+
+    assert_eq!(repo.rusgit_line(["config", "user.name"]), "Included");
+    assert_eq!(repo.rusgit_line(["config", "user.email"]), "matched@example.com");
+    assert_eq!(repo.rusgit_line(["config", "core.x"]), "globbed");
+    assert_eq!(
+        repo.rusgit_line(["config", "--get-all", "user.email"]),
+        "matched@example.com"
+    );
+}
+
+/// Runs `config -f file <args>` on each of `steps` in turn, starting from
+/// `text`, and returns the file afterwards.
+fn edit(text: &str, steps: &[&[&str]]) -> String {
+    let repo = TestRepo::new();
+    repo.write("file", text.as_bytes());
+    for args in steps {
+        repo.rusgit([&["config", "-f", "file"], *args].concat());
+    }
+    fs::read_to_string(repo.dir.join("file")).unwrap()
+}
+
+#[test]
+fn set_replaces_in_place_and_appends_to_the_section() {
+    assert_eq!(
+        edit(
+            "[core]\n\tbare = false\n# keep me\n[Section \"Sub\"]\n\tkey = old ; note\n\tother = 1\n",
+            &[
+                &["section.Sub.key", "new value; with \"quotes\""],
+                &["section.Sub.added", "  padded  "],
+                &["--add", "section.Sub.other", "2"],
+                &["New.Key", "yes"],
+                &["Fresh.Sub.Key", "tab\tx"],
+            ],
+        ),
+        "[core]
+\tbare = false
+# keep me
+[Section \"Sub\"]
+\tkey = \"new value; with \\\"quotes\\\"\"
+\tother = 1
+\tadded = \"  padded  \"
+\tother = 2
+[New]
+\tKey = yes
+[Fresh \"Sub\"]
+\tKey = tab\\tx
+"
+    );
+}
+
+#[test]
+fn unset_removes_sections_it_leaves_empty() {
+    let unset_all = |text: &str| edit(text, &[&["--unset-all", "a.k"]]);
+    assert_eq!(unset_all("[a]\n\tk = 1\n\n[b]\n\tk = 2\n"), "[b]\n\tk = 2\n");
+    assert_eq!(unset_all("[x]\n\tk = 1\n\n[a]\n\tk = 1\n\tk = 2\n\n[b]\n"), "[x]\n\tk = 1\n[b]\n");
+    assert_eq!(unset_all("[a]\n\tk = 1\n[b]\n[a]\n\tk = 2\n"), "[b]\n");
+    assert_eq!(unset_all("[a]\n[a]\n\tk = 1\n[b]\n"), "[b]\n");
+    assert_eq!(unset_all("[a]\n\tk = 1\n\tj = 2\n"), "[a]\n\tj = 2\n");
+    assert_eq!(unset_all("[a]\n\tk = 1\n[a]\n\tj = 1\n"), "[a]\n[a]\n\tj = 1\n");
+}
+
+#[test]
+fn unset_keeps_sections_with_comments() {
+    let unset_all = |text: &str| edit(text, &[&["--unset-all", "a.k"]]);
+    assert_eq!(unset_all("[a]\n# note\n\tk = 1\n"), "[a]\n# note\n");
+    assert_eq!(unset_all("[a]\n\tk = 1\n# trailing\n[b]\n"), "[a]\n# trailing\n[b]\n");
+    assert_eq!(unset_all("# about a\n[a]\n\tk = 1\n"), "# about a\n[a]\n");
+    assert_eq!(unset_all("[b] # c\n[a]\n\tk = 1\n"), "[b] # c\n[a]\n");
+}
+
+#[test]
+fn unset_refuses_several_values_without_all() {
+    let repo = TestRepo::new();
+    repo.write("file", b"[a]\n\tk = 1\n\tk = 2\n");
+    assert!(!repo.run(["config", "-f", "file", "--unset", "a.k"]).status.success());
+    assert!(!repo.run(["config", "-f", "file", "--unset", "a.missing"]).status.success());
+    assert_eq!(fs::read_to_string(repo.dir.join("file")).unwrap(), "[a]\n\tk = 1\n\tk = 2\n");
+}
@@ -0,0 +1,183 @@
+// tests/diff.rs
+
+mod common;
+
+use common::TestRepo;
+use std::ffi::OsStr;
+
+#[cfg(unix)]
+#[test]
+fn unusual_paths_are_quoted_like_git() {
+    let repo = TestRepo::new();
+    let name = common::non_utf8_name(b"n\xe9");
+    repo.write(&name, b"one\n");
+    repo.write("b\"in", b"bin\0x");
+    repo.rusgit([OsStr::new("add"), name.as_os_str(), OsStr::new("b\"in")]);
+    repo.rusgit(["commit", "-m", "initial"]);
+    repo.write(&name, b"two\n");
+    repo.write("b\"in", b"bin\0y");
+
+    assert_eq!(
+        String::from_utf8(repo.rusgit(["diff"])).unwrap(),
+        "diff --git \"a/b\\\"in\" \"b/b\\\"in\"
+index e899662..9de10b8 100644
+Binary files \"a/b\\\"in\" and \"b/b\\\"in\" differ
+diff --git \"a/n\\351\" \"b/n\\351\"
+index 5626abf..f719efd 100644
+--- \"a/n\\351\"
++++ \"b/n\\351\"
+@@ -1 +1 @@
+-one
++two
+"
+    );
+    assert_eq!(
+        String::from_utf8(repo.rusgit(["diff", "--name-status"])).unwrap(),
+        "M\t\"b\\\"in\"\nM\t\"n\\351\"\n"
+    );
+    assert_eq!(
+        String::from_utf8(repo.rusgit(["diff", "--numstat"])).unwrap(),
+        "-\t-\t\"b\\\"in\"\n1\t1\t\"n\\351\"\n"
+    );
+}
+
+/// The hunks `diff <args>` shows for a file changed from `old` to `new`,
+/// without the header lines above them.
+fn hunks(old: &[u8], new: &[u8], args: &[&str]) -> String {
+    let repo = TestRepo::new();
+    repo.write("file", old);
+    repo.rusgit(["add", "file"]);
+    repo.rusgit(["commit", "-m", "old"]);
+    repo.write("file", new);
+    let output = String::from_utf8(repo.rusgit([&["diff"], args].concat())).unwrap();
+    output.splitn(5, '\n').nth(4).unwrap_or_default().to_string()
+}
+
+// Lines that repeat, so each algorithm anchors on different ones. The
+// expected hunks are git's.
+const SHUFFLED_OLD: &[u8] = b"b\n{\n}\n{\ne\nd\nd\nf\nd\n";
+const SHUFFLED_NEW: &[u8] = b"{\nf\na\nc\na\nb\ne\n";
+
+#[test]
+fn myers_finds_the_shortest_edit() {
+    assert_eq!(
+        hunks(SHUFFLED_OLD, SHUFFLED_NEW, &[]),
+        "@@ -1,9 +1,7 @@\n-b\n-{\n-}\n {\n-e\n-d\n-d\n f\n-d\n+a\n+c\n+a\n+b\n+e\n"
+    );
+}
+
+#[test]
+fn patience_anchors_on_unique_lines() {
+    assert_eq!(
+        hunks(SHUFFLED_OLD, SHUFFLED_NEW, &["--patience"]),
+        "@@ -1,9 +1,7 @@\n+{\n+f\n+a\n+c\n+a\n b\n-{\n-}\n-{\n e\n-d\n-d\n-f\n-d\n"
+    );
+}
+
+#[test]
+fn histogram_anchors_on_the_rarest_lines() {
+    assert_eq!(
+        hunks(SHUFFLED_OLD, SHUFFLED_NEW, &["--histogram"]),
+        "@@ -1,9 +1,7 @@\n-b\n {\n-}\n-{\n-e\n-d\n-d\n f\n-d\n+a\n+c\n+a\n+b\n+e\n"
+    );
+}
+
+#[test]
+fn inserted_blocks_are_slid_to_where_git_puts_them() {
+    let old = b"void func1() {\n    x += 1\n}\n\nvoid func2() {\n    x += 2\n}\n";
+    let new = b"void func1() {\n    x += 1\n}\n\nvoid functhreehalves() {\n    x += 1.5\n}\n\nvoid func2() {\n    x += 2\n}\n";
+    for algorithm in [&[][..], &["--patience"], &["--histogram"]] {
+        assert_eq!(
+            hunks(old, new, algorithm),
+            "@@ -2,6 +2,10 @@ void func1() {
+     x += 1
+ }
+ 
++void functhreehalves() {
++    x += 1.5
++}
++
+ void func2() {
+     x += 2
+ }
+",
+            "{:?}",
+            algorithm
+        );
+    }
+}
+
+fn numbers(replace: &[(usize, &str)]) -> Vec<u8> {
+    (1..=30)
+        .map(|n| replace.iter().find(|(line, _)| *line == n).map_or(n.to_string(), |(_, text)| text.to_string()))
+        .flat_map(|line| format!("{}\n", line).into_bytes())
+        .collect()
+}
+
+#[test]
+fn changes_within_twice_the_context_share_a_hunk() {
+    let old = numbers(&[]);
+    let close = numbers(&[(5, "five"), (12, "twelve")]);
+    assert_eq!(
+        hunks(&old, &close, &[]),
+        "@@ -2,14 +2,14 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n 9\n 10\n 11\n-12\n+twelve\n 13\n 14\n 15\n"
+    );
+    let apart = numbers(&[(5, "five"), (13, "thirteen")]);
+    assert_eq!(
+        hunks(&old, &apart, &[]),
+        "@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n@@ -10,7 +10,7 @@\n 10\n 11\n 12\n-13\n+thirteen\n 14\n 15\n 16\n"
+    );
+}
+
+#[test]
+fn unified_sets_the_context() {
+    let old = numbers(&[]);
+    let new = numbers(&[(5, "five"), (12, "twelve")]);
+    assert_eq!(
+        hunks(&old, &new, &["-U1"]),
+        "@@ -4,3 +4,3 @@\n 4\n-5\n+five\n 6\n@@ -11,3 +11,3 @@\n 11\n-12\n+twelve\n 13\n"
+    );
+    assert_eq!(hunks(&old, &new, &["-U0"]), "@@ -5 +5 @@\n-5\n+five\n@@ -12 +12 @@\n-12\n+twelve\n");
+}
+
+#[test]
+fn hunk_headers_name_the_enclosing_function() {
+    let old = b"int main(void)\n{\n  int a = 1;\n  int b = 2;\n  int c = 3;\n  int d = 4;\n  int e = 5;\n  return a;\n}\n\n\
+static int helper(int x)\n{\n  int y = x;\n  y += 1;\n  y += 2;\n  y += 3;\n  y += 4;\n  return y;\n}\n";
+    let new = String::from_utf8(old.to_vec()).unwrap().replace("int e = 5", "int e = 50").replace("y += 4", "y += 40");
+    assert_eq!(
+        hunks(old, new.as_bytes(), &[]),
+        "@@ -4,7 +4,7 @@ int main(void)
+   int b = 2;
+   int c = 3;
+   int d = 4;
+-  int e = 5;
++  int e = 50;
+   return a;
+ }
+ 
+@@ -14,6 +14,6 @@ static int helper(int x)
+   y += 1;
+   y += 2;
+   y += 3;
+-  y += 4;
++  y += 40;
+   return y;
+ }
+"
+    );
+    assert_eq!(
+        hunks(old, new.as_bytes(), &["-U0"]),
+        "@@ -7 +7 @@ int main(void)\n-  int e = 5;\n+  int e = 50;\n@@ -17 +17 @@ static int helper(int x)\n-  y += 4;\n+  y += 40;\n"
+    );
+}
+
+#[test]
+fn a_missing_final_newline_is_marked() {
+    assert_eq!(
+        hunks(b"a\nb", b"a\nc", &[]),
+        "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n\\ No newline at end of file\n"
+    );
+    // Adding the newline changes the line.
+    assert_eq!(hunks(b"a\nb", b"a\nb\n", &["-U0"]), "@@ -2 +2 @@ a\n-b\n\\ No newline at end of file\n+b\n");
+}
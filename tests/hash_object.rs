@@ -0,0 +1,80 @@
+// tests/hash_object.rs
+//
+// Blob and tree ids for content and file names that aren't UTF-8. The
+// expected ids are the ones `git hash-object` and `git write-tree` give.
+
+mod common;
+
+use common::TestRepo;
+use std::ffi::OsStr;
+use std::fs;
+
+const BINARY: &[u8] = b"\xff\xfe\x00bin\x80";
+const BINARY_ID: &str = "bece2df7fa41b7cadb701590dd688445ac5dc71d";
+const LATIN1: &[u8] = b"caf\xe9\n";
+const LATIN1_ID: &str = "6f83395d973c448cdb70a7b21f7fc8018797acf6";
+
+#[test]
+fn hash_object_hashes_raw_bytes() {
+    let repo = TestRepo::new();
+    repo.write("binary", BINARY);
+    repo.write("latin1.txt", LATIN1);
+    assert_eq!(repo.rusgit_line(["hash-object", "binary"]), BINARY_ID);
+    assert_eq!(repo.rusgit_line(["hash-object", "latin1.txt"]), LATIN1_ID);
+}
+
+#[test]
+fn hash_object_writes_blobs_byte_for_byte() {
+    let repo = TestRepo::new();
+    repo.write("binary", BINARY);
+    assert_eq!(repo.rusgit_line(["hash-object", "-w", "binary"]), BINARY_ID);
+    assert_eq!(repo.rusgit(["cat-file", "-p", BINARY_ID]), BINARY);
+}
+
+#[test]
+fn add_stages_the_same_blob_as_hash_object() {
+    let repo = TestRepo::new();
+    repo.write("binary", BINARY);
+    repo.rusgit(["add", "binary"]);
+    assert_eq!(repo.rusgit(["cat-file", "-p", BINARY_ID]), BINARY);
+    assert!(repo.git_dir().join("objects/be/ce2df7fa41b7cadb701590dd688445ac5dc71d").is_file());
+}
+
+#[cfg(unix)]
+#[test]
+fn write_tree_keeps_non_utf8_names() {
+    let repo = TestRepo::new();
+    let binary = common::non_utf8_name(b"d\xe9/f\xff.bin");
+    repo.write(&binary, BINARY);
+    repo.write("latin1.txt", LATIN1);
+    repo.rusgit([OsStr::new("add"), binary.as_os_str(), OsStr::new("latin1.txt")]);
+    assert_eq!(repo.rusgit_line(["write-tree"]), "21c25b940a34c324bd4b62cdbed225a519e059e2");
+
+    // The index keeps the name too, so writing the tree again gives the same id.
+    let index = fs::read(repo.git_dir().join("index")).unwrap();
+    assert!(index.windows(9).any(|window| window == b"d\xe9/f\xff.bin"));
+    assert_eq!(repo.rusgit_line(["write-tree"]), "21c25b940a34c324bd4b62cdbed225a519e059e2");
+
+    // Once committed, the name reads back from the tree as the same bytes
+    // everywhere trees are flattened.
+    repo.rusgit(["commit", "-m", "initial"]);
+    repo.rusgit(["branch", "initial"]);
+    assert_eq!(repo.rusgit(["status", "--porcelain"]), b"");
+    repo.write(&binary, b"changed\n");
+    assert_eq!(repo.rusgit(["status", "--porcelain"]), b" M \"d\\351/f\\377.bin\"\n");
+    assert_eq!(repo.rusgit(["diff", "--name-only"]), b"\"d\\351/f\\377.bin\"\n");
+    repo.rusgit([OsStr::new("restore"), binary.as_os_str()]);
+    assert_eq!(fs::read(repo.dir.join(&binary)).unwrap(), BINARY);
+    assert_eq!(repo.rusgit(["status", "--porcelain"]), b"");
+
+    repo.write(&binary, b"changed\n");
+    repo.rusgit([OsStr::new("add"), binary.as_os_str()]);
+    repo.rusgit(["commit", "-m", "change"]);
+    assert_eq!(repo.rusgit(["diff", "--name-only", "initial", "HEAD"]), b"\"d\\351/f\\377.bin\"\n");
+    repo.rusgit(["switch", "initial"]);
+    assert_eq!(fs::read(repo.dir.join(&binary)).unwrap(), BINARY);
+    assert_eq!(repo.rusgit(["status", "--porcelain"]), b"");
+    repo.rusgit(["switch", "-"]);
+    assert_eq!(fs::read(repo.dir.join(&binary)).unwrap(), b"changed\n");
+    assert_eq!(repo.rusgit(["status", "--porcelain"]), b"");
+}
@@ -0,0 +1,153 @@
+// tests/log.rs
+
+mod common;
+
+use common::TestRepo;
+
+/// Commits with the tree of a single file holding `name`, made at `time`
+/// (seconds since the epoch, UTC), and returns its id.
+fn commit_at(repo: &TestRepo, name: &str, time: u32, parents: &[&str]) -> String {
+    repo.write("f", format!("{}\n", name).as_bytes());
+    repo.rusgit(["add", "f"]);
+    let tree = repo.rusgit_line(["write-tree"]);
+    let mut args = vec!["commit-tree", tree.as_str(), "-m", name];
+    for parent in parents {
+        args.extend(["-p", parent]);
+    }
+    let output = repo.rusgit_at(&format!("{} +0000", 1112900000 + time), &args);
+    String::from_utf8(output).unwrap().trim_end().to_string()
+}
+
+/// Two merges, each bringing in a branch whose commits interleave in time
+/// with main's:
+///
+/// ```text
+/// A(1) - B(2) - D(4) - M(6) - F(7) - M2(9)   main
+///   \      \          /              /
+///    \      `- G(3.5) - H(8) -------'
+///     `- C(3) - E(5)                          side
+/// ```
+fn history() -> TestRepo {
+    let repo = TestRepo::new();
+    let a = commit_at(&repo, "A", 1000, &[]);
+    let b = commit_at(&repo, "B", 2000, &[&a]);
+    let c = commit_at(&repo, "C", 3000, &[&a]);
+    let d = commit_at(&repo, "D", 4000, &[&b]);
+    let e = commit_at(&repo, "E", 5000, &[&c]);
+    let m = commit_at(&repo, "M", 6000, &[&d, &e]);
+    let f = commit_at(&repo, "F", 7000, &[&m]);
+    let g = commit_at(&repo, "G", 3500, &[&b]);
+    let h = commit_at(&repo, "H", 8000, &[&g]);
+    let m2 = commit_at(&repo, "M2", 9000, &[&f, &h]);
+    repo.rusgit(["update-ref", "refs/heads/main", &m2]);
+    repo.rusgit(["update-ref", "refs/heads/side", &e]);
+    repo
+}
+
+fn log(repo: &TestRepo, args: &[&str]) -> String {
+    String::from_utf8(repo.rusgit([&["log"], args].concat())).unwrap()
+}
+
+// The expected output in these tests is git's for the same history.
+
+#[test]
+fn commits_come_newest_first_across_merged_branches() {
+    let repo = history();
+    assert_eq!(log(&repo, &["--format=%s"]), "M2\nH\nF\nM\nE\nD\nG\nC\nB\nA\n");
+    assert_eq!(log(&repo, &["--format=%s", "--first-parent"]), "M2\nF\nM\nD\nB\nA\n");
+    assert_eq!(log(&repo, &["--format=%s", "side...main"]), "M2\nH\nF\nM\nD\nG\nB\n");
+    assert_eq!(log(&repo, &["--format=%s", "main..side"]), "");
+    assert_eq!(log(&repo, &["--format=%s", "-n", "3"]), "M2\nH\nF\n");
+}
+
+#[test]
+fn graph_draws_lanes_for_each_merge() {
+    let repo = history();
+    let expected = "*   M2
+|\\  
+| * H
+| * G
+* | F
+* |   M
+|\\ \\  
+| * | E
+| * | C
+* | | D
+| |/  
+|/|   
+* | B
+|/  
+* A
+";
+    assert_eq!(log(&repo, &["--graph", "--format=%s"]), expected);
+    assert_eq!(log(&repo, &["--graph", "--format=%s", "side", "main"]), expected);
+}
+
+#[test]
+fn graph_of_part_of_the_history() {
+    let repo = history();
+    assert_eq!(
+        log(&repo, &["--graph", "--format=%s", "main~2"]),
+        "*   M
+|\\  
+| * E
+| * C
+* | D
+* | B
+|/  
+* A
+"
+    );
+    assert_eq!(
+        log(&repo, &["--graph", "--format=%s", "--first-parent"]),
+        "* M2\n* F\n* M\n* D\n* B\n* A\n"
+    );
+}
+
+#[test]
+fn oneline_shows_the_short_hash_and_subject() {
+    let repo = history();
+    let short = |spec: &str| repo.rusgit_line(["rev-parse", "--short", spec]);
+    assert_eq!(
+        log(&repo, &["--oneline", "-n", "2"]),
+        format!("{} M2\n{} H\n", short("main"), short("main^2"))
+    );
+    assert_eq!(
+        log(&repo, &["--graph", "--oneline", "main~2"]).lines().next().unwrap(),
+        format!("*   {} M", short("main~2"))
+    );
+}
+
+#[test]
+fn format_placeholders_expand() {
+    let repo = TestRepo::new();
+    repo.write("f", b"1\n");
+    repo.rusgit(["add", "f"]);
+    repo.rusgit(["commit", "-m", "first"]);
+    repo.write("f", b"2\n");
+    repo.rusgit(["add", "f"]);
+    repo.rusgit_at("1112912000 +0200", ["commit", "-m", "Subject line\n\nBody"]);
+    let head = repo.rusgit_line(["rev-parse", "HEAD"]);
+    let parent = repo.rusgit_line(["rev-parse", "HEAD~"]);
+    let tree = repo.rusgit_line(["rev-parse", "HEAD^{tree}"]);
+
+    let format = |format: &str| log(&repo, &["-n", "1", &format!("--format={}", format)]);
+    assert_eq!(format("%H|%h"), format!("{}|{}\n", head, &head[..7]));
+    assert_eq!(format("%T|%t"), format!("{}|{}\n", tree, &tree[..7]));
+    assert_eq!(format("%P|%p"), format!("{}|{}\n", parent, &parent[..7]));
+    assert_eq!(
+        format("%an <%ae>|%cn <%ce>"),
+        "A U Thor <author@example.com>|C O Mitter <committer@example.com>\n"
+    );
+    assert_eq!(
+        format("%ad|%at|%ai|%aI|%as"),
+        "Fri Apr 8 00:13:20 2005 +0200|1112912000|2005-04-08 00:13:20 +0200|2005-04-08T00:13:20+02:00|2005-04-08\n"
+    );
+    assert_eq!(
+        log(&repo, &["--format=%cd|%cs"]),
+        "Fri Apr 8 00:13:20 2005 +0200|2005-04-08\nThu Apr 7 15:13:13 2005 -0700|2005-04-07\n"
+    );
+    assert_eq!(format("%s%n%x41%%"), "Subject line\nA%\n");
+    assert_eq!(log(&repo, &["--pretty=format:%s"]), "Subject line\nfirst");
+    assert_eq!(log(&repo, &["--pretty=tformat:%s"]), "Subject line\nfirst\n");
+}
@@ -0,0 +1,292 @@
+// tests/pack.rs
+
+mod common;
+
+use common::TestRepo;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use rusgit::delta;
+use rusgit::objects::{Blob, GitObject, ObjectKind, Oid};
+use rusgit::odb::{self, ObjectStore};
+use rusgit::pack::{OBJ_BLOB, OBJ_OFS_DELTA, OBJ_REF_DELTA};
+use rusgit::pack_writer::{self, PackInput};
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Bytes that zlib can't shrink, so only deltas make a pack smaller.
+fn noise(len: usize, mut seed: u32) -> Vec<u8> {
+    (0..len)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn files_that_grow_are_deltified() {
+    let repo = TestRepo::new();
+    let store = ObjectStore::new(repo.git_dir().join("objects")).unwrap();
+    // Each version doubles the file, so the newest is far larger than the rest.
+    let mut content = noise(2048, 1);
+    let mut versions = vec![content.clone()];
+    for round in 0..3 {
+        content.extend(noise(content.len(), round + 2));
+        versions.push(content.clone());
+    }
+    let inputs: Vec<PackInput> = versions
+        .iter()
+        .map(|content| PackInput {
+            oid: store.write(&GitObject::Blob(Blob { content: content.clone() })).unwrap(),
+            path: Some("file".to_string()),
+        })
+        .collect();
+
+    let written = pack_writer::write_pack(&store, &inputs, &repo.git_dir().join("objects/pack/pack")).unwrap();
+
+    // Stored whole, the four versions would take 2 + 4 + 8 + 16 KiB.
+    let pack_size = fs::metadata(&written.pack_path).unwrap().len();
+    assert!(pack_size < 20 * 1024, "pack is {} bytes", pack_size);
+    let store = ObjectStore::new(repo.git_dir().join("objects")).unwrap();
+    let pack = &store.packs()[0];
+    for (input, content) in inputs.iter().zip(&versions) {
+        let (_, data) = pack.read(&input.oid, &store).unwrap().unwrap();
+        assert_eq!(&data, content);
+    }
+}
+
+#[test]
+fn repack_keeps_objects_only_reflogs_reach() {
+    let repo = TestRepo::new();
+    for content in ["one\n", "two\n"] {
+        repo.write("file", content.as_bytes());
+        repo.rusgit(["add", "file"]);
+        repo.rusgit(["commit", "-m", content.trim_end()]);
+    }
+    let dropped = repo.rusgit_line(["rev-parse", "HEAD"]);
+    repo.rusgit(["repack", "-a", "-d"]);
+    repo.rusgit(["update-ref", "refs/heads/main", "HEAD~1"]);
+
+    // The old pack, the only copy of the dropped commit, is deleted now.
+    repo.rusgit(["repack", "-a", "-d"]);
+
+    assert_eq!(repo.rusgit_line(["rev-parse", "main@{1}"]), dropped);
+    let commit = String::from_utf8(repo.rusgit(["cat-file", "-p", "main@{1}"])).unwrap();
+    assert!(commit.trim_end().ends_with("\ntwo"));
+}
+
+/// Builds a pack entry by entry, for laying out deltas in ways the pack
+/// writer never does.
+struct PackBuilder {
+    data: Vec<u8>,
+    // Id, offset and CRC32 of each entry.
+    entries: Vec<(Oid, u64, u32)>,
+}
+
+impl PackBuilder {
+    fn new() -> Self {
+        PackBuilder { data: b"PACK\0\0\0\x02\0\0\0\0".to_vec(), entries: Vec::new() }
+    }
+
+    /// Adds a blob stored whole and returns its offset.
+    fn blob(&mut self, content: &[u8]) -> u64 {
+        self.entry(odb::hash_object(ObjectKind::Blob, content), OBJ_BLOB, &[], content)
+    }
+
+    /// Adds `content` as a delta against the entry at `base_offset`.
+    fn ofs_delta(&mut self, base_offset: u64, base: &[u8], content: &[u8]) -> u64 {
+        let mut distance = self.data.len() as u64 - base_offset;
+        let mut encoded = vec![(distance & 0x7f) as u8];
+        distance >>= 7;
+        while distance > 0 {
+            distance -= 1;
+            encoded.insert(0, 0x80 | (distance & 0x7f) as u8);
+            distance >>= 7;
+        }
+        let delta = delta::create_delta(base, content);
+        self.entry(odb::hash_object(ObjectKind::Blob, content), OBJ_OFS_DELTA, &encoded, &delta)
+    }
+
+    /// Adds `content` as a delta against the blob `base`, named by its id.
+    fn ref_delta(&mut self, base: &[u8], content: &[u8]) -> u64 {
+        let base_oid = odb::hash_object(ObjectKind::Blob, base);
+        let delta = delta::create_delta(base, content);
+        self.entry(odb::hash_object(ObjectKind::Blob, content), OBJ_REF_DELTA, &base_oid, &delta)
+    }
+
+    fn entry(&mut self, oid: Oid, type_id: u8, prefix: &[u8], data: &[u8]) -> u64 {
+        let start = self.data.len();
+        let mut byte = (type_id << 4) | (data.len() & 0x0f) as u8;
+        let mut size = data.len() >> 4;
+        while size > 0 {
+            self.data.push(byte | 0x80);
+            byte = (size & 0x7f) as u8;
+            size >>= 7;
+        }
+        self.data.push(byte);
+        self.data.extend_from_slice(prefix);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        self.data.extend(encoder.finish().unwrap());
+        let crc = crc32fast::hash(&self.data[start..]);
+        self.entries.push((oid, start as u64, crc));
+        start as u64
+    }
+
+    /// Writes the pack and its index into `objects/pack`. With
+    /// `large_offsets`, every offset but the first goes through the index's
+    /// 64-bit table, which git sizes to leave at least one out.
+    fn finish(mut self, objects: &Path, large_offsets: bool) {
+        self.data[8..12].copy_from_slice(&(self.entries.len() as u32).to_be_bytes());
+        let checksum: Oid = Sha1::digest(&self.data).into();
+        self.data.extend_from_slice(&checksum);
+
+        let mut entries = self.entries;
+        entries.sort();
+        let mut idx = b"\xfftOc\0\0\0\x02".to_vec();
+        for first in 0..=255u8 {
+            let count = entries.iter().filter(|(oid, _, _)| oid[0] <= first).count() as u32;
+            idx.extend(count.to_be_bytes());
+        }
+        for (oid, _, _) in &entries {
+            idx.extend(oid);
+        }
+        for (_, _, crc) in &entries {
+            idx.extend(crc.to_be_bytes());
+        }
+        let mut large = Vec::new();
+        for (_, offset, _) in &entries {
+            let small = if large_offsets && *offset > 12 {
+                large.push(*offset);
+                0x8000_0000 | (large.len() - 1) as u32
+            } else {
+                *offset as u32
+            };
+            idx.extend(small.to_be_bytes());
+        }
+        for offset in large {
+            idx.extend(offset.to_be_bytes());
+        }
+        idx.extend(checksum);
+        let idx_checksum: Oid = Sha1::digest(&idx).into();
+        idx.extend(idx_checksum);
+
+        let dir = objects.join("pack");
+        fs::create_dir_all(&dir).unwrap();
+        let name = format!("pack-{}", hex::encode(checksum));
+        fs::write(dir.join(format!("{}.pack", name)), &self.data).unwrap();
+        fs::write(dir.join(format!("{}.idx", name)), idx).unwrap();
+    }
+}
+
+/// Versions of a file, each a small edit of the one before.
+fn versions(count: usize) -> Vec<Vec<u8>> {
+    let mut content = noise(1024, 7);
+    let mut versions = vec![content.clone()];
+    for round in 0..count - 1 {
+        content.splice(round * 100..round * 100 + 10, noise(30, round as u32));
+        versions.push(content.clone());
+    }
+    versions
+}
+
+fn read_blob(store: &ObjectStore, content: &[u8]) -> Vec<u8> {
+    let (kind, data) = store.read_raw(&odb::hash_object(ObjectKind::Blob, content)).unwrap();
+    assert_eq!(kind, ObjectKind::Blob);
+    data
+}
+
+#[test]
+fn chained_offset_deltas_are_resolved() {
+    let repo = TestRepo::new();
+    let objects = repo.git_dir().join("objects");
+    let versions = versions(4);
+    let mut pack = PackBuilder::new();
+    let mut offset = pack.blob(&versions[0]);
+    for pair in versions.windows(2) {
+        offset = pack.ofs_delta(offset, &pair[0], &pair[1]);
+    }
+    pack.finish(&objects, false);
+
+    let store = ObjectStore::new(&objects).unwrap();
+    for content in &versions {
+        assert_eq!(&read_blob(&store, content), content);
+    }
+    let last = hex::encode(odb::hash_object(ObjectKind::Blob, &versions[3]));
+    assert_eq!(repo.rusgit(["cat-file", "-p", &last]), versions[3]);
+}
+
+#[test]
+fn ref_deltas_find_their_base_in_the_pack_or_outside_it() {
+    let repo = TestRepo::new();
+    let objects = repo.git_dir().join("objects");
+    let versions = versions(4);
+    let store = ObjectStore::new(&objects).unwrap();
+    // Only stored loose, so the pack is thin.
+    store.write_raw(ObjectKind::Blob, &versions[0]).unwrap();
+
+    let mut pack = PackBuilder::new();
+    pack.ref_delta(&versions[0], &versions[1]);
+    // A delta may come before its base.
+    pack.ref_delta(&versions[3], &versions[2]);
+    pack.blob(&versions[3]);
+    pack.finish(&objects, false);
+
+    let store = ObjectStore::new(&objects).unwrap();
+    for content in &versions {
+        assert_eq!(&read_blob(&store, content), content);
+    }
+}
+
+#[test]
+fn offsets_can_come_from_the_large_offset_table() {
+    let repo = TestRepo::new();
+    let objects = repo.git_dir().join("objects");
+    let versions = versions(3);
+    let mut pack = PackBuilder::new();
+    let base = pack.blob(&versions[0]);
+    let middle = pack.ofs_delta(base, &versions[0], &versions[1]);
+    pack.ofs_delta(middle, &versions[1], &versions[2]);
+    pack.blob(b"small\n");
+    pack.finish(&objects, true);
+
+    let store = ObjectStore::new(&objects).unwrap();
+    let index = &store.packs()[0].index;
+    assert_eq!(index.len(), 4);
+    for content in &versions {
+        assert_eq!(&read_blob(&store, content), content);
+    }
+    assert_eq!(read_blob(&store, b"small\n"), b"small\n");
+    assert_eq!(index.find(&odb::hash_object(ObjectKind::Blob, &versions[0])), Some(base));
+}
+
+#[test]
+fn objects_are_found_packed_loose_or_both() {
+    let repo = TestRepo::new();
+    let objects = repo.git_dir().join("objects");
+    // Written loose first, as the store won't write what it already has.
+    let both = ObjectStore::new(&objects)
+        .unwrap()
+        .write_raw(ObjectKind::Blob, b"packed and loose\n")
+        .unwrap();
+    let mut pack = PackBuilder::new();
+    pack.blob(b"packed only\n");
+    pack.blob(b"packed and loose\n");
+    pack.finish(&objects, false);
+    let store = ObjectStore::new(&objects).unwrap();
+    let packed = odb::hash_object(ObjectKind::Blob, b"packed only\n");
+    assert!(objects.join(&hex::encode(both)[..2]).join(&hex::encode(both)[2..]).is_file());
+    assert!(!objects.join(&hex::encode(packed)[..2]).join(&hex::encode(packed)[2..]).exists());
+
+    for (oid, content) in [(packed, &b"packed only\n"[..]), (both, &b"packed and loose\n"[..])] {
+        assert!(store.exists(&oid));
+        assert_eq!(store.header(&oid).unwrap(), (ObjectKind::Blob, content.len()));
+        assert_eq!(read_blob(&store, content), content);
+        // Found twice, but only one object.
+        let hex = hex::encode(oid);
+        assert_eq!(store.find_by_prefix(&hex[..6]).unwrap(), [oid]);
+        assert_eq!(repo.rusgit(["cat-file", "-p", &hex[..6]]), content);
+    }
+}
@@ -0,0 +1,109 @@
+// tests/reflog.rs
+
+mod common;
+
+use common::TestRepo;
+use std::fs;
+
+fn commit(repo: &TestRepo, file: &str, message: &str) -> String {
+    repo.write(file, format!("{}\n", message).as_bytes());
+    repo.rusgit(["add", file]);
+    repo.rusgit(["commit", "-m", message]);
+    repo.rusgit_line(["rev-parse", "HEAD"])
+}
+
+/// Two commits on main, a branch from the first with a commit of its own,
+/// and a switch there and back.
+fn history() -> (TestRepo, [String; 3]) {
+    let repo = TestRepo::new();
+    let one = commit(&repo, "f", "one");
+    let two = commit(&repo, "f", "two");
+    repo.rusgit(["branch", "topic", "HEAD~1"]);
+    repo.rusgit(["switch", "topic"]);
+    let three = commit(&repo, "g", "three");
+    repo.rusgit(["switch", "main"]);
+    (repo, [one, two, three])
+}
+
+/// `reflog show <args>` with the hashes replaced by the commit subjects.
+fn reflog(repo: &TestRepo, args: &[&str], commits: &[String; 3]) -> String {
+    let mut output = String::from_utf8(repo.rusgit([&["reflog", "show"], args].concat())).unwrap();
+    for (commit, name) in commits.iter().zip(["one", "two", "three"]) {
+        output = output.replace(&commit[..7], &format!("<{}>", name));
+    }
+    output
+}
+
+// The expected output in these tests is git's for the same commands.
+
+#[test]
+fn commits_branches_and_switches_are_logged() {
+    let (repo, commits) = history();
+    assert_eq!(
+        reflog(&repo, &[], &commits),
+        "<two> HEAD@{0}: checkout: moving from topic to main
+<three> HEAD@{1}: commit: three
+<one> HEAD@{2}: checkout: moving from main to topic
+<two> HEAD@{3}: commit: two
+<one> HEAD@{4}: commit (initial): one
+"
+    );
+    assert_eq!(
+        reflog(&repo, &["topic"], &commits),
+        "<three> topic@{0}: commit: three
+<one> topic@{1}: branch: Created from HEAD~1
+"
+    );
+    assert_eq!(
+        reflog(&repo, &["main"], &commits),
+        "<two> main@{0}: commit: two
+<one> main@{1}: commit (initial): one
+"
+    );
+}
+
+#[test]
+fn entries_are_written_in_gits_format() {
+    let (repo, [one, two, _]) = history();
+    let log = fs::read_to_string(repo.git_dir().join("logs/refs/heads/main")).unwrap();
+    assert_eq!(
+        log,
+        format!(
+            "{zero} {one} C O Mitter <committer@example.com> 1112911993 -0700\tcommit (initial): one
+{one} {two} C O Mitter <committer@example.com> 1112911993 -0700\tcommit: two
+",
+            zero = "0".repeat(40)
+        )
+    );
+}
+
+#[test]
+fn at_n_counts_back_through_a_log() {
+    let (repo, [one, two, three]) = history();
+    let rev_parse = |spec: &str| repo.rusgit_line(["rev-parse", spec]);
+    assert_eq!(rev_parse("HEAD@{0}"), two);
+    assert_eq!(rev_parse("HEAD@{1}"), three);
+    assert_eq!(rev_parse("HEAD@{2}"), one);
+    assert_eq!(rev_parse("HEAD@{4}"), one);
+    // Without a ref, the current branch's log rather than HEAD's.
+    assert_eq!(rev_parse("@{1}"), one);
+    assert_eq!(rev_parse("main@{1}"), one);
+    assert_eq!(rev_parse("topic@{1}"), one);
+    assert_eq!(rev_parse("topic@{0}~1"), one);
+    assert!(!repo.run(["rev-parse", "HEAD@{5}"]).status.success());
+}
+
+#[test]
+fn at_date_finds_where_a_branch_was_then() {
+    let repo = TestRepo::new();
+    for (content, date) in [("one", "1112900000 +0000"), ("two", "1112910000 +0000")] {
+        repo.write("f", content.as_bytes());
+        repo.rusgit(["add", "f"]);
+        repo.rusgit_at(date, ["commit", "-m", content]);
+    }
+    let rev_parse = |spec: &str| repo.rusgit_line(["rev-parse", spec]);
+    assert_eq!(rev_parse("main@{2005-04-07 20:00}"), rev_parse("main@{1}"));
+    assert_eq!(rev_parse("main@{2005-04-07 22:00}"), rev_parse("main"));
+    // Before the log starts, its oldest entry.
+    assert_eq!(rev_parse("main@{2005-04-06}"), rev_parse("main@{1}"));
+}
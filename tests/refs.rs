@@ -0,0 +1,31 @@
+// tests/refs.rs
+
+mod common;
+
+use common::TestRepo;
+use rusgit::objects;
+use rusgit::refs::{self, RefTransaction};
+use std::fs;
+
+#[test]
+fn a_reflog_that_cannot_be_written_leaves_every_ref_alone() {
+    let repo = TestRepo::new();
+    repo.write("file", b"content\n");
+    repo.rusgit(["add", "file"]);
+    repo.rusgit(["commit", "-m", "initial"]);
+    let head = objects::parse_oid(&repo.rusgit_line(["rev-parse", "HEAD"])).unwrap();
+    let git_dir = repo.git_dir();
+
+    // A directory where the second branch's reflog should go makes the
+    // append fail after the first branch's update has been logged.
+    fs::create_dir_all(git_dir.join("logs/refs/heads/zz/blocked")).unwrap();
+    let mut transaction = RefTransaction::new();
+    transaction.set_message("branch: Created from HEAD");
+    transaction.update("refs/heads/aa", head, Some(None));
+    transaction.update("refs/heads/zz", head, Some(None));
+    assert!(transaction.commit(&git_dir).is_err());
+
+    assert_eq!(refs::resolve_ref(&git_dir, "refs/heads/aa").unwrap(), None);
+    assert_eq!(refs::resolve_ref(&git_dir, "refs/heads/zz").unwrap(), None);
+    assert!(!git_dir.join("refs/heads/aa.lock").exists());
+}
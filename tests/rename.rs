@@ -0,0 +1,223 @@
+// tests/rename.rs
+
+mod common;
+
+use common::TestRepo;
+use std::fs;
+
+/// The numbers `first..=last`, one per line, like `seq`.
+fn seq(first: u32, last: u32) -> Vec<u8> {
+    (first..=last).map(|n| format!("{n}\n")).collect::<String>().into_bytes()
+}
+
+/// Commits `files` as the whole tree, dropping whatever the index held, and
+/// returns the new commit's id.
+fn commit_tree(repo: &TestRepo, files: &[(&str, Vec<u8>)], message: &str) -> String {
+    let _ = fs::remove_file(repo.git_dir().join("index"));
+    let mut add = vec!["add"];
+    for (path, content) in files {
+        repo.write(path, content);
+        add.push(path);
+    }
+    repo.rusgit(&add);
+    repo.rusgit(["commit", "-m", message]);
+    repo.rusgit_line(["rev-parse", "HEAD"])
+}
+
+/// Two commits that swap, move, copy, rewrite and edit files. Returns the
+/// repository with the ids of both commits.
+fn history() -> (TestRepo, String, String) {
+    let repo = TestRepo::new();
+    let old = commit_tree(
+        &repo,
+        &[
+            ("a", seq(1, 200)),
+            ("b", seq(1000, 1200)),
+            ("src", seq(300, 400)),
+            ("half", seq(2000, 2100)),
+            ("x", seq(3000, 3150)),
+            ("y", seq(4000, 4100)),
+            ("dir/sub/f", seq(5000, 5100)),
+        ],
+        "old",
+    );
+    let new = commit_tree(
+        &repo,
+        &[
+            ("a", seq(1000, 1200)),
+            ("b", seq(1, 200)),
+            ("src", [seq(300, 400), b"more\n".to_vec()].concat()),
+            ("src-copy", seq(300, 400)),
+            ("half", [seq(2000, 2050), seq(9000, 9049)].concat()),
+            ("x", seq(7000, 7150)),
+            ("x-moved", seq(3000, 3150)),
+            ("y2", [seq(4000, 4040), seq(8000, 8060)].concat()),
+            ("dir/other/f", seq(5000, 5100)),
+        ],
+        "new",
+    );
+    (repo, old, new)
+}
+
+/// `diff <old> <new> <args>` over `history`.
+fn diff(args: &[&str]) -> String {
+    let (repo, old, new) = history();
+    let output = repo.rusgit([&["diff", old.as_str(), new.as_str()], args].concat());
+    String::from_utf8(output).unwrap()
+}
+
+// The expected output in these tests is git's for the same two trees.
+
+#[test]
+fn exact_renames_are_found_by_default() {
+    assert_eq!(
+        diff(&["--name-status"]),
+        "M\ta
+M\tb
+R100\tdir/sub/f\tdir/other/f
+M\thalf
+M\tsrc
+A\tsrc-copy
+M\tx
+A\tx-moved
+D\ty
+A\ty2
+"
+    );
+}
+
+#[test]
+fn copies_of_modified_files_need_dash_c() {
+    assert_eq!(
+        diff(&["-C", "--name-status"]),
+        "M\ta
+M\tb
+R100\tdir/sub/f\tdir/other/f
+M\thalf
+M\tsrc
+C100\tsrc\tsrc-copy
+M\tx
+C100\tx\tx-moved
+D\ty
+A\ty2
+"
+    );
+}
+
+#[test]
+fn a_rename_with_edits_needs_a_low_enough_threshold() {
+    let renamed = diff(&["-M30%", "--name-status"]);
+    assert!(renamed.ends_with("R040\ty\ty2\n"), "{renamed}");
+    let below = diff(&["-M50%", "--name-status"]);
+    assert!(below.ends_with("D\ty\nA\ty2\n"), "{below}");
+    let below = diff(&["-M90%", "--name-status"]);
+    assert!(below.ends_with("D\ty\nA\ty2\n"), "{below}");
+}
+
+#[test]
+fn rewrites_are_broken_up_and_paired_again() {
+    let expected = "R100\tb\ta
+R100\ta\tb
+R100\tdir/sub/f\tdir/other/f
+M\thalf
+M\tsrc
+A\tsrc-copy
+M100\tx
+C100\tx\tx-moved
+D\ty
+A\ty2
+";
+    assert_eq!(diff(&["-B", "--name-status"]), expected);
+    assert_eq!(diff(&["-B/70%", "-M", "--name-status"]), expected);
+}
+
+#[test]
+fn a_low_break_score_marks_partial_rewrites() {
+    let output = diff(&["-B20%/30%", "-M", "--name-status"]);
+    assert!(output.contains("\nM049\thalf\n"), "{output}");
+}
+
+#[test]
+fn stat_names_renames_and_copies() {
+    assert_eq!(
+        diff(&["-C", "--stat"]),
+        " a                    | 401 ++++++++++++++++++++++++++-------------------------
+ b                    | 401 +++++++++++++++++++++++++--------------------------
+ dir/{sub => other}/f |   0
+ half                 | 100 ++++++-------
+ src                  |   1 +
+ src => src-copy      |   0
+ x                    | 302 +++++++++++++++++++-------------------
+ x => x-moved         |   0
+ y                    | 101 -------------
+ y2                   | 102 +++++++++++++
+ 10 files changed, 705 insertions(+), 703 deletions(-)
+"
+    );
+}
+
+#[test]
+fn numstat_names_renames_and_copies() {
+    assert_eq!(
+        diff(&["-C", "--numstat"]),
+        "201\t200\ta
+200\t201\tb
+0\t0\tdir/{sub => other}/f
+50\t50\thalf
+1\t0\tsrc
+0\t0\tsrc => src-copy
+151\t151\tx
+0\t0\tx => x-moved
+0\t101\ty
+102\t0\ty2
+"
+    );
+}
+
+#[test]
+fn summary_lists_renames_copies_rewrites_and_modes() {
+    assert_eq!(
+        diff(&["-B", "-C", "--summary"]),
+        " rename b => a (100%)
+ rename a => b (100%)
+ rename dir/{sub => other}/f (100%)
+ copy src => src-copy (100%)
+ rewrite x (100%)
+ copy x => x-moved (100%)
+ delete mode 100644 y
+ create mode 100644 y2
+"
+    );
+}
+
+#[test]
+fn patches_carry_the_similarity_and_dissimilarity_index() {
+    let renamed = diff(&["-M30%", "--", "y", "y2"]);
+    assert!(
+        renamed.starts_with(
+            "diff --git a/y b/y2
+similarity index 40%
+rename from y
+rename to y2
+index 2e527f1..6caf6c4 100644
+--- a/y
++++ b/y2
+@@ -39,63 +39,64 @@
+"
+        ),
+        "{renamed}"
+    );
+    let rewritten = diff(&["-B", "--", "x"]);
+    assert!(
+        rewritten.starts_with(
+            "diff --git a/x b/x
+dissimilarity index 100%
+index 5e185fa..214c31e 100644
+--- a/x
++++ b/x
+@@ -1,151 +1,151 @@
+"
+        ),
+        "{rewritten}"
+    );
+}
@@ -0,0 +1,32 @@
+// tests/restore.rs
+
+mod common;
+
+use common::TestRepo;
+use std::ffi::OsStr;
+use std::fs;
+
+#[cfg(unix)]
+#[test]
+fn restore_writes_non_utf8_paths_back_in_place() {
+    let repo = TestRepo::new();
+    let name = common::non_utf8_name(b"n\xe9");
+    repo.write(&name, b"one\n");
+    repo.rusgit([OsStr::new("add"), name.as_os_str()]);
+    repo.rusgit(["commit", "-m", "initial"]);
+
+    // From the index into the working tree.
+    repo.write(&name, b"two\n");
+    repo.rusgit([OsStr::new("restore"), name.as_os_str()]);
+    assert_eq!(fs::read(repo.dir.join(&name)).unwrap(), b"one\n");
+
+    // From HEAD into the index, then into the working tree.
+    repo.write(&name, b"three\n");
+    repo.rusgit([OsStr::new("add"), name.as_os_str()]);
+    repo.rusgit([OsStr::new("restore"), OsStr::new("--staged"), OsStr::new("--worktree"), name.as_os_str()]);
+    assert_eq!(fs::read(repo.dir.join(&name)).unwrap(), b"one\n");
+    assert_eq!(repo.rusgit(["diff", "--cached", "--name-only"]), b"");
+
+    let names: Vec<_> = fs::read_dir(&repo.dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    assert!(names.iter().all(|file| file == ".git" || file == name.as_os_str()), "{:?}", names);
+}
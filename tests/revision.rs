@@ -0,0 +1,189 @@
+// tests/revision.rs
+
+mod common;
+
+use common::TestRepo;
+
+/// A history with a merge, tags, a reflog and an upstream:
+///
+/// ```text
+/// one - two ----- merge - four   main
+///    \           /
+///     `- side --'
+/// ```
+///
+/// `v1` is an annotated tag of `two`, and `v1-outer` a tag of that tag.
+/// `origin/main`, the upstream of main, points at `one`.
+struct History {
+    repo: TestRepo,
+    one: String,
+    two: String,
+    side: String,
+    merge: String,
+    four: String,
+}
+
+impl History {
+    fn new() -> Self {
+        let repo = TestRepo::new();
+        let commit = |content: &[u8], message: &str| {
+            repo.write("f", content);
+            repo.rusgit(["add", "f"]);
+            repo.rusgit(["commit", "-m", message]);
+            repo.rusgit_line(["rev-parse", "HEAD"])
+        };
+        let one = commit(b"1\n", "one");
+        let two = commit(b"2\n", "two");
+
+        repo.write("f", b"side\n");
+        repo.write("d/g", b"deep\n");
+        repo.rusgit(["add", "f", "d/g"]);
+        let tree = repo.rusgit_line(["write-tree"]);
+        let side = repo.rusgit_line(["commit-tree", &tree, "-p", &one, "-m", "side"]);
+        repo.write("f", b"3\n");
+        repo.rusgit(["add", "f"]);
+        let tree = repo.rusgit_line(["write-tree"]);
+        let merge = repo.rusgit_line(["commit-tree", &tree, "-p", &two, "-p", &side, "-m", "merge"]);
+        repo.rusgit(["update-ref", "refs/heads/main", &merge]);
+        let four = commit(b"4\n", "four");
+
+        repo.rusgit(["tag", "-a", "v1", "-m", "first", &two]);
+        repo.rusgit(["tag", "-a", "v1-outer", "-m", "outer", "v1"]);
+        repo.rusgit(["config", "remote.origin.url", "/nowhere"]);
+        repo.rusgit(["config", "remote.origin.fetch", "+refs/heads/*:refs/remotes/origin/*"]);
+        repo.rusgit(["config", "branch.main.remote", "origin"]);
+        repo.rusgit(["config", "branch.main.merge", "refs/heads/main"]);
+        repo.rusgit(["update-ref", "refs/remotes/origin/main", &one]);
+        History { repo, one, two, side, merge, four }
+    }
+
+    fn rev_parse(&self, spec: &str) -> String {
+        self.repo.rusgit_line(["rev-parse", spec])
+    }
+
+    /// The error `rev-parse` fails with for `spec`.
+    fn rev_parse_error(&self, spec: &str) -> String {
+        let output = self.repo.run(["rev-parse", spec]);
+        assert!(!output.status.success(), "{} resolved", spec);
+        String::from_utf8(output.stderr).unwrap()
+    }
+}
+
+#[test]
+fn ancestry_operators_follow_parents() {
+    let history = History::new();
+    assert_eq!(history.rev_parse("HEAD"), history.four);
+    assert_eq!(history.rev_parse("@"), history.four);
+    assert_eq!(history.rev_parse("HEAD~"), history.merge);
+    assert_eq!(history.rev_parse("HEAD~2"), history.two);
+    assert_eq!(history.rev_parse("HEAD~^2"), history.side);
+    assert_eq!(history.rev_parse("HEAD^^2"), history.side);
+    assert_eq!(history.rev_parse("HEAD~^2~"), history.one);
+    assert_eq!(history.rev_parse("HEAD~1^0"), history.merge);
+    assert_eq!(history.rev_parse("main~3"), history.one);
+
+    assert!(history.rev_parse_error("HEAD~9").contains("HEAD~9"));
+    assert!(history.rev_parse_error("HEAD~^3").contains("HEAD~^3"));
+}
+
+#[test]
+fn tags_are_peeled_to_what_they_point_at() {
+    let history = History::new();
+    let tag = history.rev_parse("v1");
+    assert_ne!(tag, history.two);
+    assert_eq!(history.rev_parse("v1^{}"), history.two);
+    assert_eq!(history.rev_parse("v1^0"), history.two);
+    assert_eq!(history.rev_parse("v1~1"), history.one);
+
+    // Through both tags at once.
+    assert_eq!(history.rev_parse("v1-outer^{}"), history.two);
+    assert_eq!(history.rev_parse("v1-outer^{commit}"), history.two);
+    assert_eq!(history.rev_parse("v1-outer^{tree}"), history.rev_parse("HEAD~2^{tree}"));
+    let outer = history.rev_parse("v1-outer^{tag}");
+    assert_ne!(outer, tag);
+    assert!(String::from_utf8(history.repo.rusgit(["cat-file", "-p", &outer]))
+        .unwrap()
+        .starts_with(&format!("object {}\ntype tag\ntag v1-outer\n", tag)));
+}
+
+#[test]
+fn paths_name_trees_and_blobs_inside_a_commit() {
+    let history = History::new();
+    let tree = history.rev_parse("HEAD^{tree}");
+    assert_eq!(history.rev_parse("HEAD:"), tree);
+    let blob = history.rev_parse("HEAD:f");
+    assert_eq!(history.repo.rusgit(["cat-file", "-p", &blob]), b"4\n");
+    let deep = history.rev_parse("HEAD~:d/g");
+    assert_eq!(history.repo.rusgit(["cat-file", "-p", &deep]), b"deep\n");
+    assert_eq!(history.rev_parse("HEAD~^2:d/g"), deep);
+    let dir = history.rev_parse("HEAD~:d");
+    assert!(String::from_utf8(history.repo.rusgit(["cat-file", "-p", &dir]))
+        .unwrap()
+        .ends_with(&format!("blob {}\tg\n", deep)));
+
+    assert!(history.rev_parse_error("HEAD:nope").contains("path 'nope' does not exist in 'HEAD'"));
+}
+
+#[test]
+fn ranges_list_included_then_excluded_commits() {
+    let history = History::new();
+    let range = |spec: &str| history.rev_parse(spec).lines().map(str::to_string).collect::<Vec<_>>();
+    let not = |oid: &str| format!("^{}", oid);
+
+    assert_eq!(range("HEAD~2..HEAD"), [history.four.clone(), not(&history.two)]);
+    assert_eq!(range("HEAD~2.."), [history.four.clone(), not(&history.two)]);
+    assert_eq!(range("..HEAD~2"), [history.two.clone(), not(&history.four)]);
+    // Both sides, without the merge base they share.
+    assert_eq!(
+        range("HEAD~^2...HEAD~2"),
+        [history.two.clone(), history.side.clone(), not(&history.one)]
+    );
+}
+
+#[test]
+fn reflog_selectors_count_back_through_the_branch() {
+    let history = History::new();
+    assert_eq!(history.rev_parse("@{0}"), history.four);
+    assert_eq!(history.rev_parse("HEAD@{1}"), history.merge);
+    assert_eq!(history.rev_parse("main@{2}"), history.two);
+    assert_eq!(history.rev_parse("main@{3}"), history.one);
+    assert!(!history.repo.run(["rev-parse", "main@{9}"]).status.success());
+}
+
+#[test]
+fn upstream_is_the_remote_tracking_branch() {
+    let history = History::new();
+    assert_eq!(history.rev_parse("@{upstream}"), history.one);
+    assert_eq!(history.rev_parse("@{u}"), history.one);
+    assert_eq!(history.rev_parse("main@{u}"), history.one);
+    assert_eq!(
+        history.repo.rusgit_line(["rev-parse", "--symbolic-full-name", "@{u}"]),
+        "refs/remotes/origin/main"
+    );
+}
+
+#[test]
+fn abbreviations_must_be_long_enough_and_unique() {
+    let history = History::new();
+    // Two blobs whose ids share their first five digits.
+    history.repo.write("x", b"195\n");
+    history.repo.write("y", b"389\n");
+    history.repo.rusgit(["hash-object", "-w", "x"]);
+    history.repo.rusgit(["hash-object", "-w", "y"]);
+
+    assert_eq!(history.rev_parse("6bb2f9"), "6bb2f98fb0227744dff2c9023c2a8d53cc721588");
+    assert_eq!(history.rev_parse("6BB2F4"), "6bb2f4ee89f3ff56785055f588c560ce557d0655");
+    let error = history.rev_parse_error("6bb2f");
+    assert!(error.contains("short object ID 6bb2f is ambiguous"), "{}", error);
+    assert!(error.contains("6bb2f4e blob"), "{}", error);
+    assert!(error.contains("6bb2f98 blob"), "{}", error);
+
+    // Too short to be taken as a hash at all, even when unique.
+    let prefix = &history.four[..3];
+    assert!(history.rev_parse_error(prefix).contains("Not a valid object name"));
+    assert_eq!(history.rev_parse(&history.four[..4]), history.four);
+    assert_eq!(
+        history.repo.rusgit_line(["rev-parse", "--short", "6bb2f98fb0227744dff2c9023c2a8d53cc721588"]),
+        "6bb2f98"
+    );
+}
@@ -0,0 +1,84 @@
+// tests/status.rs
+
+mod common;
+
+use common::TestRepo;
+use rusgit::index::{Index, IndexEntry};
+use rusgit::odb;
+use rusgit::objects::ObjectKind;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+/// Records `path` as added with --intent-to-add, the way `git add -N` does.
+fn add_intent_to_add(repo: &TestRepo, path: &str) {
+    let index_path = repo.git_dir().join("index");
+    let mut index = Index::from_path(&index_path).unwrap();
+    index.add(IndexEntry {
+        mode: 0o100644,
+        hash: odb::hash_object(ObjectKind::Blob, b""),
+        path: PathBuf::from(path),
+        intent_to_add: true,
+        ..Default::default()
+    });
+    index.write(&index_path).unwrap();
+}
+
+#[test]
+fn intent_to_add_entries_are_unstaged_additions() {
+    let repo = TestRepo::new();
+    repo.write("a", b"a\n");
+    repo.rusgit(["add", "a"]);
+    repo.rusgit(["commit", "-m", "a"]);
+    repo.write("new", b"new\n");
+    add_intent_to_add(&repo, "new");
+
+    assert_eq!(repo.rusgit(["status", "--short"]), b" A new\n");
+    let long = String::from_utf8(repo.rusgit(["status"])).unwrap();
+    assert!(!long.contains("Changes to be committed"));
+    assert!(long.contains("Changes not staged for commit:\n\tnew file:   new\n"));
+}
+
+#[test]
+fn missing_intent_to_add_files_are_deleted() {
+    let repo = TestRepo::new();
+    add_intent_to_add(&repo, "gone");
+    assert_eq!(repo.rusgit(["status", "--porcelain"]), b" D gone\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_names_are_tracked_and_quoted() {
+    let repo = TestRepo::new();
+    let name = common::non_utf8_name(b"n\xe9");
+    repo.write(&name, b"one\n");
+    repo.rusgit([std::ffi::OsStr::new("add"), name.as_os_str()]);
+    repo.rusgit(["commit", "-m", "initial"]);
+    assert_eq!(repo.rusgit(["status", "--porcelain"]), b"");
+
+    repo.write(&name, b"two\n");
+    repo.write(common::non_utf8_name(b"d\xe9/new"), b"new\n");
+    assert_eq!(
+        String::from_utf8(repo.rusgit(["status", "--porcelain"])).unwrap(),
+        " M \"n\\351\"\n?? \"d\\351/\"\n"
+    );
+}
+
+#[test]
+fn a_closed_pipe_stops_output_quietly() {
+    let repo = TestRepo::new();
+    // More than a pipe buffer of untracked names, so printing has to block
+    // on the reader and notice that it is gone.
+    for n in 0..4000 {
+        repo.write(format!("untracked-file-number-{n}"), b"");
+    }
+    let mut child = repo
+        .command(common::DATE, ["status"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.is_empty(), "{stderr}");
+}